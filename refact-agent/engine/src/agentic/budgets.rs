@@ -0,0 +1,328 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock as ARwLock;
+use tracing::{info, warn};
+
+use crate::call_validation::{ChatContent, ChatMessage};
+use crate::caps::{resolve_chat_model, resolve_chat_model_pricing, ModelPricing, UsageTokens};
+use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
+use crate::scratchpads::scratchpad_utils::HasRagResults;
+use crate::yaml_configs::customization_loader::{load_customization, BudgetLimits, BudgetsConfig};
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BudgetSpent {
+    pub tokens: usize,
+    pub usd: f64,
+    pub tool_calls: usize,
+    #[serde(default)]
+    pub duration_minutes: f64,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct BudgetStatus {
+    pub chat: BudgetSpent,
+    pub day: BudgetSpent,
+    pub limits: Option<BudgetsConfig>,
+    pub warnings: Vec<String>,
+    pub exceeded: Vec<String>,
+}
+
+impl BudgetStatus {
+    pub fn is_exceeded(&self) -> bool {
+        !self.exceeded.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct DaySpent {
+    date: String,
+    spent: BudgetSpent,
+}
+
+#[derive(Debug, Default)]
+struct ChatBudgetState {
+    first_seen_ts: f64,
+    counted: BudgetSpent,  // already added to the day totals
+    told_warning: bool,
+    told_exceeded: bool,
+}
+
+const BUDGET_CHATS_MAX: usize = 1000;
+
+/// Lives in GlobalContext, per-chat state is in memory, day totals survive restarts in cache_dir/budget_today.json
+#[derive(Debug, Default)]
+pub struct BudgetTracker {
+    chats: IndexMap<String, ChatBudgetState>,  // least recently checked first
+    day: Option<DaySpent>,
+}
+
+impl BudgetTracker {
+    /// There's no "chat is over" event, so the least recently checked chats go once there are too many
+    fn chat_state(&mut self, chat_id: &str) -> &mut ChatBudgetState {
+        let state = self.chats.shift_remove(chat_id).unwrap_or_else(|| ChatBudgetState {
+            first_seen_ts: now_ts(),
+            ..Default::default()
+        });
+        self.chats.insert(chat_id.to_string(), state);
+        if self.chats.len() > BUDGET_CHATS_MAX {
+            self.chats.shift_remove_index(0);
+        }
+        self.chats.last_mut().unwrap().1
+    }
+}
+
+fn now_ts() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+pub fn spent_in_messages(messages: &[ChatMessage], pricing: Option<&ModelPricing>) -> BudgetSpent {
+    let mut spent = BudgetSpent::default();
+    for m in messages {
+        if let Some(usage) = &m.usage {
+            spent.tokens += usage.prompt_tokens + usage.completion_tokens;
+            if let Some(pricing) = pricing {
+                spent.usd += pricing.cost_usd(&UsageTokens {
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                    ..Default::default()
+                });
+            }
+        }
+        if m.role == "assistant" {
+            spent.tool_calls += m.tool_calls.as_ref().map(|x| x.len()).unwrap_or(0);
+        }
+    }
+    spent
+}
+
+/// Tool calls already include the ones about to run, so they're exceeded only above the limit
+pub fn check_limits(
+    scope: &str,
+    spent: &BudgetSpent,
+    limits: &BudgetLimits,
+    warn_at: f64,
+    warnings: &mut Vec<String>,
+    exceeded: &mut Vec<String>,
+) {
+    let mut check = |what: &str, spent: f64, limit: f64, strictly_above: bool, fmt: &dyn Fn(f64) -> String| {
+        if limit <= 0.0 {
+            return;
+        }
+        let is_exceeded = if strictly_above { spent > limit } else { spent >= limit };
+        if is_exceeded {
+            exceeded.push(format!("{} {} {} of {}", scope, what, fmt(spent), fmt(limit)));
+        } else if spent >= limit * warn_at {
+            warnings.push(format!("{} {} {} of {}", scope, what, fmt(spent), fmt(limit)));
+        }
+    };
+    check("tokens", spent.tokens as f64, limits.max_tokens as f64, false, &|x| format!("{}", x as usize));
+    check("cost", spent.usd, limits.max_usd, false, &|x| format!("${:.2}", x));
+    check("tool calls", spent.tool_calls as f64, limits.max_tool_calls as f64, true, &|x| format!("{}", x as usize));
+    check("duration", spent.duration_minutes, limits.max_duration_minutes, false, &|x| format!("{:.0}min", x));
+}
+
+fn budget_today_path(cache_dir: &PathBuf) -> PathBuf {
+    cache_dir.join("budget_today.json")
+}
+
+fn add_spent(total: &mut BudgetSpent, delta: &BudgetSpent) {
+    total.tokens += delta.tokens;
+    total.usd += delta.usd;
+    total.tool_calls += delta.tool_calls;
+}
+
+/// Returns None if there are no budgets configured, otherwise updates the day totals and checks the limits.
+pub async fn budget_check_chat(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    chat_id: &str,
+    model_id: &str,
+    messages: &[ChatMessage],
+) -> Option<BudgetStatus> {
+    if chat_id.is_empty() {
+        return None;  // subchats, their usage is counted in the tool message of the parent chat
+    }
+    let mut error_log = Vec::new();
+    let budgets = load_customization(gcx.clone(), true, &mut error_log).await.budgets?;
+    if budgets.per_chat.is_unlimited() && budgets.per_day.is_unlimited() {
+        return None;
+    }
+    let pricing = match try_load_caps_quickly_if_not_present(gcx.clone(), 0).await {
+        Ok(caps) => resolve_chat_model(caps.clone(), model_id).ok()
+            .and_then(|model_rec| resolve_chat_model_pricing(&caps, &model_rec)),
+        Err(_) => None,
+    };
+    if pricing.is_none() && (budgets.per_chat.max_usd > 0.0 || budgets.per_day.max_usd > 0.0) {
+        warn!("budget in USD is set, but there's no pricing for model {:?}, cost is not counted", model_id);
+    }
+
+    let (tracker, cache_dir) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.budget_tracker.clone(), gcx_locked.cache_dir.clone())
+    };
+    let mut tracker_locked = tracker.lock().await;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    if tracker_locked.day.is_none() {
+        tracker_locked.day = std::fs::read_to_string(budget_today_path(&cache_dir)).ok()
+            .and_then(|s| serde_json::from_str::<DaySpent>(&s).ok());
+    }
+    if tracker_locked.day.as_ref().map(|d| d.date != today).unwrap_or(true) {
+        tracker_locked.day = Some(DaySpent { date: today, spent: BudgetSpent::default() });
+    }
+
+    let mut chat_spent = spent_in_messages(messages, pricing.as_ref());
+    let chat_state = tracker_locked.chat_state(chat_id);
+    chat_spent.duration_minutes = (now_ts() - chat_state.first_seen_ts) / 60.0;
+    let delta = if chat_spent.tokens >= chat_state.counted.tokens && chat_spent.tool_calls >= chat_state.counted.tool_calls {
+        BudgetSpent {
+            tokens: chat_spent.tokens - chat_state.counted.tokens,
+            usd: (chat_spent.usd - chat_state.counted.usd).max(0.0),
+            tool_calls: chat_spent.tool_calls - chat_state.counted.tool_calls,
+            duration_minutes: 0.0,
+        }
+    } else {
+        BudgetSpent::default()  // history was cut or regenerated, nothing new
+    };
+    chat_state.counted = chat_spent.clone();
+
+    let day = tracker_locked.day.as_mut().unwrap();
+    add_spent(&mut day.spent, &delta);
+    let day_spent = day.spent.clone();
+    if delta != BudgetSpent::default() {
+        if let Err(e) = std::fs::write(budget_today_path(&cache_dir), serde_json::to_string(&day).unwrap()) {
+            warn!("cannot save budget totals: {}", e);
+        }
+    }
+
+    let mut status = BudgetStatus {
+        chat: chat_spent,
+        day: day_spent,
+        limits: Some(budgets.clone()),
+        ..Default::default()
+    };
+    check_limits("chat", &status.chat, &budgets.per_chat, budgets.warn_at, &mut status.warnings, &mut status.exceeded);
+    let day_limits = BudgetLimits { max_duration_minutes: 0.0, ..budgets.per_day.clone() };
+    check_limits("today's", &status.day, &day_limits, budgets.warn_at, &mut status.warnings, &mut status.exceeded);
+    if status.is_exceeded() {
+        info!("budget exceeded for chat {}: {}", chat_id, status.exceeded.join(", "));
+    }
+    Some(status)
+}
+
+/// The model hears about a budget state once, the message stays in the history after that
+pub async fn budget_cd_instruction(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str, status: &BudgetStatus) -> Option<ChatMessage> {
+    let tracker = gcx.read().await.budget_tracker.clone();
+    let mut tracker_locked = tracker.lock().await;
+    let chat_state = tracker_locked.chats.get_mut(chat_id)?;
+    let text = if status.is_exceeded() {
+        if chat_state.told_exceeded {
+            return None;
+        }
+        chat_state.told_exceeded = true;
+        format!("💸 Budget exceeded: {}. Tools are disabled now. Don't try to continue the work, summarize what is done, \
+            what is left and how to continue.", status.exceeded.join(", "))
+    } else if !status.warnings.is_empty() {
+        if chat_state.told_warning {
+            return None;
+        }
+        chat_state.told_warning = true;
+        format!("💸 Budget is almost used: {}. Finish the current step and wrap up, don't start anything new.", status.warnings.join(", "))
+    } else {
+        return None;
+    };
+    Some(ChatMessage::new("cd_instruction".to_string(), text))
+}
+
+/// Tool results for the pending tool calls of the last assistant message, so the chat stays valid without running them
+pub fn budget_refuse_tool_calls(messages: &[ChatMessage], status: &BudgetStatus) -> Vec<ChatMessage> {
+    let last_msg_tool_calls = match messages.last().filter(|m| m.role == "assistant") {
+        Some(m) => m.tool_calls.clone().unwrap_or_default(),
+        None => return vec![],
+    };
+    last_msg_tool_calls.iter().map(|t_call| ChatMessage {
+        role: "tool".to_string(),
+        content: ChatContent::SimpleText(format!("Not executed, budget exceeded: {}", status.exceeded.join(", "))),
+        tool_call_id: t_call.id.clone(),
+        tool_failed: Some(true),
+        ..Default::default()
+    }).collect()
+}
+
+/// The budget step of every chat scratchpad: counts the chat, answers the pending tool calls when over the budget,
+/// tells the model once. The added messages go into `messages` and the RAG results, tools stay off if it's exceeded.
+pub async fn budget_apply_to_chat(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    chat_id: &str,
+    model_id: &str,
+    messages: &mut Vec<ChatMessage>,
+    has_rag_results: &mut HasRagResults,
+) -> Option<BudgetStatus> {
+    let status = budget_check_chat(gcx.clone(), chat_id, model_id, messages).await?;
+    let mut budget_messages = if status.is_exceeded() { budget_refuse_tool_calls(messages, &status) } else { vec![] };
+    budget_messages.extend(budget_cd_instruction(gcx.clone(), chat_id, &status).await);
+    for msg in budget_messages {
+        has_rag_results.push_in_json(serde_json::json!(&msg));
+        messages.push(msg);
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call_validation::{ChatToolCall, ChatToolFunction, ChatUsage};
+
+    fn assistant_with_calls(n: usize, prompt_tokens: usize, completion_tokens: usize) -> ChatMessage {
+        ChatMessage {
+            role: "assistant".to_string(),
+            tool_calls: Some((0..n).map(|i| ChatToolCall {
+                id: format!("call_{}", i),
+                function: ChatToolFunction { name: "cat".to_string(), arguments: "{}".to_string() },
+                tool_type: "function".to_string(),
+            }).collect()),
+            usage: Some(ChatUsage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spent_and_limits() {
+        let pricing = ModelPricing { prompt: 2.5, generated: 10.0, ..Default::default() };
+        let messages = vec![
+            ChatMessage::new("user".to_string(), "hi".to_string()),
+            assistant_with_calls(2, 100_000, 10_000),
+            assistant_with_calls(1, 200_000, 20_000),
+        ];
+        let spent = spent_in_messages(&messages, Some(&pricing));
+        assert_eq!(spent.tokens, 330_000);
+        assert_eq!(spent.tool_calls, 3);
+        assert!((spent.usd - 1.05).abs() < 1e-9);
+
+        let (mut warnings, mut exceeded) = (vec![], vec![]);
+        let limits = BudgetLimits { max_tokens: 400_000, max_usd: 1.0, max_tool_calls: 3, max_duration_minutes: 0.0 };
+        check_limits("chat", &spent, &limits, 0.8, &mut warnings, &mut exceeded);
+        assert_eq!(exceeded, vec!["chat cost $1.05 of $1.00".to_string()]);
+        assert_eq!(warnings, vec!["chat tokens 330000 of 400000".to_string(), "chat tool calls 3 of 3".to_string()]);
+
+        let refused = budget_refuse_tool_calls(&messages, &BudgetStatus { exceeded, ..Default::default() });
+        assert_eq!(refused.len(), 1);
+        assert_eq!(refused[0].tool_call_id, "call_0");
+        assert_eq!(refused[0].tool_failed, Some(true));
+    }
+
+    #[test]
+    fn test_tracker_forgets_least_recent_chats() {
+        let mut tracker = BudgetTracker::default();
+        for i in 0..BUDGET_CHATS_MAX {
+            tracker.chat_state(&format!("chat{}", i)).told_warning = true;
+        }
+        tracker.chat_state("chat0");
+        tracker.chat_state("one more");
+        assert_eq!(tracker.chats.len(), BUDGET_CHATS_MAX);
+        assert!(tracker.chats.get("chat0").unwrap().told_warning);
+        assert!(!tracker.chats.contains_key("chat1"));
+        assert!(!tracker.chat_state("chat1").told_warning);
+    }
+}
@@ -1,3 +1,6 @@
 pub mod generate_commit_message;
 pub mod generate_follow_up_message;
-pub mod compress_trajectory;
\ No newline at end of file
+pub mod compress_trajectory;
+pub mod review;
+pub mod run_task;
+pub mod budgets;
@@ -0,0 +1,422 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use git2::{Patch, Repository};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+use tracing::{info, warn};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::at_commands::at_diff::git_repos_in_workspace;
+use crate::call_validation::{ChatContent, ChatMessage};
+use crate::files_correction::canonical_path;
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::git::operations::git_diff_between_revs;
+use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
+use crate::subchat::subchat_single;
+
+
+const REVIEW_PROMPT: &str = r#"You are reviewing a change in a code repository. You get the diff between two branches, where each line of the new version is prefixed with its line number, and definitions of the symbols that the changed code refers to.
+
+Find real problems introduced by the change: bugs, crashes, wrong edge cases, security issues, races, resource leaks, broken error handling, API misuse, and code that doesn't do what the surrounding code expects. Don't comment on formatting, don't praise, and don't repeat what the change does. Comment only on lines that were added or changed.
+
+Respond with a JSON array and nothing else, each element is a finding:
+[
+  {
+    "file": "path/as/shown/in/the/diff",
+    "line": 42,
+    "severity": "error",
+    "message": "What is wrong and why, one or two sentences.",
+    "suggested_patch": "the fixed version of the line(s), or an empty string"
+  }
+]
+`line` is the line number in the new version of the file, as shown at the left of the diff. `severity` is one of: error, warning, info.
+If there is nothing worth reporting, respond with []."#;
+
+const REVIEW_N_CTX: usize = 32000;
+const REVIEW_TEMPERATURE: f32 = 0.2;
+const REVIEW_DIFF_MAX_CHARS: usize = 40_000;
+const REVIEW_RELATED_MAX_CHARS: usize = 16_000;
+const REVIEW_RELATED_MAX_DEFS: usize = 30;
+const REVIEW_RELATED_MAX_LINES: usize = 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReviewFinding {
+    pub file: String,
+    pub line: usize,
+    pub severity: String,
+    pub message: String,
+    #[serde(default)]
+    pub suggested_patch: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewResult {
+    pub repo_root: String,
+    pub base: String,
+    pub head: String,
+    pub merge_base: String,
+    pub files: Vec<String>,
+    pub changed_symbols: Vec<String>,
+    pub findings: Vec<ReviewFinding>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewChangedFile {
+    pub path: String,                   // relative to the repository root
+    pub ranges: Vec<(usize, usize)>,    // lines of the new version touched by hunks, starting from 1
+}
+
+pub struct ReviewDiff {
+    pub merge_base: String,
+    pub files: Vec<ReviewChangedFile>,
+    pub text: String,
+}
+
+/// The diff `base...head` in a form that's easy to anchor findings to: every line of the new version gets its number
+pub fn collect_review_diff(repository: &Repository, base: &str, head: &str, max_chars: usize) -> Result<ReviewDiff, String> {
+    let (diff, merge_base) = git_diff_between_revs(repository, base, head)?;
+    let mut files = vec![];
+    let mut text = String::new();
+    for idx in 0..diff.deltas().len() {
+        let patch = match Patch::from_diff(&diff, idx).map_err(|e| format!("Failed to generate patch: {}", e))? {
+            Some(patch) => patch,
+            None => continue,  // binary
+        };
+        let delta = patch.delta();
+        let path = match delta.new_file().path().or(delta.old_file().path()) {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let mut file_text = format!("File {} ({:?})\n", path, delta.status());
+        let mut ranges = vec![];
+        for hunk_idx in 0..patch.num_hunks() {
+            let (hunk, lines_n) = patch.hunk(hunk_idx).map_err(|e| format!("Failed to read hunk: {}", e))?;
+            if hunk.new_lines() > 0 {
+                ranges.push((hunk.new_start() as usize, (hunk.new_start() + hunk.new_lines() - 1) as usize));
+            }
+            file_text.push_str(&String::from_utf8_lossy(hunk.header()));
+            for line_idx in 0..lines_n {
+                let line = patch.line_in_hunk(hunk_idx, line_idx).map_err(|e| format!("Failed to read line: {}", e))?;
+                let content = String::from_utf8_lossy(line.content());
+                let content = content.trim_end_matches(['\n', '\r']);
+                match (line.origin(), line.new_lineno()) {
+                    ('+', Some(line_n)) => file_text.push_str(&format!("{:>5} +{}\n", line_n, content)),
+                    (' ', Some(line_n)) => file_text.push_str(&format!("{:>5}  {}\n", line_n, content)),
+                    ('-', _) => file_text.push_str(&format!("{:>5} -{}\n", "", content)),
+                    _ => {},
+                }
+            }
+        }
+        if text.len() + file_text.len() > max_chars {
+            text.push_str(&format!("File {}: not shown, the diff is too big\n", path));
+        } else {
+            text.push_str(&file_text);
+        }
+        files.push(ReviewChangedFile { path, ranges });
+    }
+    Ok(ReviewDiff { merge_base: merge_base.to_string(), files, text })
+}
+
+fn in_ranges(line: usize, ranges: &[(usize, usize)]) -> bool {
+    ranges.iter().any(|(line1, line2)| *line1 <= line && line <= *line2)
+}
+
+/// Definitions touched by the diff, and the text of the definitions they use. The AST index reflects the working copy,
+/// so this is accurate when `head` is what's checked out, and degrades gracefully when it's not.
+async fn changed_symbols_and_related_definitions(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    repo_root: &Path,
+    files: &[ReviewChangedFile],
+) -> (Vec<String>, String) {
+    let ast_service = match gcx.read().await.ast_service.clone() {
+        Some(ast_service) => ast_service,
+        None => return (vec![], String::new()),
+    };
+    let ast_index = ast_service.lock().await.ast_index.clone();
+
+    let mut changed_symbols = vec![];
+    let mut used_symbols = BTreeSet::new();
+    for f in files {
+        let cpath = canonical_path(repo_root.join(&f.path).to_string_lossy().to_string()).to_string_lossy().to_string();
+        for def in crate::ast::ast_db::doc_defs(ast_index.clone(), &cpath) {
+            if !f.ranges.iter().any(|(line1, line2)| def.full_line1() <= *line2 && *line1 <= def.full_line2()) {
+                continue;
+            }
+            changed_symbols.push(def.path_drop0());
+            for usage in def.usages.iter() {
+                if usage.resolved_as.is_empty() || usage.resolved_as.starts_with("?::") || !in_ranges(usage.uline + 1, &f.ranges) {
+                    continue;
+                }
+                let name = usage.resolved_as.rsplit("::").take(2).collect::<Vec<_>>().into_iter().rev().collect::<Vec<_>>().join("::");
+                used_symbols.insert(name);
+            }
+        }
+    }
+
+    let changed_set: HashSet<&String> = changed_symbols.iter().collect();
+    let mut related = String::new();
+    for symbol in used_symbols.iter().take(REVIEW_RELATED_MAX_DEFS) {
+        let defs = crate::ast::ast_db::definitions(ast_index.clone(), symbol).unwrap_or_default();
+        let def = match defs.first() {
+            Some(def) if !changed_set.contains(&def.path_drop0()) => def,
+            _ => continue,
+        };
+        let text = match get_file_text_from_memory_or_disk(gcx.clone(), &PathBuf::from(&def.cpath)).await {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let line2 = def.full_line2().min(def.full_line1() + REVIEW_RELATED_MAX_LINES - 1);
+        let body = text.lines().skip(def.full_line1() - 1).take(line2 + 1 - def.full_line1()).collect::<Vec<_>>().join("\n");
+        let snippet = format!("{} defined in {}:{}-{}\n```\n{}\n```\n", def.path_drop0(), def.cpath, def.full_line1(), line2, body);
+        if related.len() + snippet.len() > REVIEW_RELATED_MAX_CHARS {
+            break;
+        }
+        related.push_str(&snippet);
+    }
+    (changed_symbols, related)
+}
+
+/// "b/src/x.rs" is a path copied from the diff header, unless there's a real "b" dir, so exact matches go first
+fn finding_file(file: &str, files: &[ReviewChangedFile]) -> Option<String> {
+    let file = file.trim();
+    let file = file.strip_prefix("./").unwrap_or(file);
+    let without_git_prefix = file.strip_prefix("a/").or(file.strip_prefix("b/"));
+    files.iter().find(|f| f.path == file)
+        .or_else(|| without_git_prefix.and_then(|p| files.iter().find(|f| f.path == p)))
+        .or_else(|| files.iter().find(|f| f.path.ends_with(&format!("/{}", file)) || file.ends_with(&format!("/{}", f.path))))
+        .map(|f| f.path.clone())
+}
+
+fn normalize_severity(severity: &str) -> String {
+    match severity.trim().to_lowercase().as_str() {
+        "error" | "critical" | "high" | "bug" => "error",
+        "warning" | "warn" | "medium" => "warning",
+        _ => "info",
+    }.to_string()
+}
+
+/// Tolerates code fences and chatter around the JSON, drops findings for files that are not in the diff.
+/// Line 0 means the whole file: findings without a line or with a line outside of the shown hunks get it,
+/// and lose the suggested patch that has nowhere to go.
+pub fn parse_review_findings(answer: &str, files: &[ReviewChangedFile]) -> Result<Vec<ReviewFinding>, String> {
+    let (start, end) = match (answer.find('['), answer.rfind(']')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return Err(format!("the model didn't return a JSON array of findings: {}", answer.chars().take(200).collect::<String>())),
+    };
+    let items: Vec<Value> = serde_json::from_str(&answer[start..=end])
+        .map_err(|e| format!("cannot parse the findings: {}", e))?;
+    let mut findings = vec![];
+    for item in items {
+        let file = match item.get("file").and_then(|f| f.as_str()).and_then(|f| finding_file(f, files)) {
+            Some(file) => file,
+            None => {
+                warn!("review finding for a file that is not in the diff: {}", item);
+                continue;
+            }
+        };
+        let line = match item.get("line") {
+            Some(Value::Number(n)) => n.as_u64().unwrap_or(0) as usize,
+            Some(Value::String(s)) => s.trim().parse::<usize>().unwrap_or(0),
+            _ => 0,
+        };
+        let message = item.get("message").and_then(|m| m.as_str()).unwrap_or_default().trim().to_string();
+        if message.is_empty() {
+            continue;
+        }
+        let ranges = files.iter().find(|f| f.path == file).map(|f| f.ranges.as_slice()).unwrap_or_default();
+        let (line, suggested_patch) = if in_ranges(line, ranges) {
+            (line, item.get("suggested_patch").and_then(|p| p.as_str()).unwrap_or_default().to_string())
+        } else {
+            if line != 0 {
+                warn!("review finding for {}:{} is outside of the diff, keeping it for the whole file", file, line);
+            }
+            (0, String::new())
+        };
+        findings.push(ReviewFinding {
+            file,
+            line,
+            severity: normalize_severity(item.get("severity").and_then(|s| s.as_str()).unwrap_or_default()),
+            message,
+            suggested_patch,
+        });
+    }
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(findings)
+}
+
+async fn review_repo_root(gcx: Arc<ARwLock<GlobalContext>>, project_path: &str) -> Result<PathBuf, String> {
+    if !project_path.is_empty() {
+        let repository = Repository::discover(canonical_path(project_path))
+            .map_err(|e| format!("{} is not in a git repository: {}", project_path, e))?;
+        return repository.workdir().map(|w| w.to_path_buf()).ok_or("Repository has no working directory".to_string());
+    }
+    let roots: Vec<PathBuf> = git_repos_in_workspace(gcx).await.into_iter().map(|(root, _)| root).collect();
+    match roots.len() {
+        0 => Err("no git repositories in the workspace".to_string()),
+        1 => Ok(roots[0].clone()),
+        _ => Err(format!("there are several repositories in the workspace, pass `project_path` to pick one: {}",
+            roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", "))),
+    }
+}
+
+pub async fn review_branch(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    project_path: &str,
+    base: &str,
+    head: &str,
+    model: &str,
+    instructions: &str,
+) -> Result<ReviewResult, String> {
+    let head = if head.is_empty() { "HEAD" } else { head };
+    let repo_root = review_repo_root(gcx.clone(), project_path).await?;
+    let review_diff = {
+        let repository = Repository::open(&repo_root).map_err(|e| format!("Failed to open repository: {}", e))?;
+        collect_review_diff(&repository, base, head, REVIEW_DIFF_MAX_CHARS)?
+    };
+    let mut result = ReviewResult {
+        repo_root: repo_root.to_string_lossy().to_string(),
+        base: base.to_string(),
+        head: head.to_string(),
+        merge_base: review_diff.merge_base.clone(),
+        files: review_diff.files.iter().map(|f| f.path.clone()).collect(),
+        changed_symbols: vec![],
+        findings: vec![],
+    };
+    if review_diff.files.is_empty() {
+        return Ok(result);
+    }
+
+    let (changed_symbols, related) = changed_symbols_and_related_definitions(gcx.clone(), &repo_root, &review_diff.files).await;
+    result.changed_symbols = changed_symbols;
+
+    let mut user_message = format!("Diff {}...{}:\n{}", base, head, review_diff.text);
+    if !result.changed_symbols.is_empty() {
+        user_message.push_str(&format!("\nChanged symbols: {}\n", result.changed_symbols.join(", ")));
+    }
+    if !related.is_empty() {
+        user_message.push_str(&format!("\nDefinitions used by the changed code:\n{}", related));
+    }
+    if !instructions.is_empty() {
+        user_message.push_str(&format!("\nAdditional instructions for this review:\n{}\n", instructions));
+    }
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatContent::SimpleText(REVIEW_PROMPT.to_string()),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatContent::SimpleText(user_message),
+            ..Default::default()
+        },
+    ];
+
+    let model_id = if model.is_empty() {
+        match try_load_caps_quickly_if_not_present(gcx.clone(), 0).await {
+            Ok(caps) => caps.defaults.chat_default_model.clone(),
+            Err(_) => return Err("No caps available".to_string()),
+        }
+    } else {
+        model.to_string()
+    };
+    let ccx: Arc<AMutex<AtCommandsContext>> = Arc::new(AMutex::new(AtCommandsContext::new(
+        gcx.clone(),
+        REVIEW_N_CTX,
+        1,
+        false,
+        messages.clone(),
+        "".to_string(),
+        false,
+        model_id.clone(),
+    ).await));
+    let new_messages = subchat_single(
+        ccx.clone(),
+        &model_id,
+        messages,
+        Some(vec![]),
+        None,
+        false,
+        Some(REVIEW_TEMPERATURE),
+        None,
+        1,
+        None,
+        false,
+        None,
+        None,
+        None,
+    ).await.map_err(|e| format!("Error: {}", e))?;
+
+    let answer = new_messages.into_iter().next()
+        .and_then(|x| x.into_iter().last())
+        .and_then(|m| match m.content {
+            ChatContent::SimpleText(text) => Some(text),
+            ChatContent::Multimodal(_) => None,
+        })
+        .ok_or("The model didn't answer".to_string())?;
+    result.findings = parse_review_findings(&answer, &review_diff.files)?;
+    info!("review {}...{} in {}: {} files, {} findings", base, head, result.repo_root, result.files.len(), result.findings.len());
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::git::test_utils::commit_file;
+
+    #[test]
+    fn test_collect_review_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let repository = Repository::init(temp_dir.path()).unwrap();
+        let base = commit_file(&repository, "a.py", "def f(x):\n    return x\n\n\ndef g():\n    pass\n", "base");
+        commit_file(&repository, "a.py", "def f(x):\n    return x + 1\n\n\ndef g():\n    pass\n", "change f");
+
+        let review_diff = collect_review_diff(&repository, &base.to_string(), "HEAD", 10_000).unwrap();
+        assert_eq!(review_diff.merge_base, base.to_string());
+        assert_eq!(review_diff.files, vec![ReviewChangedFile { path: "a.py".to_string(), ranges: vec![(1, 5)] }]);
+        assert!(review_diff.text.contains("    2 +    return x + 1\n"), "{}", review_diff.text);
+        assert!(review_diff.text.contains("      -    return x\n"), "{}", review_diff.text);
+        assert!(review_diff.text.contains("    1  def f(x):\n"), "{}", review_diff.text);
+
+        let too_small = collect_review_diff(&repository, &base.to_string(), "HEAD", 10).unwrap();
+        assert_eq!(too_small.text, "File a.py: not shown, the diff is too big\n");
+        assert!(collect_review_diff(&repository, "no-such-branch", "HEAD", 10_000).is_err());
+    }
+
+    #[test]
+    fn test_parse_review_findings() {
+        let files = vec![ReviewChangedFile { path: "src/a.py".to_string(), ranges: vec![(1, 5)] }];
+        let answer = r#"Here you go:
+```json
+[
+  {"file": "b/src/a.py", "line": "2", "severity": "High", "message": "off by one", "suggested_patch": "    return x\n"},
+  {"file": "other.py", "line": 1, "severity": "error", "message": "not in the diff"},
+  {"file": "a.py", "line": 1, "severity": "nit", "message": "rename"},
+  {"file": "src/a.py", "line": 3, "severity": "error", "message": ""},
+  {"file": "src/a.py", "line": 40, "severity": "warning", "message": "far away", "suggested_patch": "pass\n"},
+  {"file": "src/a.py", "severity": "info", "message": "no line"}
+]
+```"#;
+        let findings = parse_review_findings(answer, &files).unwrap();
+        assert_eq!(findings, vec![
+            ReviewFinding { file: "src/a.py".to_string(), line: 0, severity: "warning".to_string(), message: "far away".to_string(), suggested_patch: "".to_string() },
+            ReviewFinding { file: "src/a.py".to_string(), line: 0, severity: "info".to_string(), message: "no line".to_string(), suggested_patch: "".to_string() },
+            ReviewFinding { file: "src/a.py".to_string(), line: 1, severity: "info".to_string(), message: "rename".to_string(), suggested_patch: "".to_string() },
+            ReviewFinding { file: "src/a.py".to_string(), line: 2, severity: "error".to_string(), message: "off by one".to_string(), suggested_patch: "    return x\n".to_string() },
+        ]);
+        assert_eq!(parse_review_findings("[]", &files).unwrap(), vec![]);
+        assert!(parse_review_findings("looks good to me", &files).is_err());
+
+        let files = vec![
+            ReviewChangedFile { path: "a/x.py".to_string(), ranges: vec![] },
+            ReviewChangedFile { path: "x.py".to_string(), ranges: vec![] },
+        ];
+        assert_eq!(finding_file("a/x.py", &files), Some("a/x.py".to_string()));
+        assert_eq!(finding_file("b/x.py", &files), Some("x.py".to_string()));
+        assert_eq!(finding_file("b/a/x.py", &files), Some("a/x.py".to_string()));
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tracing::{error, info, warn};
+
+use crate::agentic::budgets::{budget_cd_instruction, budget_check_chat, budget_refuse_tool_calls};
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::audit_log::{audit_log_append, AuditEntry};
+use crate::call_validation::{ChatContent, ChatMessage, ChatMeta, ChatMode, ChatToolCall, ChatUsage, DiffChunk};
+use crate::caps::resolve_chat_model;
+use crate::git::checkpoints::{checkpoint_label_from_user_message, create_workspace_checkpoint, init_shadow_repos_if_needed, Checkpoint};
+use crate::global_context::{try_load_caps_quickly_if_not_present, CommandLine, GlobalContext};
+use crate::http::routers::v1::chat::CHAT_TOP_N;
+use crate::indexing_utils::wait_for_indexing_if_needed;
+use crate::scratchpads::chat_utils_prompts::prepend_the_right_system_prompt_and_maybe_more_initial_messages;
+use crate::scratchpads::scratchpad_utils::HasRagResults;
+use crate::subchat::subchat_single;
+use crate::tools::tools_description::{MatchConfirmDenyResult, Tool};
+use crate::tools::tools_execute::run_tools;
+use crate::tools::tools_list::get_available_tools_by_chat_mode;
+
+
+const MAX_NEW_TOKENS: usize = 8192;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolApprovalPolicy {
+    Deny,     // tool calls that need a confirmation are refused, the model is told why
+    Approve,  // tool calls that need a confirmation run as if the user clicked "allow"
+}
+
+impl ToolApprovalPolicy {
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "deny" | "" => Ok(ToolApprovalPolicy::Deny),
+            "approve" => Ok(ToolApprovalPolicy::Approve),
+            other => Err(format!("unknown tool approval policy {:?}, expected \"deny\" or \"approve\"", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunTaskStatus {
+    Completed,
+    MaxStepsReached,
+    BudgetExceeded,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunTaskSettings {
+    pub task: String,
+    pub chat_mode: ChatMode,
+    pub model: String,
+    pub max_steps: usize,
+    pub tool_approval: ToolApprovalPolicy,
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunTaskSummary {
+    pub chat_id: String,
+    pub task: String,
+    pub chat_mode: ChatMode,
+    pub model: String,
+    pub status: RunTaskStatus,
+    pub error: Option<String>,
+    pub steps: usize,
+    pub tool_calls: usize,
+    pub tool_calls_refused: usize,
+    pub usage: ChatUsage,
+    pub files_changed: Vec<String>,
+    pub checkpoint: Option<Checkpoint>,
+    pub final_message: String,
+    pub duration_seconds: f64,
+}
+
+impl RunTaskSettings {
+    pub fn from_cmdline(cmdline: &CommandLine, cache_dir: &PathBuf, chat_id: &str) -> Result<Self, String> {
+        let chat_mode = serde_json::from_value::<ChatMode>(Value::String(cmdline.run_task_chat_mode.to_uppercase()))
+            .map_err(|_| format!("unknown chat mode {:?}", cmdline.run_task_chat_mode))?;
+        let output_dir = if cmdline.run_task_output_dir.is_empty() {
+            cache_dir.join("tasks").join(chat_id)
+        } else {
+            crate::files_correction::canonical_path(&cmdline.run_task_output_dir)
+        };
+        Ok(RunTaskSettings {
+            task: cmdline.run_task.clone(),
+            chat_mode,
+            model: cmdline.run_task_model.clone(),
+            max_steps: cmdline.run_task_max_steps,
+            tool_approval: ToolApprovalPolicy::from_str(&cmdline.run_task_approve)?,
+            output_dir,
+        })
+    }
+}
+
+pub fn files_changed_from_messages(messages: &Vec<ChatMessage>) -> Vec<String> {
+    let mut files_changed = vec![];
+    for m in messages.iter().filter(|m| m.role == "diff") {
+        let chunks = match serde_json::from_str::<Vec<DiffChunk>>(&m.content.content_text_only()) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                warn!("cannot parse diff message: {}", e);
+                continue;
+            }
+        };
+        for chunk in chunks {
+            for file_name in std::iter::once(chunk.file_name).chain(chunk.file_name_rename) {
+                if !files_changed.contains(&file_name) {
+                    files_changed.push(file_name);
+                }
+            }
+        }
+    }
+    files_changed
+}
+
+fn refused_tool_call_message(tool_call_id: &str, command: &str, rule: &str) -> ChatMessage {
+    ChatMessage {
+        role: "tool".to_string(),
+        content: ChatContent::SimpleText(format!(
+            "tool use: command '{command}' needs a confirmation from the user (rule '{rule}'), but this is a headless run and nobody can confirm it. Try a different approach."
+        )),
+        tool_call_id: tool_call_id.to_string(),
+        tool_failed: Some(true),
+        ..Default::default()
+    }
+}
+
+async fn split_tool_calls_by_approval(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    tools: &IndexMap<String, Box<dyn Tool + Send>>,
+    tool_calls: &Vec<ChatToolCall>,
+    policy: ToolApprovalPolicy,
+) -> (Vec<ChatToolCall>, Vec<ChatMessage>) {
+    let mut approved = vec![];
+    let mut refused = vec![];
+    let mut audit_entries = vec![];
+    for t_call in tool_calls {
+        // unknown tools, broken arguments and deny rules are reported to the model by run_tools() itself
+        let args = serde_json::from_str::<HashMap<String, Value>>(&t_call.function.arguments);
+        let (tool, args) = match (tools.get(&t_call.function.name), args) {
+            (Some(tool), Ok(args)) => (tool, args),
+            _ => {
+                approved.push(t_call.clone());
+                continue;
+            }
+        };
+        match tool.match_against_confirm_deny(ccx.clone(), &args).await {
+            Ok(res) if matches!(res.result, MatchConfirmDenyResult::CONFIRMATION) => {
+                if policy == ToolApprovalPolicy::Approve {
+                    info!("run_task: auto-approved {:?} by rule {:?}", res.command, res.rule);
+                    approved.push(t_call.clone());
+                } else {
+                    info!("run_task: refused {:?} by rule {:?}", res.command, res.rule);
+                    refused.push(refused_tool_call_message(&t_call.id, &res.command, &res.rule));
+                    // these never reach run_tools(), it's the only place they are logged
+                    let chat_id = ccx.lock().await.chat_id.clone();
+                    let mut audit = AuditEntry::new(&chat_id, &t_call.id, &t_call.function.name, &t_call.function.arguments);
+                    audit.decision = "DENY".to_string();
+                    audit.rule = res.rule.clone();
+                    audit.set_error("needs a confirmation, nobody can confirm it in a headless run");
+                    audit_entries.push(audit);
+                }
+            }
+            _ => approved.push(t_call.clone()),
+        }
+    }
+    let gcx = ccx.lock().await.global_context.clone();
+    audit_log_append(gcx, audit_entries).await;
+    (approved, refused)
+}
+
+async fn write_run_task_results(
+    output_dir: &PathBuf,
+    messages: &Vec<ChatMessage>,
+    summary: &RunTaskSummary,
+) -> Result<(), String> {
+    tokio::fs::create_dir_all(output_dir).await
+        .map_err(|e| format!("cannot create {:?}: {}", output_dir, e))?;
+    let trajectory_path = output_dir.join("trajectory.json");
+    tokio::fs::write(&trajectory_path, serde_json::to_string_pretty(messages).unwrap()).await
+        .map_err(|e| format!("cannot write {:?}: {}", trajectory_path, e))?;
+    let summary_path = output_dir.join("summary.json");
+    tokio::fs::write(&summary_path, serde_json::to_string_pretty(summary).unwrap()).await
+        .map_err(|e| format!("cannot write {:?}: {}", summary_path, e))?;
+    info!("run_task: trajectory and summary saved to {:?}", output_dir);
+    Ok(())
+}
+
+async fn run_task_loop(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    settings: &RunTaskSettings,
+    chat_id: &str,
+    messages: &mut Vec<ChatMessage>,
+    summary: &mut RunTaskSummary,
+) -> Result<(), String> {
+    let caps = try_load_caps_quickly_if_not_present(gcx.clone(), 0).await.map_err(|e| e.message)?;
+    let model_rec = resolve_chat_model(caps, &settings.model)?;
+    summary.model = model_rec.base.id.clone();
+
+    let mut tools: IndexMap<String, Box<dyn Tool + Send>> = get_available_tools_by_chat_mode(gcx.clone(), settings.chat_mode).await
+        .into_iter()
+        .map(|tool| (tool.tool_description().name, tool))
+        .collect();
+    let tool_names: Vec<String> = tools.keys().cloned().collect();
+    info!("run_task: model {}, tools {:?}", model_rec.base.id, tool_names);
+
+    let chat_meta = ChatMeta {
+        chat_id: chat_id.to_string(),
+        chat_mode: settings.chat_mode,
+        ..Default::default()
+    };
+    *messages = prepend_the_right_system_prompt_and_maybe_more_initial_messages(
+        gcx.clone(),
+        messages.clone(),
+        &chat_meta,
+        &mut HasRagResults::new(),
+        tool_names.iter().cloned().collect::<HashSet<_>>(),
+    ).await;
+
+    if settings.chat_mode.supports_checkpoints() {
+        init_shadow_repos_if_needed(gcx.clone()).await;
+        match create_workspace_checkpoint(gcx.clone(), None, chat_id, &checkpoint_label_from_user_message(&settings.task)).await {
+            Ok((checkpoint, _)) => {
+                info!("run_task: checkpoint created: {:?}", checkpoint);
+                if let Some(user_msg) = messages.iter_mut().rev().find(|m| m.role == "user") {
+                    user_msg.checkpoints = vec![checkpoint.clone()];
+                }
+                summary.checkpoint = Some(checkpoint);
+            },
+            Err(e) => error!("run_task: failed to create checkpoint: {}", e),
+        }
+    }
+
+    let mut ccx = AtCommandsContext::new(
+        gcx.clone(),
+        model_rec.base.n_ctx,
+        CHAT_TOP_N,
+        false,
+        messages.clone(),
+        chat_id.to_string(),
+        false,
+        model_rec.base.id.clone(),
+    ).await;
+    ccx.chat_mode = Some(settings.chat_mode);
+    let ccx = Arc::new(AMutex::new(ccx));
+    let tokenizer = crate::tokens::cached_tokenizer(gcx.clone(), &model_rec.base).await?;
+
+    let mut over_budget = false;
+    while summary.steps < settings.max_steps {
+        let last_message = messages.last().unwrap().clone();
+        if last_message.role == "assistant" && last_message.tool_calls.as_ref().is_none_or(|x| x.is_empty()) {
+            break;
+        }
+        if let Some(status) = budget_check_chat(gcx.clone(), chat_id, &model_rec.base.id, messages).await {
+            over_budget = status.is_exceeded();
+            if over_budget && last_message.role == "assistant" {
+                messages.extend(budget_refuse_tool_calls(messages, &status));
+            }
+            messages.extend(budget_cd_instruction(gcx.clone(), chat_id, &status).await);
+        }
+        if over_budget {
+            // one last call without tools, so the model can summarize what is done
+            *messages = subchat_single(
+                ccx.clone(), &model_rec.base.id, messages.clone(), Some(vec![]), None, false,
+                None, Some(MAX_NEW_TOKENS), 1, None, false, Some(&mut summary.usage), None, None,
+            ).await?[0].clone();
+            summary.steps += 1;
+            break;
+        }
+        if last_message.role == "assistant" {
+            let tool_calls = last_message.tool_calls.clone().unwrap_or_default();
+            summary.tool_calls += tool_calls.len();
+            let (approved, refused) = split_tool_calls_by_approval(
+                ccx.clone(), &tools, &tool_calls, settings.tool_approval
+            ).await;
+            summary.tool_calls_refused += refused.len();
+            let mut new_messages = refused;
+            if !approved.is_empty() {
+                let mut messages_to_run = messages.clone();
+                messages_to_run.last_mut().unwrap().tool_calls = Some(approved);
+                let (tool_messages, _) = run_tools(
+                    ccx.clone(), &mut tools, tokenizer.clone(), MAX_NEW_TOKENS, &messages_to_run, &None
+                ).await?;
+                new_messages.extend(tool_messages);
+            }
+            messages.extend(new_messages);
+        }
+
+        *messages = subchat_single(
+            ccx.clone(),
+            &model_rec.base.id,
+            messages.clone(),
+            Some(tool_names.clone()),
+            Some("auto".to_string()),
+            false,
+            None,
+            Some(MAX_NEW_TOKENS),
+            1,
+            None,
+            false,
+            Some(&mut summary.usage),
+            None,
+            None,
+        ).await?[0].clone();
+        summary.steps += 1;
+        info!("run_task: step {}/{} done", summary.steps, settings.max_steps);
+    }
+
+    let last_message = messages.last().unwrap();
+    let finished = last_message.role == "assistant" && last_message.tool_calls.as_ref().is_none_or(|x| x.is_empty());
+    summary.status = if over_budget {
+        RunTaskStatus::BudgetExceeded
+    } else if finished {
+        RunTaskStatus::Completed
+    } else {
+        RunTaskStatus::MaxStepsReached
+    };
+    if finished {
+        summary.final_message = last_message.content.content_text_only();
+    }
+    Ok(())
+}
+
+pub async fn run_task(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    settings: RunTaskSettings,
+    chat_id: &str,
+) -> RunTaskSummary {
+    let t0 = std::time::Instant::now();
+    wait_for_indexing_if_needed(gcx.clone()).await;
+
+    let mut messages = vec![ChatMessage::new("user".to_string(), settings.task.clone())];
+    let mut summary = RunTaskSummary {
+        chat_id: chat_id.to_string(),
+        task: settings.task.clone(),
+        chat_mode: settings.chat_mode,
+        model: settings.model.clone(),
+        status: RunTaskStatus::Error,
+        error: None,
+        steps: 0,
+        tool_calls: 0,
+        tool_calls_refused: 0,
+        usage: ChatUsage::default(),
+        files_changed: vec![],
+        checkpoint: None,
+        final_message: String::new(),
+        duration_seconds: 0.0,
+    };
+
+    if let Err(e) = run_task_loop(gcx.clone(), &settings, chat_id, &mut messages, &mut summary).await {
+        error!("run_task: {}", e);
+        summary.status = RunTaskStatus::Error;
+        summary.error = Some(e);
+    }
+    summary.files_changed = files_changed_from_messages(&messages);
+    summary.duration_seconds = t0.elapsed().as_secs_f64();
+
+    if let Err(e) = write_run_task_results(&settings.output_dir, &messages, &summary).await {
+        error!("run_task: {}", e);
+    }
+    summary
+}
+
+/// Entry point for `--run-task`, returns the process exit code.
+pub async fn run_task_from_cmdline(gcx: Arc<ARwLock<GlobalContext>>) -> i32 {
+    let (cmdline, cache_dir) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.cmdline.clone(), gcx_locked.cache_dir.clone())
+    };
+    let chat_id = format!("task-{}", uuid::Uuid::new_v4());
+    let settings = match RunTaskSettings::from_cmdline(&cmdline, &cache_dir, &chat_id) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("--run-task: {}", e);
+            return 2;
+        }
+    };
+    let output_dir = settings.output_dir.clone();
+    let summary = run_task(gcx.clone(), settings, &chat_id).await;
+    println!("{}", output_dir.join("summary.json").display());
+    match summary.status {
+        RunTaskStatus::Completed => 0,
+        RunTaskStatus::MaxStepsReached | RunTaskStatus::BudgetExceeded | RunTaskStatus::Error => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_approval_policy_from_str() {
+        assert_eq!(ToolApprovalPolicy::from_str("deny").unwrap(), ToolApprovalPolicy::Deny);
+        assert_eq!(ToolApprovalPolicy::from_str(" Approve ").unwrap(), ToolApprovalPolicy::Approve);
+        assert_eq!(ToolApprovalPolicy::from_str("").unwrap(), ToolApprovalPolicy::Deny);
+        assert!(ToolApprovalPolicy::from_str("yolo").is_err());
+    }
+
+    #[test]
+    fn test_files_changed_from_messages() {
+        let chunks = vec![
+            DiffChunk { file_name: "/a.py".to_string(), file_action: "edit".to_string(), ..Default::default() },
+            DiffChunk { file_name: "/b.py".to_string(), file_action: "rename".to_string(), file_name_rename: Some("/c.py".to_string()), ..Default::default() },
+            DiffChunk { file_name: "/a.py".to_string(), file_action: "edit".to_string(), ..Default::default() },
+        ];
+        let messages = vec![
+            ChatMessage::new("user".to_string(), "fix it".to_string()),
+            ChatMessage::new("diff".to_string(), serde_json::to_string(&chunks).unwrap()),
+            ChatMessage::new("tool".to_string(), "[]".to_string()),
+        ];
+        assert_eq!(files_changed_from_messages(&messages), vec!["/a.py", "/b.py", "/c.py"]);
+    }
+
+    #[tokio::test]
+    async fn test_refused_tool_calls_are_audited() {
+        let t = crate::global_context::create_test_context(&[], vec![]).await;
+        let mut tools: IndexMap<String, Box<dyn Tool + Send>> = IndexMap::new();
+        tools.insert("create_textdoc".to_string(), Box::new(crate::tools::file_edit::tool_create_textdoc::ToolCreateTextDoc { config_path: "".to_string() }));
+        let tool_calls = vec![ChatToolCall {
+            id: "call_00".to_string(),
+            function: crate::call_validation::ChatToolFunction {
+                name: "create_textdoc".to_string(),
+                arguments: serde_json::json!({"path": t.dir.path().join("notes.txt"), "content": "notes\n"}).to_string(),
+            },
+            tool_type: "function".to_string(),
+        }];
+
+        let (approved, refused) = split_tool_calls_by_approval(t.ccx.clone(), &tools, &tool_calls, ToolApprovalPolicy::Deny).await;
+        assert!(approved.is_empty());
+        assert_eq!(refused.len(), 1);
+        let filter = crate::audit_log::AuditLogFilter { limit: 100, ..Default::default() };
+        let entries = crate::audit_log::audit_log_query(t.gcx.clone(), &filter).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].tool_call_id.as_str(), entries[0].decision.as_str(), entries[0].status.as_str()), ("call_00", "DENY", "not_run"));
+    }
+}
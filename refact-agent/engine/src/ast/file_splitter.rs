@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use itertools::Itertools;
 use tokenizers::Tokenizer;
@@ -11,6 +12,9 @@ use crate::ast::treesitter::ast_instance_structs::SymbolInformation;
 use crate::ast::treesitter::structs::SymbolType;
 use crate::files_in_workspace::Document;
 use crate::ast::treesitter::file_ast_markup::FileASTMarkup;
+use crate::ast::ast_structs::AstDefinition;
+use crate::tokens::count_text_tokens_with_fallback;
+use crate::vecdb::vdb_structs::SplitResult;
 
 pub(crate) const LINES_OVERLAP: usize = 3;
 
@@ -39,6 +43,15 @@ impl AstBasedFileSplitter {
         let doc_lines: Vec<String> = doc_text.split("\n").map(|x| x.to_string()).collect();
         let path = doc.doc_path.clone();
 
+        let ast_service_mb = gcx.read().await.ast_service.clone();
+        if let Some(ast_service) = ast_service_mb {
+            let ast_index = ast_service.lock().await.ast_index.clone();
+            let defs = crate::ast::ast_db::doc_defs(ast_index, &path.to_string_lossy().to_string());
+            if let Some(chunks) = split_by_ast_definitions(&path, &doc_lines, &defs, tokenizer.clone(), tokens_limit) {
+                return Ok(chunks);
+            }
+        }
+
         let (mut parser, language) = match get_ast_parser_by_filename(&path) {
             Ok(parser) => parser,
             Err(_e) => {
@@ -143,3 +156,188 @@ impl AstBasedFileSplitter {
         Ok(chunks)
     }
 }
+
+// Chunks along the definitions the AST index has for the file: a function or a class that fits into the window
+// is one chunk, a bigger class is split into its members, and a big function into pieces that repeat its signature.
+// Every piece of a symbol points to the whole symbol range, so search returns whole functions.
+// Lines outside of definitions (imports, module level code) are chunked as plain text.
+fn is_chunk_unit(def: &AstDefinition) -> bool {
+    matches!(def.symbol_type, SymbolType::StructDeclaration | SymbolType::FunctionDeclaration | SymbolType::TypeAlias)
+}
+
+fn contains(outer: &AstDefinition, inner: &AstDefinition) -> bool {
+    outer.full_line1() <= inner.full_line1() && inner.full_line2() <= outer.full_line2() &&
+        (outer.full_line1(), outer.full_line2()) != (inner.full_line1(), inner.full_line2())
+}
+
+fn make_split(path: &PathBuf, text: String, line1: usize, line2: usize, symbol_path: &String) -> SplitResult {
+    SplitResult {
+        file_path: path.clone(),
+        window_text_hash: crate::ast::chunk_utils::official_text_hashing_function(&text),
+        window_text: text,
+        start_line: (line1 - 1) as u64,
+        end_line: (line2 - 1) as u64,
+        symbol_path: symbol_path.clone(),
+    }
+}
+
+fn split_lines_range(
+    path: &PathBuf,
+    doc_lines: &Vec<String>,
+    line1: usize,
+    line2: usize,
+    units: &Vec<&Arc<AstDefinition>>,
+    owner_symbol_path: &String,
+    tokenizer: Option<Arc<Tokenizer>>,
+    tokens_limit: usize,
+    chunks: &mut Vec<SplitResult>,
+) {
+    let top_level = units.iter()
+        .filter(|u| !units.iter().any(|other| contains(other, u)))
+        .sorted_by_key(|u| u.full_line1())
+        .collect::<Vec<_>>();
+
+    let flush_gap = |gap1: usize, gap2: usize, chunks: &mut Vec<SplitResult>| {
+        if gap1 > gap2 || doc_lines[gap1 - 1..gap2].iter().all(|l| l.trim().is_empty()) {
+            return;
+        }
+        let text = doc_lines[gap1 - 1..gap2].join("\n");
+        chunks.extend(crate::ast::chunk_utils::get_chunks(&text, path, owner_symbol_path, (gap1 - 1, gap2 - 1),
+                                                          tokenizer.clone(), tokens_limit, LINES_OVERLAP, false));
+    };
+
+    let mut next_line = line1;
+    for unit in top_level {
+        let (u1, u2) = (unit.full_line1(), unit.full_line2());
+        if u1 < next_line {
+            continue;  // overlaps with the previous one, it's already chunked
+        }
+        flush_gap(next_line, u1 - 1, chunks);
+        next_line = u2 + 1;
+
+        let symbol_path = unit.path_drop0();
+        let text = doc_lines[u1 - 1..u2].join("\n");
+        if count_text_tokens_with_fallback(tokenizer.clone(), &text) <= tokens_limit {
+            chunks.push(make_split(path, text, u1, u2, &symbol_path));
+            continue;
+        }
+        let children = units.iter().filter(|other| contains(unit, other)).cloned().collect::<Vec<_>>();
+        if !children.is_empty() {
+            split_lines_range(path, doc_lines, u1, u2, &children, &symbol_path, tokenizer.clone(), tokens_limit, chunks);
+            continue;
+        }
+
+        // too big and nothing inside: pieces of the body, each one with the signature on top
+        let signature_end = unit.body_line1.saturating_sub(1).clamp(u1, u2);
+        let mut header = String::new();
+        for line in doc_lines[u1 - 1..signature_end].iter() {
+            if count_text_tokens_with_fallback(tokenizer.clone(), &format!("{}{}\n", header, line)) > tokens_limit / 4 {
+                break;
+            }
+            header.push_str(line);
+            header.push('\n');
+        }
+        let header_tokens = count_text_tokens_with_fallback(tokenizer.clone(), &header);
+        let pieces = crate::ast::chunk_utils::get_chunks(&text, path, &symbol_path, (u1 - 1, u2 - 1),
+                                                         tokenizer.clone(), tokens_limit.saturating_sub(header_tokens).max(1), LINES_OVERLAP, true);
+        for piece in pieces {
+            if header.is_empty() || piece.window_text.starts_with(header.as_str()) {
+                chunks.push(piece);
+            } else {
+                chunks.push(make_split(path, format!("{}{}", header, piece.window_text), u1, u2, &symbol_path));
+            }
+        }
+    }
+    flush_gap(next_line, line2, chunks);
+}
+
+pub fn split_by_ast_definitions(
+    path: &PathBuf,
+    doc_lines: &Vec<String>,
+    defs: &Vec<Arc<AstDefinition>>,
+    tokenizer: Option<Arc<Tokenizer>>,
+    tokens_limit: usize,
+) -> Option<Vec<SplitResult>> {
+    let units = defs.iter().filter(|d| is_chunk_unit(d)).collect::<Vec<_>>();
+    if units.is_empty() {
+        return None;
+    }
+    // the index might be behind the file on disk, don't make chunks out of wrong lines
+    for unit in units.iter() {
+        let line_ok = unit.full_line1() >= 1 && unit.full_line2() <= doc_lines.len() &&
+            doc_lines[unit.full_line1() - 1..unit.body_line1.clamp(unit.full_line1(), unit.full_line2())].iter().any(|l| l.contains(&unit.name()));
+        if !line_ok {
+            tracing::info!("AST index is out of date for {:?}, can't find {} at line {}", crate::nicer_logs::last_n_chars(&path.display().to_string(), 30), unit.name(), unit.full_line1());
+            return None;
+        }
+    }
+    let mut chunks = vec![];
+    split_lines_range(path, doc_lines, 1, doc_lines.len(), &units, &"".to_string(), tokenizer, tokens_limit, &mut chunks);
+    Some(chunks.into_iter().filter(|c| !c.window_text.is_empty()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(path: &str, symbol_type: SymbolType, line1: usize, body_line1: usize, line2: usize) -> Arc<AstDefinition> {
+        Arc::new(AstDefinition {
+            official_path: path.split("::").map(|x| x.to_string()).collect(),
+            symbol_type,
+            usages: vec![],
+            resolved_type: "".to_string(),
+            this_is_a_class: "".to_string(),
+            this_class_derived_from: vec![],
+            cpath: "/p/a.py".to_string(),
+            decl_line1: line1,
+            decl_line2: line2,
+            body_line1,
+            body_line2: line2,
+        })
+    }
+
+    #[test]
+    fn test_split_by_ast_definitions() {
+        let mut text = "import os\n\nclass Big:\n    x = 1\n\n    def small(self):\n        return 1\n\n    def huge(self, a, b):\n".to_string();
+        for i in 0..60 {
+            text.push_str(&format!("        a = a + b * {}  # step number {}\n", i, i));
+        }
+        text.push_str("        return a\n\ndef tail():\n    pass\n");
+        let lines = text.split("\n").map(|x| x.to_string()).collect::<Vec<_>>();
+        let huge_end = 9 + 60 + 1;
+        let defs = vec![
+            def("a::Big", SymbolType::StructDeclaration, 3, 4, huge_end),
+            def("a::Big::small", SymbolType::FunctionDeclaration, 6, 7, 7),
+            def("a::Big::huge", SymbolType::FunctionDeclaration, 9, 10, huge_end),
+            def("a::tail", SymbolType::FunctionDeclaration, huge_end + 2, huge_end + 3, huge_end + 3),
+            def("a::Big::x", SymbolType::ClassFieldDeclaration, 4, 4, 4),
+        ];
+        let path = PathBuf::from("/p/a.py");
+        let chunks = split_by_ast_definitions(&path, &lines, &defs, None, 200).unwrap();
+
+        let imports = chunks.iter().find(|c| c.window_text.starts_with("import os")).unwrap();
+        assert_eq!((imports.symbol_path.as_str(), imports.start_line), ("", 0));
+        let class_header = chunks.iter().find(|c| c.window_text.starts_with("class Big:")).unwrap();
+        assert_eq!(class_header.symbol_path, "a::Big");
+        let small = chunks.iter().find(|c| c.symbol_path == "a::Big::small").unwrap();
+        assert_eq!((small.start_line, small.end_line), (5, 6));
+        assert_eq!(small.window_text, "    def small(self):\n        return 1");
+
+        let huge = chunks.iter().filter(|c| c.symbol_path == "a::Big::huge").collect::<Vec<_>>();
+        assert!(huge.len() > 1);
+        for piece in huge.iter() {
+            assert!(piece.window_text.starts_with("    def huge(self, a, b):\n"), "{}", piece.window_text);
+            assert_eq!((piece.start_line, piece.end_line), (8, huge_end as u64 - 1));
+            assert!(count_text_tokens_with_fallback(None, &piece.window_text) <= 200);
+        }
+        assert!(huge.iter().any(|c| c.window_text.contains("step number 59")));
+
+        let tail = chunks.iter().find(|c| c.symbol_path == "a::tail").unwrap();
+        assert_eq!(tail.window_text, "def tail():\n    pass");
+
+        // the index is behind the file
+        let stale = vec![def("a::tail", SymbolType::FunctionDeclaration, 2, 3, 3)];
+        assert!(split_by_ast_definitions(&path, &lines, &stale, None, 200).is_none());
+        assert!(split_by_ast_definitions(&path, &lines, &vec![], None, 200).is_none());
+    }
+}
@@ -90,6 +90,7 @@ pub fn get_language_id_by_filename(filename: &PathBuf) -> Option<LanguageId> {
         "cpp" | "cc" | "cxx" | "c++" | "c" | "h" | "hpp" | "hxx" | "hh" => Some(LanguageId::Cpp),
         "inl" | "inc" | "tpp" | "tpl" => Some(LanguageId::Cpp),
         "py" | "py3" | "pyx" => Some(LanguageId::Python),
+        "ipynb" => Some(LanguageId::Python),  // see notebooks.rs
         "java" => Some(LanguageId::Java),
         "js" | "jsx" => Some(LanguageId::JavaScript),
         "rs" => Some(LanguageId::Rust),
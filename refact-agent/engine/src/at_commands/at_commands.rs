@@ -7,13 +7,14 @@ use async_trait::async_trait;
 use tokio::sync::Mutex as AMutex;
 use tokio::sync::RwLock as ARwLock;
 
-use crate::call_validation::{ChatMessage, ContextFile, ContextEnum, SubchatParameters, PostprocessSettings};
+use crate::call_validation::{ChatMessage, ChatMode, ContextFile, ContextEnum, SubchatParameters, PostprocessSettings};
 use crate::global_context::GlobalContext;
 
 use crate::at_commands::at_file::AtFile;
 use crate::at_commands::at_ast_definition::AtAstDefinition;
 use crate::at_commands::at_ast_reference::AtAstReference;
 use crate::at_commands::at_tree::AtTree;
+use crate::at_commands::at_diff::{AtDiff, AtDiffRev};
 use crate::at_commands::at_web::AtWeb;
 use crate::at_commands::execute_at::AtCommandMember;
 
@@ -29,7 +30,12 @@ pub struct AtCommandsContext {
     pub pp_skeleton: bool,
     pub correction_only_up_to_step: usize,  // suppresses context_file messages, writes a correction message instead
     pub chat_id: String,
+    pub chat_mode: Option<ChatMode>,
+    pub write_globs: Vec<String>,  // from the custom chat mode, see check_write_permissions
     pub current_model: String,
+    pub current_tool: String,  // set while a tool runs, so the usage of its subchats is attributed to it
+    pub subagent: String,  // set in a subagent's subchat, nobody is there to confirm its tool calls, see ToolSubagent
+    pub subagent_skips_confirmation: bool,
     pub should_execute_remotely: bool,
 
     pub at_commands: HashMap<String, Arc<dyn AtCommand + Send>>,  // a copy from static constant
@@ -62,7 +68,12 @@ impl AtCommandsContext {
             pp_skeleton: true,
             correction_only_up_to_step: 0,
             chat_id,
+            chat_mode: None,
+            write_globs: vec![],
             current_model,
+            current_tool: String::new(),
+            subagent: String::new(),
+            subagent_skips_confirmation: false,
             should_execute_remotely,
 
             at_commands: at_commands_dict(global_context.clone()).await,
@@ -98,8 +109,8 @@ pub async fn at_commands_dict(gcx: Arc<ARwLock<GlobalContext>>) -> HashMap<Strin
         ("@references".to_string(), Arc::new(AtAstReference::new()) as Arc<dyn AtCommand + Send>),
         // ("@local-notes-to-self".to_string(), Arc::new(AtLocalNotesToSelf::new()) as Arc<dyn AtCommand + Send>),
         ("@tree".to_string(), Arc::new(AtTree::new()) as Arc<dyn AtCommand + Send>),
-        // ("@diff".to_string(), Arc::new(AtDiff::new()) as Arc<dyn AtCommand + Send>),
-        // ("@diff-rev".to_string(), Arc::new(AtDiffRev::new()) as Arc<dyn AtCommand + Send>),
+        ("@diff".to_string(), Arc::new(AtDiff::new()) as Arc<dyn AtCommand + Send>),
+        ("@diff-rev".to_string(), Arc::new(AtDiffRev::new()) as Arc<dyn AtCommand + Send>),
         ("@web".to_string(), Arc::new(AtWeb::new()) as Arc<dyn AtCommand + Send>),
         ("@search".to_string(), Arc::new(crate::at_commands::at_search::AtSearch::new()) as Arc<dyn AtCommand + Send>),
         ("@knowledge-load".to_string(), Arc::new(crate::at_commands::at_knowledge::AtLoadKnowledge::new()) as Arc<dyn AtCommand + Send>),
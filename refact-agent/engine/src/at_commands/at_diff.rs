@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use async_trait::async_trait;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+use tracing::{info, warn};
+
+use crate::at_commands::at_commands::{AtCommand, AtCommandsContext, AtParam};
+use crate::at_commands::execute_at::AtCommandMember;
+use crate::call_validation::{ChatMessage, ContextEnum};
+use crate::git::operations::{git_diff_head_to_workdir_as_string, git_diff_rev_to_workdir_as_string};
+use crate::global_context::GlobalContext;
+
+
+const DIFF_MIN_CHARS: usize = 16_000;
+
+pub struct AtDiff {
+    pub params: Vec<Box<dyn AtParam>>,
+}
+
+impl AtDiff {
+    pub fn new() -> Self {
+        AtDiff {
+            params: vec![],
+        }
+    }
+}
+
+pub struct AtDiffRev {
+    pub params: Vec<Box<dyn AtParam>>,
+}
+
+impl AtDiffRev {
+    pub fn new() -> Self {
+        AtDiffRev {
+            params: vec![],
+        }
+    }
+}
+
+pub async fn git_repos_in_workspace(gcx: Arc<ARwLock<GlobalContext>>) -> Vec<(PathBuf, git2::Repository)> {
+    let workspace_vcs_roots_arc = gcx.read().await.documents_state.workspace_vcs_roots.clone();
+    let workspace_vcs_roots = workspace_vcs_roots_arc.lock().unwrap().clone();
+    workspace_vcs_roots.into_iter()
+        .filter_map(|root| git2::Repository::open(&root).ok().map(|repo| (root, repo)))
+        .collect()
+}
+
+/// `rev` is None for the working tree vs HEAD, repositories without changes are skipped
+async fn diff_all_repos(ccx: Arc<AMutex<AtCommandsContext>>, rev: Option<&str>) -> Result<String, String> {
+    let (gcx, tokens_for_rag) = {
+        let ccx_locked = ccx.lock().await;
+        (ccx_locked.global_context.clone(), ccx_locked.tokens_for_rag)
+    };
+    let repos = git_repos_in_workspace(gcx).await;
+    if repos.is_empty() {
+        return Err("no git repositories in the workspace".to_string());
+    }
+    let max_chars = (tokens_for_rag * 3).max(DIFF_MIN_CHARS) / repos.len();
+    let mut result = String::new();
+    let mut errors = vec![];
+    for (root, repo) in repos.iter() {
+        let diff_mb = match rev {
+            Some(rev) => git_diff_rev_to_workdir_as_string(repo, rev, max_chars),
+            None => git_diff_head_to_workdir_as_string(repo, max_chars),
+        };
+        match diff_mb {
+            Ok(diff) if diff.is_empty() => {},
+            Ok(diff) => {
+                if repos.len() > 1 {
+                    result.push_str(&format!("Repository {}\n", root.display()));
+                }
+                result.push_str(&diff);
+            },
+            Err(e) => {
+                warn!("diff in {:?}: {}", root, e);
+                errors.push(format!("{}: {}", root.display(), e));
+            },
+        }
+    }
+    if result.is_empty() && !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    if result.is_empty() {
+        result = match rev {
+            Some(rev) => format!("no changes since {}", rev),
+            None => "no uncommitted changes".to_string(),
+        };
+    }
+    Ok(result)
+}
+
+#[async_trait]
+impl AtCommand for AtDiff {
+    fn params(&self) -> &Vec<Box<dyn AtParam>> {
+        &self.params
+    }
+
+    async fn at_execute(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        cmd: &mut AtCommandMember,
+        args: &mut Vec<AtCommandMember>,
+    ) -> Result<(Vec<ContextEnum>, String), String> {
+        args.clear();
+        let diff = diff_all_repos(ccx.clone(), None).await.inspect_err(|e| {
+            cmd.ok = false;
+            cmd.reason = Some(e.clone());
+        })?;
+        info!("executed @diff, {} chars", diff.len());
+        Ok((vec![ContextEnum::ChatMessage(ChatMessage::new("plain_text".to_string(), diff))], "".to_string()))
+    }
+}
+
+#[async_trait]
+impl AtCommand for AtDiffRev {
+    fn params(&self) -> &Vec<Box<dyn AtParam>> {
+        &self.params
+    }
+
+    async fn at_execute(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        cmd: &mut AtCommandMember,
+        args: &mut Vec<AtCommandMember>,
+    ) -> Result<(Vec<ContextEnum>, String), String> {
+        let rev = match args.first() {
+            Some(x) if x.text != "\n" => x.text.clone(),
+            _ => {
+                cmd.ok = false; cmd.reason = Some("missing revision, try @diff-rev main".to_string());
+                args.clear();
+                return Err("missing revision".to_string());
+            }
+        };
+        args.truncate(1);
+        let diff = diff_all_repos(ccx.clone(), Some(&rev)).await.inspect_err(|e| {
+            cmd.ok = false;
+            cmd.reason = Some(e.clone());
+            args.clear();
+        })?;
+        info!("executed @diff-rev {}, {} chars", rev, diff.len());
+        Ok((vec![ContextEnum::ChatMessage(ChatMessage::new("plain_text".to_string(), diff))], "".to_string()))
+    }
+}
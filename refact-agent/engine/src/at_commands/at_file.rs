@@ -185,6 +185,25 @@ pub async fn return_one_candidate_or_a_good_error(
     Ok(candidate)
 }
 
+/// Finds an existing file, or else a directory, for rm() and mv(): Ok(Some((path, is_dir))), or Ok(None) if there are no candidates at all.
+/// Their permission checks call it too, to look at the same path that will be touched.
+pub async fn resolve_existing_file_or_dir(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path_str: &String,
+    top_n: usize,
+) -> Result<Option<(String, bool)>, String> {
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    let file_candidates = correct_to_nearest_filename(gcx.clone(), path_str, false, top_n).await;
+    if !file_candidates.is_empty() {
+        return return_one_candidate_or_a_good_error(gcx.clone(), path_str, &file_candidates, &project_dirs, false).await.map(|path| Some((path, false)));
+    }
+    let dir_candidates = correct_to_nearest_dir_path(gcx.clone(), path_str, false, top_n).await;
+    if !dir_candidates.is_empty() {
+        return return_one_candidate_or_a_good_error(gcx.clone(), path_str, &dir_candidates, &project_dirs, true).await.map(|path| Some((path, true)));
+    }
+    Ok(None)
+}
+
 
 #[derive(Debug)]
 pub struct AtParamFilePath {}
@@ -50,7 +50,7 @@ fn results2message(results: &Vec<vecdb::vdb_structs::VecdbRecord>) -> Vec<Contex
             file_content: "".to_string(),
             line1: r.start_line as usize + 1,
             line2: r.end_line as usize + 1,
-            symbols: vec![],
+            symbols: if r.symbol_path.is_empty() { vec![] } else { vec![r.symbol_path.clone()] },
             gradient_type: 4,
             usefulness,
         });
@@ -68,18 +68,35 @@ pub async fn execute_at_search(
         (ccx_locked.global_context.clone(), ccx_locked.top_n)
     };
 
+    let rerank_model = match crate::global_context::try_load_caps_quickly_if_not_present(gcx.clone(), 0).await {
+        Ok(caps) if caps.rerank_model.is_configured() => Some(caps.rerank_model.clone()),
+        _ => None,
+    };
+
     let vec_db = gcx.read().await.vec_db.clone();
-    let r = match *vec_db.lock().await {
+    let results = match *vec_db.lock().await {
         Some(ref db) => {
             let top_n_twice_as_big = top_n * 2;  // top_n will be cut at postprocessing stage, and we really care about top_n files, not pieces
+            let candidates_n = match &rerank_model {
+                Some(rerank_model) => top_n_twice_as_big.max(rerank_model.top_k),
+                None => top_n_twice_as_big,
+            };
             // TODO: this code sucks, release lock, don't hold anything during the search
-            let search_result = db.vecdb_search(query.clone(), top_n_twice_as_big, vecdb_scope_filter_mb).await?;
-            let results = search_result.results.clone();
-            return Ok(results2message(&results));
+            let search_result = db.vecdb_search(query.clone(), candidates_n, vecdb_scope_filter_mb).await?;
+            search_result.results
+        }
+        None => return Err("VecDB is not active. Possible reasons: VecDB is turned off in settings, or perhaps a vectorization model is not available.".to_string())
+    };
+
+    let results = match &rerank_model {
+        Some(rerank_model) => {
+            let mut reranked = vecdb::vdb_rerank::rerank_search_results(gcx.clone(), rerank_model, query, results).await;
+            reranked.truncate(top_n * 2);
+            reranked
         }
-        None => Err("VecDB is not active. Possible reasons: VecDB is turned off in settings, or perhaps a vectorization model is not available.".to_string())
+        None => results,
     };
-    r
+    Ok(results2message(&results))
 }
 
 #[async_trait]
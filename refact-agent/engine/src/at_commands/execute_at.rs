@@ -12,6 +12,8 @@ use crate::http::routers::v1::at_commands::{CommandExecutePost, CommandExecuteRe
 use crate::integrations::docker::docker_container_manager::docker_container_get_host_lsp_port_to_connect;
 use crate::postprocessing::pp_context_files::postprocess_context_files;
 use crate::postprocessing::pp_plain_text::postprocess_plain_text;
+use crate::postprocessing::pp_redact::{count_placeholders, log_redactions, redactions_note, RedactionCounts};
+use crate::privacy::load_privacy_if_needed;
 use crate::scratchpads::scratchpad_utils::{HasRagResults, max_tokens_for_rag_chat};
 
 
@@ -73,6 +75,7 @@ pub async fn run_at_commands_locally(
 
         info!("msg {} user_posted {:?} which is {} tokens, that leaves {} tokens for context of this message", idx + user_msg_starts, crate::nicer_logs::first_n_chars(&content, 50), content_n_tokens, context_limit);
 
+        let messages_before_this_one = new_messages.len();
         let mut messages_exec_output = vec![];
         if content.contains("@") {
             let (res, _) = execute_at_commands_in_query(ccx.clone(), &mut content).await;
@@ -86,17 +89,25 @@ pub async fn run_at_commands_locally(
         };
 
         let mut plain_text_messages = vec![];
+        let mut other_messages = vec![];
         for exec_result in messages_exec_output.into_iter() {
             // at commands exec() can produce role "user" "assistant" "diff" "plain_text"
             if let ContextEnum::ChatMessage(raw_msg) = exec_result {  // means not context_file
                 if raw_msg.role != "plain_text" {
-                    stream_back_to_user.push_in_json(json!(raw_msg));
-                    new_messages.push(raw_msg);
+                    other_messages.push(raw_msg);
                 } else {
                     plain_text_messages.push(raw_msg);
                 }
             }
         }
+        // plain text and files are redacted in postprocessing, the rest goes as it is
+        let mut redaction_counts = RedactionCounts::new();
+        load_privacy_if_needed(gcx.clone()).await.redactor.redact_messages(&mut other_messages, &mut redaction_counts);
+        log_redactions(&redaction_counts, "at-command output");
+        for m in other_messages {
+            stream_back_to_user.push_in_json(json!(m));
+            new_messages.push(m);
+        }
 
         // TODO: reduce context_limit by tokens(messages_exec_output)
 
@@ -112,7 +123,9 @@ pub async fn run_at_commands_locally(
 
             let t0 = std::time::Instant::now();
 
+            let gcx = ccx.lock().await.global_context.clone();
             let (pp_plain_text, non_used_plain) = postprocess_plain_text(
+                gcx,
                 plain_text_messages,
                 tokenizer.clone(),
                 tokens_limit_plain,
@@ -156,6 +169,11 @@ pub async fn run_at_commands_locally(
             info!("postprocess_plain_text_messages + postprocess_context_files {:.3}s", t0.elapsed().as_secs_f32());
         }
 
+        if let Some(note) = redactions_note(&count_placeholders(new_messages[messages_before_this_one..].iter())) {
+            stream_back_to_user.push_in_json(json!(note));
+            new_messages.push(note);
+        }
+
         if content.trim().len() > 0 {
             // stream back to the user, with at-commands replaced
             msg.content = ChatContent::SimpleText(content);
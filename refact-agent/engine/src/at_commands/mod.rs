@@ -5,5 +5,6 @@ pub mod at_commands;
 pub mod at_file;
 pub mod at_web;
 pub mod at_tree;
+pub mod at_diff;
 pub mod at_search;
 pub mod at_knowledge;
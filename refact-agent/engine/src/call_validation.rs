@@ -243,7 +243,7 @@ pub struct ChatPost {
     #[serde(default)]
     pub tool_choice: Option<String>,
     #[serde(default)]
-    pub checkpoints_enabled: bool,
+    pub checkpoints_enabled: Option<bool>,  // None: the custom mode decides, or off
     #[serde(default)]
     pub only_deterministic_messages: bool, // means don't sample from the model
     #[serde(default)]
@@ -267,6 +267,8 @@ pub struct ChatMeta {
     #[serde(default)]
     pub chat_mode: ChatMode,
     #[serde(default)]
+    pub custom_mode: String,  // a name from chat_modes in customization, chat_mode is then its base mode
+    #[serde(default)]
     pub current_config_file: String,
 }
 
@@ -87,6 +87,8 @@ pub struct ChatModelRecord {
     pub supports_boost_reasoning: bool,
     #[serde(default)]
     pub default_temperature: Option<f32>,
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
 }
 
 pub fn default_chat_scratchpad() -> String { "PASSTHROUGH".to_string() }
@@ -171,6 +173,93 @@ impl EmbeddingModelRecord {
     }
 }
 
+/// A vecdb index next to the default one, with its own embedding model. Files go to the first index
+/// whose filters match, the rest goes to the default index built with `embedding_model`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EmbeddingIndexRecord {
+    pub model: EmbeddingModelRecord,
+    #[serde(default)]
+    pub file_extensions: Vec<String>,  // without the dot: md, rst, txt
+    #[serde(default)]
+    pub additional_indexing_dirs: bool,  // takes files from indexing.yaml additional_indexing_dirs
+}
+
+impl EmbeddingIndexRecord {
+    pub fn has_filters(&self) -> bool {
+        !self.file_extensions.is_empty() || self.additional_indexing_dirs
+    }
+}
+
+/// Rescores top vecdb results before they go to postprocessing. "api" posts query and chunks to a /rerank style
+/// endpoint (cross-encoders and LLM rerankers served by vLLM, TEI, Cohere, Jina), "lexical" needs no model at all.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RerankModelRecord {
+    #[serde(flatten)]
+    pub base: BaseModelRecord,
+
+    #[serde(default = "default_rerank_style")]
+    pub rerank_style: String,
+    #[serde(default = "default_rerank_top_k")]
+    pub top_k: usize,
+}
+
+pub fn default_rerank_style() -> String { "api".to_string() }
+
+pub fn default_rerank_top_k() -> usize { 30 }
+
+impl HasBaseModelRecord for RerankModelRecord {
+    fn base(&self) -> &BaseModelRecord { &self.base }
+    fn base_mut(&mut self) -> &mut BaseModelRecord { &mut self.base }
+}
+
+impl RerankModelRecord {
+    pub fn is_configured(&self) -> bool {
+        self.rerank_style == "lexical" || !self.base.name.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UsageTokens {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub cache_read_tokens: usize,
+    pub cache_creation_tokens: usize,
+}
+
+impl UsageTokens {
+    /// Streaming providers repeat cumulative usage or split it across chunks (Anthropic sends the prompt
+    /// in message_start and the completion in message_delta), the max of each field covers both
+    pub fn merge_stream_chunk(&mut self, other: &UsageTokens) {
+        self.prompt_tokens = self.prompt_tokens.max(other.prompt_tokens);
+        self.completion_tokens = self.completion_tokens.max(other.completion_tokens);
+        self.cache_read_tokens = self.cache_read_tokens.max(other.cache_read_tokens);
+        self.cache_creation_tokens = self.cache_creation_tokens.max(other.cache_creation_tokens);
+    }
+}
+
+/// USD per 1M tokens, the same format as `metadata.pricing` in cloud caps
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ModelPricing {
+    #[serde(default)]
+    pub prompt: f64,
+    #[serde(default)]
+    pub generated: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+    #[serde(default, alias = "cache_write", skip_serializing_if = "Option::is_none")]
+    pub cache_creation: Option<f64>,
+}
+
+impl ModelPricing {
+    /// Cache reads and writes cost the prompt price when the pricing doesn't say otherwise
+    pub fn cost_usd(&self, tokens: &UsageTokens) -> f64 {
+        (tokens.prompt_tokens as f64 * self.prompt
+            + tokens.completion_tokens as f64 * self.generated
+            + tokens.cache_read_tokens as f64 * self.cache_read.unwrap_or(self.prompt)
+            + tokens.cache_creation_tokens as f64 * self.cache_creation.unwrap_or(self.prompt)) / 1_000_000.0
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CapsMetadata {
     pub pricing: serde_json::Value,
@@ -193,6 +282,10 @@ pub struct CodeAssistantCaps {
     pub chat_models: IndexMap<String, Arc<ChatModelRecord>>,
     #[serde(skip_deserializing)]
     pub embedding_model: EmbeddingModelRecord,
+    #[serde(skip_deserializing)]
+    pub embedding_indexes: IndexMap<String, EmbeddingIndexRecord>,
+    #[serde(skip_deserializing)]
+    pub rerank_model: RerankModelRecord,
 
     #[serde(flatten, skip_deserializing)]
     pub defaults: DefaultModels,
@@ -412,6 +505,16 @@ pub fn resolve_chat_model<'a>(
     resolve_model(&caps.chat_models, model_id)
 }
 
+/// Pricing from the provider config wins, then cloud caps metadata, looked up by model name
+pub fn resolve_chat_model_pricing(caps: &CodeAssistantCaps, model_rec: &ChatModelRecord) -> Option<ModelPricing> {
+    if let Some(pricing) = &model_rec.pricing {
+        return Some(pricing.clone());
+    }
+    [&model_rec.base.name, &model_rec.base.id].iter()
+        .filter_map(|key| caps.metadata.pricing.get(key.as_str()))
+        .find_map(|v| serde_json::from_value::<ModelPricing>(v.clone()).ok())
+}
+
 pub fn resolve_completion_model<'a>(
     caps: Arc<CodeAssistantCaps>,
     requested_model_id: &str,
@@ -8,7 +8,7 @@ use structopt::StructOpt;
 
 use crate::caps::{
     BaseModelRecord, ChatModelRecord, CodeAssistantCaps, CompletionModelRecord, DefaultModels,
-    EmbeddingModelRecord, HasBaseModelRecord, default_embedding_batch, default_rejection_threshold,
+    EmbeddingIndexRecord, EmbeddingModelRecord, HasBaseModelRecord, RerankModelRecord, default_embedding_batch, default_rejection_threshold,
     load_caps_value_from_url, resolve_relative_urls, strip_model_from_finetune, normalize_string
 };
 use crate::custom_error::{MapErrToString, YamlError};
@@ -34,6 +34,8 @@ pub struct CapsProvider {
     pub chat_endpoint: String,
     #[serde(default, alias = "endpoint_embeddings_template")]
     pub embedding_endpoint: String,
+    #[serde(default)]
+    pub rerank_endpoint: String,
 
     #[serde(default)]
     pub api_key: String,
@@ -53,6 +55,10 @@ pub struct CapsProvider {
     pub chat_models: IndexMap<String, ChatModelRecord>,
     #[serde(default, alias = "default_embeddings_model")]
     pub embedding_model: EmbeddingModelRecord,
+    #[serde(default)]
+    pub embedding_indexes: IndexMap<String, EmbeddingIndexRecord>,
+    #[serde(default)]
+    pub rerank_model: RerankModelRecord,
 
     #[serde(default)]
     pub models_dict_patch: IndexMap<String, serde_json::Value>, // Used to patch some params from cloud, like n_ctx for pro/free users
@@ -78,6 +84,9 @@ impl CapsProvider {
             self.embedding_model.base.removable = true;
             self.embedding_model.base.user_configured = true;
         }
+        set_field_if_exists::<IndexMap<String, EmbeddingIndexRecord>>(&mut self.embedding_indexes, "embedding_indexes", &value)?;
+        set_field_if_exists::<String>(&mut self.rerank_endpoint, "rerank_endpoint", &value)?;
+        set_field_if_exists::<RerankModelRecord>(&mut self.rerank_model, "rerank_model", &value)?;
 
         extend_model_collection::<ChatModelRecord>(&mut self.chat_models, "chat_models", &value, &self.running_models)?;
         extend_model_collection::<CompletionModelRecord>(&mut self.completion_models, "completion_models", &value, &self.running_models)?;
@@ -456,6 +465,35 @@ pub fn add_models_to_caps(caps: &mut CodeAssistantCaps, providers: Vec<CapsProvi
             caps.embedding_model = embedding_model;
         }
 
+        let embedding_indexes = std::mem::take(&mut provider.embedding_indexes);
+        for (index_name, mut index_rec) in embedding_indexes {
+            if !index_rec.model.is_configured() || !index_rec.model.base.enabled {
+                continue;
+            }
+            if !index_rec.has_filters() {
+                tracing::warn!("embedding index {:?} of provider {} has neither file_extensions nor additional_indexing_dirs, skipping", index_name, provider.name);
+                continue;
+            }
+            if index_rec.model.base.endpoint.is_empty() {
+                let model_name = index_rec.model.base.name.clone();
+                add_provider_details_to_model(
+                    &mut index_rec.model.base, &provider, &model_name, &provider.embedding_endpoint
+                );
+            }
+            caps.embedding_indexes.insert(index_name, index_rec);
+        }
+
+        if provider.rerank_model.is_configured() && provider.rerank_model.base.enabled {
+            let mut rerank_model = std::mem::take(&mut provider.rerank_model);
+            if rerank_model.base.endpoint.is_empty() {
+                let model_name = rerank_model.base.name.clone();
+                add_provider_details_to_model(
+                    &mut rerank_model.base, &provider, &model_name, &provider.rerank_endpoint
+                );
+            }
+            caps.rerank_model = rerank_model;
+        }
+
         caps.defaults.apply_override(&provider.defaults, Some(&provider.name));
     }
 }
@@ -474,6 +512,14 @@ fn add_name_and_id_to_model_records(provider: &mut CapsProvider) {
     if provider.embedding_model.is_configured() {
         provider.embedding_model.base.id = format!("{}/{}", provider.name, provider.embedding_model.base.name);
     }
+    for index_rec in provider.embedding_indexes.values_mut() {
+        if index_rec.model.is_configured() {
+            index_rec.model.base.id = format!("{}/{}", provider.name, index_rec.model.base.name);
+        }
+    }
+    if provider.rerank_model.is_configured() {
+        provider.rerank_model.base.id = format!("{}/{}", provider.name, provider.rerank_model.base.name);
+    }
 }
 
 fn apply_models_dict_patch(provider: &mut CapsProvider) {
@@ -537,7 +583,8 @@ fn populate_model_records(provider: &mut CapsProvider, experimental: bool) {
     for model in &provider.running_models {
         if !provider.completion_models.contains_key(model) &&
             !provider.chat_models.contains_key(model) &&
-            !(model == &provider.embedding_model.base.name) {
+            model != &provider.embedding_model.base.name &&
+            !provider.embedding_indexes.values().any(|i| model == &i.model.base.name) {
             tracing::warn!("Indicated as running, unknown model {:?} for provider {}, maybe update this rust binary", model, provider.name);
         }
     }
@@ -551,6 +598,19 @@ fn populate_model_records(provider: &mut CapsProvider, experimental: bool) {
             tracing::warn!("Unknown embedding model '{}', maybe configure it or update this binary", model_name);
         }
     }
+
+    for (index_name, index_rec) in provider.embedding_indexes.iter_mut() {
+        if index_rec.model.is_configured() || index_rec.model.base.name.is_empty() {
+            continue;
+        }
+        let model_name = index_rec.model.base.name.clone();
+        if let Some(model_rec) = find_model_match(&model_name, &IndexMap::new(), &known_models.embedding_models, experimental) {
+            index_rec.model = model_rec;
+            index_rec.model.base.name = model_name;
+        } else {
+            tracing::warn!("Unknown embedding model '{}' for index {:?}, maybe configure it or update this binary", model_name, index_name);
+        }
+    }
 }
 
 fn find_model_match<T: Clone + HasBaseModelRecord>(
@@ -688,4 +748,30 @@ mod tests {
     fn test_parse_known_models() {
         let _ = get_known_models(); // This will panic if any model fails to parse
     }
+
+    #[test]
+    fn test_embedding_indexes() {
+        let mut provider: CapsProvider = serde_yaml::from_str(r#"
+name: myprovider
+embedding_endpoint: https://example.com/v1/$MODEL/embeddings
+api_key: sk-123
+embedding_model: text-embedding-3-small
+embedding_indexes:
+  docs:
+    model: text-embedding-3-small
+    file_extensions: [md, rst]
+  unfiltered:
+    model: text-embedding-3-small
+"#).unwrap();
+        post_process_provider(&mut provider, true, false);
+        let mut caps = CodeAssistantCaps::default();
+        add_models_to_caps(&mut caps, vec![provider]);
+        assert_eq!(caps.embedding_indexes.len(), 1);
+        let docs = &caps.embedding_indexes["docs"];
+        assert_eq!(docs.file_extensions, vec!["md", "rst"]);
+        assert_eq!(docs.model.embedding_size, 1536);
+        assert_eq!(docs.model.base.id, "myprovider/text-embedding-3-small");
+        assert_eq!(docs.model.base.endpoint, "https://example.com/v1/text-embedding-3-small/embeddings");
+        assert_eq!(docs.model.base.api_key, "sk-123");
+    }
 }
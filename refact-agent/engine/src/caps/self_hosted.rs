@@ -6,7 +6,7 @@ use serde::Deserialize;
 
 use crate::caps::{
     BaseModelRecord, ChatModelRecord, CodeAssistantCaps, CompletionModelRecord, DefaultModels,
-    EmbeddingModelRecord, CapsMetadata, default_chat_scratchpad, default_completion_scratchpad,
+    EmbeddingModelRecord, RerankModelRecord, CapsMetadata, default_chat_scratchpad, default_completion_scratchpad,
     default_completion_scratchpad_patch, default_embedding_batch, default_hf_tokenizer_template,
     default_rejection_threshold, relative_to_full_url, normalize_string, resolve_relative_urls
 };
@@ -225,6 +225,7 @@ impl SelfHostedCapsModelRecord {
             supports_reasoning: self.supports_reasoning.clone(),
             supports_boost_reasoning: self.supports_boost_reasoning,
             default_temperature: self.default_temperature,
+            pricing: None,
         })
     }
 }
@@ -271,6 +272,8 @@ impl SelfHostedCaps {
             completion_models: IndexMap::new(),
             chat_models: IndexMap::new(),
             embedding_model: EmbeddingModelRecord::default(),
+            embedding_indexes: IndexMap::new(),
+            rerank_model: RerankModelRecord::default(),
 
             defaults: DefaultModels {
                 completion_default_model: format!("{}/{}", self.cloud_name, self.completion.default_model),
@@ -338,6 +341,7 @@ impl SelfHostedCaps {
             completion_endpoint: self.completion.endpoint.clone(),
             chat_endpoint: self.chat.endpoint.clone(),
             embedding_endpoint: self.embedding.endpoint.clone(),
+            rerank_endpoint: String::new(),
             api_key: cmdline_api_key.to_string(),
             tokenizer_api_key: cmdline_api_key.to_string(),
             code_completion_n_ctx: 0,
@@ -345,6 +349,8 @@ impl SelfHostedCaps {
             completion_models: IndexMap::new(),
             chat_models: IndexMap::new(),
             embedding_model: EmbeddingModelRecord::default(),
+            embedding_indexes: IndexMap::new(),
+            rerank_model: RerankModelRecord::default(),
             models_dict_patch: IndexMap::new(),
             defaults: DefaultModels {
                 completion_default_model: self.completion.default_model.clone(),
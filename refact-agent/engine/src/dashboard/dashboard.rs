@@ -3,6 +3,7 @@ use chrono::{Datelike, DateTime};
 use serde_json::{json, Value};
 use crate::dashboard::structs::{RHData, RHTableStatsByDate, RHTableStatsByLang};
 use crate::dashboard::utils::{get_week_n};
+use crate::telemetry::usage_ledger::UsageReportRow;
 
 
 async fn table_stats_by_lang(records: &Vec<RHData>) -> Value {
@@ -106,4 +107,39 @@ pub async fn records2plots(records: &mut Vec<RHData>) -> Result<Value, String>{
         "table_refact_impact": table_refact_impact_json,
         "refact_impact_dates": refact_impact_dates_json,
     }))
-}
\ No newline at end of file
+}
+
+fn usage_group_str(row: &UsageReportRow, key: &str) -> String {
+    row.group.get(key).and_then(|x| x.as_str()).unwrap_or_default().to_string()
+}
+
+/// Plots from the local usage ledger, they work without any telemetry sent
+pub fn usage2plots(by_model: &Vec<UsageReportRow>, by_day: &Vec<UsageReportRow>) -> Value {
+    let table_rows: Vec<Value> = by_model.iter().map(|r| json!({
+        "provider": usage_group_str(r, "provider"),
+        "model": usage_group_str(r, "model"),
+        "requests": r.requests,
+        "prompt_tokens": r.tokens.prompt_tokens,
+        "completion_tokens": r.tokens.completion_tokens,
+        "cache_read_tokens": r.tokens.cache_read_tokens,
+        "cost_usd": (r.cost_usd * 100.0).round() / 100.0,
+    })).collect();
+    let mut daily: Vec<&UsageReportRow> = by_day.iter().collect();
+    daily.sort_by_key(|r| usage_group_str(r, "day"));
+    json!({
+        "table_usage_by_model": {
+            "data": table_rows,
+            "columns": vec!["Provider", "Model", "Requests", "Prompt", "Completion", "Cached", "Cost (USD)"],
+            "title": "Usage by model",
+        },
+        "usage_dates": {
+            "data": {
+                "daily": daily.iter().map(|r| (usage_group_str(r, "day"), json!({
+                    "requests": r.requests,
+                    "tokens": r.tokens.prompt_tokens + r.tokens.completion_tokens,
+                    "cost_usd": r.cost_usd,
+                }))).collect::<serde_json::Map<String, Value>>(),
+            }
+        },
+    })
+}
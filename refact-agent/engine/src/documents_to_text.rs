@@ -0,0 +1,290 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+// PDF, docx and odt are binary, readers (cat, @file, vecdb) get their text instead: every page starts with
+// a `=== page N ===` line, so a page range maps to a line range, and long paragraphs are wrapped to keep
+// lines short for vecdb splitting and line-based reading. Docx and odt don't store pages, page breaks
+// saved by the word processor are used.
+
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["pdf", "docx", "odt"];
+const DOCUMENT_WRAP_WIDTH: usize = 120;
+
+pub fn is_document(path: &Path) -> bool {
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    DOCUMENT_EXTENSIONS.contains(&ext.as_str())
+}
+
+pub fn document_to_text(path: &Path, bytes: &[u8]) -> Result<String, String> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let pages = match ext.as_str() {
+        "pdf" => pdf_pages(bytes)?,
+        "docx" => docx_pages(bytes)?,
+        "odt" => odt_pages(bytes)?,
+        _ => return Err(format!("{} is not a pdf, docx or odt document", path.display())),
+    };
+    if pages.iter().all(|p| p.trim().is_empty()) {
+        return Err(format!("{} has no text, maybe it's scanned images", path.display()));
+    }
+    Ok(pages_to_text(&pages))
+}
+
+fn pdf_pages(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let doc = lopdf::Document::load_mem(bytes).map_err(|e| format!("cannot parse pdf: {}", e))?;
+    let mut pages = vec![];
+    for page_n in doc.get_pages().keys() {
+        // one broken page (unknown font encoding and such) should not hide the rest of the document
+        pages.push(doc.extract_text(&[*page_n]).unwrap_or_else(|e| format!("[cannot extract text: {}]\n", e)));
+    }
+    Ok(pages)
+}
+
+fn zip_entry_text(bytes: &[u8], name: &str) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("cannot open as zip: {}", e))?;
+    let mut entry = archive.by_name(name).map_err(|e| format!("cannot find {}: {}", name, e))?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text).map_err(|e| format!("cannot read {}: {}", name, e))?;
+    Ok(text)
+}
+
+#[derive(Default)]
+struct PagesCollector {
+    pages: Vec<String>,
+    current: String,
+}
+
+impl PagesCollector {
+    fn page_break(&mut self) {
+        // word processors save both explicit breaks and the place the page was rendered last time, don't make empty pages
+        if !self.current.trim().is_empty() {
+            self.pages.push(std::mem::take(&mut self.current));
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.page_break();
+        self.pages
+    }
+}
+
+fn docx_walk(node: roxmltree::Node, collector: &mut PagesCollector) {
+    match node.tag_name().name() {
+        "t" => collector.current.push_str(node.text().unwrap_or_default()),
+        // w:tab is also a tab stop in paragraph properties, only the one inside a run is text
+        "tab" if node.parent_element().map(|p| p.tag_name().name() == "r").unwrap_or(false) => collector.current.push('\t'),
+        "br" | "cr" => {
+            if node.attributes().any(|a| a.name() == "type" && a.value() == "page") {
+                collector.page_break();
+            } else {
+                collector.current.push('\n');
+            }
+        },
+        "lastRenderedPageBreak" => collector.page_break(),
+        _ => {},
+    }
+    for child in node.children().filter(|c| c.is_element()) {
+        docx_walk(child, collector);
+    }
+    if node.tag_name().name() == "p" {
+        collector.current.push('\n');
+    }
+}
+
+fn docx_pages(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let xml = zip_entry_text(bytes, "word/document.xml")?;
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| format!("cannot parse word/document.xml: {}", e))?;
+    let mut collector = PagesCollector::default();
+    docx_walk(doc.root_element(), &mut collector);
+    Ok(collector.finish())
+}
+
+fn odt_walk(node: roxmltree::Node, collector: &mut PagesCollector) {
+    if node.is_text() {
+        // whitespace between tags is formatting, inside paragraphs it's text
+        if node.ancestors().any(|a| matches!(a.tag_name().name(), "p" | "h")) {
+            collector.current.push_str(node.text().unwrap_or_default());
+        }
+        return;
+    }
+    match node.tag_name().name() {
+        "s" => {
+            let count = node.attributes().find(|a| a.name() == "c").and_then(|a| a.value().parse::<usize>().ok()).unwrap_or(1);
+            collector.current.push_str(&" ".repeat(count));
+        },
+        "tab" => collector.current.push('\t'),
+        "line-break" => collector.current.push('\n'),
+        "soft-page-break" => collector.page_break(),
+        _ => {},
+    }
+    for child in node.children() {
+        odt_walk(child, collector);
+    }
+    if matches!(node.tag_name().name(), "p" | "h") {
+        collector.current.push('\n');
+    }
+}
+
+fn odt_pages(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let xml = zip_entry_text(bytes, "content.xml")?;
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| format!("cannot parse content.xml: {}", e))?;
+    let mut collector = PagesCollector::default();
+    odt_walk(doc.root_element(), &mut collector);
+    Ok(collector.finish())
+}
+
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut result = vec![];
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            result.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    result.push(current);
+    result
+}
+
+fn pages_to_text(pages: &Vec<String>) -> String {
+    let mut text = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        text.push_str(&format!("=== page {} ===\n", i + 1));
+        let mut prev_empty = true;
+        for line in page.lines() {
+            let line = line.trim_end();
+            if line.trim().is_empty() {
+                // pdf text is full of empty lines, one is enough
+                if !prev_empty {
+                    text.push('\n');
+                }
+                prev_empty = true;
+                continue;
+            }
+            prev_empty = false;
+            if line.chars().count() <= DOCUMENT_WRAP_WIDTH {
+                text.push_str(line);
+                text.push('\n');
+            } else {
+                for wrapped in wrap_line(line, DOCUMENT_WRAP_WIDTH) {
+                    text.push_str(&wrapped);
+                    text.push('\n');
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Lines (1-based, inclusive) that pages page1..=page2 occupy in the text made by document_to_text
+pub fn document_page_range_to_lines(text: &str, page1: usize, page2: usize) -> Option<(usize, usize)> {
+    let mut line1 = None;
+    let mut line2 = None;
+    let mut total_lines = 0;
+    for (i, line) in text.lines().enumerate() {
+        total_lines = i + 1;
+        let page_n = match line.strip_prefix("=== page ").and_then(|x| x.strip_suffix(" ===")).and_then(|x| x.parse::<usize>().ok()) {
+            Some(n) => n,
+            None => continue,
+        };
+        if page_n == page1 {
+            line1 = Some(i + 1);
+        }
+        if page_n == page2 + 1 {
+            line2 = Some(i);
+            break;
+        }
+    }
+    line1.map(|l1| (l1, line2.unwrap_or(total_lines)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use lopdf::dictionary;
+
+    fn make_zip(name: &str, content: &str) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn make_pdf(pages_text: &[&str]) -> Vec<u8> {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{Document, Object, Stream};
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {"Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier"});
+        let resources_id = doc.add_object(dictionary! {"Font" => dictionary! {"F1" => font_id}});
+        let mut kids = vec![];
+        for page_text in pages_text {
+            let content = Content { operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![100.into(), 600.into()]),
+                Operation::new("Tj", vec![Object::string_literal(*page_text)]),
+                Operation::new("ET", vec![]),
+            ]};
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            kids.push(doc.add_object(dictionary! {"Type" => "Page", "Parent" => pages_id, "Contents" => content_id}).into());
+        }
+        let pages = dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! {"Type" => "Catalog", "Pages" => pages_id});
+        doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_pdf_to_text() {
+        let pdf = make_pdf(&["Introduction to the protocol", "Message format"]);
+        let text = document_to_text(Path::new("spec.pdf"), &pdf).unwrap();
+        assert!(text.starts_with("=== page 1 ===\nIntroduction to the protocol"), "{}", text);
+        assert!(text.contains("=== page 2 ===\nMessage format"), "{}", text);
+        assert_eq!(document_page_range_to_lines(&text, 2, 2).map(|(l1, _)| l1), Some(text.lines().position(|l| l == "=== page 2 ===").unwrap() + 1));
+        assert!(document_to_text(Path::new("broken.pdf"), b"%PDF-1.5 garbage").is_err());
+    }
+
+    #[test]
+    fn test_docx_and_odt_to_text() {
+        let long_sentence = "word ".repeat(50);
+        let docx = make_zip("word/document.xml", &format!(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:r><w:t>Design</w:t></w:r><w:r><w:t xml:space="preserve"> notes</w:t></w:r></w:p>
+<w:p><w:r><w:t>{}</w:t></w:r></w:p>
+<w:p><w:r><w:br w:type="page"/></w:r><w:r><w:lastRenderedPageBreak/><w:t>Second</w:t><w:tab/><w:t>page</w:t></w:r></w:p>
+</w:body></w:document>"#, long_sentence));
+        let text = document_to_text(Path::new("notes.DOCX"), &docx).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "=== page 1 ===");
+        assert_eq!(lines[1], "Design notes");
+        assert!(lines[2].len() <= DOCUMENT_WRAP_WIDTH && lines[3].starts_with("word"));
+        assert_eq!(lines[lines.len() - 2..], ["=== page 2 ===", "Second\tpage"]);
+        assert_eq!(document_page_range_to_lines(&text, 1, 1), Some((1, lines.len() - 2)));
+        assert_eq!(document_page_range_to_lines(&text, 2, 5), Some((lines.len() - 1, lines.len())));
+        assert_eq!(document_page_range_to_lines(&text, 3, 3), None);
+
+        let odt = make_zip("content.xml", r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0">
+  <office:body><office:text>
+    <text:h>Title</text:h>
+    <text:p>a<text:s text:c="2"/>b <text:span>c</text:span></text:p>
+    <text:soft-page-break/>
+    <text:p>next page</text:p>
+  </office:text></office:body>
+</office:document-content>"#);
+        assert_eq!(document_to_text(Path::new("a.odt"), &odt).unwrap(), "=== page 1 ===\nTitle\na  b c\n=== page 2 ===\nnext page\n");
+        assert!(document_to_text(Path::new("a.docx"), &make_zip("word/document.xml", "<w:document xmlns:w=\"x\"/>")).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 
 const LARGE_FILE_SIZE_THRESHOLD: u64 = 4096*1024; // 4Mb files
+const LARGE_DOCUMENT_SIZE_THRESHOLD: u64 = 32*1024*1024; // pdf, docx: mostly images and fonts, the text is much smaller
 const SMALL_FILE_SIZE_THRESHOLD: u64 = 5;        // 5 Bytes
 
 pub const SOURCE_FILE_EXTENSIONS: &[&str] = &[
@@ -13,7 +14,7 @@ pub const SOURCE_FILE_EXTENSIONS: &[&str] = &[
     "scss", "sass", "less", "json", "xml", "yml", "yaml", "md", "sql", "cfg",
     "conf", "ini", "toml", "dockerfile", "ipynb", "rmd", "xml", "kt", "xaml",
     "unity", "gd", "uproject", "asm", "s", "tex", "makefile", "mk", "cmake",
-    "gradle", "liquid"
+    "gradle", "liquid", "pdf", "docx", "odt"
 ];
 
 pub fn is_valid_file(path: &PathBuf, allow_hidden_folders: bool, ignore_size_thresholds: bool) -> Result<(), Box<dyn std::error::Error>> {
@@ -34,7 +35,8 @@ pub fn is_valid_file(path: &PathBuf, allow_hidden_folders: bool, ignore_size_thr
         if !ignore_size_thresholds && file_size < SMALL_FILE_SIZE_THRESHOLD {
             return Err("File size is too small".into());
         }
-        if !ignore_size_thresholds && file_size > LARGE_FILE_SIZE_THRESHOLD {
+        let large_threshold = if crate::documents_to_text::is_document(path) { LARGE_DOCUMENT_SIZE_THRESHOLD } else { LARGE_FILE_SIZE_THRESHOLD };
+        if !ignore_size_thresholds && file_size > large_threshold {
             return Err("File size is too large".into());
         }
         #[cfg(not(windows))]
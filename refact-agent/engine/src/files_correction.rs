@@ -4,7 +4,7 @@ use std::path::{PathBuf, Component, Path};
 use serde::Deserialize;
 use tokio::process::Command;
 use tokio::sync::RwLock as ARwLock;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::global_context::GlobalContext;
 use crate::custom_error::MapErrToString;
@@ -369,6 +369,25 @@ pub fn any_glob_matches_path(globs: &[String], path: &Path) -> bool {
     })
 }
 
+/// For globs relative to a project root: a glob without a slash matches file names at any depth,
+/// `dir/**` also matches `dir` itself
+pub fn project_glob_matches(glob: &str, relative: &Path) -> bool {
+    let glob = glob.trim().trim_start_matches("./");
+    let glob = if glob.contains('/') { glob.to_string() } else { format!("**/{}", glob) };
+    if let Some(dir) = glob.strip_suffix("/**") {
+        if glob::Pattern::new(dir).map(|p| p.matches_path(relative)).unwrap_or(false) {
+            return true;
+        }
+    }
+    match glob::Pattern::new(&glob) {
+        Ok(pattern) => pattern.matches_path(relative),
+        Err(e) => {
+            warn!("bad glob {:?}: {}", glob, e);
+            false
+        }
+    }
+}
+
 pub fn serialize_path<S: serde::Serializer>(path: &PathBuf, serializer: S) -> Result<S::Ok, S::Error> {
     serializer.serialize_str(&path.to_string_lossy())
 }
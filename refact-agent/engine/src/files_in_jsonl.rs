@@ -43,7 +43,7 @@ pub async fn enqueue_all_docs_from_jsonl(
         }
     }
     match *vec_db_module.lock().await {
-        Some(ref mut db) => db.vectorizer_enqueue_files(&docs, false).await,
+        Some(ref mut db) => db.vectorizer_enqueue_files(gcx.clone(), &docs, false).await,
         None => {},
     };
 }
@@ -65,8 +65,8 @@ pub async fn get_file_text_from_memory_or_disk(global_context: Arc<ARwLock<Globa
 
     if let Some(doc) = global_context.read().await.documents_state.memory_document_map.get(file_path) {
         let doc = doc.read().await;
-        if doc.doc_text.is_some() {
-            return Ok(doc.doc_text.as_ref().unwrap().to_string());
+        if let Some(doc_text) = &doc.doc_text {
+            return Ok(crate::notebooks::notebook_view_if_notebook(file_path, doc_text.to_string()));
         }
     }
     read_file_from_disk_without_privacy_check(&file_path)
@@ -74,6 +74,16 @@ pub async fn get_file_text_from_memory_or_disk(global_context: Arc<ARwLock<Globa
         .map_err(|e|format!("Not found in memory, not found on disk: {}", e))
 }
 
+/// Notebooks come as the JSON on disk, not as the python view, for diffs that have to restore the exact file
+pub async fn get_raw_file_text_from_memory_or_disk(global_context: Arc<ARwLock<GlobalContext>>, file_path: &PathBuf) -> Result<String, String>
+{
+    if !crate::notebooks::is_notebook(file_path) {
+        return get_file_text_from_memory_or_disk(global_context, file_path).await;
+    }
+    check_file_privacy(load_privacy_if_needed(global_context.clone()).await, &file_path, &FilePrivacyLevel::AllowToSendAnywhere)?;
+    tokio::fs::read_to_string(file_path).await.map_err(|e| format!("Failed to read {:?}: {}", file_path, e))
+}
+
 impl Document {
     pub fn new(doc_path: &PathBuf) -> Self {
         Self { doc_path: doc_path.clone(),  doc_text: None }
@@ -246,8 +256,17 @@ pub async fn watcher_init(
 async fn read_file_from_disk_without_privacy_check(
     path: &PathBuf,
 ) -> Result<Rope, String> {
+    if crate::documents_to_text::is_document(path) {
+        let bytes = tokio::fs::read(path).await
+            .map_err(|e| format!("failed to read file {}: {}", crate::nicer_logs::last_n_chars(&path.display().to_string(), 30), e))?;
+        let path_copy = path.clone();
+        // big pdfs take a while to parse
+        let text = tokio::task::spawn_blocking(move || crate::documents_to_text::document_to_text(&path_copy, &bytes)).await
+            .map_err(|e| format!("failed to extract text from {}: {}", crate::nicer_logs::last_n_chars(&path.display().to_string(), 30), e))??;
+        return Ok(Rope::from_str(&text));
+    }
     tokio::fs::read_to_string(path).await
-        .map(|x|Rope::from_str(&x))
+        .map(|x|Rope::from_str(&crate::notebooks::notebook_view_if_notebook(path, x)))
         .map_err(|e|
             format!("failed to read file {}: {}", crate::nicer_logs::last_n_chars(&path.display().to_string(), 30), e)
         )
@@ -566,7 +585,7 @@ async fn enqueue_some_docs(
         (cx.vec_db.clone(), cx.ast_service.clone())
     };
     if let Some(ref mut db) = *vec_db_module.lock().await {
-        db.vectorizer_enqueue_files(&paths, force).await;
+        db.vectorizer_enqueue_files(gcx.clone(), &paths, force).await;
     }
     if let Some(ast) = &ast_service {
         ast_indexer_enqueue_files(ast.clone(), paths, force).await;
@@ -638,7 +657,7 @@ pub async fn enqueue_all_files_from_workspace_folders(
     let paths_nodups: Vec<String> = updated_or_removed.into_iter().collect();
 
     if let Some(ref mut db) = *vec_db_module.lock().await {
-        db.vectorizer_enqueue_files(&paths_nodups, wake_up_indexers).await;
+        db.vectorizer_enqueue_files(gcx.clone(), &paths_nodups, wake_up_indexers).await;
     }
 
     if let Some(ast) = ast_service {
@@ -15,7 +15,7 @@ use crate::files_blocklist::reload_indexing_everywhere_if_needed;
 use crate::files_correction::{deserialize_path, get_active_workspace_folder, get_project_dirs, serialize_path};
 use crate::global_context::GlobalContext;
 use crate::git::{FileChange, FileChangeStatus, from_unix_glob_pattern_to_gitignore};
-use crate::git::operations::{checkout_head_and_branch_to_commit, commit, get_commit_datetime, get_diff_statuses, get_diff_statuses_index_to_commit, get_or_create_branch, stage_changes, open_or_init_repo};
+use crate::git::operations::{add_changes_to_index, checkout_head_and_branch_to_commit, commit, get_commit_datetime, get_diff_statuses, get_diff_statuses_index_to_commit, get_or_create_branch, stage_changes, open_or_init_repo};
 use crate::git::cleanup::RECENT_COMMITS_DURATION;
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
@@ -23,6 +23,10 @@ pub struct Checkpoint {
     #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
     pub workspace_folder: PathBuf,
     pub commit_hash: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub created_ts: i64,
 }
 
 impl Checkpoint {
@@ -31,6 +35,58 @@ impl Checkpoint {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointFileDiff {
+    #[serde(flatten)]
+    pub file_change: FileChange,
+    pub diff: String,
+    pub hunks_n: usize,
+}
+
+/// `hunks` are indices in the diff between the checkpoint and the current file, as /v1/checkpoints-diff shows them
+/// without `to`, None restores the whole file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointFileRestore {
+    pub path: String,
+    #[serde(default)]
+    pub hunks: Option<Vec<usize>>,
+}
+
+const CHECKPOINT_COMMIT_PREFIX: &str = "Auto commit for chat";
+const CHECKPOINT_LABEL_MAX_CHARS: usize = 80;
+const CHECKPOINTS_LIST_MAX: usize = 500;
+
+pub fn checkpoint_label_from_user_message(text: &str) -> String {
+    let first_line = text.trim().lines().next().unwrap_or_default().trim();
+    if first_line.chars().count() > CHECKPOINT_LABEL_MAX_CHARS {
+        format!("{}...", first_line.chars().take(CHECKPOINT_LABEL_MAX_CHARS).collect::<String>())
+    } else {
+        first_line.to_string()
+    }
+}
+
+fn checkpoint_commit_message(chat_id: &str, label: &str) -> String {
+    if label.is_empty() {
+        format!("{CHECKPOINT_COMMIT_PREFIX} {chat_id}")
+    } else {
+        format!("{CHECKPOINT_COMMIT_PREFIX} {chat_id}\n\n{label}")
+    }
+}
+
+fn checkpoint_from_commit(workspace_folder: &Path, chat_id: &str, commit: &git2::Commit) -> Option<Checkpoint> {
+    let message = commit.message().unwrap_or_default();
+    let (first_line, rest) = message.split_once('\n').unwrap_or((message, ""));
+    if first_line != format!("{CHECKPOINT_COMMIT_PREFIX} {chat_id}") {
+        return None;
+    }
+    Some(Checkpoint {
+        workspace_folder: workspace_folder.to_path_buf(),
+        commit_hash: commit.id().to_string(),
+        label: rest.trim().to_string(),
+        created_ts: commit.time().seconds(),
+    })
+}
+
 async fn open_shadow_repo_and_nested_repos(
     gcx: Arc<ARwLock<GlobalContext>>, workspace_folder: &Path, allow_init_main_repo: bool,
 ) -> Result<(Repository, Vec<Repository>, String), String> {
@@ -116,6 +172,7 @@ pub async fn create_workspace_checkpoint(
     gcx: Arc<ARwLock<GlobalContext>>,
     prev_checkpoint: Option<&Checkpoint>,
     chat_id: &str,
+    label: &str,
 ) -> Result<(Checkpoint, Repository), String> {
     let t0 = Instant::now();
 
@@ -146,13 +203,18 @@ pub async fn create_workspace_checkpoint(
         file_changes.extend(flatened_nested_file_changes);
 
         stage_changes(&repo, &file_changes, &abort_flag)?;
-        let commit_oid = commit(&repo, &branch, &format!("Auto commit for chat {chat_id}"), "Refact Agent", "agent@refact.ai")?;
+        let commit_oid = commit(&repo, &branch, &checkpoint_commit_message(chat_id, label), "Refact Agent", "agent@refact.ai")?;
 
         for (nested_repo, changes) in nested_file_changes {
             stage_changes(&nested_repo, &changes, &abort_flag)?;
         }
 
-        Checkpoint {workspace_folder, commit_hash: commit_oid.to_string()}
+        Checkpoint {
+            workspace_folder,
+            commit_hash: commit_oid.to_string(),
+            label: label.to_string(),
+            created_ts: Utc::now().timestamp(),
+        }
     };
 
     tracing::info!("Checkpoint created in {:.2}s", t0.elapsed().as_secs_f64());
@@ -163,7 +225,7 @@ pub async fn create_workspace_checkpoint(
 pub async fn preview_changes_for_workspace_checkpoint(
     gcx: Arc<ARwLock<GlobalContext>>, checkpoint_to_restore: &Checkpoint, chat_id: &str
 ) -> Result<(Vec<FileChange>, DateTime<Utc>, Checkpoint), String> {
-    let (checkpoint_for_undo, repo) = create_workspace_checkpoint(gcx.clone(), Some(checkpoint_to_restore), chat_id).await?;
+    let (checkpoint_for_undo, repo) = create_workspace_checkpoint(gcx.clone(), Some(checkpoint_to_restore), chat_id, "Before restoring a checkpoint").await?;
 
     let commit_to_restore_oid = Oid::from_str(&checkpoint_to_restore.commit_hash).map_err_to_string()?;
     let reverted_to = get_commit_datetime(&repo, &commit_to_restore_oid)?;
@@ -230,6 +292,198 @@ pub async fn restore_workspace_checkpoint(
     Ok(())
 }
 
+/// All checkpoints of the chat, newest first, including the ones that are not on the branch anymore after a restore
+pub async fn list_workspace_checkpoints(
+    gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str
+) -> Result<Vec<Checkpoint>, String> {
+    let workspace_folder = get_active_workspace_folder(gcx.clone()).await
+        .ok_or_else(|| "No active workspace folder".to_string())?;
+    let (repo, _, _) = open_shadow_repo_and_nested_repos(gcx.clone(), &workspace_folder, false).await?;
+
+    let ref_name = format!("refs/heads/refact-{chat_id}");
+    let mut oids: Vec<Oid> = match repo.reflog(&ref_name) {
+        Ok(reflog) => reflog.iter().map(|entry| entry.id_new()).collect(),
+        Err(_) => vec![],
+    };
+    if let Ok(tip) = repo.refname_to_id(&ref_name) {
+        let mut revwalk = repo.revwalk().map_err_to_string()?;
+        revwalk.push(tip).map_err_to_string()?;
+        oids.extend(revwalk.filter_map(|oid| oid.ok()).take(CHECKPOINTS_LIST_MAX));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut checkpoints: Vec<Checkpoint> = oids.into_iter()
+        .filter(|oid| seen.insert(*oid))
+        .filter_map(|oid| repo.find_commit(oid).ok())
+        .filter_map(|commit| checkpoint_from_commit(&workspace_folder, chat_id, &commit))
+        .collect();
+    checkpoints.sort_by_key(|c| std::cmp::Reverse(c.created_ts));
+    checkpoints.truncate(CHECKPOINTS_LIST_MAX);
+    Ok(checkpoints)
+}
+
+fn file_change_status_from_delta(delta: git2::Delta) -> FileChangeStatus {
+    match delta {
+        git2::Delta::Added | git2::Delta::Untracked => FileChangeStatus::ADDED,
+        git2::Delta::Deleted => FileChangeStatus::DELETED,
+        _ => FileChangeStatus::MODIFIED,
+    }
+}
+
+/// The workspace as the next checkpoint would save it, only the tree is written: no commit, and the index
+/// on disk stays as it is
+fn write_workspace_tree(repo: &Repository, nested_repos: &[Repository], abort_flag: &Arc<AtomicBool>) -> Result<Oid, String> {
+    let (_, mut file_changes) = get_diff_statuses(git2::StatusShow::Workdir, repo, false)?;
+    let (_, nested_file_changes) = get_file_changes_from_nested_repos(repo, nested_repos, false)?;
+    file_changes.extend(nested_file_changes);
+
+    let mut index = repo.index().map_err_to_string()?;
+    add_changes_to_index(&mut index, &file_changes, abort_flag)?;
+    let tree_oid = index.write_tree().map_err_to_string();
+    index.read(true).map_err_to_string()?;
+    tree_oid
+}
+
+/// Without `to` the checkpoint is compared to the workspace as it is now, `to` in the result is None then
+pub async fn diff_workspace_checkpoints(
+    gcx: Arc<ARwLock<GlobalContext>>, from: &Checkpoint, to: Option<&Checkpoint>,
+) -> Result<(Vec<CheckpointFileDiff>, Option<Checkpoint>), String> {
+    let abort_flag: Arc<AtomicBool> = gcx.read().await.git_operations_abort_flag.clone();
+    let workspace_folder = match to {
+        Some(to) => to.workspace_folder.clone(),
+        None => get_active_workspace_folder(gcx.clone()).await
+            .ok_or_else(|| "No active workspace folder".to_string())?,
+    };
+    let (repo, nested_repos, workspace_folder_hash) =
+        open_shadow_repo_and_nested_repos(gcx.clone(), &workspace_folder, false).await?;
+    if from.workspace_hash() != workspace_folder_hash || to.is_some_and(|to| to.workspace_hash() != workspace_folder_hash) {
+        return Err("Can not compare checkpoints for different workspace folders".to_string());
+    }
+
+    let tree_of = |commit_hash: &str| -> Result<git2::Tree, String> {
+        let oid = Oid::from_str(commit_hash).map_err_to_string()?;
+        repo.find_commit(oid).and_then(|c| c.tree())
+            .map_err(|e| format!("Checkpoint {} not found, it may have expired: {}", commit_hash, e))
+    };
+    let from_tree = tree_of(&from.commit_hash)?;
+    let to_tree = match to {
+        Some(to) => tree_of(&to.commit_hash)?,
+        None => {
+            let tree_oid = write_workspace_tree(&repo, &nested_repos, &abort_flag)?;
+            repo.find_tree(tree_oid).map_err_to_string()?
+        },
+    };
+    let diff = repo.diff_tree_to_tree(Some(&from_tree), Some(&to_tree), None).map_err_to_string()?;
+
+    let mut result = Vec::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let relative_path = delta.new_file().path().or(delta.old_file().path())
+            .map(|p| p.to_path_buf()).unwrap_or_default();
+        let (diff_text, hunks_n) = match git2::Patch::from_diff(&diff, idx).map_err_to_string()? {
+            Some(mut patch) => {
+                let text = patch.to_buf().map(|buf| buf.as_str().unwrap_or_default().to_string()).unwrap_or_default();
+                (text, patch.num_hunks())
+            },
+            None => ("Binary files differ\n".to_string(), 0),
+        };
+        result.push(CheckpointFileDiff {
+            file_change: FileChange {
+                absolute_path: workspace_folder.join(&relative_path),
+                relative_path,
+                status: file_change_status_from_delta(delta.status()),
+            },
+            diff: diff_text,
+            hunks_n,
+        });
+    }
+    Ok((result, to.cloned()))
+}
+
+/// Puts back selected hunks of `checkpoint_text` into `current_text`, hunk numbers are the same as in
+/// a patch from checkpoint to current with the default 3 context lines.
+pub fn restore_hunks_in_text(checkpoint_text: &str, current_text: &str, hunks: &[usize]) -> Result<String, String> {
+    let patch = git2::Patch::from_buffers(checkpoint_text.as_bytes(), None, current_text.as_bytes(), None, None)
+        .map_err_to_string()?;
+    let hunks_n = patch.num_hunks();
+    if let Some(bad) = hunks.iter().find(|h| **h >= hunks_n) {
+        return Err(format!("hunk {} does not exist, the file has {} changed hunks now", bad, hunks_n));
+    }
+    let old_lines: Vec<&str> = checkpoint_text.split_inclusive('\n').collect();
+    let mut new_lines: Vec<&str> = current_text.split_inclusive('\n').collect();
+    let mut ranges = Vec::new();
+    for hunk_idx in hunks {
+        let (hunk, _) = patch.hunk(*hunk_idx).map_err_to_string()?;
+        // for an empty side, start points to the line before the hunk
+        let old_from = if hunk.old_lines() == 0 { hunk.old_start() } else { hunk.old_start() - 1 } as usize;
+        let new_from = if hunk.new_lines() == 0 { hunk.new_start() } else { hunk.new_start() - 1 } as usize;
+        ranges.push((new_from, hunk.new_lines() as usize, old_from, hunk.old_lines() as usize));
+    }
+    ranges.sort_by_key(|r| std::cmp::Reverse(r.0));
+    ranges.dedup();
+    for (new_from, new_n, old_from, old_n) in ranges {
+        new_lines.splice(new_from..new_from + new_n, old_lines[old_from..old_from + old_n].iter().cloned());
+    }
+    Ok(new_lines.concat())
+}
+
+/// Restores some files (or some hunks in them) from a checkpoint, the rest of the workspace stays as it is.
+/// Nothing is written unless every file can be restored, errors writing single files are collected.
+/// Returns the restored files, a checkpoint made right before to undo, and those errors.
+pub async fn restore_files_from_workspace_checkpoint(
+    gcx: Arc<ARwLock<GlobalContext>>, checkpoint_to_restore: &Checkpoint, files: &[CheckpointFileRestore], chat_id: &str
+) -> Result<(Vec<FileChange>, Checkpoint, Vec<String>), String> {
+    for file in files {
+        if !Path::new(&file.path).components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+            return Err(format!("{:?} should be relative to the workspace folder", file.path));
+        }
+    }
+    let (checkpoint_for_undo, repo) = create_workspace_checkpoint(
+        gcx.clone(), Some(checkpoint_to_restore), chat_id, "Before restoring files").await?;
+    let commit_oid = Oid::from_str(&checkpoint_to_restore.commit_hash).map_err_to_string()?;
+    let tree = repo.find_commit(commit_oid).and_then(|c| c.tree())
+        .map_err(|e| format!("Checkpoint {} not found, it may have expired: {}", checkpoint_to_restore.commit_hash, e))?;
+
+    let mut planned = Vec::new();
+    for file in files {
+        let relative_path = PathBuf::from(&file.path);
+        let absolute_path = checkpoint_to_restore.workspace_folder.join(&relative_path);
+        let checkpoint_content: Option<Vec<u8>> = match tree.get_path(&relative_path) {
+            Ok(entry) => Some(repo.find_blob(entry.id()).map_err_to_string()?.content().to_vec()),
+            Err(_) => None,
+        };
+        let current_content: Option<Vec<u8>> = std::fs::read(&absolute_path).ok();
+
+        let new_content = match &file.hunks {
+            None => checkpoint_content.clone(),
+            Some(hunks) => {
+                let as_text = |bytes: &Option<Vec<u8>>| -> Result<String, String> {
+                    String::from_utf8(bytes.clone().unwrap_or_default()).map_err(|_| format!("{} is not a text file, restore it whole", file.path))
+                };
+                let text = restore_hunks_in_text(&as_text(&checkpoint_content)?, &as_text(&current_content)?, hunks)?;
+                if checkpoint_content.is_none() && text.is_empty() { None } else { Some(text.into_bytes()) }
+            }
+        };
+        planned.push((relative_path, absolute_path, current_content.is_some(), new_content));
+    }
+
+    let mut restored = Vec::new();
+    let mut errors = Vec::new();
+    for (relative_path, absolute_path, exists_now, new_content) in planned {
+        let result = match (exists_now, &new_content) {
+            (_, Some(content)) => absolute_path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&absolute_path, content))
+                .map(|_| if exists_now { FileChangeStatus::MODIFIED } else { FileChangeStatus::ADDED }),
+            (true, None) => std::fs::remove_file(&absolute_path).map(|_| FileChangeStatus::DELETED),
+            (false, None) => continue,
+        };
+        match result {
+            Ok(status) => restored.push(FileChange { relative_path, absolute_path, status }),
+            Err(e) => errors.push(format!("Failed to restore {:?}: {}", absolute_path, e)),
+        }
+    }
+    Ok((restored, checkpoint_for_undo, errors))
+}
+
 pub async fn init_shadow_repos_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> () {
     let init_shadow_repos_lock: Arc<AMutex<bool>> = gcx.read().await.init_shadow_repos_lock.clone();
     let _init_shadow_repos_lock = init_shadow_repos_lock.lock().await;  // wait for previous init
@@ -305,4 +559,54 @@ pub async fn abort_init_shadow_repos(
     // NOTE: actually we can't abort git tasks, so we should use atomic abort_flag here
     gcx_locked.git_operations_abort_flag.store(true, Ordering::SeqCst);
     gcx_locked.init_shadow_repos_background_task_holder.abort().await;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_hunks_in_text() {
+        let checkpoint = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let current = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        assert_eq!(restore_hunks_in_text(checkpoint, current, &[0]).unwrap(), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n");
+        assert_eq!(restore_hunks_in_text(checkpoint, current, &[1]).unwrap(), "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n");
+        assert_eq!(restore_hunks_in_text(checkpoint, current, &[1, 0]).unwrap(), checkpoint);
+        assert_eq!(restore_hunks_in_text(checkpoint, current, &[]).unwrap(), current);
+        assert!(restore_hunks_in_text(checkpoint, current, &[2]).is_err());
+        assert_eq!(restore_hunks_in_text("", "new file\n", &[0]).unwrap(), "");
+        assert_eq!(restore_hunks_in_text("x\n", "", &[0]).unwrap(), "x\n");
+    }
+
+    #[test]
+    fn test_checkpoint_label() {
+        assert_eq!(checkpoint_label_from_user_message("  fix the bug\nin main.rs"), "fix the bug");
+        assert_eq!(checkpoint_label_from_user_message(&"x".repeat(100)), format!("{}...", "x".repeat(80)));
+        let message = checkpoint_commit_message("chat1", "fix the bug");
+        assert!(message.starts_with("Auto commit for chat chat1\n"));
+    }
+
+    #[test]
+    fn test_write_workspace_tree_does_not_commit() {
+        let shadow_dir = tempfile::TempDir::new().unwrap();
+        let workspace_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(workspace_dir.path().join("a.txt"), "old\n").unwrap();
+        let repo = Repository::init(shadow_dir.path()).unwrap();
+        repo.set_workdir(workspace_dir.path(), false).unwrap();
+        let abort_flag = Arc::new(AtomicBool::new(false));
+        let initial_tree = write_workspace_tree(&repo, &[], &abort_flag).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let head = repo.commit(Some("HEAD"), &signature, &signature, "init", &repo.find_tree(initial_tree).unwrap(), &[]).unwrap();
+
+        std::fs::write(workspace_dir.path().join("a.txt"), "new\n").unwrap();
+        std::fs::write(workspace_dir.path().join("b.txt"), "added\n").unwrap();
+        let tree = repo.find_tree(write_workspace_tree(&repo, &[], &abort_flag).unwrap()).unwrap();
+        let blob_text = |name: &str| String::from_utf8(repo.find_blob(tree.get_name(name).unwrap().id()).unwrap().content().to_vec()).unwrap();
+        assert_eq!(blob_text("a.txt"), "new\n");
+        assert_eq!(blob_text("b.txt"), "added\n");
+
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        let (_, still_unstaged) = get_diff_statuses(git2::StatusShow::Workdir, &repo, false).unwrap();
+        assert_eq!(still_unstaged.len(), 2);
+    }
+}
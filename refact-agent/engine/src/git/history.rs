@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{TimeZone, Utc};
+use git2::{BlameOptions, Commit, DiffOptions, Oid, Repository};
+
+use crate::custom_error::MapErrToString;
+use crate::git::operations::git_diff_as_string;
+
+// Read-only history queries for the git_history tool, paths are relative to the repository workdir.
+
+const LOG_MAX_WALK: usize = 10_000;
+
+fn short_id(oid: &Oid) -> String {
+    oid.to_string().chars().take(8).collect()
+}
+
+fn commit_date(commit: &Commit, fmt: &str) -> String {
+    Utc.timestamp_opt(commit.time().seconds(), 0).single()
+        .map(|dt| dt.format(fmt).to_string())
+        .unwrap_or_default()
+}
+
+fn resolve_commit<'repo>(repository: &'repo Repository, rev: &str) -> Result<Commit<'repo>, String> {
+    let rev = if rev.is_empty() { "HEAD" } else { rev };
+    repository.revparse_single(rev).and_then(|obj| obj.peel_to_commit())
+        .map_err_with_prefix(format!("Failed to resolve revision {:?}:", rev))
+}
+
+/// Compared to the first parent only, same as `git log --first-parent -- path` would show
+fn commit_touches_path(commit: &Commit, path: &Path) -> bool {
+    let entry_id = |c: &Commit| c.tree().ok().and_then(|t| t.get_path(path).ok()).map(|e| e.id());
+    match commit.parent(0) {
+        Ok(parent) => entry_id(commit) != entry_id(&parent),
+        Err(_) => entry_id(commit).is_some(),
+    }
+}
+
+pub fn git_log_as_string(repository: &Repository, rev: &str, path: Option<&Path>, max_count: usize) -> Result<String, String> {
+    let start = resolve_commit(repository, rev)?;
+    let mut revwalk = repository.revwalk().map_err_with_prefix("Failed to walk history:")?;
+    revwalk.set_sorting(git2::Sort::TIME).map_err_with_prefix("Failed to walk history:")?;
+    revwalk.push(start.id()).map_err_with_prefix("Failed to walk history:")?;
+
+    let mut lines = Vec::new();
+    for oid in revwalk.take(LOG_MAX_WALK) {
+        let oid = oid.map_err_with_prefix("Failed to walk history:")?;
+        let commit = repository.find_commit(oid).map_err_with_prefix("Failed to find commit:")?;
+        if let Some(path) = path {
+            if !commit_touches_path(&commit, path) {
+                continue;
+            }
+        }
+        lines.push(format!("{} {} {}: {}",
+            short_id(&oid), commit_date(&commit, "%Y-%m-%d"), commit.author().name().unwrap_or_default(), commit.summary().unwrap_or_default()));
+        if lines.len() >= max_count {
+            break;
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Blames the file as it is on disk, lines that are not committed yet are marked with 00000000
+pub fn git_blame_as_string(repository: &Repository, path: &Path, line_range: Option<(usize, usize)>) -> Result<String, String> {
+    let workdir = repository.workdir().ok_or("Repository has no working directory".to_string())?;
+    let text = std::fs::read_to_string(workdir.join(path)).map_err_with_prefix(format!("Failed to read {:?}:", path))?;
+    let lines: Vec<&str> = text.lines().collect();
+    let (line1, line2) = match line_range {
+        Some((line1, line2)) => (line1.max(1), line2.min(lines.len())),
+        None => (1, lines.len()),
+    };
+    if line1 > line2 {
+        return Err(format!("Line range {}-{} is outside of the file, it has {} lines", line1, line2, lines.len()));
+    }
+
+    let mut blame_options = BlameOptions::new();
+    let blame = repository.blame_file(path, Some(&mut blame_options)).map_err_with_prefix("Failed to blame:")?;
+    let blame = blame.blame_buffer(text.as_bytes()).map_err_with_prefix("Failed to blame:")?;
+
+    let mut commits_cache: HashMap<Oid, String> = HashMap::new();
+    let mut result = String::new();
+    for line_n in line1..=line2 {
+        let oid = blame.get_line(line_n).map(|hunk| hunk.final_commit_id()).unwrap_or(Oid::zero());
+        let who_when = commits_cache.entry(oid).or_insert_with(|| {
+            match repository.find_commit(oid) {
+                Ok(commit) if !oid.is_zero() => format!("{} {} {}",
+                    short_id(&oid), commit.author().name().unwrap_or_default(), commit_date(&commit, "%Y-%m-%d")),
+                _ => "00000000 Not Committed Yet".to_string(),
+            }
+        });
+        result.push_str(&format!("{} {:>5} | {}\n", who_when, line_n, lines[line_n - 1]));
+    }
+    Ok(result)
+}
+
+pub fn git_show_as_string(repository: &Repository, rev: &str, path: Option<&Path>, max_size: usize) -> Result<String, String> {
+    let commit = resolve_commit(repository, rev)?;
+    let mut result = format!("commit {}\nAuthor: {} <{}>\nDate:   {}\n\n",
+        commit.id(), commit.author().name().unwrap_or_default(), commit.author().email().unwrap_or_default(),
+        commit_date(&commit, "%Y-%m-%d %H:%M:%S UTC"));
+    for line in commit.message().unwrap_or_default().trim_end().lines() {
+        result.push_str(&format!("    {}\n", line));
+    }
+    result.push('\n');
+
+    let tree = commit.tree().map_err_with_prefix("Failed to get commit tree:")?;
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let mut diff_options = DiffOptions::new();
+    if let Some(path) = path {
+        diff_options.pathspec(path);
+    }
+    let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))
+        .map_err_with_prefix("Failed to generate diff:")?;
+    result.push_str(&git_diff_as_string(&diff, max_size)?);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::git::test_utils::commit_file;
+
+    #[test]
+    fn test_log_blame_show() {
+        let temp_dir = TempDir::new().unwrap();
+        let repository = Repository::init(temp_dir.path()).unwrap();
+        commit_file(&repository, "a.txt", "one\ntwo\n", "add a");
+        commit_file(&repository, "b.txt", "bbb\n", "add b");
+        let third = commit_file(&repository, "a.txt", "one\nTWO\n", "change a");
+        std::fs::write(temp_dir.path().join("a.txt"), "one\nTWO\nthree\n").unwrap();
+
+        let log = git_log_as_string(&repository, "", Some(Path::new("a.txt")), 10).unwrap();
+        let subjects = log.lines().map(|l| l.split(": ").last().unwrap()).collect::<Vec<_>>();
+        assert_eq!(subjects, vec!["change a", "add a"]);
+        assert_eq!(git_log_as_string(&repository, "HEAD~1", None, 1).unwrap().lines().count(), 1);
+
+        let blame = git_blame_as_string(&repository, Path::new("a.txt"), Some((2, 10))).unwrap();
+        let blame_lines = blame.lines().collect::<Vec<_>>();
+        assert_eq!(blame_lines.len(), 2);
+        assert!(blame_lines[0].starts_with(&short_id(&third)), "{}", blame);
+        assert!(blame_lines[0].ends_with("2 | TWO"), "{}", blame);
+        assert!(blame_lines[1].starts_with("00000000 Not Committed Yet"), "{}", blame);
+
+        let show = git_show_as_string(&repository, &third.to_string(), None, 10_000).unwrap();
+        assert!(show.contains("    change a"));
+        assert!(show.contains("-two\n+TWO\n"), "{}", show);
+        assert!(resolve_commit(&repository, "no-such-branch").is_err());
+
+        let since = crate::git::operations::git_diff_rev_to_workdir_as_string(&repository, "HEAD~1", 10_000).unwrap();
+        assert!(since.contains("-two\n+TWO\n+three\n"), "{}", since);
+        assert!(!since.contains("bbb"), "{}", since);
+    }
+}
@@ -1,9 +1,12 @@
 pub mod checkpoints;
 pub mod cleanup;
 pub mod commit_info;
+pub mod history;
 pub mod operations;
 #[cfg(test)]
 pub mod cleanup_tests;
+#[cfg(test)]
+pub mod test_utils;
 
 use serde::{Serialize, Deserialize};
 use std::path::PathBuf;
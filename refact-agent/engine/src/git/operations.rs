@@ -25,7 +25,6 @@ fn status_options(include_unmodified: bool, show: git2::StatusShow) -> git2::Sta
     options
 }
 
-#[allow(dead_code)]
 pub fn get_git_remotes(repository_path: &Path) -> Result<Vec<(String, String)>, String> {
     let repository = Repository::discover(repository_path)
         .map_err(|e| format!("Failed to open repository: {}", e))?;
@@ -183,7 +182,13 @@ pub fn get_diff_statuses_index_to_commit(repository: &Repository, commit_oid: &g
 
 pub fn stage_changes(repository: &Repository, file_changes: &Vec<FileChange>, abort_flag: &Arc<AtomicBool>) -> Result<(), String> {
     let mut index = repository.index().map_err_with_prefix("Failed to get index:")?;
+    add_changes_to_index(&mut index, file_changes, abort_flag)?;
+    index.write().map_err_with_prefix("Failed to write index:")?;
+    Ok(())
+}
 
+/// Like `stage_changes`, but the index is only changed in memory
+pub fn add_changes_to_index(index: &mut git2::Index, file_changes: &[FileChange], abort_flag: &Arc<AtomicBool>) -> Result<(), String> {
     for file_change in file_changes {
         // NOTE: this loop can take a lot of time (25s for linux) when we just init the repo
         if abort_flag.load(Ordering::SeqCst) {
@@ -200,8 +205,6 @@ pub fn stage_changes(repository: &Repository, file_changes: &Vec<FileChange>, ab
             },
         }
     }
-
-    index.write().map_err_with_prefix("Failed to write index:")?;
     Ok(())
 }
 
@@ -271,14 +274,66 @@ pub fn git_diff_head_to_workdir<'repo>(repository: &'repo Repository) -> Result<
     Ok(diff)
 }
 
+/// Like `git diff <rev>...` plus uncommitted changes: compares the merge base of rev and HEAD to the working dir,
+/// so "what changed since main" doesn't show the commits that landed on main in the meantime.
+pub fn git_diff_rev_to_workdir<'repo>(repository: &'repo Repository, rev: &str) -> Result<git2::Diff<'repo>, String> {
+    let mut diff_options = DiffOptions::new();
+    diff_options.include_untracked(true);
+    diff_options.recurse_untracked_dirs(true);
+
+    let rev_commit = repository.revparse_single(rev).and_then(|obj| obj.peel_to_commit())
+        .map_err(|e| format!("Failed to resolve revision {:?}: {}", rev, e))?;
+    let base_oid = match repository.head().and_then(|head_ref| head_ref.peel_to_commit()) {
+        Ok(head_commit) => repository.merge_base(rev_commit.id(), head_commit.id()).unwrap_or(rev_commit.id()),
+        Err(_) => rev_commit.id(),
+    };
+    let base_tree = repository.find_commit(base_oid).and_then(|c| c.tree())
+        .map_err(|e| format!("Failed to get tree for {}: {}", base_oid, e))?;
+
+    let diff = repository.diff_tree_to_workdir_with_index(Some(&base_tree), Some(&mut diff_options))
+        .map_err(|e| format!("Failed to generate diff: {}", e))?;
+
+    Ok(diff)
+}
+
+/// Like `git diff base...head`: compares the merge base of the two revisions to head, returns the diff and the merge base
+pub fn git_diff_between_revs<'repo>(repository: &'repo Repository, base: &str, head: &str) -> Result<(git2::Diff<'repo>, Oid), String> {
+    let resolve = |rev: &str| repository.revparse_single(rev).and_then(|obj| obj.peel_to_commit())
+        .map_err(|e| format!("Failed to resolve revision {:?}: {}", rev, e));
+    let base_commit = resolve(base)?;
+    let head_commit = resolve(head)?;
+    let base_oid = repository.merge_base(base_commit.id(), head_commit.id())
+        .map_err(|e| format!("No common ancestor for {} and {}: {}", base, head, e))?;
+    let base_tree = repository.find_commit(base_oid).and_then(|c| c.tree())
+        .map_err(|e| format!("Failed to get tree for {}: {}", base_oid, e))?;
+    let head_tree = head_commit.tree()
+        .map_err(|e| format!("Failed to get tree for {}: {}", head, e))?;
+
+    let diff = repository.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)
+        .map_err(|e| format!("Failed to generate diff: {}", e))?;
+
+    Ok((diff, base_oid))
+}
+
+pub fn git_diff_rev_to_workdir_as_string(repository: &Repository, rev: &str, max_size: usize) -> Result<String, String> {
+    let diff = git_diff_rev_to_workdir(repository, rev)?;
+    git_diff_as_string(&diff, max_size)
+}
+
 pub fn git_diff_head_to_workdir_as_string(repository: &Repository, max_size: usize) -> Result<String, String> {
     let diff = git_diff_head_to_workdir(repository)?;
+    git_diff_as_string(&diff, max_size)
+}
 
+pub fn git_diff_as_string(diff: &git2::Diff, max_size: usize) -> Result<String, String> {
     let mut diff_str = String::new();
     diff.print(git2::DiffFormat::Patch, |_, _, line| {
         let line_content = std::str::from_utf8(line.content()).unwrap_or("");
         if diff_str.len() + line_content.len() < max_size {
-            diff_str.push(line.origin());
+            // file and hunk headers have their own origin markers ('F', 'H'), they are not part of the patch text
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                diff_str.push(line.origin());
+            }
             diff_str.push_str(line_content);
             if diff_str.len() > max_size {
                 diff_str.truncate(max_size - 4);
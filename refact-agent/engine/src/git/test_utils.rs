@@ -0,0 +1,15 @@
+use std::path::Path;
+use git2::{Oid, Repository, Signature};
+
+
+/// Writes the file into the work tree and commits it on top of HEAD
+pub fn commit_file(repository: &Repository, file_name: &str, text: &str, message: &str) -> Oid {
+    std::fs::write(repository.workdir().unwrap().join(file_name), text).unwrap();
+    let mut index = repository.index().unwrap();
+    index.add_path(Path::new(file_name)).unwrap();
+    index.write().unwrap();
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test User", "test@example.com").unwrap();
+    let parents = repository.head().ok().and_then(|h| h.peel_to_commit().ok()).into_iter().collect::<Vec<_>>();
+    repository.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents.iter().collect::<Vec<_>>()).unwrap()
+}
@@ -45,6 +45,16 @@ pub struct CommandLine {
 
     #[structopt(long, short="p", default_value="0", help="Bind 127.0.0.1:<port> to listen for HTTP requests, such as /v1/code-completion, /v1/chat, /v1/caps.")]
     pub http_port: u16,
+    #[structopt(long, default_value="", help="Address to bind the HTTP server to, 127.0.0.1 by default (0.0.0.0 with --inside-container). Binding a non-loopback address requires --http-auth.")]
+    pub http_bind: String,
+    #[structopt(long, help="Require \"Authorization: Bearer <token>\" on all /v1 requests. The token is generated at startup and written to a file only the current user can read, see --http-auth-token-file.")]
+    pub http_auth: bool,
+    #[structopt(long, default_value="", help="Where --http-auth writes the token, ~/.cache/refact/http-auth-token by default.")]
+    pub http_auth_token_file: String,
+    #[structopt(long, default_value="", help="PEM certificate chain to serve HTTPS instead of HTTP, requires --https-key.")]
+    pub https_cert: String,
+    #[structopt(long, default_value="", help="PEM private key for --https-cert.")]
+    pub https_key: String,
     #[structopt(long, default_value="0", help="Bind 127.0.0.1:<port> and act as an LSP server. This is compatible with having an HTTP server at the same time.")]
     pub lsp_port: u16,
     #[structopt(long, default_value="0", help="Act as an LSP server, use stdin stdout for communication. This is compatible with having an HTTP server at the same time. But it's not compatible with LSP port.")]
@@ -109,6 +119,19 @@ pub struct CommandLine {
     pub active_group_id: Option<String>,
     #[structopt(long, help="Enable cloud threads support")]
     pub cloud_threads: bool,
+
+    #[structopt(long, default_value="", help="Run a task without an IDE: drive the chat and tools until the model stops calling tools, write the trajectory and summary.json, and exit. Exit code is 0 if the task completed.")]
+    pub run_task: String,
+    #[structopt(long, default_value="AGENT", help="Chat mode for --run-task: AGENT, EXPLORE, NO_TOOLS, CONFIGURE or PROJECT_SUMMARY.")]
+    pub run_task_chat_mode: String,
+    #[structopt(long, default_value="", help="Chat model for --run-task, the default chat model from caps is used if empty.")]
+    pub run_task_model: String,
+    #[structopt(long, default_value="30", help="Maximum number of model calls for --run-task.")]
+    pub run_task_max_steps: usize,
+    #[structopt(long, default_value="deny", help="What --run-task does with tool calls that need a confirmation: \"deny\" refuses them and tells the model, \"approve\" runs them. Deny rules are always respected.")]
+    pub run_task_approve: String,
+    #[structopt(long, default_value="", help="Where --run-task writes trajectory.json and summary.json, ~/.cache/refact/tasks/<chat_id> by default.")]
+    pub run_task_output_dir: String,
 }
 
 impl CommandLine {
@@ -166,6 +189,10 @@ pub struct GlobalContext {
     pub telemetry: Arc<StdRwLock<telemetry_structs::Storage>>,
     pub vec_db: Arc<AMutex<Option<crate::vecdb::vdb_highlev::VecDb>>>,
     pub vec_db_error: String,
+    pub http_auth_token: Option<String>,
+    pub budget_tracker: Arc<AMutex<crate::agentic::budgets::BudgetTracker>>,
+    pub usage_ledger: Arc<AMutex<Option<crate::telemetry::usage_ledger::UsageLedger>>>,
+    pub local_threads_db: Arc<AMutex<Option<crate::local_threads::LocalThreadsDb>>>,
     pub ast_service: Option<Arc<AMutex<AstIndexService>>>,
     pub ask_shutdown_sender: Arc<StdMutex<std::sync::mpsc::Sender<String>>>,
     pub documents_state: DocumentsState,
@@ -174,6 +201,7 @@ pub struct GlobalContext {
     pub indexing_everywhere: Arc<crate::files_blocklist::IndexingEverywhere>,
     pub integration_sessions: HashMap<String, Arc<AMutex<Box<dyn IntegrationSession>>>>,
     pub codelens_cache: Arc<AMutex<crate::http::routers::v1::code_lens::CodeLensCache>>,
+    pub subagents_cache: Arc<AMutex<crate::yaml_configs::customization_loader::SubagentsCache>>,
     pub docker_ssh_tunnel: Arc<AMutex<Option<SshTunnel>>>,
     pub active_group_id: Option<String>,
     pub init_shadow_repos_background_task_holder: BackgroundTasksHolder,
@@ -385,7 +413,14 @@ pub async fn create_global_context(
     cache_dir: PathBuf,
     config_dir: PathBuf,
 ) -> (Arc<ARwLock<GlobalContext>>, std::sync::mpsc::Receiver<String>, CommandLine) {
-    let cmdline = CommandLine::from_args();
+    create_global_context_with_cmdline(cache_dir, config_dir, CommandLine::from_args()).await
+}
+
+pub async fn create_global_context_with_cmdline(
+    cache_dir: PathBuf,
+    config_dir: PathBuf,
+    cmdline: CommandLine,
+) -> (Arc<ARwLock<GlobalContext>>, std::sync::mpsc::Receiver<String>, CommandLine) {
     let (ask_shutdown_sender, ask_shutdown_receiver) = std::sync::mpsc::channel::<String>();
     let mut http_client_builder = reqwest::Client::builder();
     if cmdline.insecure {
@@ -415,6 +450,10 @@ pub async fn create_global_context(
         telemetry: Arc::new(StdRwLock::new(telemetry_structs::Storage::new())),
         vec_db: Arc::new(AMutex::new(None)),
         vec_db_error: String::new(),
+        http_auth_token: None,
+        budget_tracker: Arc::new(AMutex::new(crate::agentic::budgets::BudgetTracker::default())),
+        usage_ledger: Arc::new(AMutex::new(None)),
+        local_threads_db: Arc::new(AMutex::new(None)),
         ast_service: None,
         ask_shutdown_sender: Arc::new(StdMutex::new(ask_shutdown_sender)),
         documents_state: DocumentsState::new(workspace_dirs.clone()).await,
@@ -423,6 +462,7 @@ pub async fn create_global_context(
         indexing_everywhere: Arc::new(crate::files_blocklist::IndexingEverywhere::default()),
         integration_sessions: HashMap::new(),
         codelens_cache: Arc::new(AMutex::new(crate::http::routers::v1::code_lens::CodeLensCache::default())),
+        subagents_cache: Arc::new(AMutex::new(crate::yaml_configs::customization_loader::SubagentsCache::default())),
         docker_ssh_tunnel: Arc::new(AMutex::new(None)),
         active_group_id: cmdline.active_group_id.clone(),
         init_shadow_repos_background_task_holder: BackgroundTasksHolder::new(vec![]),
@@ -435,3 +475,37 @@ pub async fn create_global_context(
     crate::files_in_workspace::watcher_init(gcx.clone()).await;
     (gcx, ask_shutdown_receiver, cmdline)
 }
+
+#[cfg(test)]
+pub struct TestContext {
+    pub dir: tempfile::TempDir,  // cache and config dir, removed on drop
+    pub gcx: Arc<ARwLock<GlobalContext>>,
+    pub ccx: Arc<AMutex<crate::at_commands::at_commands::AtCommandsContext>>,
+    pub ask_shutdown_receiver: std::sync::mpsc::Receiver<String>,
+}
+
+#[cfg(test)]
+impl TestContext {
+    /// What the workspace scan would find, files are resolved against these
+    pub async fn set_workspace(&self, folders: Vec<PathBuf>, files: Vec<PathBuf>) {
+        let gcx_locked = self.gcx.read().await;
+        *gcx_locked.documents_state.workspace_folders.lock().unwrap() = folders;
+        *gcx_locked.documents_state.workspace_files.lock().unwrap() = files;
+        *gcx_locked.documents_state.cache_dirty.lock().await = 1.0;
+    }
+}
+
+/// `args` go after the binary name. The default privacy.yaml is there, without it every file is blocked.
+#[cfg(test)]
+pub async fn create_test_context(args: &[&str], messages: Vec<crate::call_validation::ChatMessage>) -> TestContext {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("privacy.yaml"), include_str!("yaml_configs/default_privacy.yaml")).unwrap();
+    let cmdline = CommandLine::from_iter(std::iter::once("refact-lsp").chain(args.iter().cloned()));
+    let (gcx, ask_shutdown_receiver, _) = create_global_context_with_cmdline(
+        dir.path().to_path_buf(), dir.path().to_path_buf(), cmdline,
+    ).await;
+    let ccx = Arc::new(AMutex::new(crate::at_commands::at_commands::AtCommandsContext::new(
+        gcx.clone(), 32000, 5, false, messages, "test".to_string(), false, "model".to_string(),
+    ).await));
+    TestContext { dir, gcx, ccx, ask_shutdown_receiver }
+}
@@ -1,8 +1,10 @@
 use std::{io::Write, time::Duration};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 
-use axum::{Extension, http::{StatusCode, Uri}, response::IntoResponse};
+use axum::{Extension, Router, http::{StatusCode, Uri}, response::IntoResponse};
+use axum_server::tls_rustls::RustlsConfig;
 use hyper::Server;
 use tokio::sync::RwLock as ARwLock;
 use tokio::task::JoinHandle;
@@ -14,6 +16,7 @@ use crate::global_context::GlobalContext;
 use crate::http::routers::make_refact_http_server;
 
 pub mod routers;
+pub mod auth;
 mod utils;
 
 async fn handler_404(path: Uri) -> impl IntoResponse {
@@ -26,39 +29,146 @@ pub async fn start_server(
     gcx: Arc<ARwLock<GlobalContext>>,
     ask_shutdown_receiver: std::sync::mpsc::Receiver<String>,
 ) -> Option<JoinHandle<()>> {
-    let (port, is_inside_container) = {
-        let gcx_locked= gcx.read().await;
-        (gcx_locked.cmdline.http_port, gcx_locked.cmdline.inside_container)
+    let (cmdline, cache_dir) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.cmdline.clone(), gcx_locked.cache_dir.clone())
     };
-    if port == 0 {
+    if cmdline.http_port == 0 {
         return None
     }
+    let addr = match auth::http_bind_address(&cmdline) {
+        Ok(addr) => addr,
+        Err(e) => {
+            let _ = write!(std::io::stderr(), "{}\n", e);
+            error!("server error: {}", e);
+            return None;
+        }
+    };
+    let mut token_path = None;
+    if cmdline.http_auth {
+        let token = auth::generate_auth_token();
+        let path = auth::auth_token_file_path(&cmdline, &cache_dir);
+        if let Err(e) = auth::write_auth_token_file(&path, &token) {
+            error!("server error: {}", e);
+            return None;
+        }
+        gcx.write().await.http_auth_token = Some(token);
+        token_path = Some(path);
+    }
     let shutdown_flag: Arc<AtomicBool> = gcx.read().await.shutdown_flag.clone();
     Some(tokio::spawn(async move {
-        let addr = if is_inside_container { ([0, 0, 0, 0], port).into() } else { ([127, 0, 0, 1], port).into() };
-        let builder = Server::try_bind(&addr).map_err(|e| {
-            let _ = write!(std::io::stderr(), "PORT_BUSY {}\n", e);
-            format!("port busy, address {}: {}", addr, e)
-        });
-        match builder {
-            Ok(builder) => {
-                info!("HTTP server listening on {}", addr);
-                let router = make_refact_http_server().layer(Extension(gcx.clone()));
-                let server = builder
-                    .serve(router.into_make_service())
-                    .with_graceful_shutdown(crate::global_context::block_until_signal(ask_shutdown_receiver, shutdown_flag));
-                let resp = server.await.map_err(|e| format!("HTTP server error: {}", e));
-                if let Err(e) = resp {
-                    error!("server error: {}", e);
-                } else {
-                    info!("clean shutdown");
-                }
-            }
-            Err(e) => {
+        let router = make_refact_http_server().layer(Extension(gcx.clone()));
+        if auth::https_enabled(&cmdline) {
+            serve_https(addr, &cmdline.https_cert, &cmdline.https_key, router, ask_shutdown_receiver, shutdown_flag).await;
+        } else {
+            serve_http(addr, router, ask_shutdown_receiver, shutdown_flag).await;
+        }
+        if let Some(token_path) = token_path {
+            let _ = std::fs::remove_file(token_path);
+        }
+    }))
+}
+
+/// LSP initialize waits for this, so it talks to the server the way any client would: bind address, scheme, token
+pub async fn wait_for_http_server(gcx: Arc<ARwLock<GlobalContext>>) -> Result<(), String> {
+    let (cmdline, token) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.cmdline.clone(), gcx_locked.http_auth_token.clone())
+    };
+    let url = auth::http_self_url(&cmdline, "/v1/ping")?;
+    // the certificate is issued for a host name, not for the address connected to here
+    let client = Client::builder()
+        .danger_accept_invalid_certs(auth::https_enabled(&cmdline))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut last_error = String::new();
+    for _ in 0..15 {
+        let mut request = client.get(&url);
+        if let Some(token) = &token {
+            request = request.bearer_auth(token);
+        }
+        match request.send().await {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => last_error = format!("status {}", res.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+        tokio::time::sleep(Duration::from_millis(150)).await;
+    }
+    Err(format!("HTTP server is not ready after 15 attempts, {}: {}", url, last_error))
+}
+
+async fn serve_http(
+    addr: SocketAddr,
+    router: Router,
+    ask_shutdown_receiver: std::sync::mpsc::Receiver<String>,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let builder = Server::try_bind(&addr).map_err(|e| {
+        let _ = write!(std::io::stderr(), "PORT_BUSY {}\n", e);
+        format!("port busy, address {}: {}", addr, e)
+    });
+    match builder {
+        Ok(builder) => {
+            info!("HTTP server listening on {}", addr);
+            let server = builder
+                .serve(router.into_make_service())
+                .with_graceful_shutdown(crate::global_context::block_until_signal(ask_shutdown_receiver, shutdown_flag));
+            let resp = server.await.map_err(|e| format!("HTTP server error: {}", e));
+            if let Err(e) = resp {
                 error!("server error: {}", e);
+            } else {
+                info!("clean shutdown");
             }
         }
-    }))
+        Err(e) => {
+            error!("server error: {}", e);
+        }
+    }
+}
+
+async fn serve_https(
+    addr: SocketAddr,
+    cert_path: &str,
+    key_path: &str,
+    router: Router,
+    ask_shutdown_receiver: std::sync::mpsc::Receiver<String>,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    if cert_path.is_empty() || key_path.is_empty() {
+        error!("server error: HTTPS needs both --https-cert and --https-key");
+        return;
+    }
+    let config = match RustlsConfig::from_pem_file(cert_path, key_path).await {
+        Ok(config) => config,
+        Err(e) => {
+            error!("server error: cannot load certificate {:?} or key {:?}: {}", cert_path, key_path, e);
+            return;
+        }
+    };
+    let listener = match std::net::TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = write!(std::io::stderr(), "PORT_BUSY {}\n", e);
+            error!("server error: port busy, address {}: {}", addr, e);
+            return;
+        }
+    };
+    let handle = axum_server::Handle::new();
+    let handle_for_signal = handle.clone();
+    tokio::spawn(async move {
+        crate::global_context::block_until_signal(ask_shutdown_receiver, shutdown_flag).await;
+        handle_for_signal.graceful_shutdown(Some(Duration::from_secs(5)));
+    });
+    info!("HTTPS server listening on {}", addr);
+    let resp = axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(router.into_make_service())
+        .await;
+    if let Err(e) = resp {
+        error!("server error: {}", e);
+    } else {
+        info!("clean shutdown");
+    }
 }
 
 async fn _make_http_request<T: Serialize>(
@@ -131,4 +241,22 @@ pub async fn http_get_json<R: for<'de> serde::Deserialize<'de>>(
 ) -> Result<R, String> {
     let get_result = _make_http_request("GET", url, &(), 1).await?;
     get_result.json::<R>().await.map_err(|e| e.to_string())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_context::create_test_context;
+
+    #[tokio::test]
+    async fn test_wait_for_http_server_with_auth() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let t = create_test_context(&["--http-port", &port.to_string(), "--http-auth"], vec![]).await;
+        let server = start_server(t.gcx.clone(), t.ask_shutdown_receiver).await.unwrap();
+
+        wait_for_http_server(t.gcx.clone()).await.unwrap();
+        let without_token = reqwest::get(format!("http://127.0.0.1:{}/v1/ping", port)).await.unwrap();
+        assert_eq!(without_token.status().as_u16(), 401);
+        server.abort();
+    }
+}
@@ -0,0 +1,137 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use axum::Extension;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand::RngCore;
+use tracing::info;
+
+use crate::custom_error::ScratchError;
+use crate::global_context::{CommandLine, SharedGlobalContext};
+
+
+pub fn generate_auth_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn auth_token_file_path(cmdline: &CommandLine, cache_dir: &PathBuf) -> PathBuf {
+    if cmdline.http_auth_token_file.is_empty() {
+        cache_dir.join("http-auth-token")
+    } else {
+        PathBuf::from(&cmdline.http_auth_token_file)
+    }
+}
+
+pub fn write_auth_token_file(path: &PathBuf, token: &str) -> Result<(), String> {
+    use std::io::Write;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("cannot create {:?}: {}", parent, e))?;
+    }
+    // remove first, so a file left from before with wider permissions is not reused
+    let _ = std::fs::remove_file(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| format!("cannot create {:?}: {}", path, e))?;
+    file.write_all(token.as_bytes()).map_err(|e| format!("cannot write {:?}: {}", path, e))?;
+    info!("HTTP auth token written to {:?}", path);
+    Ok(())
+}
+
+pub fn http_bind_address(cmdline: &CommandLine) -> Result<SocketAddr, String> {
+    let ip: IpAddr = if !cmdline.http_bind.is_empty() {
+        cmdline.http_bind.parse().map_err(|e| format!("invalid --http-bind {:?}: {}", cmdline.http_bind, e))?
+    } else if cmdline.inside_container {
+        [0, 0, 0, 0].into()
+    } else {
+        [127, 0, 0, 1].into()
+    };
+    // inside_container was binding 0.0.0.0 before auth existed, the container network is the boundary there
+    if !cmdline.http_bind.is_empty() && !ip.is_loopback() && !cmdline.http_auth {
+        return Err(format!("refusing to bind {} without --http-auth, anyone on the network could run shell commands", ip));
+    }
+    Ok(SocketAddr::new(ip, cmdline.http_port))
+}
+
+/// Where the engine reaches its own server: the bind address, loopback when it listens on all interfaces
+pub fn http_self_url(cmdline: &CommandLine, path: &str) -> Result<String, String> {
+    let mut addr = http_bind_address(cmdline)?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(if addr.is_ipv4() { Ipv4Addr::LOCALHOST.into() } else { Ipv6Addr::LOCALHOST.into() });
+    }
+    let scheme = if https_enabled(cmdline) { "https" } else { "http" };
+    Ok(format!("{}://{}{}", scheme, addr, path))
+}
+
+pub fn https_enabled(cmdline: &CommandLine) -> bool {
+    !cmdline.https_cert.is_empty() || !cmdline.https_key.is_empty()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn is_authorized(expected_token: &str, authorization_header: Option<&str>) -> bool {
+    match authorization_header.and_then(|h| h.strip_prefix("Bearer ")) {
+        Some(token) => constant_time_eq(token.trim().as_bytes(), expected_token.as_bytes()),
+        None => false,
+    }
+}
+
+pub async fn auth_middleware<B>(
+    ex: Extension<SharedGlobalContext>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ScratchError> {
+    let expected_token = ex.read().await.http_auth_token.clone();
+    if let Some(expected_token) = expected_token {
+        let authorization = request.headers().get(header::AUTHORIZATION).and_then(|h| h.to_str().ok());
+        if !is_authorized(&expected_token, authorization) {
+            return Err(ScratchError::new_but_skip_telemetry(StatusCode::UNAUTHORIZED, "missing or invalid bearer token".to_string()));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn test_is_authorized() {
+        let token = generate_auth_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_auth_token());
+        assert!(is_authorized(&token, Some(&format!("Bearer {}", token))));
+        assert!(!is_authorized(&token, Some(&format!("Bearer {}x", token))));
+        assert!(!is_authorized(&token, Some(&token)));
+        assert!(!is_authorized(&token, Some("Bearer ")));
+        assert!(!is_authorized(&token, None));
+    }
+
+    #[test]
+    fn test_http_bind_address() {
+        let cmdline = CommandLine::from_iter(["refact-lsp", "--http-port", "8001"]);
+        assert_eq!(http_bind_address(&cmdline).unwrap().to_string(), "127.0.0.1:8001");
+        let cmdline = CommandLine::from_iter(["refact-lsp", "--http-port", "8001", "--http-bind", "0.0.0.0"]);
+        assert!(http_bind_address(&cmdline).is_err());
+        let cmdline = CommandLine::from_iter(["refact-lsp", "--http-port", "8001", "--http-bind", "0.0.0.0", "--http-auth"]);
+        assert_eq!(http_bind_address(&cmdline).unwrap().to_string(), "0.0.0.0:8001");
+        let cmdline = CommandLine::from_iter(["refact-lsp", "--http-bind", "::1"]);
+        assert!(http_bind_address(&cmdline).unwrap().ip().is_loopback());
+        assert_eq!(http_self_url(&cmdline, "/v1/ping").unwrap(), "http://[::1]:0/v1/ping");
+        let cmdline = CommandLine::from_iter(["refact-lsp", "--http-port", "8001", "--http-bind", "0.0.0.0", "--http-auth", "--https-cert", "c.pem", "--https-key", "k.pem"]);
+        assert_eq!(http_self_url(&cmdline, "/v1/ping").unwrap(), "https://127.0.0.1:8001/v1/ping");
+    }
+}
@@ -3,9 +3,11 @@ use axum::Router;
 use axum::routing::{get, post, delete};
 use tower_http::cors::CorsLayer;
 
+use crate::http::auth::auth_middleware;
 use crate::http::utils::telemetry_middleware;
 use crate::http::routers::v1::code_completion::{handle_v1_code_completion_web, handle_v1_code_completion_prompt};
 use crate::http::routers::v1::code_lens::handle_v1_code_lens;
+use crate::http::routers::v1::audit_log::handle_v1_audit_log;
 use crate::http::routers::v1::ast::{handle_v1_ast_file_dump, handle_v1_ast_file_symbols, handle_v1_ast_status};
 use crate::http::routers::v1::at_commands::{handle_v1_command_completion, handle_v1_command_preview, handle_v1_at_command_execute};
 use crate::http::routers::v1::at_tools::{handle_v1_get_tools, handle_v1_tools_check_if_confirmation_needed, handle_v1_tools_execute};
@@ -13,19 +15,23 @@ use crate::http::routers::v1::caps::handle_v1_caps;
 use crate::http::routers::v1::caps::handle_v1_ping;
 use crate::http::routers::v1::chat::{handle_v1_chat, handle_v1_chat_completions};
 use crate::http::routers::v1::chat_based_handlers::{handle_v1_commit_message_from_diff, handle_v1_trajectory_compress};
-use crate::http::routers::v1::chat_based_handlers::handle_v1_trajectory_save;
+use crate::http::routers::v1::chat_based_handlers::{handle_v1_review, handle_v1_trajectory_save};
 use crate::http::routers::v1::dashboard::get_dashboard_plots;
 use crate::http::routers::v1::docker::{handle_v1_docker_container_action, handle_v1_docker_container_list};
-use crate::http::routers::v1::git::{handle_v1_git_commit, handle_v1_checkpoints_preview, handle_v1_checkpoints_restore};
+use crate::http::routers::v1::git::{handle_v1_git_commit, handle_v1_checkpoints_preview, handle_v1_checkpoints_restore,
+    handle_v1_checkpoints_list, handle_v1_checkpoints_diff, handle_v1_checkpoints_restore_files};
 use crate::http::routers::v1::graceful_shutdown::handle_v1_graceful_shutdown;
 use crate::http::routers::v1::snippet_accepted::handle_v1_snippet_accepted;
 use crate::http::routers::v1::telemetry_network::handle_v1_telemetry_network;
 use crate::http::routers::v1::telemetry_chat::handle_v1_telemetry_chat;
+use crate::http::routers::v1::usage::handle_v1_usage_report;
 use crate::http::routers::v1::links::handle_v1_links;
+use crate::http::routers::v1::local_threads::{handle_v1_threads_list, handle_v1_thread_get, handle_v1_thread_append, handle_v1_thread_fork, handle_v1_thread_delete};
 use crate::http::routers::v1::lsp_like_handlers::{handle_v1_lsp_did_change, handle_v1_lsp_add_folder, handle_v1_lsp_initialize, handle_v1_lsp_remove_folder, handle_v1_set_active_document};
 use crate::http::routers::v1::status::handle_v1_rag_status;
 use crate::http::routers::v1::customization::handle_v1_customization;
 use crate::http::routers::v1::customization::handle_v1_config_path;
+use crate::http::routers::v1::customization::handle_v1_chat_modes;
 use crate::http::routers::v1::gui_help_handlers::handle_v1_fullpath;
 use crate::http::routers::v1::subchat::{handle_v1_subchat, handle_v1_subchat_single};
 use crate::http::routers::v1::sync_files::handle_v1_sync_files_extract_tar;
@@ -40,6 +46,7 @@ use crate::http::routers::v1::file_edit_tools::handle_v1_file_edit_tool_dry_run;
 use crate::http::routers::v1::workspace::{handle_v1_get_app_searchable_id, handle_v1_set_active_group_id};
 
 mod ast;
+mod audit_log;
 pub mod at_commands;
 pub mod at_tools;
 pub mod caps;
@@ -54,6 +61,7 @@ mod git;
 pub mod graceful_shutdown;
 mod gui_help_handlers;
 pub mod links;
+mod local_threads;
 pub mod lsp_like_handlers;
 pub mod snippet_accepted;
 pub mod status;
@@ -63,6 +71,7 @@ pub mod system_prompt;
 pub mod telemetry_chat;
 pub mod telemetry_network;
 pub mod providers;
+mod usage;
 mod file_edit_tools;
 mod v1_integrations;
 pub mod vecdb;
@@ -82,6 +91,7 @@ pub fn make_v1_router() -> Router {
         .route("/telemetry-network", post(handle_v1_telemetry_network))
         .route("/telemetry-chat", post(handle_v1_telemetry_chat))
         .route("/snippet-accepted", post(handle_v1_snippet_accepted))
+        .route("/usage-report", post(handle_v1_usage_report))
 
         .route("/caps", get(handle_v1_caps))
 
@@ -89,6 +99,7 @@ pub fn make_v1_router() -> Router {
         .route("/tools", post(handle_v1_post_tools))
         .route("/tools-check-if-confirmation-needed", post(handle_v1_tools_check_if_confirmation_needed))
         .route("/tools-execute", post(handle_v1_tools_execute)) // because it works remotely
+        .route("/audit-log", get(handle_v1_audit_log))
 
         .route("/lsp-initialize", post(handle_v1_lsp_initialize))
         .route("/lsp-did-changed", post(handle_v1_lsp_did_change))
@@ -104,6 +115,7 @@ pub fn make_v1_router() -> Router {
         .route("/config-path", get(handle_v1_config_path))
 
         .route("/customization", get(handle_v1_customization))
+        .route("/chat-modes", get(handle_v1_chat_modes))
 
         .route("/sync-files-extract-tar", post(handle_v1_sync_files_extract_tar))
 
@@ -131,9 +143,18 @@ pub fn make_v1_router() -> Router {
 
         .route("/checkpoints-preview", post(handle_v1_checkpoints_preview))
         .route("/checkpoints-restore", post(handle_v1_checkpoints_restore))
+        .route("/checkpoints-list", post(handle_v1_checkpoints_list))
+        .route("/checkpoints-diff", post(handle_v1_checkpoints_diff))
+        .route("/checkpoints-restore-files", post(handle_v1_checkpoints_restore_files))
 
         .route("/links", post(handle_v1_links))
 
+        .route("/threads", post(handle_v1_threads_list))
+        .route("/thread-get", post(handle_v1_thread_get))
+        .route("/thread-append", post(handle_v1_thread_append))
+        .route("/thread-fork", post(handle_v1_thread_fork))
+        .route("/thread-delete", delete(handle_v1_thread_delete))
+
         .route("/file_edit_tool_dry_run", post(handle_v1_file_edit_tool_dry_run))
         
         .route("/providers", get(handle_v1_providers))
@@ -157,6 +178,7 @@ pub fn make_v1_router() -> Router {
 
         .route("/code-completion-prompt", post(handle_v1_code_completion_prompt))
         .route("/commit-message-from-diff", post(handle_v1_commit_message_from_diff))
+        .route("/review", post(handle_v1_review))
 
         // to remove
         .route("/subchat", post(handle_v1_subchat))
@@ -170,6 +192,7 @@ pub fn make_v1_router() -> Router {
         ;
 
     builder
+        .layer(axum::middleware::from_fn(auth_middleware))
         .layer(axum::middleware::from_fn(telemetry_middleware))
         .layer(CorsLayer::very_permissive())
 }
@@ -16,10 +16,11 @@ use crate::http::routers::v1::chat::CHAT_TOP_N;
 use crate::indexing_utils::wait_for_indexing_if_needed;
 use crate::integrations::docker::docker_container_manager::docker_container_get_host_lsp_port_to_connect;
 use crate::tools::tools_description::{set_tool_config, MatchConfirmDenyResult, ToolConfig, ToolDesc, ToolGroupCategory, ToolSource};
-use crate::tools::tools_list::{get_available_tool_groups, get_available_tools};
+use crate::tools::tools_list::{get_available_tool_groups, get_available_tools, get_tools_to_execute};
 use crate::custom_error::ScratchError;
 use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
 use crate::tools::tools_execute::run_tools;
+use crate::yaml_configs::customization_loader::chat_mode_write_globs;
 
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -58,6 +59,12 @@ pub struct ToolsExecutePost {
     pub model_name: String,
     pub chat_id: String,
     pub style: Option<String>,
+    #[serde(default)]
+    pub custom_mode: String,  // limits the tools that can run, see ChatMeta::custom_mode
+    #[serde(default)]
+    pub subagent: String,
+    #[serde(default)]
+    pub subagent_skips_confirmation: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -182,7 +189,7 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
           .unwrap());
     }
 
-    let ccx = Arc::new(AMutex::new(AtCommandsContext::new(
+    let mut ccx = AtCommandsContext::new(
         gcx.clone(),
         1000,
         1,
@@ -191,7 +198,9 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
         "".to_string(),
         false,
         "".to_string(),
-    ).await)); // used only for should_confirm
+    ).await; // used only for should_confirm
+    ccx.write_globs = chat_mode_write_globs(gcx.clone(), &post.meta.custom_mode).await;
+    let ccx = Arc::new(AMutex::new(ccx));
 
     let all_tools = get_available_tools(gcx.clone()).await.into_iter()
         .map(|tool| {
@@ -289,13 +298,12 @@ pub async fn handle_v1_tools_execute(
     ).await;
     ccx.subchat_tool_parameters = tools_execute_post.subchat_tool_parameters.clone();
     ccx.postprocess_parameters = tools_execute_post.postprocess_parameters.clone();
+    ccx.subagent = tools_execute_post.subagent.clone();
+    ccx.subagent_skips_confirmation = tools_execute_post.subagent_skips_confirmation;
+    ccx.write_globs = chat_mode_write_globs(gcx.clone(), &tools_execute_post.custom_mode).await;
     let ccx_arc = Arc::new(AMutex::new(ccx));
 
-    let mut at_tools = get_available_tools(gcx.clone()).await.into_iter()
-        .map(|tool| {
-            let spec = tool.tool_description();
-            (spec.name, tool)
-        }).collect::<IndexMap<_, _>>();
+    let mut at_tools = get_tools_to_execute(gcx.clone(), &tools_execute_post.custom_mode).await;
 
     let (messages, tools_ran) = run_tools(
         ccx_arc.clone(), &mut at_tools, tokenizer.clone(), tools_execute_post.maxgen, &tools_execute_post.messages, &tools_execute_post.style
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use axum::Extension;
+use axum::extract::Query;
+use axum::http::{Response, StatusCode};
+use hyper::Body;
+use serde::Deserialize;
+use tokio::sync::RwLock as ARwLock;
+
+use crate::audit_log::{audit_log_query, AuditLogFilter};
+use crate::custom_error::ScratchError;
+use crate::global_context::GlobalContext;
+
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    #[serde(default)]
+    pub chat_id: String,
+    #[serde(default)]
+    pub tool_name: String,
+    #[serde(default)]
+    pub decision: String,
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub since: String,  // rfc3339, compared to ts
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize { 100 }
+
+pub async fn handle_v1_audit_log(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Response<Body>, ScratchError> {
+    let since = if query.since.is_empty() {
+        None
+    } else {
+        Some(chrono::DateTime::parse_from_rfc3339(&query.since).map_err(|e| {
+            ScratchError::new(StatusCode::BAD_REQUEST, format!("since {:?} is not rfc3339, like 2025-01-31T12:00:00+00:00: {}", query.since, e))
+        })?)
+    };
+    let filter = AuditLogFilter {
+        chat_id: query.chat_id,
+        tool_name: query.tool_name,
+        decision: query.decision,
+        path: query.path,
+        since,
+        limit: query.limit,
+    };
+    let entries = audit_log_query(gcx.clone(), &filter).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string_pretty(&serde_json::json!({"entries": entries})).unwrap()))
+        .unwrap())
+}
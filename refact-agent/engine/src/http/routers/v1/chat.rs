@@ -10,12 +10,13 @@ use crate::call_validation::{ChatContent, ChatMessage, ChatPost};
 use crate::caps::resolve_chat_model;
 use crate::custom_error::ScratchError;
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::git::checkpoints::create_workspace_checkpoint;
+use crate::git::checkpoints::{checkpoint_label_from_user_message, create_workspace_checkpoint};
 use crate::global_context::{GlobalContext, SharedGlobalContext};
 use crate::indexing_utils::wait_for_indexing_if_needed;
 use crate::integrations::docker::docker_container_manager::docker_container_check_status_or_start;
 use crate::tools::tools_description::ToolDesc;
-use crate::tools::tools_list::get_available_tools_by_chat_mode;
+use crate::tools::tools_list::get_available_tools_by_chat_meta;
+use crate::yaml_configs::customization_loader::{load_chat_mode, ChatModeConfig};
 
 pub const CHAT_TOP_N: usize = 12;
 
@@ -62,6 +63,16 @@ fn fill_sampling_params(chat_post: &mut ChatPost, n_ctx: usize, model_id: &str)
     chat_post.parameters.temperature = Some(chat_post.parameters.temperature.unwrap_or(chat_post.temperature.unwrap_or(0.0)));
 }
 
+/// The mode sets defaults, whatever the request sets explicitly stays
+fn apply_custom_chat_mode(chat_post: &mut ChatPost, mode: &ChatModeConfig) {
+    chat_post.meta.chat_mode = mode.base_mode;
+    if chat_post.model.is_empty() {
+        chat_post.model = mode.model.clone();
+    }
+    if chat_post.checkpoints_enabled.is_none() {
+        chat_post.checkpoints_enabled = mode.checkpoints;
+    }
+}
 
 async fn _chat(
     gcx: Arc<ARwLock<GlobalContext>>,
@@ -81,9 +92,17 @@ async fn _chat(
 
     let mut messages = deserialize_messages_from_post(&chat_post.messages)?;
 
+    let mut write_globs = vec![];
+    if !chat_post.meta.custom_mode.is_empty() {
+        let mode = load_chat_mode(gcx.clone(), &chat_post.meta.custom_mode).await
+            .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+        apply_custom_chat_mode(&mut chat_post, &mode);
+        write_globs = mode.write_globs;
+        tracing::info!("custom chat mode {:?}", chat_post.meta.custom_mode);
+    }
     tracing::info!("chat_mode {:?}", chat_post.meta.chat_mode);
 
-    let tools: Vec<ToolDesc> = get_available_tools_by_chat_mode(gcx.clone(), chat_post.meta.chat_mode).await
+    let tools: Vec<ToolDesc> = get_available_tools_by_chat_meta(gcx.clone(), &chat_post.meta).await
         .into_iter()
         .map(|tool| tool.tool_description())
         .collect();
@@ -151,14 +170,15 @@ async fn _chat(
         None
     };
 
-    if chat_post.checkpoints_enabled {
+    if chat_post.checkpoints_enabled.unwrap_or(false) {
         let latest_checkpoint = messages.iter().rev()
             .find(|msg| msg.role == "user" && !msg.checkpoints.is_empty())
             .and_then(|msg| msg.checkpoints.first().cloned());
 
         if let Some(latest_user_msg) = messages.last_mut().filter(|m| m.role == "user") {
             if chat_post.meta.chat_mode.supports_checkpoints() && latest_user_msg.checkpoints.is_empty() {
-                match create_workspace_checkpoint(gcx.clone(), latest_checkpoint.as_ref(), &chat_post.meta.chat_id).await {
+                let label = checkpoint_label_from_user_message(&latest_user_msg.content.content_text_only());
+                match create_workspace_checkpoint(gcx.clone(), latest_checkpoint.as_ref(), &chat_post.meta.chat_id, &label).await {
                     Ok((checkpoint, _)) => {
                         tracing::info!("Checkpoint created: {:?}", checkpoint);
                         latest_user_msg.checkpoints = vec![checkpoint];
@@ -207,6 +227,8 @@ async fn _chat(
         should_execute_remotely,
         model_rec.base.id.clone(),
     ).await;
+    ccx.chat_mode = Some(chat_post.meta.chat_mode);
+    ccx.write_globs = write_globs;
     ccx.subchat_tool_parameters = chat_post.subchat_tool_parameters.clone();
     ccx.postprocess_parameters = chat_post.postprocess_parameters.clone();
     let ccx_arc = Arc::new(AMutex::new(ccx));
@@ -233,3 +255,24 @@ async fn _chat(
         ).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call_validation::ChatMode;
+
+    #[test]
+    fn test_apply_custom_chat_mode() {
+        let mode: ChatModeConfig = serde_yaml::from_str("base_mode: EXPLORE\nmodel: gpt-4.1\ncheckpoints: false").unwrap();
+        let mut chat_post = ChatPost { checkpoints_enabled: Some(true), model: "claude".to_string(), ..Default::default() };
+        apply_custom_chat_mode(&mut chat_post, &mode);
+        assert_eq!(chat_post.meta.chat_mode, ChatMode::EXPLORE);
+        assert_eq!(chat_post.checkpoints_enabled, Some(true));
+        assert_eq!(chat_post.model, "claude");
+
+        let mut chat_post = ChatPost::default();
+        apply_custom_chat_mode(&mut chat_post, &mode);
+        assert_eq!(chat_post.checkpoints_enabled, Some(false));
+        assert_eq!(chat_post.model, "gpt-4.1");
+    }
+}
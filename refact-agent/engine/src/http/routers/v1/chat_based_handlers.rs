@@ -8,6 +8,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock as ARwLock;
 use crate::agentic::generate_commit_message::generate_commit_message_by_diff;
 use crate::agentic::compress_trajectory::compress_trajectory;
+use crate::agentic::review::review_branch;
 use crate::call_validation::ChatMessage;
 
 #[derive(Deserialize)]
@@ -104,3 +105,37 @@ pub async fn handle_v1_trajectory_save(
         .body(Body::from(serde_json::to_string(&response).unwrap()))
         .unwrap())
 }
+
+#[derive(Deserialize)]
+struct ReviewPost {
+    base: String,
+    #[serde(default)]
+    head: String,  // HEAD if empty
+    #[serde(default)]
+    project_path: String,  // needed if there are several repositories in the workspace
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    instructions: String,
+}
+
+pub async fn handle_v1_review(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> axum::response::Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ReviewPost>(&body_bytes).map_err(|e| {
+        ScratchError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("JSON problem: {}", e),
+        )
+    })?;
+
+    let result = review_branch(gcx.clone(), &post.project_path, &post.base, &post.head, &post.model, &post.instructions)
+        .await.map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(&result).unwrap()))
+        .unwrap())
+}
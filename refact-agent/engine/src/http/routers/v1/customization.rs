@@ -6,7 +6,7 @@ use tokio::sync::RwLock as ARwLock;
 
 use crate::global_context::GlobalContext;
 use crate::custom_error::{ScratchError, YamlError};
-use crate::yaml_configs::customization_loader::load_customization;
+use crate::yaml_configs::customization_loader::{check_chat_mode, load_customization};
 
 
 pub async fn handle_v1_config_path(
@@ -35,3 +35,38 @@ pub async fn handle_v1_customization(
         .body(Body::from(serde_json::to_string_pretty(&response_body).unwrap()))
         .unwrap())
 }
+
+/// Custom chat modes for the mode picker, without their system prompts
+pub async fn handle_v1_chat_modes(
+    Extension(global_context): Extension<Arc<ARwLock<GlobalContext>>>,
+    _body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let mut error_log: Vec<YamlError> = Vec::new();
+    let tconfig = load_customization(global_context.clone(), true, &mut error_log).await;
+
+    let mut chat_modes = vec![];
+    for (name, mode) in tconfig.chat_modes.iter() {
+        if let Err(e) = check_chat_mode(name, mode) {
+            error_log.push(YamlError { path: "customization.yaml".to_string(), error_line: 0, error_msg: e });
+            continue;
+        }
+        chat_modes.push(serde_json::json!({
+            "name": name,
+            "description": mode.description,
+            "base_mode": mode.base_mode,
+            "tools": mode.tools,
+            "model": mode.model,
+            "checkpoints": mode.checkpoints,
+        }));
+    }
+    let response_body = serde_json::json!({
+        "chat_modes": chat_modes,
+        "error_log": error_log,
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string_pretty(&response_body).unwrap()))
+        .unwrap())
+}
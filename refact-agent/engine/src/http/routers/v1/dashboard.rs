@@ -6,11 +6,12 @@ use crate::global_context::SharedGlobalContext;
 
 use reqwest;
 use serde::{Serialize, Deserialize};
-use tracing::info;
+use tracing::{info, warn};
 use tokio::io;
 use tokio::io::AsyncBufReadExt;
-use crate::dashboard::dashboard::records2plots;
+use crate::dashboard::dashboard::{records2plots, usage2plots};
 use crate::dashboard::structs::RHData;
+use crate::telemetry::usage_ledger::usage_ledger;
 
 
 #[derive(Debug, Deserialize)]
@@ -59,11 +60,21 @@ async fn fetch_data(
     Ok(data)
 }
 
-pub async fn get_dashboard_plots(
-    Extension(global_context): Extension<SharedGlobalContext>,
-    _: hyper::body::Bytes,
-) -> axum::response::Result<Response<Body>, ScratchError> {
+async fn local_usage_plots(global_context: SharedGlobalContext) -> Option<serde_json::Value> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    let from_ts = now - 30.0 * 86400.0;
+    let ledger = usage_ledger(global_context).await.map_err(|e| warn!("usage ledger: {}", e)).ok()?;
+    let by_model = ledger.report(from_ts, now + 1.0, &vec!["provider".to_string(), "model".to_string()]).await
+        .map_err(|e| warn!("usage ledger: {}", e)).ok()?;
+    let by_day = ledger.report(from_ts, now + 1.0, &vec!["day".to_string()]).await
+        .map_err(|e| warn!("usage ledger: {}", e)).ok()?;
+    if by_model.is_empty() {
+        return None;
+    }
+    Some(usage2plots(&by_model, &by_day))
+}
 
+async fn remote_telemetry_plots(global_context: SharedGlobalContext) -> Result<serde_json::Value, ScratchError> {
     let caps = crate::global_context::try_load_caps_quickly_if_not_present(global_context.clone(), 0).await?;
     let (http_client, api_key, url) = {
         let gcx_locked = global_context.read().await;
@@ -84,11 +95,30 @@ pub async fn get_dashboard_plots(
         }
     };
 
-    let plots = match records2plots(&mut records).await {
-        Ok(plots) => plots,
-        Err(e) => {
-            return Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error plotting reports: {}", e)));
+    match records2plots(&mut records).await {
+        Ok(plots) => Ok(plots),
+        Err(e) => Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error plotting reports: {}", e))),
+    }
+}
+
+pub async fn get_dashboard_plots(
+    Extension(global_context): Extension<SharedGlobalContext>,
+    _: hyper::body::Bytes,
+) -> axum::response::Result<Response<Body>, ScratchError> {
+    let usage_plots = local_usage_plots(global_context.clone()).await;
+    let plots = match (remote_telemetry_plots(global_context.clone()).await, usage_plots) {
+        (Ok(mut plots), Some(usage_plots)) => {
+            if let (Some(plots_obj), Some(usage_obj)) = (plots.as_object_mut(), usage_plots.as_object()) {
+                plots_obj.extend(usage_obj.clone());
+            }
+            plots
+        }
+        (Ok(plots), None) => plots,
+        (Err(e), Some(usage_plots)) => {
+            info!("dashboard shows local usage only: {}", e.message);
+            usage_plots
         }
+        (Err(e), None) => return Err(e),
     };
     let body = match serde_json::to_string_pretty(&DashboardPlotsResponse{data: plots.to_string()}) {
         Ok(res) => res,
@@ -15,7 +15,8 @@ use crate::files_correction::{deserialize_path, serialize_path};
 use crate::custom_error::ScratchError;
 use crate::git::{CommitInfo, FileChange};
 use crate::git::operations::{get_configured_author_email_and_name, stage_changes};
-use crate::git::checkpoints::{preview_changes_for_workspace_checkpoint, restore_workspace_checkpoint, Checkpoint};
+use crate::git::checkpoints::{diff_workspace_checkpoints, list_workspace_checkpoints, preview_changes_for_workspace_checkpoint,
+    restore_files_from_workspace_checkpoint, restore_workspace_checkpoint, Checkpoint, CheckpointFileDiff, CheckpointFileRestore};
 use crate::global_context::GlobalContext;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -45,6 +46,41 @@ pub struct CheckpointsPreviewResponse {
     pub error_log: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsListPost {
+    pub meta: ChatMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsDiffPost {
+    pub from: Checkpoint,
+    #[serde(default)]
+    pub to: Option<Checkpoint>,
+    pub meta: ChatMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CheckpointsDiffResponse {
+    pub files: Vec<CheckpointFileDiff>,
+    pub to: Option<Checkpoint>,
+    pub error_log: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsRestoreFilesPost {
+    pub checkpoint: Checkpoint,
+    pub files: Vec<CheckpointFileRestore>,
+    pub meta: ChatMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CheckpointsRestoreFilesResponse {
+    pub success: bool,
+    pub restored_files: Vec<FileChange>,
+    pub checkpoints_for_undo: Vec<Checkpoint>,
+    pub error_log: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct CheckpointsRestoreResponse {
     pub success: bool, 
@@ -209,4 +245,62 @@ pub async fn handle_v1_checkpoints_restore(
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&response).unwrap()))
         .unwrap())
-}
\ No newline at end of file
+}
+
+fn json_ok_response<T: Serialize>(response: &T) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(response).unwrap()))
+        .unwrap()
+}
+
+pub async fn handle_v1_checkpoints_list(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsListPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let (checkpoints, error_log) = match list_workspace_checkpoints(gcx.clone(), &post.meta.chat_id).await {
+        Ok(checkpoints) => (checkpoints, vec![]),
+        Err(e) => (vec![], vec![e]),
+    };
+    Ok(json_ok_response(&serde_json::json!({
+        "checkpoints": checkpoints,
+        "error_log": error_log,
+    })))
+}
+
+pub async fn handle_v1_checkpoints_diff(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsDiffPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let response = match diff_workspace_checkpoints(gcx.clone(), &post.from, post.to.as_ref()).await {
+        Ok((files, to)) => CheckpointsDiffResponse { files, to, error_log: vec![] },
+        Err(e) => CheckpointsDiffResponse { error_log: vec![e], ..Default::default() },
+    };
+    Ok(json_ok_response(&response))
+}
+
+pub async fn handle_v1_checkpoints_restore_files(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsRestoreFilesPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    if post.files.is_empty() {
+        return Err(ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, "No files to restore".to_string()));
+    }
+    let response = match restore_files_from_workspace_checkpoint(gcx.clone(), &post.checkpoint, &post.files, &post.meta.chat_id).await {
+        Ok((restored_files, checkpoint_for_undo, error_log)) => CheckpointsRestoreFilesResponse {
+            success: error_log.is_empty(),
+            restored_files,
+            checkpoints_for_undo: vec![checkpoint_for_undo],
+            error_log,
+        },
+        Err(e) => CheckpointsRestoreFilesResponse { error_log: vec![e], ..Default::default() },
+    };
+    Ok(json_ok_response(&response))
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use axum::Extension;
+use axum::extract::Query;
+use axum::http::{Response, StatusCode};
+use hyper::Body;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock as ARwLock;
+
+use crate::custom_error::ScratchError;
+use crate::global_context::GlobalContext;
+use crate::local_threads::{local_threads_db, local_to_message_json, LocalThread, LocalThreadsDb, ThreadFork};
+
+
+#[derive(Deserialize)]
+struct ThreadsListPost {
+    #[serde(default)]
+    search: String,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize { 100 }
+
+#[derive(Deserialize)]
+struct ThreadGetPost {
+    ft_id: String,
+}
+
+#[derive(Deserialize)]
+struct ThreadAppendPost {
+    ft_id: String,
+    #[serde(default)]
+    ft_title: String,
+    #[serde(default)]
+    ft_model: String,
+    #[serde(default)]
+    ft_chat_mode: String,
+    messages: Vec<Value>,  // raw json, the same way ChatPost keeps them, so nothing is lost
+}
+
+#[derive(Deserialize)]
+struct ThreadForkPost {
+    ft_id: String,
+    #[serde(default)]
+    up_to_num: Option<i32>,
+    #[serde(default)]
+    new_ft_id: String,
+    #[serde(default)]
+    new_title: String,
+}
+
+#[derive(Deserialize)]
+pub struct ThreadDeleteQueryParams {
+    ft_id: String,
+}
+
+fn json_response(v: Value) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string_pretty(&v).unwrap()))
+        .unwrap()
+}
+
+async fn get_db(gcx: Arc<ARwLock<GlobalContext>>) -> Result<LocalThreadsDb, ScratchError> {
+    local_threads_db(gcx).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("cannot open threads database: {}", e)))
+}
+
+fn db_error(e: String) -> ScratchError {
+    ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+pub async fn handle_v1_threads_list(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ThreadsListPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let threads = get_db(gcx).await?.threads_list(&post.search, post.limit).await.map_err(db_error)?;
+    Ok(json_response(json!({ "threads": threads })))
+}
+
+pub async fn handle_v1_thread_get(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ThreadGetPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let (thread, messages) = get_db(gcx).await?.thread_get(&post.ft_id).await.map_err(db_error)?
+        .ok_or(ScratchError::new(StatusCode::NOT_FOUND, format!("thread {} not found", post.ft_id)))?;
+    // "messages" can be sent to /v1/chat as is to resume the conversation
+    Ok(json_response(json!({
+        "thread": thread,
+        "messages": messages.iter().map(local_to_message_json).collect::<Vec<_>>(),
+    })))
+}
+
+pub async fn handle_v1_thread_append(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ThreadAppendPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    if post.ft_id.is_empty() {
+        return Err(ScratchError::new(StatusCode::BAD_REQUEST, "ft_id is empty".to_string()));
+    }
+    let thread = LocalThread {
+        ft_id: post.ft_id,
+        ft_title: post.ft_title,
+        ft_model: post.ft_model,
+        ft_chat_mode: post.ft_chat_mode,
+        ..Default::default()
+    };
+    let (thread, new_messages) = get_db(gcx).await?.thread_append(thread, post.messages).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    Ok(json_response(json!({
+        "thread": thread,
+        "appended_nums": new_messages.iter().map(|m| m.ftm_num).collect::<Vec<_>>(),
+    })))
+}
+
+pub async fn handle_v1_thread_fork(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ThreadForkPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let new_ft_id = if post.new_ft_id.is_empty() { uuid::Uuid::new_v4().to_string() } else { post.new_ft_id };
+    match get_db(gcx).await?.thread_fork(&post.ft_id, post.up_to_num, &new_ft_id, &post.new_title).await.map_err(db_error)? {
+        ThreadFork::Forked(thread) => Ok(json_response(json!({ "thread": thread }))),
+        ThreadFork::SourceNotFound => Err(ScratchError::new(StatusCode::NOT_FOUND, format!("thread {} not found", post.ft_id))),
+        ThreadFork::IdTaken => Err(ScratchError::new(StatusCode::CONFLICT, format!("thread {} already exists", new_ft_id))),
+    }
+}
+
+pub async fn handle_v1_thread_delete(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    Query(params): Query<ThreadDeleteQueryParams>,
+) -> Result<Response<Body>, ScratchError> {
+    let deleted = get_db(gcx).await?.thread_delete(&params.ft_id).await.map_err(db_error)?;
+    if !deleted {
+        return Err(ScratchError::new(StatusCode::NOT_FOUND, format!("thread {} not found", params.ft_id)));
+    }
+    Ok(json_response(json!({ "success": true })))
+}
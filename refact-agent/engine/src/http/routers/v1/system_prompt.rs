@@ -11,7 +11,7 @@ use crate::global_context::GlobalContext;
 use crate::indexing_utils::wait_for_indexing_if_needed;
 use crate::scratchpads::chat_utils_prompts::prepend_the_right_system_prompt_and_maybe_more_initial_messages;
 use crate::scratchpads::scratchpad_utils::HasRagResults;
-use crate::tools::tools_list::get_available_tools_by_chat_mode;
+use crate::tools::tools_list::get_available_tools_by_chat_meta;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PrependSystemPromptPost {
@@ -40,7 +40,7 @@ pub async fn handle_v1_prepend_system_prompt_and_maybe_more_initial_messages(
         post.messages, 
         &post.chat_meta, 
         &mut has_rag_results,
-        get_available_tools_by_chat_mode(gcx.clone(), post.chat_meta.chat_mode)
+        get_available_tools_by_chat_meta(gcx.clone(), &post.chat_meta)
             .await
             .into_iter()
             .map(|t| t.tool_description().name)
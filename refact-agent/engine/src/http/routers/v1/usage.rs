@@ -0,0 +1,48 @@
+use axum::Extension;
+use axum::http::{Response, StatusCode};
+use hyper::Body;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::custom_error::ScratchError;
+use crate::global_context::SharedGlobalContext;
+use crate::telemetry::usage_ledger::usage_ledger;
+
+
+#[derive(Deserialize)]
+struct UsageReportPost {
+    #[serde(default)]
+    from_ts: f64,
+    #[serde(default)]
+    to_ts: Option<f64>,
+    #[serde(default)]
+    group_by: Vec<String>,
+}
+
+pub async fn handle_v1_usage_report(
+    Extension(gcx): Extension<SharedGlobalContext>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<UsageReportPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let to_ts = post.to_ts.unwrap_or_else(|| {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64() + 1.0
+    });
+    let ledger = usage_ledger(gcx).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("cannot open usage ledger: {}", e)))?;
+    let rows = ledger.report(post.from_ts, to_ts, &post.group_by).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    let total = ledger.report(post.from_ts, to_ts, &vec![]).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string_pretty(&json!({
+            "from_ts": post.from_ts,
+            "to_ts": to_ts,
+            "group_by": post.group_by,
+            "rows": rows,
+            "total": total.into_iter().next(),
+        })).unwrap()))
+        .unwrap())
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::integrations::forge::{arg_str, arg_str_required, arg_usize, arg_usize_required, forge_request, trim_patches, trimmed_str, url_encode,
+    ForgeAction, ForgeApi, ForgeKind, FORGE_LIST_MAX, FORGE_TEXT_MAX_CHARS};
+
+
+fn user_login(v: &Value) -> Value {
+    v.get("login").cloned().unwrap_or(Value::Null)
+}
+
+fn short_issue(issue: &Value) -> Value {
+    json!({
+        "number": issue["number"],
+        "title": issue["title"],
+        "state": issue["state"],
+        "labels": issue["labels"].as_array().map(|a| a.iter().map(|l| l["name"].clone()).collect::<Vec<_>>()).unwrap_or_default(),
+        "author": user_login(&issue["user"]),
+        "comments": issue["comments"],
+        "updated_at": issue["updated_at"],
+        "url": issue["html_url"],
+    })
+}
+
+fn short_comment(comment: &Value) -> Value {
+    json!({
+        "id": comment["id"],
+        "author": user_login(&comment["user"]),
+        "created_at": comment["created_at"],
+        "body": trimmed_str(&comment["body"], FORGE_TEXT_MAX_CHARS),
+    })
+}
+
+async fn get(http_client: &reqwest::Client, api: &ForgeApi, path: &str) -> Result<Value, String> {
+    forge_request(http_client, ForgeKind::GitHub, api, Method::GET, path, None).await
+}
+
+async fn head_sha(http_client: &reqwest::Client, api: &ForgeApi, repo: &str, number: usize) -> Result<String, String> {
+    let pr = get(http_client, api, &format!("repos/{repo}/pulls/{number}")).await?;
+    pr["head"]["sha"].as_str().map(|s| s.to_string()).ok_or(format!("pull request {} has no head sha", number))
+}
+
+pub async fn github_execute(
+    http_client: &reqwest::Client,
+    api: &ForgeApi,
+    action: ForgeAction,
+    repo: &str,
+    args: &HashMap<String, Value>,
+) -> Result<Value, String> {
+    match action {
+        ForgeAction::ListIssues => {
+            let state = arg_str(args, "state")?.unwrap_or("open".to_string());
+            let limit = arg_usize(args, "limit")?.unwrap_or(20).min(FORGE_LIST_MAX);
+            let mut path = format!("repos/{repo}/issues?state={}&per_page={limit}", url_encode(&state));
+            if let Some(labels) = arg_str(args, "labels")? {
+                path.push_str(&format!("&labels={}", url_encode(&labels)));
+            }
+            let issues = get(http_client, api, &path).await?;
+            // the issues endpoint returns pull requests too
+            let issues: Vec<Value> = issues.as_array().cloned().unwrap_or_default().iter()
+                .filter(|i| i.get("pull_request").is_none())
+                .map(short_issue)
+                .collect();
+            Ok(json!({ "repo": repo, "issues": issues }))
+        },
+        ForgeAction::GetIssue => {
+            let number = arg_usize_required(args, "number")?;
+            let issue = get(http_client, api, &format!("repos/{repo}/issues/{number}")).await?;
+            let comments = get(http_client, api, &format!("repos/{repo}/issues/{number}/comments?per_page={FORGE_LIST_MAX}")).await?;
+            let mut result = short_issue(&issue);
+            result["body"] = trimmed_str(&issue["body"], FORGE_TEXT_MAX_CHARS);
+            result["comments"] = json!(comments.as_array().cloned().unwrap_or_default().iter().map(short_comment).collect::<Vec<_>>());
+            Ok(result)
+        },
+        ForgeAction::GetChangeRequest => {
+            let number = arg_usize_required(args, "number")?;
+            let pr = get(http_client, api, &format!("repos/{repo}/pulls/{number}")).await?;
+            let files = get(http_client, api, &format!("repos/{repo}/pulls/{number}/files?per_page={FORGE_LIST_MAX}")).await?;
+            let mut files: Vec<Value> = files.as_array().cloned().unwrap_or_default().iter().map(|f| json!({
+                "path": f["filename"],
+                "status": f["status"],
+                "additions": f["additions"],
+                "deletions": f["deletions"],
+                "patch": f["patch"],
+            })).collect();
+            trim_patches(&mut files);
+            Ok(json!({
+                "number": pr["number"],
+                "title": pr["title"],
+                "state": pr["state"],
+                "draft": pr["draft"],
+                "author": user_login(&pr["user"]),
+                "base": pr["base"]["ref"],
+                "head": pr["head"]["ref"],
+                "head_sha": pr["head"]["sha"],
+                "mergeable": pr["mergeable"],
+                "url": pr["html_url"],
+                "body": trimmed_str(&pr["body"], FORGE_TEXT_MAX_CHARS),
+                "files": files,
+            }))
+        },
+        ForgeAction::ListReviewComments => {
+            let number = arg_usize_required(args, "number")?;
+            let comments = get(http_client, api, &format!("repos/{repo}/pulls/{number}/comments?per_page={FORGE_LIST_MAX}")).await?;
+            let comments: Vec<Value> = comments.as_array().cloned().unwrap_or_default().iter().map(|c| {
+                let mut short = short_comment(c);
+                short["path"] = c["path"].clone();
+                short["line"] = if c["line"].is_null() { c["original_line"].clone() } else { c["line"].clone() };
+                short["in_reply_to_id"] = c["in_reply_to_id"].clone();
+                short
+            }).collect();
+            Ok(json!({ "number": number, "review_comments": comments }))
+        },
+        ForgeAction::PostReviewComment => {
+            let number = arg_usize_required(args, "number")?;
+            let path = arg_str_required(args, "path")?;
+            let line = arg_usize_required(args, "line")?;
+            let body = arg_str_required(args, "body")?;
+            let commit_id = head_sha(http_client, api, repo, number).await?;
+            let comment = forge_request(http_client, ForgeKind::GitHub, api, Method::POST, &format!("repos/{repo}/pulls/{number}/comments"), Some(json!({
+                "body": body,
+                "commit_id": commit_id,
+                "path": path,
+                "line": line,
+                "side": "RIGHT",
+            }))).await?;
+            Ok(json!({ "posted": true, "id": comment["id"], "url": comment["html_url"] }))
+        },
+        ForgeAction::CiStatus => {
+            let sha = match (arg_usize(args, "number")?, arg_str(args, "ref")?) {
+                (Some(number), _) => head_sha(http_client, api, repo, number).await?,
+                (None, Some(git_ref)) => git_ref,
+                (None, None) => return Err("pass either `number` or `ref`".to_string()),
+            };
+            let check_runs = get(http_client, api, &format!("repos/{repo}/commits/{}/check-runs?per_page={FORGE_LIST_MAX}", url_encode(&sha))).await?;
+            let status = get(http_client, api, &format!("repos/{repo}/commits/{}/status", url_encode(&sha))).await?;
+            let checks: Vec<Value> = check_runs["check_runs"].as_array().cloned().unwrap_or_default().iter().map(|c| json!({
+                "name": c["name"],
+                "status": c["status"],
+                "conclusion": c["conclusion"],
+                "url": c["html_url"],
+            })).collect();
+            let statuses: Vec<Value> = status["statuses"].as_array().cloned().unwrap_or_default().iter().map(|s| json!({
+                "context": s["context"],
+                "state": s["state"],
+                "description": s["description"],
+                "url": s["target_url"],
+            })).collect();
+            Ok(json!({
+                "sha": sha,
+                "combined_state": status["state"],
+                "checks": checks,
+                "statuses": statuses,
+            }))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router};
+    use axum::routing::get as axum_get;
+    use axum::extract::Path;
+
+    #[tokio::test]
+    async fn test_github_against_mock_server() {
+        let app = Router::new()
+            .route("/repos/acme/app/issues", axum_get(|| async { Json(json!([
+                {"number": 1, "title": "bug", "state": "open", "labels": [{"name": "p1"}], "user": {"login": "ann"}, "html_url": "u1"},
+                {"number": 2, "title": "a PR", "state": "open", "labels": [], "user": {"login": "bob"}, "pull_request": {}},
+            ])) }))
+            .route("/repos/acme/app/pulls/:number", axum_get(|Path(number): Path<usize>| async move { Json(json!({
+                "number": number, "title": "feature", "head": {"sha": "abc123", "ref": "feat"}, "base": {"ref": "main"}, "user": {"login": "bob"},
+            })) }))
+            .route("/repos/acme/app/pulls/:number/comments", axum::routing::post(|Json(body): Json<Value>| async move {
+                assert_eq!(body["commit_id"], json!("abc123"));
+                assert_eq!(body["line"], json!(42));
+                Json(json!({"id": 7, "html_url": "c7"}))
+            }));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+        let http_client = reqwest::Client::new();
+        let api = ForgeApi { api_base_url: format!("http://{}", addr), token: "t".to_string() };
+
+        let issues = github_execute(&http_client, &api, ForgeAction::ListIssues, "acme/app", &HashMap::new()).await.unwrap();
+        assert_eq!(issues["issues"].as_array().unwrap().len(), 1);
+        assert_eq!(issues["issues"][0]["labels"], json!(["p1"]));
+
+        let args = HashMap::from([
+            ("number".to_string(), json!(5)),
+            ("path".to_string(), json!("src/main.rs")),
+            ("line".to_string(), json!(42)),
+            ("body".to_string(), json!("nit")),
+        ]);
+        let posted = github_execute(&http_client, &api, ForgeAction::PostReviewComment, "acme/app", &args).await.unwrap();
+        assert_eq!(posted["id"], json!(7));
+
+        let err = github_execute(&http_client, &api, ForgeAction::GetIssue, "acme/app", &HashMap::from([("number".to_string(), json!(9))])).await;
+        assert!(err.unwrap_err().contains("404"));
+    }
+}
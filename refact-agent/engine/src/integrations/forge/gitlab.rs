@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::integrations::forge::{arg_str, arg_str_required, arg_usize, arg_usize_required, forge_request, trim_patches, trimmed_str, url_encode,
+    ForgeAction, ForgeApi, ForgeKind, FORGE_LIST_MAX, FORGE_TEXT_MAX_CHARS};
+
+
+fn author_username(v: &Value) -> Value {
+    v.get("username").cloned().unwrap_or(Value::Null)
+}
+
+fn short_issue(issue: &Value) -> Value {
+    json!({
+        "number": issue["iid"],
+        "title": issue["title"],
+        "state": issue["state"],
+        "labels": issue["labels"],
+        "author": author_username(&issue["author"]),
+        "comments": issue["user_notes_count"],
+        "updated_at": issue["updated_at"],
+        "url": issue["web_url"],
+    })
+}
+
+fn short_note(note: &Value) -> Value {
+    json!({
+        "id": note["id"],
+        "author": author_username(&note["author"]),
+        "created_at": note["created_at"],
+        "body": trimmed_str(&note["body"], FORGE_TEXT_MAX_CHARS),
+    })
+}
+
+fn short_pipeline(pipeline: &Value) -> Value {
+    json!({
+        "id": pipeline["id"],
+        "status": pipeline["status"],
+        "ref": pipeline["ref"],
+        "sha": pipeline["sha"],
+        "url": pipeline["web_url"],
+    })
+}
+
+async fn get(http_client: &reqwest::Client, api: &ForgeApi, path: &str) -> Result<Value, String> {
+    forge_request(http_client, ForgeKind::GitLab, api, Method::GET, path, None).await
+}
+
+pub async fn gitlab_execute(
+    http_client: &reqwest::Client,
+    api: &ForgeApi,
+    action: ForgeAction,
+    repo: &str,
+    args: &HashMap<String, Value>,
+) -> Result<Value, String> {
+    let project = format!("projects/{}", url_encode(repo));
+    match action {
+        ForgeAction::ListIssues => {
+            let state = match arg_str(args, "state")?.as_deref() {
+                None | Some("open") | Some("opened") => "opened",
+                Some("closed") => "closed",
+                Some("all") => "all",
+                Some(other) => return Err(format!("unknown state {:?}, should be one of: open, closed, all", other)),
+            };
+            let limit = arg_usize(args, "limit")?.unwrap_or(20).min(FORGE_LIST_MAX);
+            let mut path = format!("{project}/issues?per_page={limit}");
+            if state != "all" {
+                path.push_str(&format!("&state={state}"));
+            }
+            if let Some(labels) = arg_str(args, "labels")? {
+                path.push_str(&format!("&labels={}", url_encode(&labels)));
+            }
+            let issues = get(http_client, api, &path).await?;
+            let issues: Vec<Value> = issues.as_array().cloned().unwrap_or_default().iter().map(short_issue).collect();
+            Ok(json!({ "repo": repo, "issues": issues }))
+        },
+        ForgeAction::GetIssue => {
+            let number = arg_usize_required(args, "number")?;
+            let issue = get(http_client, api, &format!("{project}/issues/{number}")).await?;
+            let notes = get(http_client, api, &format!("{project}/issues/{number}/notes?sort=asc&per_page={FORGE_LIST_MAX}")).await?;
+            let mut result = short_issue(&issue);
+            result["body"] = trimmed_str(&issue["description"], FORGE_TEXT_MAX_CHARS);
+            // system notes are "changed the label" kind of events, not comments
+            result["comments"] = json!(notes.as_array().cloned().unwrap_or_default().iter()
+                .filter(|n| !n["system"].as_bool().unwrap_or(false))
+                .map(short_note)
+                .collect::<Vec<_>>());
+            Ok(result)
+        },
+        ForgeAction::GetChangeRequest => {
+            let number = arg_usize_required(args, "number")?;
+            let mr = get(http_client, api, &format!("{project}/merge_requests/{number}/changes")).await?;
+            let mut files: Vec<Value> = mr["changes"].as_array().cloned().unwrap_or_default().iter().map(|c| json!({
+                "path": c["new_path"],
+                "status": if c["new_file"].as_bool().unwrap_or(false) { "added" }
+                    else if c["deleted_file"].as_bool().unwrap_or(false) { "removed" }
+                    else if c["renamed_file"].as_bool().unwrap_or(false) { "renamed" }
+                    else { "modified" },
+                "patch": c["diff"],
+            })).collect();
+            trim_patches(&mut files);
+            Ok(json!({
+                "number": mr["iid"],
+                "title": mr["title"],
+                "state": mr["state"],
+                "draft": mr["draft"],
+                "author": author_username(&mr["author"]),
+                "base": mr["target_branch"],
+                "head": mr["source_branch"],
+                "head_sha": mr["sha"],
+                "mergeable": mr["merge_status"],
+                "url": mr["web_url"],
+                "body": trimmed_str(&mr["description"], FORGE_TEXT_MAX_CHARS),
+                "files": files,
+            }))
+        },
+        ForgeAction::ListReviewComments => {
+            let number = arg_usize_required(args, "number")?;
+            let discussions = get(http_client, api, &format!("{project}/merge_requests/{number}/discussions?per_page={FORGE_LIST_MAX}")).await?;
+            let mut comments = vec![];
+            for discussion in discussions.as_array().cloned().unwrap_or_default() {
+                for note in discussion["notes"].as_array().cloned().unwrap_or_default() {
+                    if note["system"].as_bool().unwrap_or(false) {
+                        continue;
+                    }
+                    let mut short = short_note(&note);
+                    short["discussion_id"] = discussion["id"].clone();
+                    short["path"] = note["position"]["new_path"].clone();
+                    short["line"] = note["position"]["new_line"].clone();
+                    short["resolved"] = note["resolved"].clone();
+                    comments.push(short);
+                }
+            }
+            Ok(json!({ "number": number, "review_comments": comments }))
+        },
+        ForgeAction::PostReviewComment => {
+            let number = arg_usize_required(args, "number")?;
+            let path = arg_str_required(args, "path")?;
+            let line = arg_usize_required(args, "line")?;
+            let body = arg_str_required(args, "body")?;
+            let mr = get(http_client, api, &format!("{project}/merge_requests/{number}")).await?;
+            let diff_refs = &mr["diff_refs"];
+            if diff_refs.is_null() {
+                return Err(format!("merge request !{} has no diff refs yet, try again later", number));
+            }
+            let discussion = forge_request(http_client, ForgeKind::GitLab, api, Method::POST, &format!("{project}/merge_requests/{number}/discussions"), Some(json!({
+                "body": body,
+                "position": {
+                    "position_type": "text",
+                    "base_sha": diff_refs["base_sha"],
+                    "start_sha": diff_refs["start_sha"],
+                    "head_sha": diff_refs["head_sha"],
+                    "old_path": path,
+                    "new_path": path,
+                    "new_line": line,
+                },
+            }))).await?;
+            Ok(json!({ "posted": true, "id": discussion["id"], "url": mr["web_url"] }))
+        },
+        ForgeAction::CiStatus => {
+            let pipelines = match (arg_usize(args, "number")?, arg_str(args, "ref")?) {
+                (Some(number), _) => get(http_client, api, &format!("{project}/merge_requests/{number}/pipelines")).await?,
+                (None, Some(git_ref)) => get(http_client, api, &format!("{project}/pipelines?ref={}&per_page=1", url_encode(&git_ref))).await?,
+                (None, None) => return Err("pass either `number` or `ref`".to_string()),
+            };
+            let pipeline = match pipelines.as_array().and_then(|a| a.first()) {
+                Some(pipeline) => pipeline.clone(),
+                None => return Ok(json!({ "pipeline": null, "jobs": [] })),
+            };
+            let jobs = get(http_client, api, &format!("{project}/pipelines/{}/jobs?per_page={FORGE_LIST_MAX}", pipeline["id"])).await?;
+            let jobs: Vec<Value> = jobs.as_array().cloned().unwrap_or_default().iter().map(|j| json!({
+                "name": j["name"],
+                "stage": j["stage"],
+                "status": j["status"],
+                "allow_failure": j["allow_failure"],
+                "url": j["web_url"],
+            })).collect();
+            Ok(json!({ "pipeline": short_pipeline(&pipeline), "jobs": jobs }))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router};
+    use axum::routing::{get as axum_get, post as axum_post};
+
+    #[tokio::test]
+    async fn test_gitlab_against_mock_server() {
+        let app = Router::new()
+            .route("/projects/grp%2Fapp/merge_requests/3", axum_get(|| async { Json(json!({
+                "iid": 3, "web_url": "mr3", "diff_refs": {"base_sha": "b", "start_sha": "s", "head_sha": "h"},
+            })) }))
+            .route("/projects/grp%2Fapp/merge_requests/3/discussions", axum_post(|Json(body): Json<Value>| async move {
+                assert_eq!(body["position"]["head_sha"], json!("h"));
+                assert_eq!(body["position"]["new_line"], json!(10));
+                Json(json!({"id": "d1"}))
+            }))
+            .route("/projects/grp%2Fapp/pipelines", axum_get(|| async { Json(json!([{"id": 77, "status": "failed", "ref": "main"}])) }))
+            .route("/projects/grp%2Fapp/pipelines/77/jobs", axum_get(|| async { Json(json!([
+                {"name": "test", "stage": "test", "status": "failed"},
+            ])) }));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+        let http_client = reqwest::Client::new();
+        let api = ForgeApi { api_base_url: format!("http://{}/", addr), token: "t".to_string() };
+
+        let args = HashMap::from([
+            ("number".to_string(), json!(3)),
+            ("path".to_string(), json!("a.py")),
+            ("line".to_string(), json!("10")),
+            ("body".to_string(), json!("why?")),
+        ]);
+        let posted = gitlab_execute(&http_client, &api, ForgeAction::PostReviewComment, "grp/app", &args).await.unwrap();
+        assert_eq!(posted["id"], json!("d1"));
+
+        let ci = gitlab_execute(&http_client, &api, ForgeAction::CiStatus, "grp/app", &HashMap::from([("ref".to_string(), json!("main"))])).await.unwrap();
+        assert_eq!(ci["pipeline"]["status"], json!("failed"));
+        assert_eq!(ci["jobs"][0]["name"], json!("test"));
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::Mutex as AMutex;
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
+use crate::files_correction::get_project_dirs;
+use crate::git::operations::get_git_remotes;
+use crate::integrations::go_to_configuration_message;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation};
+use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+
+pub mod github;
+pub mod gitlab;
+
+// Typed GitHub / GitLab tools on top of REST APIs, integr_github and integr_gitlab create one tool per action.
+
+pub const FORGE_TEXT_MAX_CHARS: usize = 4000;
+pub const FORGE_PATCH_MAX_CHARS: usize = 8000;
+pub const FORGE_DIFF_MAX_CHARS: usize = 40000;
+pub const FORGE_LIST_MAX: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForgeAction {
+    ListIssues,
+    GetIssue,
+    GetChangeRequest,
+    ListReviewComments,
+    PostReviewComment,
+    CiStatus,
+}
+
+pub const FORGE_ACTIONS: [ForgeAction; 6] = [
+    ForgeAction::ListIssues,
+    ForgeAction::GetIssue,
+    ForgeAction::GetChangeRequest,
+    ForgeAction::ListReviewComments,
+    ForgeAction::PostReviewComment,
+    ForgeAction::CiStatus,
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct ForgeApi {
+    pub api_base_url: String,
+    pub token: String,
+}
+
+impl ForgeKind {
+    pub fn integr_name(&self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "github",
+            ForgeKind::GitLab => "gitlab",
+        }
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "GitHub",
+            ForgeKind::GitLab => "GitLab",
+        }
+    }
+
+    fn change_request(&self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "pull request",
+            ForgeKind::GitLab => "merge request",
+        }
+    }
+}
+
+impl ForgeAction {
+    pub fn tool_name(&self, kind: ForgeKind) -> String {
+        let action = match (self, kind) {
+            (ForgeAction::ListIssues, _) => "list_issues",
+            (ForgeAction::GetIssue, _) => "get_issue",
+            (ForgeAction::GetChangeRequest, ForgeKind::GitHub) => "get_pr",
+            (ForgeAction::GetChangeRequest, ForgeKind::GitLab) => "get_mr",
+            (ForgeAction::ListReviewComments, _) => "list_review_comments",
+            (ForgeAction::PostReviewComment, _) => "post_review_comment",
+            (ForgeAction::CiStatus, _) => "ci_status",
+        };
+        format!("{}_{}", kind.integr_name(), action)
+    }
+}
+
+pub struct ToolForge {
+    pub kind: ForgeKind,
+    pub action: ForgeAction,
+    pub api: ForgeApi,
+    pub common: IntegrationCommon,
+    pub config_path: String,
+}
+
+fn param(name: &str, param_type: &str, description: &str) -> ToolParam {
+    ToolParam { name: name.to_string(), param_type: param_type.to_string(), description: description.to_string() }
+}
+
+pub fn trim_text(text: &str, max_chars: usize) -> String {
+    let n = text.chars().count();
+    if n <= max_chars {
+        return text.to_string();
+    }
+    format!("{}\n...[{} more chars]", text.chars().take(max_chars).collect::<String>(), n - max_chars)
+}
+
+pub fn trimmed_str(v: &Value, max_chars: usize) -> Value {
+    match v.as_str() {
+        Some(s) => Value::String(trim_text(s, max_chars)),
+        None => Value::Null,
+    }
+}
+
+/// Patches of all files together stay within FORGE_DIFF_MAX_CHARS, files over the budget are listed without a patch
+pub fn trim_patches(files: &mut Vec<Value>) {
+    let mut budget = FORGE_DIFF_MAX_CHARS;
+    for f in files.iter_mut() {
+        let patch = f.get("patch").and_then(|p| p.as_str()).unwrap_or_default().to_string();
+        let patch = trim_text(&patch, FORGE_PATCH_MAX_CHARS);
+        if patch.len() > budget {
+            f["patch"] = json!("...skipped, the diff is too big, look at the file itself");
+        } else {
+            budget -= patch.len();
+            f["patch"] = json!(patch);
+        }
+    }
+}
+
+pub fn arg_str(args: &HashMap<String, Value>, name: &str) -> Result<Option<String>, String> {
+    match args.get(name) {
+        Some(Value::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.trim().to_string())),
+        Some(Value::Null) | None => Ok(None),
+        Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+    }
+}
+
+pub fn arg_usize(args: &HashMap<String, Value>, name: &str) -> Result<Option<usize>, String> {
+    match args.get(name) {
+        Some(Value::Number(n)) => n.as_u64().map(|n| Some(n as usize)).ok_or(format!("argument `{}` should be a positive integer", name)),
+        Some(Value::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(Value::String(s)) => s.trim().trim_start_matches('#').parse::<usize>().map(Some)
+            .map_err(|_| format!("argument `{}` should be a positive integer, got {:?}", name, s)),
+        Some(Value::Null) | None => Ok(None),
+        Some(v) => Err(format!("argument `{}` is not a number: {:?}", name, v)),
+    }
+}
+
+pub fn arg_usize_required(args: &HashMap<String, Value>, name: &str) -> Result<usize, String> {
+    arg_usize(args, name)?.ok_or(format!("Missing argument `{}`", name))
+}
+
+pub fn arg_str_required(args: &HashMap<String, Value>, name: &str) -> Result<String, String> {
+    arg_str(args, name)?.ok_or(format!("Missing argument `{}`", name))
+}
+
+/// "git@github.com:owner/repo.git", "https://github.com/owner/repo", "ssh://git@host:22/group/sub/repo.git" => (host, path)
+pub fn parse_remote_url(remote_url: &str) -> Option<(String, String)> {
+    let (host, path) = if let Ok(url) = url::Url::parse(remote_url) {
+        (url.host_str()?.to_string(), url.path().to_string())
+    } else {
+        let (user_host, path) = remote_url.split_once(':')?;
+        let host = user_host.rsplit('@').next()?;
+        (host.to_string(), path.to_string())
+    };
+    let path = path.trim_matches('/').trim_end_matches(".git").to_string();
+    if host.is_empty() || !path.contains('/') {
+        return None;
+    }
+    Some((host.to_lowercase(), path))
+}
+
+/// api.github.com serves github.com, GitHub Enterprise and GitLab keep the API on the same host
+pub fn api_host_matches_remote(api_base_url: &str, remote_host: &str) -> bool {
+    let api_host = match url::Url::parse(api_base_url).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+        Some(h) => h,
+        None => return false,
+    };
+    api_host == remote_host || api_host.strip_prefix("api.") == Some(remote_host)
+}
+
+async fn detect_repo(ccx: Arc<AMutex<AtCommandsContext>>, api_base_url: &str) -> Result<String, String> {
+    let gcx = ccx.lock().await.global_context.clone();
+    let mut found: Vec<String> = vec![];
+    for project_dir in get_project_dirs(gcx).await {
+        for (_, remote_url) in get_git_remotes(&project_dir).unwrap_or_default() {
+            if let Some((host, path)) = parse_remote_url(&remote_url) {
+                if api_host_matches_remote(api_base_url, &host) && !found.contains(&path) {
+                    found.push(path);
+                }
+            }
+        }
+    }
+    match found.len() {
+        0 => Err(format!("cannot find a git remote for {} in the project, pass `repo` explicitly", api_base_url)),
+        1 => Ok(found.remove(0)),
+        _ => Err(format!("several repositories found, pass one of them in `repo`: {}", found.join(", "))),
+    }
+}
+
+pub fn url_encode(s: &str) -> String {
+    url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+pub async fn forge_request(
+    http_client: &reqwest::Client,
+    kind: ForgeKind,
+    api: &ForgeApi,
+    method: reqwest::Method,
+    path_and_query: &str,
+    body: Option<Value>,
+) -> Result<Value, String> {
+    let url = format!("{}/{}", api.api_base_url.trim_end_matches('/'), path_and_query.trim_start_matches('/'));
+    let mut req = http_client.request(method.clone(), &url).header("User-Agent", "refact-lsp");
+    if !api.token.is_empty() {
+        req = match kind {
+            ForgeKind::GitHub => req.header("Authorization", format!("Bearer {}", api.token)),
+            ForgeKind::GitLab => req.header("PRIVATE-TOKEN", api.token.clone()),
+        };
+    }
+    if kind == ForgeKind::GitHub {
+        req = req.header("Accept", "application/vnd.github+json");
+    }
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let resp = req.send().await.map_err(|e| format!("{} {} failed: {}", method, url, e))?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| format!("{} {} failed reading the response: {}", method, url, e))?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<Value>(&text).ok()
+            .and_then(|v| v.get("message").or(v.get("error")).map(|m| m.to_string()))
+            .unwrap_or(trim_text(&text, 300));
+        let hint = if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            format!("\n{}", go_to_configuration_message(kind.integr_name()))
+        } else {
+            String::new()
+        };
+        return Err(format!("{} API {} {}: {}{}", kind.display_name(), method, status, message, hint));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("{} API returned not a json: {}", kind.display_name(), e))
+}
+
+#[async_trait]
+impl Tool for ToolForge {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn tool_description(&self) -> ToolDesc {
+        let cr = self.kind.change_request();
+        let repo_param = param("repo", "string", match self.kind {
+            ForgeKind::GitHub => "owner/name, leave empty to use the git remote of the current project",
+            ForgeKind::GitLab => "group/project path, leave empty to use the git remote of the current project",
+        });
+        let number_param = |what: &str| param("number", "integer", &format!("The {} number", what));
+        let (description, mut parameters, required) = match self.action {
+            ForgeAction::ListIssues => (
+                "List issues, returns number, title, state, labels, author and url for each.".to_string(),
+                vec![
+                    param("state", "string", "open, closed or all, default open"),
+                    param("labels", "string", "Comma-separated labels to filter by"),
+                    param("limit", "integer", "How many issues to return, default 20"),
+                ],
+                vec![],
+            ),
+            ForgeAction::GetIssue => (
+                "Read an issue with its description and comments.".to_string(),
+                vec![number_param("issue")],
+                vec!["number"],
+            ),
+            ForgeAction::GetChangeRequest => (
+                format!("Read a {cr}: title, description, branches and the diff of each changed file."),
+                vec![number_param(cr)],
+                vec!["number"],
+            ),
+            ForgeAction::ListReviewComments => (
+                format!("List review comments of a {cr}, with the file and line each comment is attached to."),
+                vec![number_param(cr)],
+                vec!["number"],
+            ),
+            ForgeAction::PostReviewComment => (
+                format!("Post a review comment on a specific line of a file in a {cr}."),
+                vec![
+                    number_param(cr),
+                    param("path", "string", "File path relative to the repository root"),
+                    param("line", "integer", "Line number in the new version of the file"),
+                    param("body", "string", "Comment text, markdown"),
+                ],
+                vec!["number", "path", "line", "body"],
+            ),
+            ForgeAction::CiStatus => (
+                format!("CI status: checks or pipeline jobs with their results, for a {cr} or a branch."),
+                vec![
+                    param("number", "integer", &format!("The {cr} number")),
+                    param("ref", "string", &format!("A branch, tag or commit, if there's no {cr}")),
+                ],
+                vec![],
+            ),
+        };
+        parameters.push(repo_param);
+        ToolDesc {
+            name: self.action.tool_name(self.kind),
+            display_name: format!("{} {}", self.kind.display_name(), self.action.tool_name(self.kind).split('_').skip(1).collect::<Vec<_>>().join(" ")),
+            source: ToolSource {
+                source_type: ToolSourceType::Integration,
+                config_path: self.config_path.clone(),
+            },
+            agentic: self.action == ForgeAction::PostReviewComment,
+            experimental: false,
+            description,
+            parameters,
+            parameters_required: required.into_iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let repo = match arg_str(args, "repo")? {
+            Some(repo) => repo,
+            None => detect_repo(ccx.clone(), &self.api.api_base_url).await?,
+        };
+        let http_client = {
+            let gcx = ccx.lock().await.global_context.clone();
+            let client = gcx.read().await.http_client.clone();
+            client
+        };
+        let result = match self.kind {
+            ForgeKind::GitHub => github::github_execute(&http_client, &self.api, self.action, &repo, args).await?,
+            ForgeKind::GitLab => gitlab::gitlab_execute(&http_client, &self.api, self.action, &repo, args).await?,
+        };
+        let content = serde_json::to_string_pretty(&result).unwrap_or_default();
+        Ok((false, vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(content),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })]))
+    }
+
+    async fn command_to_match_against_confirm_deny(
+        &self,
+        _ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let mut command = vec![self.action.tool_name(self.kind)];
+        for name in ["repo", "number", "path", "ref"] {
+            if let Some(v) = args.get(name) {
+                command.push(v.as_str().map(|s| s.to_string()).unwrap_or(v.to_string()));
+            }
+        }
+        Ok(command.join(" "))
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.common.confirmation.clone())
+    }
+
+    fn has_config_path(&self) -> Option<String> {
+        Some(self.config_path.clone())
+    }
+}
+
+pub fn forge_tools(kind: ForgeKind, api: &ForgeApi, common: &IntegrationCommon, config_path: &str) -> Vec<Box<dyn Tool + Send>> {
+    FORGE_ACTIONS.iter().map(|action| Box::new(ToolForge {
+        kind,
+        action: *action,
+        api: api.clone(),
+        common: common.clone(),
+        config_path: config_path.to_string(),
+    }) as Box<dyn Tool + Send>).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_url() {
+        assert_eq!(parse_remote_url("git@github.com:smallcloudai/refact.git"), Some(("github.com".to_string(), "smallcloudai/refact".to_string())));
+        assert_eq!(parse_remote_url("https://github.com/smallcloudai/refact"), Some(("github.com".to_string(), "smallcloudai/refact".to_string())));
+        assert_eq!(parse_remote_url("ssh://git@gitlab.example.com:2222/group/sub/project.git"), Some(("gitlab.example.com".to_string(), "group/sub/project".to_string())));
+        assert_eq!(parse_remote_url("/local/path"), None);
+        assert!(api_host_matches_remote("https://api.github.com", "github.com"));
+        assert!(api_host_matches_remote("https://ghe.example.com/api/v3", "ghe.example.com"));
+        assert!(!api_host_matches_remote("https://gitlab.com/api/v4", "github.com"));
+    }
+
+    #[test]
+    fn test_trim_patches() {
+        let mut files = vec![
+            json!({"filename": "a.rs", "patch": "x".repeat(FORGE_PATCH_MAX_CHARS * 2)}),
+            json!({"filename": "b.rs", "patch": "+small"}),
+        ];
+        trim_patches(&mut files);
+        assert!(files[0]["patch"].as_str().unwrap().ends_with(&format!("[{} more chars]", FORGE_PATCH_MAX_CHARS)));
+        assert_eq!(files[1]["patch"], json!("+small"));
+    }
+}
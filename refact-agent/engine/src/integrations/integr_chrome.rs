@@ -14,6 +14,7 @@ use crate::global_context::GlobalContext;
 use crate::call_validation::{ChatContent, ChatMessage};
 use crate::scratchpads::multimodality::MultimodalElement;
 use crate::postprocessing::pp_command_output::{CmdlineOutputFilter, output_mini_postprocessing};
+use crate::privacy::load_privacy_if_needed;
 use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
 use crate::integrations::integr_abstract::{IntegrationTrait, IntegrationCommon, IntegrationConfirmation};
 use crate::integrations::docker::docker_container_manager::get_container_name;
@@ -895,7 +896,8 @@ async fn chrome_command_exec(
                 grep_context_lines: 0,
                 remove_from_output: "".to_string(),
             };
-            let filtered_log = output_mini_postprocessing(&filter, tab_log.as_str());
+            let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+            let filtered_log = output_mini_postprocessing(&filter, tab_log.as_str(), &privacy_settings.redactor);
             tool_log.push(filtered_log.clone());
         },
         Command::Eval(args) => {
@@ -19,7 +19,10 @@ use crate::integrations::process_io_utils::{execute_command, AnsiStrippable};
 use crate::tools::tools_description::{ToolParam, Tool, ToolDesc, ToolSource, ToolSourceType};
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 use crate::postprocessing::pp_command_output::{CmdlineOutputFilter, output_mini_postprocessing};
+use crate::postprocessing::pp_redact::Redactor;
+use crate::privacy::load_privacy_if_needed;
 use crate::integrations::integr_abstract::{IntegrationTrait, IntegrationCommon, IntegrationConfirmation};
+use crate::integrations::sandbox::{shell_command, SandboxSettings};
 use crate::integrations::utils::{serialize_num_to_str, deserialize_str_to_num, serialize_opt_num_to_str, deserialize_str_to_opt_num};
 use crate::custom_error::YamlError;
 
@@ -40,6 +43,8 @@ pub struct CmdlineToolConfig {
     pub timeout: String,
     #[serde(default)]
     pub output_filter: CmdlineOutputFilter,
+    #[serde(flatten)]
+    pub sandbox: SandboxSettings,
 
     // background
     #[serde(default, serialize_with = "serialize_opt_num_to_str", deserialize_with = "deserialize_str_to_opt_num")]
@@ -175,10 +180,12 @@ pub fn create_command_from_string(
     command_workdir: &String,
     env_variables: &HashMap<String, String>,
     project_dirs: Vec<PathBuf>,
+    sandbox: &SandboxSettings,
 ) -> Result<Command, String> {
-    let shell = if cfg!(target_os = "windows") { "powershell.exe" } else { "sh" };
-    let shell_arg = if cfg!(target_os = "windows") { "-Command" } else { "-c" };
-    let mut cmd = Command::new(shell);
+    if cmd_string.is_empty() {
+        return Err("Command is empty".to_string());
+    }
+    let mut cmd = shell_command(cmd_string, sandbox, &project_dirs)?;
 
     if command_workdir.is_empty() {
         if let Some(first_project_dir) = project_dirs.first() {
@@ -194,11 +201,7 @@ pub fn create_command_from_string(
         cmd.env(key, value);
     }
 
-    if cmd_string.is_empty() {
-        return Err("Command is empty".to_string());
-    }
     cmd.stdin(std::process::Stdio::null());
-    cmd.arg(shell_arg).arg(cmd_string);
     tracing::info!("command: {}", cmd_string);
 
     Ok(cmd)
@@ -210,19 +213,20 @@ pub async fn execute_blocking_command(
     command_workdir: &String,
     env_variables: &HashMap<String, String>,
     project_dirs: Vec<PathBuf>,
+    redactor: &Redactor,
 ) -> Result<String, String> {
     info!("EXEC workdir {:?}:\n{:?}", command_workdir, command);
 
     let timeout_secs = cfg.timeout.parse::<u64>().unwrap_or(10);
 
-    let cmd = create_command_from_string(command, command_workdir, env_variables, project_dirs)?;
+    let cmd = create_command_from_string(command, command_workdir, env_variables, project_dirs, &cfg.sandbox)?;
     let t0 = tokio::time::Instant::now();
     let output = execute_command(cmd, timeout_secs, command).await?;
     let duration = t0.elapsed();
     info!("EXEC: /finished in {:?}", duration);
 
-    let stdout = output_mini_postprocessing(&cfg.output_filter, &output.stdout.to_string_lossy_and_strip_ansi());
-    let stderr = output_mini_postprocessing(&cfg.output_filter, &output.stderr.to_string_lossy_and_strip_ansi());
+    let stdout = output_mini_postprocessing(&cfg.output_filter, &output.stdout.to_string_lossy_and_strip_ansi(), redactor);
+    let stderr = output_mini_postprocessing(&cfg.output_filter, &output.stderr.to_string_lossy_and_strip_ansi(), redactor);
 
     let mut out = format_output(&stdout, &stderr);
     let exit_code = output.status.code().unwrap_or_default();
@@ -273,7 +277,8 @@ impl Tool for ToolCmdline {
         let env_variables = crate::integrations::setting_up_integrations::get_vars_for_replacements(gcx.clone(), &mut error_log).await;
         let project_dirs = crate::files_correction::get_project_dirs(gcx.clone()).await;
 
-        let tool_output = execute_blocking_command(&command, &self.cfg, &workdir, &env_variables, project_dirs).await?;
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+        let tool_output = execute_blocking_command(&command, &self.cfg, &workdir, &env_variables, project_dirs, &privacy_settings.redactor).await?;
 
         let result = vec![ContextEnum::ChatMessage(ChatMessage {
             role: "tool".to_string(),
@@ -352,6 +357,31 @@ fields:
     f_desc: "The output from the command can be long or even quasi-infinite. This section allows to set limits, prioritize top or bottom, or use regexp to show the model the relevant part."
     f_placeholder: "filter"
     f_extra: true
+  sandbox:
+    f_type: bool
+    f_desc: "Linux only. Run the command in a sandbox: the filesystem is read-only except the project dirs and the writable paths below, and the command gets a private /tmp with bubblewrap (bwrap), or a private TMPDIR with landlock when bwrap isn't installed. Reads aren't restricted and there's no seccomp filter. If the sandbox can't be set up, the command doesn't run."
+    f_extra: true
+  sandbox_network:
+    f_type: bool
+    f_desc: "Allow network access inside the sandbox."
+    f_default: true
+    f_extra: true
+  sandbox_writable_paths:
+    f_type: string_array
+    f_desc: "More directories the command can write to, for example ~/.cargo or ~/.cache/pip."
+    f_extra: true
+  sandbox_memory_mb:
+    f_type: string_short
+    f_desc: "Address space limit for each process in the sandbox, in megabytes. Empty for no limit."
+    f_extra: true
+  sandbox_cpu_seconds:
+    f_type: string_short
+    f_desc: "CPU time limit for each process in the sandbox, in seconds. Empty for no limit."
+    f_extra: true
+  sandbox_max_processes:
+    f_type: string_short
+    f_desc: "Process limit, like ulimit -u it counts all processes of your user. Empty for no limit."
+    f_extra: true
 description: |
   There you can adapt any command line tool for use by AI model. You can give the model instructions why to call it, which parameters to provide,
   set a timeout and restrict the output. If you want a tool that runs in the background such as a web server, use service_* instead.
@@ -13,6 +13,7 @@ use crate::tools::tools_description::{Tool, ToolParam, ToolDesc, ToolSource, Too
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 use crate::global_context::GlobalContext;
 use crate::postprocessing::pp_command_output::output_mini_postprocessing;
+use crate::privacy::load_privacy_if_needed;
 use crate::integrations::process_io_utils::{blocking_read_until_token_or_timeout, is_someone_listening_on_that_tcp_port};
 use crate::integrations::sessions::IntegrationSession;
 use crate::integrations::integr_abstract::{IntegrationTrait, IntegrationCommon, IntegrationConfirmation};
@@ -122,6 +123,7 @@ async fn execute_background_command(
     env_variables: &HashMap<String, String>,
 ) -> Result<String, String> {
     let session_key = format!("custom_service_{service_name}");
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
     let mut session_mb = gcx.read().await.integration_sessions.get(&session_key).cloned();
     let command_str = command_str.to_string();
     let mut actions_log = String::new();
@@ -132,8 +134,8 @@ async fn execute_background_command(
         let session = session_locked.as_any_mut().downcast_mut::<CmdlineSession>().unwrap();
         actions_log.push_str(&format!("Currently the service is running.\nworkdir: {}\ncommand line: {}\n\n", session.cmdline_workdir, session.cmdline_string));
         let (stdout_out, stderr_out) = get_stdout_and_stderr(100, &mut session.cmdline_stdout, &mut session.cmdline_stderr).await?;
-        let filtered_stdout = output_mini_postprocessing(&cfg.output_filter, &stdout_out);
-        let filtered_stderr = output_mini_postprocessing(&cfg.output_filter, &stderr_out);
+        let filtered_stdout = output_mini_postprocessing(&cfg.output_filter, &stdout_out, &privacy_settings.redactor);
+        let filtered_stderr = output_mini_postprocessing(&cfg.output_filter, &stderr_out, &privacy_settings.redactor);
         actions_log.push_str(&format!("Here are stdin/stderr since the last checking out on the service:\n{}\n\n", format_output(&filtered_stdout, &filtered_stderr)));
     } else {
         actions_log.push_str(&format!("Service is currently not running\n"));
@@ -167,7 +169,7 @@ async fn execute_background_command(
         actions_log.push_str(&format!("Starting service with the following command line:\n{}\n", command_str));
         let project_dirs = crate::files_correction::get_project_dirs(gcx.clone()).await;
 
-        let mut command = create_command_from_string(&command_str, cmdline_workdir, env_variables, project_dirs)?;
+        let mut command = create_command_from_string(&command_str, cmdline_workdir, env_variables, project_dirs, &cfg.sandbox)?;
         command.stdout(Stdio::piped());
         command.stderr(Stdio::piped());
         let mut command_wrap = TokioCommandWrap::from(command);
@@ -230,8 +232,8 @@ async fn execute_background_command(
             tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
         }
 
-        let filtered_stdout = output_mini_postprocessing(&cfg.output_filter, &accumulated_stdout);
-        let filtered_stderr = output_mini_postprocessing(&cfg.output_filter, &accumulated_stderr);
+        let filtered_stdout = output_mini_postprocessing(&cfg.output_filter, &accumulated_stdout, &privacy_settings.redactor);
+        let filtered_stderr = output_mini_postprocessing(&cfg.output_filter, &accumulated_stderr, &privacy_settings.redactor);
         let out = format_output(&filtered_stdout, &filtered_stderr);
         actions_log.push_str(&out);
 
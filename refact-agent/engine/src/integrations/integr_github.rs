@@ -1,34 +1,40 @@
 use std::sync::Arc;
-use std::collections::HashMap;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex as AMutex;
+use serde_json::Value;
 use tokio::sync::RwLock as ARwLock;
-use tokio::process::Command;
 
-use crate::files_correction::CommandSimplifiedDirExt;
 use crate::global_context::GlobalContext;
-use crate::at_commands::at_commands::AtCommandsContext;
-use crate::call_validation::{ContextEnum, ChatMessage, ChatContent, ChatUsage};
-use crate::files_correction::canonical_path;
-use crate::integrations::go_to_configuration_message;
-use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
-use serde_json::Value;
-use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation, IntegrationTrait};
-use crate::integrations::process_io_utils::AnsiStrippable;
+use crate::integrations::forge::{forge_tools, ForgeApi, ForgeKind};
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationTrait};
+
 
+fn default_gh_api_base_url() -> String {
+    "https://api.github.com".to_string()
+}
 
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[allow(non_snake_case)]
 pub struct SettingsGitHub {
-    pub gh_binary_path: String,
     pub gh_token: String,
+    #[serde(default = "default_gh_api_base_url")]
+    pub gh_api_base_url: String,
+}
+
+impl Default for SettingsGitHub {
+    fn default() -> Self {
+        SettingsGitHub {
+            gh_token: String::new(),
+            gh_api_base_url: default_gh_api_base_url(),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct ToolGithub {
     pub common: IntegrationCommon,
-    pub settings_github: SettingsGitHub, pub config_path: String,
+    pub settings_github: SettingsGitHub,
+    pub config_path: String,
 }
 
 #[async_trait]
@@ -51,161 +57,14 @@ impl IntegrationTrait for ToolGithub {
     }
 
     async fn integr_tools(&self, _integr_name: &str) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
-        vec![Box::new(ToolGithub {
-            common: self.common.clone(),
-            settings_github: self.settings_github.clone(),
-            config_path: self.config_path.clone(),
-        })]
-    }
-
-    fn integr_schema(&self) -> &str { GITHUB_INTEGRATION_SCHEMA }
-}
-
-#[async_trait]
-impl Tool for ToolGithub {
-    fn as_any(&self) -> &dyn std::any::Any { self }
-
-    fn tool_description(&self) -> ToolDesc {
-        ToolDesc {
-            name: "github".to_string(),
-            display_name: "GitHub CLI".to_string(),
-            source: ToolSource {
-                source_type: ToolSourceType::Integration,
-                config_path: self.config_path.clone(),
-            },
-            agentic: true,
-            experimental: false,
-            description: "Access to gh command line command, to fetch issues, review PRs.".to_string(),
-            parameters: vec![
-                ToolParam {
-                    name: "project_dir".to_string(),
-                    param_type: "string".to_string(),
-                    description: "Look at system prompt for location of version control (.git folder) of the active file.".to_string(),
-                },
-                ToolParam {
-                    name: "command".to_string(),
-                    param_type: "string".to_string(),
-                    description: "Examples:\ngh issue create --body \"hello world\" --title \"Testing gh integration\"\ngh issue list --author @me --json number,title,updatedAt,url\n".to_string(),
-                }
-            ],
-            parameters_required: vec!["project_dir".to_string(), "command".to_string()],
-        }
-    }
-
-    async fn tool_execute(
-        &mut self,
-        _ccx: Arc<AMutex<AtCommandsContext>>,
-        tool_call_id: &String,
-        args: &HashMap<String, Value>,
-    ) -> Result<(bool, Vec<ContextEnum>), String> {
-        let project_dir = match args.get("project_dir") {
-            Some(Value::String(s)) => s,
-            Some(v) => return Err(format!("argument `project_dir` is not a string: {:?}", v)),
-            None => return Err("Missing argument `project_dir`".to_string())
-        };
-        let command_args = parse_command_args(args)?;
-
-        let mut gh_binary_path = self.settings_github.gh_binary_path.clone();
-        if gh_binary_path.is_empty() {
-            gh_binary_path = "gh".to_string();
-        }
-        let output = Command::new(&gh_binary_path)
-            .args(&command_args)
-            .current_dir_simplified(&canonical_path(project_dir))
-            .env("GH_TOKEN", &self.settings_github.gh_token)
-            .env("GITHUB_TOKEN", &self.settings_github.gh_token)
-            .stdin(std::process::Stdio::null())
-            .output()
-            .await
-            .map_err(|e| format!("!{}, {} failed:\n{}",
-                go_to_configuration_message("github"), gh_binary_path, e.to_string()))?;
-
-        let stdout = output.stdout.to_string_lossy_and_strip_ansi();
-        let stderr = output.stderr.to_string_lossy_and_strip_ansi();
-
-        let stdout_content = if stdout.starts_with("[") {
-            match serde_json::from_str::<Value>(&stdout) {
-                Ok(Value::Array(arr)) => {
-                    let row_count = arr.len();
-                    format!("{}\n\n💿 The UI has the capability to view tool result json efficiently. The result contains {} rows. Unless user specified otherwise, write no more than 3 rows as text and possibly \"and N more\" wording, keep it short.",
-                        stdout, row_count
-                    )
-                },
-                Ok(_) => stdout,
-                Err(_) => stdout,
-            }
-        } else {
-            stdout
+        let api = ForgeApi {
+            api_base_url: self.settings_github.gh_api_base_url.clone(),
+            token: self.settings_github.gh_token.clone(),
         };
-
-        let mut content = String::new();
-        if !stdout_content.is_empty() {
-            content.push_str(format!("stdout:\n{}\n", stdout_content).as_str());
-        }
-        if !stderr.is_empty() {
-            content.push_str(format!("stderr:\n{}\n", stderr).as_str());
-        }
-
-        let mut results = vec![];
-        results.push(ContextEnum::ChatMessage(ChatMessage {
-            role: "tool".to_string(),
-            content: ChatContent::SimpleText(content),
-            tool_calls: None,
-            tool_call_id: tool_call_id.clone(),
-            ..Default::default()
-        }));
-
-        Ok((false, results))
-    }
-
-    async fn command_to_match_against_confirm_deny(
-        &self,
-        _ccx: Arc<AMutex<AtCommandsContext>>,
-        args: &HashMap<String, Value>,
-    ) -> Result<String, String> {
-        let mut command_args = parse_command_args(args)?;
-        command_args.insert(0, "gh".to_string());
-        Ok(command_args.join(" "))
-    }
-
-    fn tool_depends_on(&self) -> Vec<String> {
-        vec![]
-    }
-
-    fn usage(&mut self) -> &mut Option<ChatUsage> {
-        static mut DEFAULT_USAGE: Option<ChatUsage> = None;
-        #[allow(static_mut_refs)]
-        unsafe { &mut DEFAULT_USAGE }
+        forge_tools(ForgeKind::GitHub, &api, &self.common, &self.config_path)
     }
 
-    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
-        Some(self.integr_common().confirmation)
-    }
-
-    fn has_config_path(&self) -> Option<String> {
-        Some(self.config_path.clone())
-    }
-}
-
-fn parse_command_args(args: &HashMap<String, Value>) -> Result<Vec<String>, String> {
-    let command = match args.get("command") {
-        Some(Value::String(s)) => s,
-        Some(v) => return Err(format!("argument `command` is not a string: {:?}", v)),
-        None => return Err("Missing argument `command`".to_string())
-    };
-
-    let mut parsed_args = shell_words::split(&command).map_err(|e| e.to_string())?;
-    if parsed_args.is_empty() {
-        return Err("Parsed command is empty".to_string());
-    }
-    for (i, arg) in parsed_args.iter().enumerate() {
-        tracing::info!("argument[{}]: {}", i, arg);
-    }
-    if parsed_args[0] == "gh" {
-        parsed_args.remove(0);
-    }
-
-    Ok(parsed_args)
+    fn integr_schema(&self) -> &str { GITHUB_INTEGRATION_SCHEMA }
 }
 
 const GITHUB_INTEGRATION_SCHEMA: &str = r#"
@@ -218,27 +77,29 @@ fields:
     smartlinks:
       - sl_label: "Open secrets.yaml"
         sl_goto: "EDITOR:secrets.yaml"
-  gh_binary_path:
+  gh_api_base_url:
     f_type: string_long
-    f_desc: "Path to the GitHub CLI binary. Leave empty if you have it in PATH."
-    f_placeholder: "/usr/local/bin/gh"
-    f_label: "GH Binary Path"
+    f_desc: "GitHub REST API address, change it for GitHub Enterprise Server, for example https://github.example.com/api/v3"
+    f_default: "https://api.github.com"
+    f_label: "API Base URL"
     f_extra: true
 description: |
-  The GitHub integration allows interaction with GitHub repositories using the GitHub CLI.
-  It provides functionality for various GitHub operations such as creating issues, pull requests, and more.
+  The GitHub integration talks to the GitHub REST API directly, no `gh` binary is needed.
+  It gives the model tools to list and read issues, read pull request diffs and review comments,
+  post review comments on specific lines, and check CI status. The repository is detected from
+  the git remotes of the project, or can be passed explicitly.
 available:
   on_your_laptop_possible: true
   when_isolated_possible: true
 confirmation:
-  ask_user_default: ["gh * delete *", "gh * close *"]
-  deny_default: ["gh auth token *"]
+  ask_user_default: ["github_post_review_comment *"]
+  deny_default: []
 smartlinks:
   - sl_label: "Test"
     sl_chat:
       - role: "user"
         content: |
-          🔧 The `github` tool should be visible now. To test the tool, list opened pull requests for the current project, and briefly describe them.
-          If it doesn't work or the tool isn't available, go through the usual plan in the system prompt.
+          🔧 The `github_*` tools should be visible now. To test them, list open issues for the current project using `github_list_issues`, and briefly describe them.
+          If it doesn't work or the tools aren't available, go through the usual plan in the system prompt.
     sl_enable_only_with_tool: true
 "#;
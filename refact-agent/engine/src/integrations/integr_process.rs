@@ -0,0 +1,541 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use async_trait::async_trait;
+use process_wrap::tokio::*;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::ChildStdin;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tokio::time::{Duration, Instant};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
+use crate::custom_error::YamlError;
+use crate::files_correction::{get_active_project_path, get_project_dirs, CommandSimplifiedDirExt};
+use crate::global_context::GlobalContext;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation};
+use crate::integrations::integr_shell::{resolve_shell_workdir, shell_confirm_deny, SettingsShell};
+use crate::integrations::process_io_utils::{write_to_stdin_and_flush, AnsiStrippable};
+use crate::integrations::sandbox::shell_command;
+use crate::integrations::sessions::{get_session_hashmap_key, IntegrationSession};
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+
+// Long-running processes the model starts itself: dev servers, file watchers, REPLs. It comes with the shell
+// integration and uses its settings: confirmation rules, sandbox, env variables. Stdout and stderr of a process go
+// into one ring buffer in the order they arrive, read_output returns what's new since an offset. A process nobody
+// looked at for an hour is stopped by the session expiry, all of them are stopped when the engine exits.
+
+const PROCESS_INTEGRATION_NAME: &str = "process";
+const PROCESS_OUTPUT_RING_BYTES: usize = 1024 * 1024;
+const PROCESS_READ_MAX_BYTES: usize = 16 * 1024;
+const PROCESS_IDLE_EXPIRE: Duration = Duration::from_secs(60 * 60);
+const PROCESS_MAX_RUNNING: usize = 10;
+const PROCESS_START_WAIT_SECONDS: u64 = 3;
+const PROCESS_WAIT_MAX_SECONDS: u64 = 60;
+
+static NEXT_PROCESS_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The last `capacity` bytes of output, with offsets counted from the very first byte
+pub struct OutputRing {
+    buf: VecDeque<u8>,
+    total: u64,
+    capacity: usize,
+}
+
+impl OutputRing {
+    pub fn new(capacity: usize) -> Self {
+        OutputRing { buf: VecDeque::new(), total: 0, capacity }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend(bytes);
+        self.total += bytes.len() as u64;
+        if self.buf.len() > self.capacity {
+            let extra = self.buf.len() - self.capacity;
+            self.buf.drain(..extra);
+        }
+    }
+
+    pub fn start_offset(&self) -> u64 {
+        self.total - self.buf.len() as u64
+    }
+
+    pub fn end_offset(&self) -> u64 {
+        self.total
+    }
+
+    /// At most `max_bytes` starting at `since`, or at the oldest byte still there. Returns the bytes and their offsets.
+    pub fn read_since(&self, since: u64, max_bytes: usize) -> (Vec<u8>, u64, u64) {
+        let from = since.clamp(self.start_offset(), self.total);
+        let skip = (from - self.start_offset()) as usize;
+        let bytes: Vec<u8> = self.buf.iter().skip(skip).take(max_bytes).cloned().collect();
+        let to = from + bytes.len() as u64;
+        (bytes, from, to)
+    }
+}
+
+pub struct ProcessSession {
+    id: String,
+    command: String,
+    workdir: String,
+    process: Box<dyn TokioChildWrapper>,
+    stdin: Option<ChildStdin>,
+    output: Arc<StdMutex<OutputRing>>,
+    read_cursor: u64,
+    started_ts: Instant,
+    last_used_ts: Instant,
+}
+
+impl IntegrationSession for ProcessSession {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_expired(&self) -> bool {
+        self.last_used_ts.elapsed() > PROCESS_IDLE_EXPIRE
+    }
+
+    fn try_stop(&mut self, self_arc: Arc<AMutex<Box<dyn IntegrationSession>>>) -> Box<dyn Future<Output = String> + Send> {
+        Box::new(async move {
+            let mut session_locked = self_arc.lock().await;
+            let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+            _stop_locked(session).await
+        })
+    }
+}
+
+impl ProcessSession {
+    fn status(&mut self) -> String {
+        match self.process.try_wait() {
+            Ok(Some(status)) => match status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => "killed by a signal".to_string(),
+            },
+            Ok(None) => format!("running for {:.0}s", self.started_ts.elapsed().as_secs_f64()),
+            Err(e) => format!("unknown: {}", e),
+        }
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.process.try_wait(), Ok(None))
+    }
+
+    fn read_output(&mut self, since: Option<u64>) -> String {
+        let ring = self.output.lock().unwrap();
+        let since = since.unwrap_or(self.read_cursor);
+        let (bytes, from, to) = ring.read_since(since, PROCESS_READ_MAX_BYTES);
+        let end = ring.end_offset();
+        drop(ring);
+        self.read_cursor = to;
+        let mut out = String::new();
+        if from > since {
+            out.push_str(&format!("(output before offset {} is gone, only the last {} bytes are kept)\n", from, PROCESS_OUTPUT_RING_BYTES));
+        }
+        if bytes.is_empty() {
+            out.push_str(&format!("No new output, offset {}\n", to));
+        } else {
+            out.push_str(&format!("Output from offset {} to {}:\n```\n{}\n```\n", from, to, bytes.to_string_lossy_and_strip_ansi().trim_end()));
+        }
+        if to < end {
+            out.push_str(&format!("There's more, {} bytes, call read_output again to get it.\n", end - to));
+        }
+        out
+    }
+}
+
+async fn _stop_locked(sess: &mut ProcessSession) -> String {
+    tracing::info!("PROCESS STOP {} workdir {}:\n{:?}", sess.id, sess.workdir, sess.command);
+    if !sess.is_running() {
+        let status = sess.status();
+        return format!("Process {} is not running, {}\n", sess.id, status);
+    }
+    match Box::into_pin(sess.process.kill()).await {
+        Ok(_) => format!("Process {} stopped\n", sess.id),
+        Err(e) => {
+            tracing::warn!("Failed to kill process {}: {}", sess.id, e);
+            format!("Failed to stop process {}: {}\n", sess.id, e)
+        }
+    }
+}
+
+fn spawn_output_reader<R: AsyncRead + Unpin + Send + 'static>(mut reader: R, output: Arc<StdMutex<OutputRing>>) {
+    tokio::spawn(async move {
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => output.lock().unwrap().push(&buf[..n]),
+            }
+        }
+    });
+}
+
+fn process_session_key(id: &str) -> String {
+    get_session_hashmap_key(PROCESS_INTEGRATION_NAME, id)
+}
+
+async fn process_sessions(gcx: Arc<ARwLock<GlobalContext>>) -> Vec<Arc<AMutex<Box<dyn IntegrationSession>>>> {
+    let prefix = process_session_key("");
+    let gcx_locked = gcx.read().await;
+    let mut keys: Vec<&String> = gcx_locked.integration_sessions.keys().filter(|k| k.starts_with(&prefix)).collect();
+    keys.sort();
+    keys.into_iter().map(|k| gcx_locked.integration_sessions[k].clone()).collect()
+}
+
+/// Waits up to `wait_seconds` for new output to appear and stop coming, or for the process to exit
+async fn wait_for_output(session_arc: &Arc<AMutex<Box<dyn IntegrationSession>>>, wait_seconds: u64) {
+    let t0 = Instant::now();
+    let deadline = Duration::from_secs(wait_seconds.min(PROCESS_WAIT_MAX_SECONDS));
+    let mut prev_end: Option<u64> = None;
+    while t0.elapsed() < deadline {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut session_locked = session_arc.lock().await;
+        let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+        let end = session.output.lock().unwrap().end_offset();
+        if !session.is_running() {
+            break;
+        }
+        // keep waiting while output flows, a server prints a few lines before it says it's ready
+        if end > session.read_cursor && prev_end == Some(end) {
+            break;
+        }
+        prev_end = Some(end);
+    }
+}
+
+async fn process_start(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cfg: &SettingsShell,
+    command: &str,
+    workdir_maybe: &Option<PathBuf>,
+    wait_seconds: u64,
+) -> Result<String, String> {
+    let mut running = 0;
+    for session_arc in process_sessions(gcx.clone()).await {
+        let mut session_locked = session_arc.lock().await;
+        if session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap().is_running() {
+            running += 1;
+        }
+    }
+    if running >= PROCESS_MAX_RUNNING {
+        return Err(format!("There are {} processes running already, stop some of them first", running));
+    }
+
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    let mut cmd = shell_command(command, &cfg.sandbox, &project_dirs)?;
+    let workdir = match workdir_maybe {
+        Some(workdir) => Some(workdir.clone()),
+        None => get_active_project_path(gcx.clone()).await,
+    };
+    if let Some(workdir) = &workdir {
+        cmd.current_dir_simplified(workdir);
+    }
+    let mut error_log = Vec::<YamlError>::new();
+    let env_variables = crate::integrations::setting_up_integrations::get_vars_for_replacements(gcx.clone(), &mut error_log).await;
+    for (key, value) in env_variables {
+        cmd.env(key, value);
+    }
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut command_wrap = TokioCommandWrap::from(cmd);
+    #[cfg(unix)]
+    command_wrap.wrap(ProcessGroup::leader());
+    #[cfg(windows)]
+    command_wrap.wrap(JobObject);
+    let mut process = command_wrap.spawn().map_err(|e| format!("failed to start `{}`: {}", command, e))?;
+
+    let output = Arc::new(StdMutex::new(OutputRing::new(PROCESS_OUTPUT_RING_BYTES)));
+    spawn_output_reader(process.stdout().take().ok_or("failed to open stdout")?, output.clone());
+    spawn_output_reader(process.stderr().take().ok_or("failed to open stderr")?, output.clone());
+    let stdin = process.stdin().take();
+    let pid = process.id().map(|pid| pid.to_string()).unwrap_or_default();
+
+    let id = format!("p{}", NEXT_PROCESS_ID.fetch_add(1, Ordering::SeqCst));
+    let workdir_str = workdir.map(|w| w.to_string_lossy().to_string()).unwrap_or_default();
+    tracing::info!("PROCESS START {} workdir {}:\n{:?}", id, workdir_str, command);
+    let session: Box<dyn IntegrationSession> = Box::new(ProcessSession {
+        id: id.clone(),
+        command: command.to_string(),
+        workdir: workdir_str.clone(),
+        process,
+        stdin,
+        output,
+        read_cursor: 0,
+        started_ts: Instant::now(),
+        last_used_ts: Instant::now(),
+    });
+    let session_arc = Arc::new(AMutex::new(session));
+    gcx.write().await.integration_sessions.insert(process_session_key(&id), session_arc.clone());
+
+    wait_for_output(&session_arc, wait_seconds).await;
+    let mut session_locked = session_arc.lock().await;
+    let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+    let status = session.status();
+    Ok(format!(
+        "Started process {} (pid {}) in {}:\n{}\nStatus: {}\n{}",
+        id, pid, workdir_str, command, status, session.read_output(None),
+    ))
+}
+
+async fn get_process_session(gcx: Arc<ARwLock<GlobalContext>>, id: &str) -> Result<Arc<AMutex<Box<dyn IntegrationSession>>>, String> {
+    gcx.read().await.integration_sessions.get(&process_session_key(id)).cloned()
+        .ok_or(format!("No process {}, call the tool with action=list to see what's there", id))
+}
+
+async fn process_list(gcx: Arc<ARwLock<GlobalContext>>) -> String {
+    let sessions = process_sessions(gcx.clone()).await;
+    if sessions.is_empty() {
+        return "No processes\n".to_string();
+    }
+    let mut out = String::new();
+    for session_arc in sessions {
+        let mut session_locked = session_arc.lock().await;
+        let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+        let (end, unread) = {
+            let ring = session.output.lock().unwrap();
+            (ring.end_offset(), ring.end_offset().saturating_sub(session.read_cursor))
+        };
+        let status = session.status();
+        out.push_str(&format!(
+            "{}: {}\n  workdir {}\n  {}, output {} bytes, {} unread\n",
+            session.id, session.command, session.workdir, status, end, unread,
+        ));
+    }
+    out
+}
+
+async fn process_read_output(gcx: Arc<ARwLock<GlobalContext>>, id: &str, since: Option<u64>, wait_seconds: u64) -> Result<String, String> {
+    let session_arc = get_process_session(gcx, id).await?;
+    if wait_seconds > 0 {
+        wait_for_output(&session_arc, wait_seconds).await;
+    }
+    let mut session_locked = session_arc.lock().await;
+    let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+    session.last_used_ts = Instant::now();
+    let status = session.status();
+    let output = session.read_output(since);
+    Ok(format!("Process {}: {}\n{}", session.id, status, output))
+}
+
+async fn process_send_stdin(gcx: Arc<ARwLock<GlobalContext>>, id: &str, text: &str, wait_seconds: u64) -> Result<String, String> {
+    let session_arc = get_process_session(gcx.clone(), id).await?;
+    {
+        let mut session_locked = session_arc.lock().await;
+        let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+        if !session.is_running() {
+            return Err(format!("Process {} is not running, {}", id, session.status()));
+        }
+        let stdin = session.stdin.as_mut().ok_or(format!("Process {} has no stdin", id))?;
+        write_to_stdin_and_flush(stdin, text).await?;
+        session.last_used_ts = Instant::now();
+    }
+    process_read_output(gcx, id, None, wait_seconds.max(1)).await
+}
+
+async fn process_stop(gcx: Arc<ARwLock<GlobalContext>>, id: &str) -> Result<String, String> {
+    let session_arc = get_process_session(gcx.clone(), id).await?;
+    gcx.write().await.integration_sessions.remove(&process_session_key(id));
+    let mut session_locked = session_arc.lock().await;
+    let session = session_locked.as_any_mut().downcast_mut::<ProcessSession>().unwrap();
+    let mut out = _stop_locked(session).await;
+    // whatever it printed while shutting down
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    out.push_str(&session.read_output(None));
+    Ok(out)
+}
+
+pub struct ToolProcess {
+    pub common: IntegrationCommon,
+    pub cfg: SettingsShell,
+    pub config_path: String,
+}
+
+pub fn arg_str<'a>(args: &'a HashMap<String, Value>, name: &str) -> Result<Option<&'a str>, String> {
+    match args.get(name) {
+        Some(Value::String(s)) if !s.is_empty() => Ok(Some(s.as_str())),
+        Some(Value::String(_)) | Some(Value::Null) | None => Ok(None),
+        Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+    }
+}
+
+/// Numbers come as strings from most models, accept both
+fn arg_u64(args: &HashMap<String, Value>, name: &str) -> Result<Option<u64>, String> {
+    match args.get(name) {
+        Some(Value::Number(n)) => n.as_u64().map(Some).ok_or(format!("argument `{}` should be a non-negative integer", name)),
+        Some(Value::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(Value::String(s)) => s.trim().parse::<u64>().map(Some).map_err(|_| format!("argument `{}` should be a non-negative integer, got {:?}", name, s)),
+        Some(Value::Null) | None => Ok(None),
+        Some(v) => Err(format!("argument `{}` should be a non-negative integer, got {:?}", name, v)),
+    }
+}
+
+fn required<'a>(value: Option<&'a str>, name: &str, action: &str) -> Result<&'a str, String> {
+    value.ok_or(format!("argument `{}` is required for action={}", name, action))
+}
+
+#[async_trait]
+impl Tool for ToolProcess {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let action = arg_str(args, "action")?.ok_or("Missing argument `action`")?;
+        let process_id = arg_str(args, "process_id")?;
+        let wait_seconds = arg_u64(args, "wait_seconds")?;
+
+        let tool_output = match action {
+            "start" => {
+                let command = required(arg_str(args, "command")?, "command", action)?;
+                let workdir = match arg_str(args, "workdir")? {
+                    Some(workdir) => Some(resolve_shell_workdir(gcx.clone(), workdir).await?),
+                    None => None,
+                };
+                process_start(gcx.clone(), &self.cfg, command, &workdir, wait_seconds.unwrap_or(PROCESS_START_WAIT_SECONDS)).await?
+            }
+            "list" => process_list(gcx.clone()).await,
+            "read_output" => {
+                let process_id = required(process_id, "process_id", action)?;
+                process_read_output(gcx.clone(), process_id, arg_u64(args, "since")?, wait_seconds.unwrap_or(0)).await?
+            }
+            "send_stdin" => {
+                let process_id = required(process_id, "process_id", action)?;
+                let text = required(arg_str(args, "text")?, "text", action)?;
+                process_send_stdin(gcx.clone(), process_id, text, wait_seconds.unwrap_or(1)).await?
+            }
+            "stop" => process_stop(gcx.clone(), required(process_id, "process_id", action)?).await?,
+            _ => return Err(format!("Unknown action `{}`, should be one of start, list, read_output, send_stdin, stop", action)),
+        };
+
+        Ok((false, vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(tool_output),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })]))
+    }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: "process".to_string(),
+            display_name: "Background Process".to_string(),
+            source: ToolSource {
+                source_type: ToolSourceType::Integration,
+                config_path: self.config_path.clone(),
+            },
+            agentic: true,
+            experimental: false,
+            description: "Run a long-running command in the background, such as a dev server or a file watcher, and check on it later. \
+                Actions: start (needs command, returns process_id and the first output), list, read_output (new output since the last read, \
+                or since the given offset), send_stdin (a line of text), stop. Use shell for commands that finish on their own.".to_string(),
+            parameters: vec![
+                ToolParam {
+                    name: "action".to_string(),
+                    param_type: "string".to_string(),
+                    description: "One of: start, list, read_output, send_stdin, stop".to_string(),
+                },
+                ToolParam {
+                    name: "command".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For start: the shell command to run".to_string(),
+                },
+                ToolParam {
+                    name: "workdir".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For start: workdir for the command, the project root if empty".to_string(),
+                },
+                ToolParam {
+                    name: "process_id".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For read_output, send_stdin, stop: the id that start returned, like p1".to_string(),
+                },
+                ToolParam {
+                    name: "since".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For read_output: byte offset to read from, by default where the last read ended".to_string(),
+                },
+                ToolParam {
+                    name: "text".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For send_stdin: the line to send, a newline is added".to_string(),
+                },
+                ToolParam {
+                    name: "wait_seconds".to_string(),
+                    param_type: "string".to_string(),
+                    description: format!("How long to wait for output before returning, up to {}. Defaults: start {}, send_stdin 1, read_output 0", PROCESS_WAIT_MAX_SECONDS, PROCESS_START_WAIT_SECONDS),
+                },
+            ],
+            parameters_required: vec!["action".to_string()],
+        }
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>
+    ) -> Result<MatchConfirmDeny, String> {
+        let command_to_match = self.command_to_match_against_confirm_deny(ccx.clone(), args).await?;
+        if command_to_match.is_empty() {
+            // looking at processes and stopping them is harmless
+            return Ok(MatchConfirmDeny {
+                result: MatchConfirmDenyResult::PASS,
+                command: String::new(),
+                rule: String::new(),
+            });
+        }
+        Ok(shell_confirm_deny(&command_to_match, &self.confirm_deny_rules(), self.cfg.sandbox.isolated()))
+    }
+
+    /// What goes to the shell: the command to start, or a line sent to stdin
+    async fn command_to_match_against_confirm_deny(
+        &self,
+        _ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        match arg_str(args, "action")? {
+            Some("start") => Ok(arg_str(args, "command")?.unwrap_or_default().to_string()),
+            Some("send_stdin") => Ok(arg_str(args, "text")?.unwrap_or_default().to_string()),
+            _ => Ok(String::new()),
+        }
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.common.confirmation.clone())
+    }
+
+    fn has_config_path(&self) -> Option<String> {
+        Some(self.config_path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_ring() {
+        let mut ring = OutputRing::new(10);
+        ring.push(b"hello ");
+        assert_eq!(ring.read_since(0, 100), (b"hello ".to_vec(), 0, 6));
+        ring.push(b"world!!");
+        // 13 bytes written, the first 3 are gone
+        assert_eq!(ring.start_offset(), 3);
+        assert_eq!(ring.read_since(0, 100), (b"lo world!!".to_vec(), 3, 13));
+        assert_eq!(ring.read_since(6, 3), (b"wor".to_vec(), 6, 9));
+        assert_eq!(ring.read_since(13, 100), (vec![], 13, 13));
+        assert_eq!(ring.read_since(50, 100), (vec![], 13, 13));
+    }
+}
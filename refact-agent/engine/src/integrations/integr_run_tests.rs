@@ -0,0 +1,860 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex as StdMutex;
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, ContextFile};
+use crate::custom_error::YamlError;
+use crate::files_correction::{canonical_path, correct_to_nearest_filename, get_active_project_path, get_project_dirs, CommandSimplifiedDirExt};
+use crate::global_context::GlobalContext;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation};
+use crate::integrations::integr_process::arg_str;
+use crate::integrations::integr_shell::{resolve_shell_workdir, shell_confirm_deny, SettingsShell};
+use crate::integrations::process_io_utils::{execute_command, AnsiStrippable};
+use crate::integrations::sandbox::shell_command;
+use crate::postprocessing::pp_command_output::output_mini_postprocessing;
+use crate::privacy::load_privacy_if_needed;
+use crate::tools::tools_description::{MatchConfirmDeny, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+
+// Runs the project's tests and gives the model lists instead of a wall of text. Each runner is asked for the
+// format it can produce without plugins: libtest text (its JSON output needs nightly), JUnit XML from pytest and
+// vitest, JSON from jest and go test. Failure locations become context files. Failed tests are remembered per chat
+// and workdir, rerun_failed runs only them. Comes with the shell integration, so the sandbox and the confirmation
+// rules of shell apply to the generated command.
+
+const RUN_TESTS_TIMEOUT_SECONDS: u64 = 600;
+const RUN_TESTS_FAILED_SHOW_MAX: usize = 20;
+const RUN_TESTS_PASSED_SHOW_MAX: usize = 30;
+const RUN_TESTS_MESSAGE_MAX_LINES: usize = 40;
+const RUN_TESTS_MESSAGE_MAX_CHARS: usize = 3000;
+const RUN_TESTS_CONTEXT_FILES_MAX: usize = 5;
+const RUN_TESTS_LAST_FAILED_MAX: usize = 100;
+
+lazy_static! {
+    static ref LOCATION_RE: Regex = Regex::new(r"([A-Za-z0-9_.@/\\-]+\.(?:rs|py|go|js|jsx|mjs|cjs|ts|tsx|mts|cts)):(\d+)").unwrap();
+    static ref LIBTEST_RESULT_RE: Regex = Regex::new(r"^test (.+?) \.\.\. (ok|FAILED|ignored)").unwrap();
+    static ref LIBTEST_SECTION_RE: Regex = Regex::new(r"^---- (.+?) stdout ----$").unwrap();
+    static ref LIBTEST_PANIC_RE: Regex = Regex::new(r"panicked at (?:'.*', )?([^\s:']+):(\d+):\d+").unwrap();
+    static ref LAST_FAILED: StdMutex<IndexMap<String, (TestFramework, Vec<TestResult>)>> = StdMutex::new(IndexMap::new());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestFramework {
+    Cargo,
+    Pytest,
+    Jest,
+    Vitest,
+    Go,
+}
+
+impl TestFramework {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TestFramework::Cargo => "cargo",
+            TestFramework::Pytest => "pytest",
+            TestFramework::Jest => "jest",
+            TestFramework::Vitest => "vitest",
+            TestFramework::Go => "go",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "cargo" | "rust" => Some(TestFramework::Cargo),
+            "pytest" | "python" => Some(TestFramework::Pytest),
+            "jest" => Some(TestFramework::Jest),
+            "vitest" => Some(TestFramework::Vitest),
+            "go" | "golang" => Some(TestFramework::Go),
+            _ => None,
+        }
+    }
+
+    /// Relative to the workdir, next to the runner's own cache. Cargo and go print their results to stdout.
+    fn report_path(&self) -> Option<&'static str> {
+        match self {
+            TestFramework::Pytest => Some(".pytest_cache/run_tests_junit.xml"),
+            TestFramework::Jest => Some("node_modules/.cache/run_tests_jest.json"),
+            TestFramework::Vitest => Some("node_modules/.cache/run_tests_vitest.xml"),
+            TestFramework::Cargo | TestFramework::Go => None,
+        }
+    }
+}
+
+/// The first match wins, a project that has several can set the framework explicitly
+pub fn detect_framework(workdir: &Path) -> Option<TestFramework> {
+    if workdir.join("Cargo.toml").exists() {
+        return Some(TestFramework::Cargo);
+    }
+    if workdir.join("go.mod").exists() {
+        return Some(TestFramework::Go);
+    }
+    if let Ok(package_json) = std::fs::read_to_string(workdir.join("package.json")) {
+        if package_json.contains("vitest") {
+            return Some(TestFramework::Vitest);
+        }
+        if package_json.contains("jest") {
+            return Some(TestFramework::Jest);
+        }
+    }
+    let python_markers = ["pytest.ini", "conftest.py", "pyproject.toml", "setup.py", "setup.cfg", "tox.ini"];
+    if python_markers.iter().any(|f| workdir.join(f).exists()) {
+        return Some(TestFramework::Pytest);
+    }
+    None
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    pub name: String,  // what the runner takes to select exactly this test
+    pub group: String,  // test file or go package, when the runner needs it to find the test again
+    pub status: TestStatus,
+    pub message: String,
+    pub locations: Vec<(String, usize)>,  // file and line, most relevant first
+}
+
+impl TestResult {
+    fn new(name: &str, group: &str, status: TestStatus) -> Self {
+        TestResult { name: name.to_string(), group: group.to_string(), status, message: String::new(), locations: vec![] }
+    }
+
+    /// A go package that didn't build, a jest or vitest file that didn't load: there are no test names in it
+    fn is_whole_group(&self) -> bool {
+        !self.group.is_empty() && self.name == self.group
+    }
+}
+
+fn extract_locations(text: &str) -> Vec<(String, usize)> {
+    let mut locations = vec![];
+    for caps in LOCATION_RE.captures_iter(text) {
+        let location = (caps[1].to_string(), caps[2].parse::<usize>().unwrap_or(0));
+        if location.1 > 0 && !location.0.contains("node_modules") && !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    locations
+}
+
+fn unique<'a>(items: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    items.filter(|x| !x.is_empty() && seen.insert(*x)).collect()
+}
+
+fn quote(s: &str) -> String {
+    shell_words::quote(s).to_string()
+}
+
+/// `filter` is a test name or a path, `rerun` selects exactly these tests and takes precedence. If a package or
+/// a file failed as a whole, the failed packages or files run without a name filter, a name filter would skip it.
+pub fn build_test_command(framework: TestFramework, filter: &str, rerun: &[TestResult]) -> String {
+    let filter = filter.trim();
+    let path_like = filter.contains('/') || filter.contains('\\');
+    let report = framework.report_path().unwrap_or_default();
+    let mut cmd = match framework {
+        TestFramework::Cargo => "cargo test --no-fail-fast".to_string(),
+        TestFramework::Pytest => format!(
+            "{} -m pytest -q -o junit_family=xunit1 --junitxml={}",
+            if cfg!(target_os = "windows") { "python" } else { "python3" }, report,
+        ),
+        TestFramework::Jest => format!("npx jest --ci --json --testLocationInResults --outputFile={}", report),
+        TestFramework::Vitest => format!("npx vitest run --reporter=junit --outputFile={}", report),
+        TestFramework::Go => "go test -json".to_string(),
+    };
+    if !rerun.is_empty() {
+        match framework {
+            TestFramework::Cargo => {
+                cmd.push_str(" -- --exact");
+                for t in rerun {
+                    cmd.push_str(&format!(" {}", quote(&t.name)));
+                }
+            }
+            TestFramework::Pytest => {
+                for t in rerun {
+                    cmd.push_str(&format!(" {}", quote(&t.name)));
+                }
+            }
+            TestFramework::Jest | TestFramework::Vitest => {
+                for file in unique(rerun.iter().map(|t| t.group.as_str())) {
+                    cmd.push_str(&format!(" {}", quote(file)));
+                }
+                if !rerun.iter().any(|t| t.is_whole_group()) {
+                    // vitest joins describe blocks with " > " in the report, but matches -t against names joined with spaces
+                    let names: Vec<String> = rerun.iter().map(|t| regex::escape(&t.name.replace(" > ", " "))).collect();
+                    cmd.push_str(&format!(" -t {}", quote(&format!("^(?:{})$", names.join("|")))));
+                }
+            }
+            TestFramework::Go => {
+                if !rerun.iter().any(|t| t.is_whole_group()) {
+                    let top_level: Vec<String> = unique(rerun.iter().map(|t| t.name.split('/').next().unwrap_or_default()))
+                        .into_iter().map(regex::escape).collect();
+                    cmd.push_str(&format!(" -run {}", quote(&format!("^(?:{})$", top_level.join("|")))));
+                }
+                for package in unique(rerun.iter().map(|t| t.group.as_str())) {
+                    cmd.push_str(&format!(" {}", quote(package)));
+                }
+            }
+        }
+        return cmd;
+    }
+    match framework {
+        TestFramework::Cargo if !filter.is_empty() => cmd.push_str(&format!(" {}", quote(filter))),
+        TestFramework::Pytest if !filter.is_empty() => {
+            if path_like || filter.contains("::") || filter.ends_with(".py") {
+                cmd.push_str(&format!(" {}", quote(filter)));
+            } else {
+                cmd.push_str(&format!(" -k {}", quote(filter)));
+            }
+        }
+        TestFramework::Jest | TestFramework::Vitest if !filter.is_empty() => {
+            if path_like || filter.contains(".test.") || filter.contains(".spec.") {
+                cmd.push_str(&format!(" {}", quote(filter)));
+            } else {
+                cmd.push_str(&format!(" -t {}", quote(filter)));
+            }
+        }
+        TestFramework::Go => {
+            if filter.is_empty() {
+                cmd.push_str(" ./...");
+            } else if path_like || filter.starts_with('.') {
+                cmd.push_str(&format!(" {}", quote(filter)));
+            } else {
+                cmd.push_str(&format!(" -run {} ./...", quote(filter)));
+            }
+        }
+        _ => {}
+    }
+    cmd
+}
+
+/// `cargo test` text output: result lines, then a stdout section for each failed test
+pub fn parse_libtest_output(output: &str) -> Vec<TestResult> {
+    let mut results: Vec<TestResult> = vec![];
+    let mut sections: HashMap<String, String> = HashMap::new();
+    let mut current_section: Option<String> = None;
+    for line in output.lines() {
+        if let Some(caps) = LIBTEST_RESULT_RE.captures(line) {
+            let status = match &caps[2] {
+                "ok" => TestStatus::Passed,
+                "FAILED" => TestStatus::Failed,
+                _ => TestStatus::Skipped,
+            };
+            results.push(TestResult::new(&caps[1], "", status));
+        } else if let Some(caps) = LIBTEST_SECTION_RE.captures(line) {
+            current_section = Some(caps[1].to_string());
+            sections.insert(caps[1].to_string(), String::new());
+        } else if line == "failures:" || line.starts_with("test result:") {
+            current_section = None;
+        } else if let Some(name) = &current_section {
+            let section = sections.get_mut(name).unwrap();
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    for result in results.iter_mut().filter(|r| r.status == TestStatus::Failed) {
+        result.message = sections.get(&result.name).map(|s| s.trim().to_string()).unwrap_or_default();
+        let mut locations: Vec<(String, usize)> = LIBTEST_PANIC_RE.captures_iter(&result.message)
+            .map(|caps| (caps[1].to_string(), caps[2].parse::<usize>().unwrap_or(0)))
+            .collect();
+        for location in extract_locations(&result.message) {
+            if !locations.contains(&location) {
+                locations.push(location);
+            }
+        }
+        result.locations = locations;
+    }
+    results
+}
+
+/// pytest node id from an xunit1 testcase: tests/test_a.py::TestClass::test_name
+fn pytest_node_id(file: &str, classname: &str, name: &str) -> String {
+    if file.is_empty() {
+        return format!("{}::{}", classname, name);
+    }
+    let module = file.trim_end_matches(".py").replace(['/', '\\'], ".");
+    let class_path = classname.strip_prefix(&module).unwrap_or_default().trim_start_matches('.');
+    if class_path.is_empty() {
+        format!("{}::{}", file, name)
+    } else {
+        format!("{}::{}::{}", file, class_path.replace('.', "::"), name)
+    }
+}
+
+/// pytest (with junit_family=xunit1, it has file and line) and vitest reports
+pub fn parse_junit_xml(xml: &str, framework: TestFramework) -> Result<Vec<TestResult>, String> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| format!("cannot parse JUnit XML: {}", e))?;
+    let mut results = vec![];
+    for case in doc.descendants().filter(|n| n.has_tag_name("testcase")) {
+        let name = case.attribute("name").unwrap_or_default();
+        let classname = case.attribute("classname").unwrap_or_default();
+        let file = case.attribute("file").unwrap_or_default();
+        let mut result = match framework {
+            TestFramework::Pytest => TestResult::new(&pytest_node_id(file, classname, name), "", TestStatus::Passed),
+            _ => TestResult::new(name, classname, TestStatus::Passed),
+        };
+        for child in case.children().filter(|n| n.is_element()) {
+            match child.tag_name().name() {
+                "failure" | "error" => {
+                    result.status = TestStatus::Failed;
+                    let text = child.text().unwrap_or_default().trim();
+                    let message = if text.is_empty() { child.attribute("message").unwrap_or_default() } else { text };
+                    if !result.message.is_empty() {
+                        result.message.push('\n');
+                    }
+                    result.message.push_str(message);
+                }
+                "skipped" if result.status != TestStatus::Failed => result.status = TestStatus::Skipped,
+                _ => {}
+            }
+        }
+        if result.status == TestStatus::Failed {
+            result.locations = extract_locations(&result.message);
+            if framework == TestFramework::Pytest {
+                // a traceback goes from the test down to where it failed
+                result.locations.reverse();
+            }
+            if let (false, Some(line)) = (file.is_empty(), case.attribute("line").and_then(|l| l.parse::<usize>().ok())) {
+                result.locations.push((file.to_string(), line + 1));  // pytest counts from zero
+            }
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+pub fn parse_jest_json(json: &str) -> Result<Vec<TestResult>, String> {
+    let report: Value = serde_json::from_str(json).map_err(|e| format!("cannot parse jest report: {}", e))?;
+    let mut results = vec![];
+    for suite in report["testResults"].as_array().into_iter().flatten() {
+        let file = suite["name"].as_str().unwrap_or_default();
+        let assertions = suite["assertionResults"].as_array().cloned().unwrap_or_default();
+        if assertions.is_empty() && suite["status"] == "failed" {
+            // the file didn't even load: syntax error, missing import
+            let mut result = TestResult::new(file, file, TestStatus::Failed);
+            result.message = suite["message"].as_str().unwrap_or_default().to_string();
+            result.locations = extract_locations(&result.message);
+            results.push(result);
+            continue;
+        }
+        for assertion in assertions {
+            let status = match assertion["status"].as_str().unwrap_or_default() {
+                "passed" => TestStatus::Passed,
+                "failed" => TestStatus::Failed,
+                _ => TestStatus::Skipped,
+            };
+            let mut result = TestResult::new(assertion["fullName"].as_str().unwrap_or_default(), file, status);
+            if result.status == TestStatus::Failed {
+                result.message = assertion["failureMessages"].as_array().into_iter().flatten()
+                    .filter_map(|m| m.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                result.locations = extract_locations(&result.message);
+                if let Some(line) = assertion["location"]["line"].as_u64() {
+                    result.locations.push((file.to_string(), line as usize));
+                }
+            }
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+/// `go test -json` events, one per line
+pub fn parse_go_test_json(output: &str) -> Vec<TestResult> {
+    let mut outputs: HashMap<(String, String), String> = HashMap::new();
+    let mut packages_with_tests = HashSet::new();
+    let mut results = vec![];
+    for line in output.lines() {
+        let event: Value = match serde_json::from_str(line) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+        let action = event["Action"].as_str().unwrap_or_default();
+        let package = event["Package"].as_str().unwrap_or_default().to_string();
+        let test = event["Test"].as_str().unwrap_or_default().to_string();
+        let status = match action {
+            "output" => {
+                outputs.entry((package, test)).or_default().push_str(event["Output"].as_str().unwrap_or_default());
+                continue;
+            }
+            "pass" => TestStatus::Passed,
+            "fail" => TestStatus::Failed,
+            "skip" => TestStatus::Skipped,
+            _ => continue,
+        };
+        if test.is_empty() {
+            // a package that fails without a failed test didn't build or crashed
+            if status == TestStatus::Failed && !packages_with_tests.contains(&package) {
+                let mut result = TestResult::new(&package, &package, status);
+                result.message = outputs.get(&(package.clone(), test)).cloned().unwrap_or_default().trim().to_string();
+                result.locations = extract_locations(&result.message);
+                results.push(result);
+            }
+            continue;
+        }
+        packages_with_tests.insert(package.clone());
+        let mut result = TestResult::new(&test, &package, status);
+        if result.status == TestStatus::Failed {
+            result.message = outputs.get(&(package, test)).cloned().unwrap_or_default()
+                .lines()
+                .filter(|l| !l.starts_with("=== "))
+                .collect::<Vec<_>>()
+                .join("\n");
+            result.locations = extract_locations(&result.message);
+        }
+        results.push(result);
+    }
+    results
+}
+
+fn shorten_message(message: &str) -> String {
+    let lines: Vec<&str> = message.lines().collect();
+    let mut out: String = lines.iter().take(RUN_TESTS_MESSAGE_MAX_LINES).cloned().collect::<Vec<_>>().join("\n");
+    if out.len() > RUN_TESTS_MESSAGE_MAX_CHARS {
+        out = out.chars().take(RUN_TESTS_MESSAGE_MAX_CHARS).collect();
+    }
+    if lines.len() > RUN_TESTS_MESSAGE_MAX_LINES || out.len() < message.trim_end().len() {
+        out.push_str("\n...");
+    }
+    out
+}
+
+/// Only files inside the project are worth showing, the standard library is not
+async fn resolve_location(gcx: Arc<ARwLock<GlobalContext>>, workdir: &Path, project_dirs: &[PathBuf], file: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(file);
+    let candidate = if path.is_absolute() { path } else { workdir.join(path) };
+    if candidate.is_file() {
+        let candidate = canonical_path(candidate.to_string_lossy().to_string());
+        return project_dirs.iter().any(|d| candidate.starts_with(d)).then_some(candidate);
+    }
+    // go prints paths relative to the package, libtest relative to the workspace root
+    let candidates = correct_to_nearest_filename(gcx, &file.to_string(), false, 2).await;
+    if candidates.len() == 1 {
+        Some(PathBuf::from(&candidates[0]))
+    } else {
+        None
+    }
+}
+
+fn last_failed_key(chat_id: &str, workdir: &Path) -> String {
+    format!("{}:{}", chat_id, workdir.display())
+}
+
+struct TestRun {
+    framework: TestFramework,
+    workdir: PathBuf,
+    command: String,
+    rerun_count: usize,
+}
+
+async fn prepare_test_run(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str, args: &HashMap<String, Value>) -> Result<TestRun, String> {
+    let workdir = match arg_str(args, "workdir")? {
+        Some(workdir) => resolve_shell_workdir(gcx.clone(), workdir).await?,
+        None => get_active_project_path(gcx.clone()).await.ok_or("No project is open, set workdir")?,
+    };
+    let rerun_failed = match args.get("rerun_failed") {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => s.trim().eq_ignore_ascii_case("true"),
+        _ => false,
+    };
+    if rerun_failed {
+        let last_failed = LAST_FAILED.lock().unwrap().get(&last_failed_key(chat_id, &workdir)).cloned();
+        let (framework, failed) = last_failed.ok_or(format!("Nothing to re-run, the last run in {} had no failed tests", workdir.display()))?;
+        let command = build_test_command(framework, "", &failed);
+        return Ok(TestRun { framework, workdir, command, rerun_count: failed.len() });
+    }
+    let framework = match arg_str(args, "framework")? {
+        Some(name) => TestFramework::from_name(name)
+            .ok_or(format!("Unknown framework `{}`, should be one of cargo, pytest, jest, vitest, go", name))?,
+        None => detect_framework(&workdir)
+            .ok_or(format!("Cannot tell which test framework {} uses, set framework to one of cargo, pytest, jest, vitest, go", workdir.display()))?,
+    };
+    let command = build_test_command(framework, arg_str(args, "filter")?.unwrap_or_default(), &[]);
+    Ok(TestRun { framework, workdir, command, rerun_count: 0 })
+}
+
+pub struct ToolRunTests {
+    pub common: IntegrationCommon,
+    pub cfg: SettingsShell,
+    pub config_path: String,
+}
+
+impl ToolRunTests {
+    async fn run(&self, gcx: Arc<ARwLock<GlobalContext>>, run: &TestRun) -> Result<(Vec<TestResult>, String, i32, f64), String> {
+        let project_dirs = get_project_dirs(gcx.clone()).await;
+        let mut cmd = shell_command(&run.command, &self.cfg.sandbox, &project_dirs)?;
+        cmd.current_dir_simplified(&run.workdir);
+        let mut error_log = Vec::<YamlError>::new();
+        let env_variables = crate::integrations::setting_up_integrations::get_vars_for_replacements(gcx.clone(), &mut error_log).await;
+        for (key, value) in env_variables {
+            cmd.env(key, value);
+        }
+        let report_path = run.framework.report_path().map(|p| run.workdir.join(p));
+        if let Some(report_path) = &report_path {
+            let _ = tokio::fs::remove_file(report_path).await;
+            if let Some(parent) = report_path.parent() {
+                let _ = tokio::fs::create_dir_all(parent).await;
+            }
+        }
+
+        let timeout = self.cfg.run_tests_timeout.parse::<u64>().unwrap_or(RUN_TESTS_TIMEOUT_SECONDS);
+        tracing::info!("RUN_TESTS: running in {:?}\n{:?}", run.workdir, run.command);
+        let t0 = tokio::time::Instant::now();
+        let output = execute_command(cmd, timeout, &run.command).await?;
+        let duration = t0.elapsed().as_secs_f64();
+        let stdout = output.stdout.to_string_lossy_and_strip_ansi();
+        let stderr = output.stderr.to_string_lossy_and_strip_ansi();
+        tracing::info!("RUN_TESTS: /finished in {:.3}s", duration);
+
+        let report = match &report_path {
+            Some(report_path) => {
+                let report = tokio::fs::read_to_string(report_path).await.unwrap_or_default();
+                let _ = tokio::fs::remove_file(report_path).await;
+                report
+            }
+            None => String::new(),
+        };
+        let parsed = match run.framework {
+            TestFramework::Cargo => Ok(parse_libtest_output(&stdout)),
+            TestFramework::Go => Ok(parse_go_test_json(&stdout)),
+            _ if report.is_empty() => Ok(vec![]),
+            TestFramework::Jest => parse_jest_json(&report),
+            TestFramework::Pytest | TestFramework::Vitest => parse_junit_xml(&report, run.framework),
+        };
+        let mut results = parsed.unwrap_or_else(|e| {
+            tracing::warn!("RUN_TESTS: {}", e);
+            vec![]
+        });
+        for result in results.iter_mut().filter(|r| !r.message.is_empty()) {
+            result.message = result.message.as_bytes().to_string_lossy_and_strip_ansi();
+        }
+
+        // jest prints its JSON to stdout too, that's not worth showing
+        let raw_stdout = if run.framework == TestFramework::Jest && !results.is_empty() { String::new() } else { stdout };
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+        let raw_output = crate::integrations::integr_cmdline::format_output(
+            &output_mini_postprocessing(&self.cfg.output_filter, &raw_stdout, &privacy_settings.redactor),
+            &output_mini_postprocessing(&self.cfg.output_filter, &stderr, &privacy_settings.redactor),
+        );
+        Ok((results, raw_output, output.status.code().unwrap_or_default(), duration))
+    }
+}
+
+#[async_trait]
+impl Tool for ToolRunTests {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let (gcx, chat_id) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.chat_id.clone())
+        };
+        let run = prepare_test_run(gcx.clone(), &chat_id, args).await?;
+        let (results, raw_output, exit_code, duration) = self.run(gcx.clone(), &run).await?;
+
+        let failed: Vec<&TestResult> = results.iter().filter(|r| r.status == TestStatus::Failed).collect();
+        let passed: Vec<&TestResult> = results.iter().filter(|r| r.status == TestStatus::Passed).collect();
+        let skipped_n = results.len() - failed.len() - passed.len();
+        {
+            let mut last_failed = LAST_FAILED.lock().unwrap();
+            let key = last_failed_key(&chat_id, &run.workdir);
+            last_failed.shift_remove(&key);
+            if !failed.is_empty() {
+                // rerun needs only the names, the oldest chats are forgotten first
+                let to_rerun = failed.iter().map(|r| TestResult::new(&r.name, &r.group, TestStatus::Failed)).collect();
+                last_failed.insert(key, (run.framework, to_rerun));
+                if last_failed.len() > RUN_TESTS_LAST_FAILED_MAX {
+                    last_failed.shift_remove_index(0);
+                }
+            }
+        }
+
+        let mut out = format!(
+            "Ran `{}` in {}, {:.1}s, exit code {}\n",
+            run.command, run.workdir.display(), duration, exit_code,
+        );
+        if run.rerun_count > 0 {
+            out.push_str(&format!("Re-ran {} tests that failed last time\n", run.rerun_count));
+        }
+        out.push_str(&format!("{}: {} passed, {} failed, {} skipped\n", run.framework.name(), passed.len(), failed.len(), skipped_n));
+
+        let project_dirs = get_project_dirs(gcx.clone()).await;
+        let mut context_files = vec![];
+        let mut linked = HashSet::new();
+        for (i, test) in failed.iter().enumerate() {
+            if i == RUN_TESTS_FAILED_SHOW_MAX {
+                out.push_str(&format!("\n...and {} more failed: {}\n", failed.len() - i, failed[i..].iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", ")));
+                break;
+            }
+            let mut location = None;
+            for (file, line) in test.locations.iter() {
+                if let Some(path) = resolve_location(gcx.clone(), &run.workdir, &project_dirs, file).await {
+                    location = Some((path, *line));
+                    break;
+                }
+            }
+            match &location {
+                Some((path, line)) => out.push_str(&format!("\nFAILED {} at {}:{}\n", test.name, path.display(), line)),
+                None => out.push_str(&format!("\nFAILED {}\n", test.name)),
+            }
+            if !test.message.is_empty() {
+                out.push_str(&format!("```\n{}\n```\n", shorten_message(&test.message)));
+            }
+            if let Some((path, line)) = location {
+                if context_files.len() < RUN_TESTS_CONTEXT_FILES_MAX && linked.insert((path.clone(), line)) {
+                    context_files.push(ContextEnum::ContextFile(ContextFile {
+                        file_name: path.to_string_lossy().to_string(),
+                        file_content: "".to_string(),
+                        line1: line,
+                        line2: line,
+                        symbols: vec![],
+                        gradient_type: 4,
+                        usefulness: 100.0,
+                    }));
+                }
+            }
+        }
+        if !passed.is_empty() {
+            let names: Vec<&str> = passed.iter().take(RUN_TESTS_PASSED_SHOW_MAX).map(|t| t.name.as_str()).collect();
+            out.push_str(&format!("\nPassed: {}", names.join(", ")));
+            if passed.len() > RUN_TESTS_PASSED_SHOW_MAX {
+                out.push_str(&format!(" and {} more", passed.len() - RUN_TESTS_PASSED_SHOW_MAX));
+            }
+            out.push('\n');
+        }
+        // no results or no failure explains the exit code: most likely it didn't compile, the output tells why
+        if results.is_empty() || (exit_code != 0 && failed.iter().all(|t| t.message.is_empty())) {
+            if results.is_empty() {
+                out.push_str("\nNo test results found in the output.\n");
+            }
+            out.push_str(&format!("\n{}", raw_output));
+        }
+        if !failed.is_empty() {
+            out.push_str(&format!("\nCall run_tests with rerun_failed=true to run only the {} failed tests again.\n", failed.len()));
+        }
+
+        let mut results = vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(out),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })];
+        results.extend(context_files);
+        Ok((false, results))
+    }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: "run_tests".to_string(),
+            display_name: "Run Tests".to_string(),
+            source: ToolSource {
+                source_type: ToolSourceType::Integration,
+                config_path: self.config_path.clone(),
+            },
+            agentic: true,
+            experimental: false,
+            description: "Run the project's tests and get lists of passed and failed tests, with failure messages and locations. \
+                Detects cargo test, pytest, jest, vitest and go test. Prefer it to shell for running tests. \
+                After fixing something, call it with rerun_failed=true to run only the tests that failed.".to_string(),
+            parameters: vec![
+                ToolParam {
+                    name: "filter".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Run only tests whose name matches, or a test file or directory. Empty to run everything.".to_string(),
+                },
+                ToolParam {
+                    name: "rerun_failed".to_string(),
+                    param_type: "boolean".to_string(),
+                    description: "Run only the tests that failed in the previous run in this workdir, filter is ignored".to_string(),
+                },
+                ToolParam {
+                    name: "workdir".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Directory with Cargo.toml, package.json, go.mod or the python project, the project root if empty".to_string(),
+                },
+                ToolParam {
+                    name: "framework".to_string(),
+                    param_type: "string".to_string(),
+                    description: "cargo, pytest, jest, vitest or go, detected if empty".to_string(),
+                },
+            ],
+            parameters_required: vec![],
+        }
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>
+    ) -> Result<MatchConfirmDeny, String> {
+        let command_to_match = self.command_to_match_against_confirm_deny(ccx.clone(), args).await?;
+        Ok(shell_confirm_deny(&command_to_match, &self.confirm_deny_rules(), self.cfg.sandbox.isolated()))
+    }
+
+    async fn command_to_match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let (gcx, chat_id) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.chat_id.clone())
+        };
+        Ok(prepare_test_run(gcx, &chat_id, args).await?.command)
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.common.confirmation.clone())
+    }
+
+    fn has_config_path(&self) -> Option<String> {
+        Some(self.config_path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(results: &[TestResult]) -> Vec<(String, TestStatus)> {
+        results.iter().map(|r| (r.name.clone(), r.status.clone())).collect()
+    }
+
+    #[test]
+    fn test_parse_test_reports() {
+        let libtest = "running 3 tests\n\
+            test tests::a ... ok\n\
+            test tests::b ... FAILED\n\
+            test tests::c ... ignored, slow\n\
+            \n\
+            failures:\n\
+            \n\
+            ---- tests::b stdout ----\n\
+            thread 'tests::b' panicked at src/lib.rs:12:9:\n\
+            assertion `left == right` failed\n\
+            \n\
+            \n\
+            failures:\n    tests::b\n\n\
+            test result: FAILED. 1 passed; 1 failed; 1 ignored\n";
+        let results = parse_libtest_output(libtest);
+        assert_eq!(statuses(&results), vec![
+            ("tests::a".to_string(), TestStatus::Passed),
+            ("tests::b".to_string(), TestStatus::Failed),
+            ("tests::c".to_string(), TestStatus::Skipped),
+        ]);
+        assert!(results[1].message.ends_with("assertion `left == right` failed"));
+        assert_eq!(results[1].locations[0], ("src/lib.rs".to_string(), 12));
+
+        let pytest = r#"<?xml version="1.0" encoding="utf-8"?><testsuites><testsuite name="pytest">
+            <testcase classname="tests.test_math.TestAdd" name="test_ok" file="tests/test_math.py" line="4" time="0.001"/>
+            <testcase classname="tests.test_math" name="test_bad" file="tests/test_math.py" line="9" time="0.001">
+                <failure message="assert 1 == 2">def test_bad():
+&gt;       assert helper() == 2
+tests/test_math.py:11:
+tests/helpers.py:3: in helper
+E   assert 1 == 2</failure>
+            </testcase>
+            <testcase classname="tests.test_math" name="test_skip" file="tests/test_math.py" line="13"><skipped message="no"/></testcase>
+        </testsuite></testsuites>"#;
+        let results = parse_junit_xml(pytest, TestFramework::Pytest).unwrap();
+        assert_eq!(statuses(&results), vec![
+            ("tests/test_math.py::TestAdd::test_ok".to_string(), TestStatus::Passed),
+            ("tests/test_math.py::test_bad".to_string(), TestStatus::Failed),
+            ("tests/test_math.py::test_skip".to_string(), TestStatus::Skipped),
+        ]);
+        assert_eq!(results[1].locations, vec![
+            ("tests/helpers.py".to_string(), 3),
+            ("tests/test_math.py".to_string(), 11),
+            ("tests/test_math.py".to_string(), 10),
+        ]);
+
+        let jest = serde_json::json!({"testResults": [
+            {"name": "/p/src/sum.test.js", "status": "failed", "assertionResults": [
+                {"fullName": "sum adds", "status": "passed", "failureMessages": []},
+                {"fullName": "sum fails", "status": "failed", "location": {"line": 7, "column": 3},
+                 "failureMessages": ["Error: expect(received).toBe(expected)\n    at Object.<anonymous> (/p/src/sum.test.js:8:17)\n    at /p/node_modules/jest-circus/build/utils.js:298:28"]},
+            ]},
+            {"name": "/p/src/broken.test.js", "status": "failed", "message": "SyntaxError: Unexpected token (3:4)", "assertionResults": []},
+        ]}).to_string();
+        let results = parse_jest_json(&jest).unwrap();
+        assert_eq!(statuses(&results), vec![
+            ("sum adds".to_string(), TestStatus::Passed),
+            ("sum fails".to_string(), TestStatus::Failed),
+            ("/p/src/broken.test.js".to_string(), TestStatus::Failed),
+        ]);
+        assert_eq!(results[1].locations, vec![("/p/src/sum.test.js".to_string(), 8), ("/p/src/sum.test.js".to_string(), 7)]);
+
+        let go = [
+            r#"{"Action":"run","Package":"ex/calc","Test":"TestAdd"}"#,
+            r#"{"Action":"output","Package":"ex/calc","Test":"TestAdd","Output":"=== RUN   TestAdd\n"}"#,
+            r#"{"Action":"output","Package":"ex/calc","Test":"TestAdd","Output":"    calc_test.go:9: got 3, want 4\n"}"#,
+            r#"{"Action":"fail","Package":"ex/calc","Test":"TestAdd","Elapsed":0}"#,
+            r#"{"Action":"pass","Package":"ex/calc","Test":"TestSub","Elapsed":0}"#,
+            r#"{"Action":"fail","Package":"ex/calc","Elapsed":0.1}"#,
+            r#"{"Action":"output","Package":"ex/broken","Output":"broken.go:3:1: syntax error\n"}"#,
+            r#"{"Action":"fail","Package":"ex/broken","Elapsed":0}"#,
+        ].join("\n");
+        let results = parse_go_test_json(&go);
+        assert_eq!(statuses(&results), vec![
+            ("TestAdd".to_string(), TestStatus::Failed),
+            ("TestSub".to_string(), TestStatus::Passed),
+            ("ex/broken".to_string(), TestStatus::Failed),
+        ]);
+        assert_eq!(results[0].message.trim(), "calc_test.go:9: got 3, want 4");
+        assert_eq!(results[0].locations, vec![("calc_test.go".to_string(), 9)]);
+    }
+
+    #[test]
+    fn test_build_test_command() {
+        assert_eq!(build_test_command(TestFramework::Cargo, "parser", &[]), "cargo test --no-fail-fast parser");
+        assert_eq!(build_test_command(TestFramework::Go, "", &[]), "go test -json ./...");
+        assert_eq!(build_test_command(TestFramework::Go, "TestAdd", &[]), "go test -json -run TestAdd ./...");
+        assert!(build_test_command(TestFramework::Pytest, "tests/test_a.py::test_b", &[]).ends_with("run_tests_junit.xml tests/test_a.py::test_b"));
+        assert!(build_test_command(TestFramework::Pytest, "slow and not db", &[]).ends_with(" -k 'slow and not db'"));
+
+        let failed = |name: &str, group: &str| TestResult::new(name, group, TestStatus::Failed);
+        assert_eq!(
+            build_test_command(TestFramework::Cargo, "ignored", &[failed("a::b", ""), failed("c", "")]),
+            "cargo test --no-fail-fast -- --exact a::b c",
+        );
+        assert_eq!(
+            build_test_command(TestFramework::Go, "", &[failed("TestA/sub", "ex/a"), failed("TestA", "ex/a"), failed("TestB", "ex/b")]),
+            "go test -json -run '^(?:TestA|TestB)$' ex/a ex/b",
+        );
+        assert!(build_test_command(TestFramework::Vitest, "", &[failed("math > adds 1+1", "src/math.test.ts")])
+            .ends_with(" src/math.test.ts -t '^(?:math adds 1\\+1)$'"));
+
+        // a package that didn't build or a file that didn't load runs whole, but only it and the other failed ones
+        assert_eq!(
+            build_test_command(TestFramework::Go, "", &[failed("TestA", "ex/a"), failed("ex/broken", "ex/broken")]),
+            "go test -json ex/a ex/broken",
+        );
+        assert!(build_test_command(TestFramework::Jest, "", &[failed("sum fails", "/p/src/sum.test.js"), failed("/p/src/broken.test.js", "/p/src/broken.test.js")])
+            .ends_with(" /p/src/sum.test.js /p/src/broken.test.js"));
+    }
+
+    #[test]
+    fn test_shorten_message() {
+        assert_eq!(shorten_message("assert 1 == 2\n"), "assert 1 == 2");
+        let long = (0..100).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n");
+        assert!(shorten_message(&long).ends_with("line 39\n..."));
+    }
+}
@@ -6,7 +6,6 @@ use serde::Serialize;
 use serde_json::Value;
 use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use async_trait::async_trait;
-use tokio::process::Command;
 
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::at_commands::at_file::return_one_candidate_or_a_good_error;
@@ -23,9 +22,13 @@ use crate::integrations::process_io_utils::{execute_command, AnsiStrippable};
 use crate::tools::tools_description::{ToolParam, Tool, ToolDesc, ToolSource, ToolSourceType, MatchConfirmDeny, MatchConfirmDenyResult};
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 use crate::postprocessing::pp_command_output::CmdlineOutputFilter;
-use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationTrait};
+use crate::privacy::load_privacy_if_needed;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation, IntegrationTrait};
+use crate::integrations::sandbox::{shell_command, SandboxSettings};
+use crate::integrations::integr_process::ToolProcess;
+use crate::integrations::integr_run_tests::ToolRunTests;
 use crate::custom_error::YamlError;
-use crate::tools::tools_execute::command_should_be_denied;
+use crate::tools::tools_execute::{command_should_be_confirmed_by_user, command_should_be_denied};
 
 
 #[derive(Deserialize, Serialize, Clone, Default)]
@@ -33,7 +36,11 @@ pub struct SettingsShell {
     #[serde(default)]
     pub timeout: String,
     #[serde(default)]
+    pub run_tests_timeout: String,
+    #[serde(default)]
     pub output_filter: CmdlineOutputFilter,
+    #[serde(flatten)]
+    pub sandbox: SandboxSettings,
 }
 
 #[derive(Default)]
@@ -68,11 +75,23 @@ impl IntegrationTrait for ToolShell {
     }
 
     async fn integr_tools(&self, _integr_name: &str) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
-        vec![Box::new(ToolShell {
-            common: self.common.clone(),
-            cfg: self.cfg.clone(),
-            config_path: self.config_path.clone(),
-        })]
+        vec![
+            Box::new(ToolShell {
+                common: self.common.clone(),
+                cfg: self.cfg.clone(),
+                config_path: self.config_path.clone(),
+            }),
+            Box::new(ToolProcess {
+                common: self.common.clone(),
+                cfg: self.cfg.clone(),
+                config_path: self.config_path.clone(),
+            }),
+            Box::new(ToolRunTests {
+                common: self.common.clone(),
+                cfg: self.cfg.clone(),
+                config_path: self.config_path.clone(),
+            }),
+        ]
     }
 }
 
@@ -98,6 +117,7 @@ impl Tool for ToolShell {
             &workdir_maybe,
             timeout,
             &self.cfg.output_filter,
+            &self.cfg.sandbox,
             &env_variables,
             gcx.clone(),
         ).await?;
@@ -158,22 +178,7 @@ impl Tool for ToolShell {
         if command_to_match.is_empty() {
             return Err("Empty command to match".to_string());
         }
-        if let Some(rules) = &self.confirm_deny_rules() {
-            let (is_denied, deny_rule) = command_should_be_denied(&command_to_match, &rules.deny);
-            if is_denied {
-                return Ok(MatchConfirmDeny {
-                    result: MatchConfirmDenyResult::DENY,
-                    command: command_to_match.clone(),
-                    rule: deny_rule.clone(),
-                });
-            }
-        }
-        // NOTE: do not match command if not denied, always wait for confirmation from user
-        Ok(MatchConfirmDeny {
-            result: MatchConfirmDenyResult::CONFIRMATION,
-            command: command_to_match.clone(),
-            rule: "*".to_string(),
-        })
+        Ok(shell_confirm_deny(&command_to_match, &self.confirm_deny_rules(), self.cfg.sandbox.isolated()))
     }
 
     async fn command_to_match_against_confirm_deny(
@@ -186,22 +191,55 @@ impl Tool for ToolShell {
         Ok(command)
     }
 
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.integr_common().confirmation)
+    }
+
     fn has_config_path(&self) -> Option<String> {
         Some(self.config_path.clone())
     }
 }
 
+/// Commands that go to a shell: deny rules first, then every command needs a confirmation, unless it runs in
+/// the sandbox with no network, where ask_user decides like for any other tool
+pub fn shell_confirm_deny(command: &String, rules: &Option<IntegrationConfirmation>, isolated: bool) -> MatchConfirmDeny {
+    if let Some(rules) = rules {
+        let (is_denied, deny_rule) = command_should_be_denied(command, &rules.deny);
+        if is_denied {
+            return MatchConfirmDeny {
+                result: MatchConfirmDenyResult::DENY,
+                command: command.clone(),
+                rule: deny_rule,
+            };
+        }
+        if isolated {
+            let (needs_confirmation, confirmation_rule) = command_should_be_confirmed_by_user(command, &rules.ask_user);
+            return MatchConfirmDeny {
+                result: if needs_confirmation { MatchConfirmDenyResult::CONFIRMATION } else { MatchConfirmDenyResult::PASS },
+                command: command.clone(),
+                rule: confirmation_rule,
+            };
+        }
+    }
+    // NOTE: do not match command if not denied, always wait for confirmation from user
+    MatchConfirmDeny {
+        result: MatchConfirmDenyResult::CONFIRMATION,
+        command: command.clone(),
+        rule: "*".to_string(),
+    }
+}
+
 pub async fn execute_shell_command(
     command: &str,
     workdir_maybe: &Option<PathBuf>,
     timeout: u64,
     output_filter: &CmdlineOutputFilter,
+    sandbox: &SandboxSettings,
     env_variables: &HashMap<String, String>,
     gcx: Arc<ARwLock<GlobalContext>>,
 ) -> Result<String, String> {
-    let shell = if cfg!(target_os = "windows") { "powershell.exe" } else { "sh" };
-    let shell_arg = if cfg!(target_os = "windows") { "-Command" } else { "-c" };
-    let mut cmd = Command::new(shell);
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    let mut cmd = shell_command(command, sandbox, &project_dirs)?;
 
     if let Some(workdir) = workdir_maybe {
         cmd.current_dir_simplified(workdir);
@@ -215,8 +253,6 @@ pub async fn execute_shell_command(
         cmd.env(key, value);
     }
 
-    cmd.arg(shell_arg).arg(command);
-
     tracing::info!("SHELL: running command directory {:?}\n{:?}", workdir_maybe, command);
     let t0 = tokio::time::Instant::now();
     let output = execute_command(cmd, timeout, command).await?;
@@ -226,8 +262,9 @@ pub async fn execute_shell_command(
     let stdout = output.stdout.to_string_lossy_and_strip_ansi();
     let stderr = output.stderr.to_string_lossy_and_strip_ansi();
 
-    let filtered_stdout = crate::postprocessing::pp_command_output::output_mini_postprocessing(output_filter, &stdout);
-    let filtered_stderr = crate::postprocessing::pp_command_output::output_mini_postprocessing(output_filter, &stderr);
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+    let filtered_stdout = crate::postprocessing::pp_command_output::output_mini_postprocessing(output_filter, &stdout, &privacy_settings.redactor);
+    let filtered_stderr = crate::postprocessing::pp_command_output::output_mini_postprocessing(output_filter, &stderr, &privacy_settings.redactor);
 
     let mut out = crate::integrations::integr_cmdline::format_output(&filtered_stdout, &filtered_stderr);
     let exit_code = output.status.code().unwrap_or_default();
@@ -263,7 +300,7 @@ async fn parse_args(gcx: Arc<ARwLock<GlobalContext>>, args: &HashMap<String, Val
     Ok((command, workdir))
 }
 
-async fn resolve_shell_workdir(gcx: Arc<ARwLock<GlobalContext>>, raw_path: &str) -> Result<PathBuf, String> {
+pub async fn resolve_shell_workdir(gcx: Arc<ARwLock<GlobalContext>>, raw_path: &str) -> Result<PathBuf, String> {
     let path_str = preprocess_path_for_normalization(raw_path.to_string());
     let path = PathBuf::from(&path_str);
 
@@ -291,10 +328,40 @@ fields:
     f_type: string_short
     f_desc: "The command must immediately return the results, it can't be interactive. If the command runs for too long, it will be terminated and stderr/stdout collected will be presented to the model."
     f_default: "10"
+  run_tests_timeout:
+    f_type: string_short
+    f_desc: "Timeout for run_tests in seconds, test suites take longer than a single command."
+    f_default: "600"
+    f_extra: true
   output_filter:
     f_type: "output_filter"
     f_desc: "The output from the command can be long or even quasi-infinite. This section allows to set limits, prioritize top or bottom, or use regexp to show the model the relevant part."
     f_extra: true
+  sandbox:
+    f_type: bool
+    f_desc: "Linux only. Run the command in a sandbox: the filesystem is read-only except the project dirs and the writable paths below, and the command gets a private /tmp with bubblewrap (bwrap), or a private TMPDIR with landlock when bwrap isn't installed. Reads aren't restricted and there's no seccomp filter. If the sandbox can't be set up, the command doesn't run. With the sandbox on and the network off, commands that don't match ask_user run without a confirmation."
+    f_extra: true
+  sandbox_network:
+    f_type: bool
+    f_desc: "Allow network access inside the sandbox."
+    f_default: true
+    f_extra: true
+  sandbox_writable_paths:
+    f_type: string_array
+    f_desc: "More directories the command can write to, for example ~/.cargo or ~/.cache/pip."
+    f_extra: true
+  sandbox_memory_mb:
+    f_type: string_short
+    f_desc: "Address space limit for each process in the sandbox, in megabytes. Empty for no limit."
+    f_extra: true
+  sandbox_cpu_seconds:
+    f_type: string_short
+    f_desc: "CPU time limit for each process in the sandbox, in seconds. Empty for no limit."
+    f_extra: true
+  sandbox_max_processes:
+    f_type: string_short
+    f_desc: "Process limit, like ulimit -u it counts all processes of your user. Empty for no limit."
+    f_extra: true
 description: |
   Allows to execute any command line tool with confirmation from the chat itself.
 available:
@@ -18,9 +18,13 @@ pub mod integr_mysql;
 pub mod integr_cmdline;
 pub mod integr_cmdline_service;
 pub mod integr_shell;
+pub mod integr_process;
+pub mod integr_run_tests;
 pub mod mcp;
+pub mod forge;
 
 pub mod process_io_utils;
+pub mod sandbox;
 pub mod docker;
 pub mod sessions;
 pub mod config_chat;
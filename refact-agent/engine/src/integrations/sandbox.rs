@@ -0,0 +1,311 @@
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+// Optional sandbox for shell and cmdline_* commands, Linux only, no Docker needed.
+//
+// With bubblewrap (bwrap) installed, the command runs in new pid/ipc/uts namespaces with the whole filesystem mounted
+// read-only except the project dirs and sandbox_writable_paths, a private /tmp, and with no network if it's off.
+// Without bwrap the kernel does the same directly: landlock allows writes only below those dirs and a private TMPDIR,
+// /tmp itself stays read-only, and a new user+network namespace leaves the command with loopback only. CPU, memory
+// and process limits are rlimits in both cases.
+//
+// It's not a full jail: reads aren't restricted, and there's no seccomp filter, all syscalls are allowed. With the
+// network on, whatever the command can read it can send somewhere, so only commands without network skip the
+// confirmation.
+//
+// If the sandbox can't be set up the command doesn't run: a sandbox that quietly isn't there is worse than none.
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SandboxSettings {
+    #[serde(default)]
+    pub sandbox: bool,
+    #[serde(default = "default_true")]
+    pub sandbox_network: bool,
+    #[serde(default)]
+    pub sandbox_writable_paths: Vec<String>,
+    #[serde(default)]
+    pub sandbox_memory_mb: String,
+    #[serde(default)]
+    pub sandbox_cpu_seconds: String,
+    #[serde(default)]
+    pub sandbox_max_processes: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SandboxSettings {
+    fn default() -> Self {
+        SandboxSettings {
+            sandbox: false,
+            sandbox_network: true,
+            sandbox_writable_paths: vec![],
+            sandbox_memory_mb: String::new(),
+            sandbox_cpu_seconds: String::new(),
+            sandbox_max_processes: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SandboxLimits {
+    pub memory_bytes: Option<u64>,
+    pub cpu_seconds: Option<u64>,
+    pub max_processes: Option<u64>,
+}
+
+fn parse_limit(name: &str, value: &str) -> Result<Option<u64>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value.parse::<u64>().map(Some).map_err(|_| format!("{} should be a number, got {:?}", name, value))
+}
+
+impl SandboxSettings {
+    pub fn limits(&self) -> Result<SandboxLimits, String> {
+        Ok(SandboxLimits {
+            memory_bytes: parse_limit("sandbox_memory_mb", &self.sandbox_memory_mb)?.map(|mb| mb * 1024 * 1024),
+            cpu_seconds: parse_limit("sandbox_cpu_seconds", &self.sandbox_cpu_seconds)?,
+            max_processes: parse_limit("sandbox_max_processes", &self.sandbox_max_processes)?,
+        })
+    }
+
+    /// Sandbox on and network off, commands that don't match ask_user can run without a confirmation
+    pub fn isolated(&self) -> bool {
+        self.sandbox && !self.sandbox_network
+    }
+
+    /// Project dirs and sandbox_writable_paths, the ones that exist
+    pub fn writable_dirs(&self, project_dirs: &Vec<PathBuf>) -> Vec<PathBuf> {
+        let home = home::home_dir();
+        let mut dirs = project_dirs.clone();
+        for p in self.sandbox_writable_paths.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let path = match (p.strip_prefix("~/"), &home) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(p),
+            };
+            match path.canonicalize() {
+                Ok(path) => dirs.push(path),
+                Err(e) => tracing::warn!("sandbox: skipping writable path {:?}: {}", path, e),
+            }
+        }
+        dirs
+    }
+}
+
+/// `sh -c` or `powershell.exe -Command`, inside the sandbox if it's on
+pub fn shell_command(cmd_string: &str, settings: &SandboxSettings, project_dirs: &Vec<PathBuf>) -> Result<Command, String> {
+    let shell = if cfg!(target_os = "windows") { "powershell.exe" } else { "sh" };
+    let shell_arg = if cfg!(target_os = "windows") { "-Command" } else { "-c" };
+    if !settings.sandbox {
+        let mut cmd = Command::new(shell);
+        cmd.arg(shell_arg).arg(cmd_string);
+        return Ok(cmd);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::sandboxed_command(&[shell, shell_arg, cmd_string], settings, &settings.writable_dirs(project_dirs))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = project_dirs;
+        Err("the sandbox works only on Linux, set `sandbox: false` in the tool config to run commands without it".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
+    use tokio::process::Command;
+
+    use super::{SandboxLimits, SandboxSettings};
+
+    fn find_bwrap() -> Option<PathBuf> {
+        let paths = std::env::var_os("PATH")?;
+        std::env::split_paths(&paths).map(|dir| dir.join("bwrap")).find(|p| p.is_file())
+    }
+
+    pub fn bwrap_args(argv: &[&str], network: bool, writable_dirs: &[PathBuf]) -> Vec<String> {
+        let mut args: Vec<String> = ["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"]
+            .iter().map(|s| s.to_string()).collect();
+        // after --tmpfs /tmp, or a project under /tmp would be hidden by it
+        for dir in writable_dirs {
+            let dir = dir.to_string_lossy().to_string();
+            args.extend(["--bind".to_string(), dir.clone(), dir]);
+        }
+        args.extend(["--unshare-pid", "--unshare-ipc", "--unshare-uts", "--die-with-parent"].iter().map(|s| s.to_string()));
+        if !network {
+            args.push("--unshare-net".to_string());
+        }
+        args.push("--".to_string());
+        args.extend(argv.iter().map(|s| s.to_string()));
+        args
+    }
+
+    /// One per engine run, 0700, landlock commands get it as TMPDIR instead of the shared /tmp
+    fn private_tmp() -> Result<PathBuf, String> {
+        static PRIVATE_TMP: OnceLock<Result<tempfile::TempDir, String>> = OnceLock::new();
+        PRIVATE_TMP.get_or_init(|| {
+            tempfile::Builder::new().prefix("refact-sandbox-").tempdir()
+                .map_err(|e| format!("sandbox: cannot create a private temp dir: {}", e))
+        }).as_ref().map(|d| d.path().to_path_buf()).map_err(|e| e.clone())
+    }
+
+    pub fn sandboxed_command(argv: &[&str], settings: &SandboxSettings, writable_dirs: &[PathBuf]) -> Result<Command, String> {
+        let limits = settings.limits()?;
+        let cmd = match find_bwrap() {
+            Some(bwrap) => {
+                tracing::info!("sandbox: bwrap, network {}, writable {:?}", settings.sandbox_network, writable_dirs);
+                let mut cmd = Command::new(bwrap);
+                cmd.args(bwrap_args(argv, settings.sandbox_network, writable_dirs));
+                set_limits_before_exec(&mut cmd, limits, None, true);
+                cmd
+            }
+            None => {
+                tracing::info!("sandbox: landlock, network {}, writable {:?}", settings.sandbox_network, writable_dirs);
+                // /dev for /dev/null and friends, a temp dir because half of the tools out there need one
+                let tmp = private_tmp()?;
+                let mut dirs = writable_dirs.to_vec();
+                dirs.extend([tmp.clone(), PathBuf::from("/dev")]);
+                let dir_fds = dirs.iter().map(|d| open_path(d)).collect::<Result<Vec<_>, _>>()?;
+                let mut cmd = Command::new(argv[0]);
+                cmd.args(&argv[1..]);
+                cmd.env("TMPDIR", &tmp);
+                set_limits_before_exec(&mut cmd, limits, Some(dir_fds), settings.sandbox_network);
+                cmd
+            }
+        };
+        Ok(cmd)
+    }
+
+    fn open_path(path: &Path) -> Result<OwnedFd, String> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e| format!("sandbox: bad path {:?}: {}", path, e))?;
+        let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(format!("sandbox: cannot open {:?}: {}", path, std::io::Error::last_os_error()));
+        }
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    // landlock ABI, include/uapi/linux/landlock.h
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+    const LANDLOCK_ACCESS_FS_WRITE_V1: u64 =
+        (1 << 1) |   // WRITE_FILE
+        (1 << 4) |   // REMOVE_DIR
+        (1 << 5) |   // REMOVE_FILE
+        (1 << 6) |   // MAKE_CHAR
+        (1 << 7) |   // MAKE_DIR
+        (1 << 8) |   // MAKE_REG
+        (1 << 9) |   // MAKE_SOCK
+        (1 << 10) |  // MAKE_FIFO
+        (1 << 11) |  // MAKE_BLOCK
+        (1 << 12);   // MAKE_SYM
+    const LANDLOCK_ACCESS_FS_REFER: u64 = 1 << 13;     // ABI 2
+    const LANDLOCK_ACCESS_FS_TRUNCATE: u64 = 1 << 14;  // ABI 3
+
+    #[repr(C)]
+    struct LandlockRulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct LandlockPathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    fn check(ret: libc::c_long) -> std::io::Result<libc::c_long> {
+        if ret < 0 { Err(std::io::Error::last_os_error()) } else { Ok(ret) }
+    }
+
+    /// Runs in the forked child right before exec: only syscalls, no allocations
+    unsafe fn landlock_allow_writes_only_below(dir_fds: &[OwnedFd]) -> std::io::Result<()> {
+        let abi = check(libc::syscall(libc::SYS_landlock_create_ruleset, std::ptr::null::<LandlockRulesetAttr>(), 0usize, LANDLOCK_CREATE_RULESET_VERSION))?;
+        let mut access = LANDLOCK_ACCESS_FS_WRITE_V1;
+        if abi >= 2 {
+            access |= LANDLOCK_ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            access |= LANDLOCK_ACCESS_FS_TRUNCATE;
+        }
+        let ruleset_attr = LandlockRulesetAttr { handled_access_fs: access };
+        let ruleset_fd = check(libc::syscall(
+            libc::SYS_landlock_create_ruleset, &ruleset_attr as *const LandlockRulesetAttr, std::mem::size_of::<LandlockRulesetAttr>(), 0u32,
+        ))? as libc::c_int;
+        for fd in dir_fds {
+            let rule = LandlockPathBeneathAttr { allowed_access: access, parent_fd: fd.as_raw_fd() };
+            check(libc::syscall(libc::SYS_landlock_add_rule, ruleset_fd, LANDLOCK_RULE_PATH_BENEATH, &rule as *const LandlockPathBeneathAttr, 0u32))?;
+        }
+        check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) as libc::c_long)?;
+        check(libc::syscall(libc::SYS_landlock_restrict_self, ruleset_fd, 0u32))?;
+        libc::close(ruleset_fd);
+        Ok(())
+    }
+
+    #[cfg(target_env = "gnu")]
+    type RlimitResource = libc::__rlimit_resource_t;
+    #[cfg(not(target_env = "gnu"))]
+    type RlimitResource = libc::c_int;
+
+    unsafe fn set_rlimit(resource: RlimitResource, value: Option<u64>) -> std::io::Result<()> {
+        if let Some(value) = value {
+            let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+            check(libc::setrlimit(resource, &limit) as libc::c_long)?;
+        }
+        Ok(())
+    }
+
+    fn set_limits_before_exec(cmd: &mut Command, limits: SandboxLimits, landlock_dir_fds: Option<Vec<OwnedFd>>, network: bool) {
+        // the fds are O_CLOEXEC, the program itself doesn't get them, and they close with the Command
+        unsafe {
+            cmd.pre_exec(move || {
+                set_rlimit(libc::RLIMIT_AS, limits.memory_bytes)?;
+                set_rlimit(libc::RLIMIT_CPU, limits.cpu_seconds)?;
+                set_rlimit(libc::RLIMIT_NPROC, limits.max_processes)?;
+                if let Some(dir_fds) = &landlock_dir_fds {
+                    if !network {
+                        check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) as libc::c_long)?;
+                    }
+                    landlock_allow_writes_only_below(dir_fds)?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_settings() {
+        let settings: SandboxSettings = serde_json::from_value(serde_json::json!({
+            "sandbox": true,
+            "sandbox_network": false,
+            "sandbox_memory_mb": "512",
+            "sandbox_max_processes": "64",
+        })).unwrap();
+        assert_eq!(settings.limits().unwrap(), SandboxLimits { memory_bytes: Some(512 * 1024 * 1024), cpu_seconds: None, max_processes: Some(64) });
+        assert!(SandboxSettings { sandbox_cpu_seconds: "lots".to_string(), ..Default::default() }.limits().is_err());
+        assert!(SandboxSettings::default().sandbox_network);
+        assert!(settings.isolated());
+        assert!(!SandboxSettings { sandbox: true, ..Default::default() }.isolated());
+
+        #[cfg(target_os = "linux")]
+        {
+            let args = linux::bwrap_args(&["sh", "-c", "make test"], false, &[PathBuf::from("/tmp/project")]);
+            let joined = args.join(" ");
+            assert!(joined.starts_with("--ro-bind / / --dev /dev --proc /proc --tmpfs /tmp --bind /tmp/project /tmp/project"));
+            assert!(joined.ends_with("--unshare-net -- sh -c make test"));
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::RwLock as ARwLock;
+use tokio_rusqlite::Connection;
+use tracing::info;
+
+use crate::global_context::GlobalContext;
+
+// Local alternative to cloud threads (cloud/threads_req.rs, cloud/messages_req.rs), stored in the cache dir.
+// Field names follow the cloud Thread/ThreadMessage model, messages keep everything the GUI sends
+// (checkpoints, thinking blocks, usage) so a chat can be resumed exactly as it was.
+
+const MESSAGE_FIELDS: [&str; 5] = ["role", "content", "tool_calls", "tool_call_id", "usage"];
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LocalThread {
+    pub ft_id: String,
+    #[serde(default)]
+    pub ft_title: String,
+    #[serde(default)]
+    pub ft_model: String,
+    #[serde(default)]
+    pub ft_chat_mode: String,
+    #[serde(default)]
+    pub ft_forked_from_ft_id: Option<String>,
+    #[serde(default)]
+    pub ft_created_ts: f64,
+    #[serde(default)]
+    pub ft_updated_ts: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LocalThreadMessage {
+    pub ftm_belongs_to_ft_id: String,
+    pub ftm_num: i32,
+    pub ftm_role: String,
+    pub ftm_content: Option<Value>,
+    pub ftm_tool_calls: Option<Value>,
+    pub ftm_call_id: String,
+    pub ftm_usage: Option<Value>,
+    pub ftm_extra: Option<Value>,  // all other message fields: checkpoints, thinking_blocks, tool_failed, ...
+    pub ftm_created_ts: f64,
+}
+
+#[derive(Debug)]
+pub enum ThreadFork {
+    Forked(LocalThread),
+    SourceNotFound,
+    IdTaken,
+}
+
+#[derive(Clone)]
+pub struct LocalThreadsDb {
+    conn: Connection,
+}
+
+fn now_ts() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+fn json_to_sql(v: &Option<Value>) -> Option<String> {
+    v.as_ref().filter(|v| !v.is_null()).map(|v| v.to_string())
+}
+
+fn json_from_sql(s: Option<String>) -> Option<Value> {
+    s.and_then(|s| serde_json::from_str(&s).ok())
+}
+
+pub fn message_json_to_local(ft_id: &str, num: i32, message: &Value) -> Result<LocalThreadMessage, String> {
+    let obj = message.as_object().ok_or("message is not a dict".to_string())?;
+    let role = obj.get("role").and_then(|x| x.as_str()).ok_or("message has no role".to_string())?;
+    let extra: serde_json::Map<String, Value> = obj.iter()
+        .filter(|(k, _)| !MESSAGE_FIELDS.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Ok(LocalThreadMessage {
+        ftm_belongs_to_ft_id: ft_id.to_string(),
+        ftm_num: num,
+        ftm_role: role.to_string(),
+        ftm_content: obj.get("content").cloned(),
+        ftm_tool_calls: obj.get("tool_calls").cloned().filter(|x| !x.is_null()),
+        ftm_call_id: obj.get("tool_call_id").and_then(|x| x.as_str()).unwrap_or_default().to_string(),
+        ftm_usage: obj.get("usage").cloned().filter(|x| !x.is_null()),
+        ftm_extra: if extra.is_empty() { None } else { Some(Value::Object(extra)) },
+        ftm_created_ts: now_ts(),
+    })
+}
+
+pub fn local_to_message_json(m: &LocalThreadMessage) -> Value {
+    let mut obj = match &m.ftm_extra {
+        Some(Value::Object(extra)) => extra.clone(),
+        _ => serde_json::Map::new(),
+    };
+    obj.insert("role".to_string(), json!(m.ftm_role));
+    obj.insert("content".to_string(), m.ftm_content.clone().unwrap_or(json!("")));
+    if let Some(tool_calls) = &m.ftm_tool_calls {
+        obj.insert("tool_calls".to_string(), tool_calls.clone());
+    }
+    if !m.ftm_call_id.is_empty() {
+        obj.insert("tool_call_id".to_string(), json!(m.ftm_call_id));
+    }
+    if let Some(usage) = &m.ftm_usage {
+        obj.insert("usage".to_string(), usage.clone());
+    }
+    Value::Object(obj)
+}
+
+fn thread_from_row(row: &rusqlite::Row) -> rusqlite::Result<LocalThread> {
+    Ok(LocalThread {
+        ft_id: row.get(0)?,
+        ft_title: row.get(1)?,
+        ft_model: row.get(2)?,
+        ft_chat_mode: row.get(3)?,
+        ft_forked_from_ft_id: row.get(4)?,
+        ft_created_ts: row.get(5)?,
+        ft_updated_ts: row.get(6)?,
+    })
+}
+
+fn thread_get_sync(conn: &rusqlite::Connection, ft_id: &str) -> rusqlite::Result<Option<LocalThread>> {
+    conn.query_row(
+        "SELECT ft_id, ft_title, ft_model, ft_chat_mode, ft_forked_from_ft_id, ft_created_ts, ft_updated_ts \
+         FROM threads WHERE ft_id = ?1",
+        params![ft_id],
+        thread_from_row,
+    ).optional()
+}
+
+fn thread_upsert_sync(conn: &rusqlite::Connection, t: &LocalThread) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO threads (ft_id, ft_title, ft_model, ft_chat_mode, ft_forked_from_ft_id, ft_created_ts, ft_updated_ts) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
+         ON CONFLICT(ft_id) DO UPDATE SET ft_title = ?2, ft_model = ?3, ft_chat_mode = ?4, ft_updated_ts = ?7",
+        params![t.ft_id, t.ft_title, t.ft_model, t.ft_chat_mode, t.ft_forked_from_ft_id, t.ft_created_ts, t.ft_updated_ts],
+    )?;
+    Ok(())
+}
+
+fn messages_get_sync(conn: &rusqlite::Connection, ft_id: &str, up_to_num: Option<i32>) -> rusqlite::Result<Vec<LocalThreadMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT ftm_belongs_to_ft_id, ftm_num, ftm_role, ftm_content, ftm_tool_calls, ftm_call_id, ftm_usage, ftm_extra, ftm_created_ts \
+         FROM thread_messages WHERE ftm_belongs_to_ft_id = ?1 AND ftm_num <= ?2 ORDER BY ftm_num"
+    )?;
+    let rows = stmt.query_map(params![ft_id, up_to_num.unwrap_or(i32::MAX)], |row| {
+        Ok(LocalThreadMessage {
+            ftm_belongs_to_ft_id: row.get(0)?,
+            ftm_num: row.get(1)?,
+            ftm_role: row.get(2)?,
+            ftm_content: json_from_sql(row.get(3)?),
+            ftm_tool_calls: json_from_sql(row.get(4)?),
+            ftm_call_id: row.get(5)?,
+            ftm_usage: json_from_sql(row.get(6)?),
+            ftm_extra: json_from_sql(row.get(7)?),
+            ftm_created_ts: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn messages_insert_sync(tx: &rusqlite::Transaction, messages: &Vec<LocalThreadMessage>) -> rusqlite::Result<()> {
+    for m in messages {
+        tx.execute(
+            "INSERT INTO thread_messages (ftm_belongs_to_ft_id, ftm_num, ftm_role, ftm_content, ftm_tool_calls, ftm_call_id, ftm_usage, ftm_extra, ftm_created_ts) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                m.ftm_belongs_to_ft_id, m.ftm_num, m.ftm_role, json_to_sql(&m.ftm_content), json_to_sql(&m.ftm_tool_calls),
+                m.ftm_call_id, json_to_sql(&m.ftm_usage), json_to_sql(&m.ftm_extra), m.ftm_created_ts,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+impl LocalThreadsDb {
+    pub async fn open(db_path: &PathBuf) -> Result<LocalThreadsDb, String> {
+        let conn = Connection::open(db_path).await.map_err(|e| format!("cannot open {:?}: {}", db_path, e))?;
+        Self::init(conn).await
+    }
+
+    #[cfg(test)]
+    pub async fn open_in_memory() -> Result<LocalThreadsDb, String> {
+        Self::init(Connection::open_in_memory().await.map_err(|e| e.to_string())?).await
+    }
+
+    async fn init(conn: Connection) -> Result<LocalThreadsDb, String> {
+        conn.call(|conn| {
+            let _: String = conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS threads (
+                    ft_id TEXT PRIMARY KEY,
+                    ft_title TEXT NOT NULL,
+                    ft_model TEXT NOT NULL,
+                    ft_chat_mode TEXT NOT NULL,
+                    ft_forked_from_ft_id TEXT,
+                    ft_created_ts REAL NOT NULL,
+                    ft_updated_ts REAL NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS thread_messages (
+                    ftm_belongs_to_ft_id TEXT NOT NULL,
+                    ftm_num INTEGER NOT NULL,
+                    ftm_role TEXT NOT NULL,
+                    ftm_content TEXT,
+                    ftm_tool_calls TEXT,
+                    ftm_call_id TEXT NOT NULL,
+                    ftm_usage TEXT,
+                    ftm_extra TEXT,
+                    ftm_created_ts REAL NOT NULL,
+                    PRIMARY KEY (ftm_belongs_to_ft_id, ftm_num)
+                );
+                CREATE INDEX IF NOT EXISTS idx_threads_updated ON threads (ft_updated_ts);"
+            )?;
+            Ok(())
+        }).await.map_err(|e| e.to_string())?;
+        Ok(LocalThreadsDb { conn })
+    }
+
+    pub async fn threads_list(&self, search: &str, limit: usize) -> Result<Vec<LocalThread>, String> {
+        let pattern = format!("%{}%", search.replace('%', "\\%").replace('_', "\\_"));
+        self.conn.call(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT ft_id, ft_title, ft_model, ft_chat_mode, ft_forked_from_ft_id, ft_created_ts, ft_updated_ts \
+                 FROM threads t WHERE ?1 = '%%' OR ft_title LIKE ?1 ESCAPE '\\' OR EXISTS ( \
+                    SELECT 1 FROM thread_messages m WHERE m.ftm_belongs_to_ft_id = t.ft_id AND m.ftm_content LIKE ?1 ESCAPE '\\' \
+                 ) ORDER BY ft_updated_ts DESC LIMIT ?2"
+            )?;
+            let rows = stmt.query_map(params![pattern, limit as i64], thread_from_row)?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        }).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn thread_get(&self, ft_id: &str) -> Result<Option<(LocalThread, Vec<LocalThreadMessage>)>, String> {
+        let ft_id = ft_id.to_string();
+        self.conn.call(move |conn| {
+            match thread_get_sync(conn, &ft_id)? {
+                Some(thread) => Ok(Some((thread, messages_get_sync(conn, &ft_id, None)?))),
+                None => Ok(None),
+            }
+        }).await.map_err(|e| e.to_string())
+    }
+
+    /// Creates the thread if it doesn't exist, updates non-empty title/model/mode, appends messages at the end.
+    pub async fn thread_append(&self, thread: LocalThread, messages: Vec<Value>) -> Result<(LocalThread, Vec<LocalThreadMessage>), String> {
+        self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            let now = now_ts();
+            let mut t = thread_get_sync(&tx, &thread.ft_id)?.unwrap_or(LocalThread {
+                ft_id: thread.ft_id.clone(),
+                ft_created_ts: now,
+                ..Default::default()
+            });
+            if !thread.ft_title.is_empty() { t.ft_title = thread.ft_title.clone(); }
+            if !thread.ft_model.is_empty() { t.ft_model = thread.ft_model.clone(); }
+            if !thread.ft_chat_mode.is_empty() { t.ft_chat_mode = thread.ft_chat_mode.clone(); }
+            t.ft_updated_ts = now;
+            thread_upsert_sync(&tx, &t)?;
+            let next_num: i32 = tx.query_row(
+                "SELECT COALESCE(MAX(ftm_num) + 1, 0) FROM thread_messages WHERE ftm_belongs_to_ft_id = ?1",
+                params![t.ft_id],
+                |row| row.get(0),
+            )?;
+            let mut new_messages = vec![];
+            for (i, m) in messages.iter().enumerate() {
+                let local = message_json_to_local(&t.ft_id, next_num + i as i32, m)
+                    .map_err(|e| tokio_rusqlite::Error::Other(e.into()))?;
+                new_messages.push(local);
+            }
+            messages_insert_sync(&tx, &new_messages)?;
+            tx.commit()?;
+            Ok((t, new_messages))
+        }).await.map_err(|e| e.to_string())
+    }
+
+    /// Copies a thread (or its messages up to and including `up_to_num`) into a new thread.
+    pub async fn thread_fork(&self, ft_id: &str, up_to_num: Option<i32>, new_ft_id: &str, new_title: &str) -> Result<ThreadFork, String> {
+        let (ft_id, new_ft_id, new_title) = (ft_id.to_string(), new_ft_id.to_string(), new_title.to_string());
+        self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            let source = match thread_get_sync(&tx, &ft_id)? {
+                Some(source) => source,
+                None => return Ok(ThreadFork::SourceNotFound),
+            };
+            if thread_get_sync(&tx, &new_ft_id)?.is_some() {
+                return Ok(ThreadFork::IdTaken);
+            }
+            let now = now_ts();
+            let forked = LocalThread {
+                ft_id: new_ft_id.clone(),
+                ft_title: if new_title.is_empty() { format!("{} (fork)", source.ft_title) } else { new_title },
+                ft_model: source.ft_model.clone(),
+                ft_chat_mode: source.ft_chat_mode.clone(),
+                ft_forked_from_ft_id: Some(source.ft_id.clone()),
+                ft_created_ts: now,
+                ft_updated_ts: now,
+            };
+            thread_upsert_sync(&tx, &forked)?;
+            let messages = messages_get_sync(&tx, &ft_id, up_to_num)?.into_iter().map(|mut m| {
+                m.ftm_belongs_to_ft_id = new_ft_id.clone();
+                m
+            }).collect();
+            messages_insert_sync(&tx, &messages)?;
+            tx.commit()?;
+            Ok(ThreadFork::Forked(forked))
+        }).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn thread_delete(&self, ft_id: &str) -> Result<bool, String> {
+        let ft_id = ft_id.to_string();
+        self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM thread_messages WHERE ftm_belongs_to_ft_id = ?1", params![ft_id])?;
+            let deleted = tx.execute("DELETE FROM threads WHERE ft_id = ?1", params![ft_id])?;
+            tx.commit()?;
+            Ok(deleted > 0)
+        }).await.map_err(|e| e.to_string())
+    }
+}
+
+pub async fn local_threads_db(gcx: Arc<ARwLock<GlobalContext>>) -> Result<LocalThreadsDb, String> {
+    let (db_arc, cache_dir) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.local_threads_db.clone(), gcx_locked.cache_dir.clone())
+    };
+    let mut db_locked = db_arc.lock().await;
+    if let Some(db) = db_locked.as_ref() {
+        return Ok(db.clone());
+    }
+    let db_path = cache_dir.join("threads.sqlite");
+    let db = LocalThreadsDb::open(&db_path).await?;
+    info!("local threads database opened at {:?}", db_path);
+    *db_locked = Some(db.clone());
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip_keeps_extra_fields() {
+        let msg = json!({
+            "role": "assistant",
+            "content": "hello",
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "cat", "arguments": "{}"}}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12},
+            "checkpoints": [{"workspace_folder": "/tmp", "commit_hash": "abc"}],
+            "thinking_blocks": [{"thinking": "hmm"}],
+        });
+        let local = message_json_to_local("t1", 3, &msg).unwrap();
+        assert_eq!(local.ftm_num, 3);
+        assert_eq!(local.ftm_role, "assistant");
+        assert!(local.ftm_extra.as_ref().unwrap().get("checkpoints").is_some());
+        assert_eq!(local_to_message_json(&local), msg);
+        assert!(message_json_to_local("t1", 0, &json!({"content": "no role"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_append_fork_delete() {
+        let db = LocalThreadsDb::open_in_memory().await.unwrap();
+        let thread = LocalThread { ft_id: "t1".to_string(), ft_title: "Frog".to_string(), ..Default::default() };
+        db.thread_append(thread.clone(), vec![json!({"role": "user", "content": "where is the frog?"})]).await.unwrap();
+        let (t, new_messages) = db.thread_append(
+            LocalThread { ft_id: "t1".to_string(), ..Default::default() },
+            vec![json!({"role": "assistant", "content": "in the pond"}), json!({"role": "user", "content": "thanks"})],
+        ).await.unwrap();
+        assert_eq!(t.ft_title, "Frog");
+        assert_eq!(new_messages.iter().map(|m| m.ftm_num).collect::<Vec<_>>(), vec![1, 2]);
+
+        assert_eq!(db.threads_list("", 10).await.unwrap().len(), 1);
+        assert_eq!(db.threads_list("pond", 10).await.unwrap().len(), 1);
+        assert_eq!(db.threads_list("toad", 10).await.unwrap().len(), 0);
+
+        let forked = match db.thread_fork("t1", Some(1), "t2", "").await.unwrap() {
+            ThreadFork::Forked(forked) => forked,
+            _ => panic!("fork failed"),
+        };
+        assert_eq!(forked.ft_forked_from_ft_id, Some("t1".to_string()));
+        let (_, forked_messages) = db.thread_get("t2").await.unwrap().unwrap();
+        assert_eq!(forked_messages.len(), 2);
+        assert_eq!(forked_messages[1].ftm_content, Some(json!("in the pond")));
+        assert!(matches!(db.thread_fork("nope", None, "t3", "").await.unwrap(), ThreadFork::SourceNotFound));
+        assert!(matches!(db.thread_fork("t1", None, "t2", "").await.unwrap(), ThreadFork::IdTaken));
+        assert_eq!(db.thread_get("t2").await.unwrap().unwrap().1.len(), 2);
+
+        assert!(db.thread_delete("t1").await.unwrap());
+        assert!(!db.thread_delete("t1").await.unwrap());
+        assert!(db.thread_get("t1").await.unwrap().is_none());
+        assert_eq!(db.thread_get("t2").await.unwrap().unwrap().1.len(), 2);
+    }
+}
@@ -35,6 +35,8 @@ mod json_utils;
 mod file_filter;
 mod files_in_workspace;
 mod files_in_jsonl;
+mod documents_to_text;
+mod notebooks;
 mod files_blocklist;
 mod fuzzy_search;
 mod files_correction;
@@ -62,10 +64,13 @@ mod http;
 
 mod integrations;
 mod privacy;
+mod project_rules;
+mod audit_log;
 mod git;
 mod cloud;
 mod agentic;
 mod memories;
+mod local_threads;
 // TODO: do we need this?
 mod files_correction_cache;
 pub mod constants;
@@ -179,6 +184,14 @@ async fn main() {
     let mut background_tasks = start_background_tasks(gcx.clone(), &config_dir).await;
     // vector db will spontaneously start if the downloaded caps and command line parameters are right
 
+    if !cmdline.run_task.is_empty() {
+        let exit_code = agentic::run_task::run_task_from_cmdline(gcx.clone()).await;
+        background_tasks.abort().await;
+        git::checkpoints::abort_init_shadow_repos(gcx.clone()).await;
+        integrations::sessions::stop_sessions(gcx.clone()).await;
+        std::process::exit(exit_code);
+    }
+
     let should_start_http = cmdline.http_port != 0;
     let should_start_lsp = (cmdline.lsp_port == 0 && cmdline.lsp_stdin_stdout == 1) ||
         (cmdline.lsp_port != 0 && cmdline.lsp_stdin_stdout == 0);
@@ -0,0 +1,258 @@
+use std::path::Path;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::integrations::process_io_utils::AnsiStrippable;
+
+// Jupyter notebooks are JSON, but everything that reads files (cat, search, vecdb, AST) gets a Python view of them:
+// every cell starts with a `# %% [cell N] code` marker line, so any line of the view maps back to a cell, markdown
+// cells are commented out, and trimmed outputs follow code cells as comments. Edits go through notebook_edit,
+// it changes the JSON one cell at a time, so editing code reads notebooks straight from disk, not through the view.
+
+const NOTEBOOK_OUTPUT_MAX_CHARS: usize = 2000;
+const NOTEBOOK_OUTPUT_MAX_LINES: usize = 30;
+
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension().map(|e| e.eq_ignore_ascii_case("ipynb")).unwrap_or(false)
+}
+
+pub fn notebook_parse(text: &str) -> Result<Value, String> {
+    let nb: Value = serde_json::from_str(text).map_err(|e| format!("not a valid notebook, JSON error: {}", e))?;
+    if !nb.get("cells").map(|c| c.is_array()).unwrap_or(false) {
+        return Err("not a valid notebook, there is no `cells` list".to_string());
+    }
+    Ok(nb)
+}
+
+/// nbformat allows both a string and a list of lines
+fn multiline_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+fn source_to_json(source: &str) -> Value {
+    json!(source.split_inclusive('\n').collect::<Vec<_>>())
+}
+
+fn output_text(output: &Value) -> String {
+    match output["output_type"].as_str().unwrap_or_default() {
+        "stream" => multiline_text(&output["text"]),
+        "execute_result" | "display_data" => {
+            let data = output["data"].as_object().cloned().unwrap_or_default();
+            let mut text = data.keys()
+                .filter(|mime| !mime.starts_with("text/"))
+                .map(|mime| format!("[{} output]\n", mime))
+                .collect::<String>();
+            if let Some(plain) = data.get("text/plain") {
+                text.push_str(&multiline_text(plain));
+                text.push('\n');
+            }
+            text
+        },
+        "error" => {
+            // the traceback ends with "ename: evalue" anyway
+            let traceback = output["traceback"].as_array().cloned().unwrap_or_default();
+            if traceback.is_empty() {
+                return format!("{}: {}\n", output["ename"].as_str().unwrap_or_default(), output["evalue"].as_str().unwrap_or_default());
+            }
+            traceback.iter()
+                .map(|line| format!("{}\n", line.as_str().unwrap_or_default().as_bytes().to_string_lossy_and_strip_ansi()))
+                .collect()
+        },
+        _ => String::new(),
+    }
+}
+
+fn trim_output(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut result = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i >= NOTEBOOK_OUTPUT_MAX_LINES || result.len() + line.len() > NOTEBOOK_OUTPUT_MAX_CHARS {
+            result.push_str(&format!("... {} more lines\n", lines.len() - i));
+            break;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
+
+pub fn notebook_to_view(text: &str) -> Result<String, String> {
+    let nb = notebook_parse(text)?;
+    let mut view = String::new();
+    for (idx, cell) in nb["cells"].as_array().unwrap().iter().enumerate() {
+        let cell_type = cell["cell_type"].as_str().unwrap_or("code");
+        view.push_str(&format!("# %% [cell {}] {}\n", idx, cell_type));
+        for line in multiline_text(&cell["source"]).lines() {
+            if cell_type == "code" {
+                view.push_str(line);
+            } else if line.is_empty() {
+                view.push('#');
+            } else {
+                view.push_str("# ");
+                view.push_str(line);
+            }
+            view.push('\n');
+        }
+        let outputs = cell["outputs"].as_array().cloned().unwrap_or_default().iter().map(output_text).collect::<String>();
+        if !outputs.trim().is_empty() {
+            view.push_str("# Out:\n");
+            for line in trim_output(&outputs).lines() {
+                view.push_str(&format!("# {}\n", line));
+            }
+        }
+    }
+    Ok(view)
+}
+
+/// What readers of the file see, the raw text if it's not a notebook or it's broken
+pub fn notebook_view_if_notebook(path: &Path, text: String) -> String {
+    if !is_notebook(path) {
+        return text;
+    }
+    match notebook_to_view(&text) {
+        Ok(view) => view,
+        Err(e) => {
+            warn!("{}: {}, will use the raw text", path.display(), e);
+            text
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotebookEdit {
+    ReplaceCell { cell: usize, source: String },
+    InsertCell { cell: usize, cell_type: String, source: String },
+    DeleteCell { cell: usize },
+}
+
+pub fn notebook_serialize(nb: &Value) -> Result<String, String> {
+    // the same layout Jupyter writes: one space indent, non-ascii as is, newline at the end
+    let mut buf = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, serde_json::ser::PrettyFormatter::with_indent(b" "));
+    nb.serialize(&mut serializer).map_err(|e| format!("cannot serialize the notebook: {}", e))?;
+    let mut text = String::from_utf8(buf).map_err(|e| format!("cannot serialize the notebook: {}", e))?;
+    text.push('\n');
+    Ok(text)
+}
+
+pub fn notebook_apply_edit(text: &str, edit: &NotebookEdit) -> Result<String, String> {
+    let mut nb = notebook_parse(text)?;
+    let needs_cell_ids = nb["nbformat"].as_u64().unwrap_or(4) > 4 || nb["nbformat_minor"].as_u64().unwrap_or(0) >= 5;
+    let cells = nb["cells"].as_array_mut().unwrap();
+    let cells_n = cells.len();
+    match edit {
+        NotebookEdit::ReplaceCell { cell, source } => {
+            let c = cells.get_mut(*cell).ok_or(format!("there is no cell {}, the notebook has {} cells", cell, cells_n))?;
+            c["source"] = source_to_json(source);
+            if c["cell_type"] == "code" {
+                // outputs of the old code would be misleading
+                c["outputs"] = json!([]);
+                c["execution_count"] = Value::Null;
+            }
+        },
+        NotebookEdit::InsertCell { cell, cell_type, source } => {
+            if *cell > cells_n {
+                return Err(format!("cannot insert at {}, the notebook has {} cells, use {} to append", cell, cells_n, cells_n));
+            }
+            let mut new_cell = match cell_type.as_str() {
+                "code" => json!({"cell_type": "code", "execution_count": null, "metadata": {}, "outputs": [], "source": source_to_json(source)}),
+                "markdown" | "raw" => json!({"cell_type": cell_type, "metadata": {}, "source": source_to_json(source)}),
+                _ => return Err(format!("unknown cell_type {:?}, should be one of: code, markdown, raw", cell_type)),
+            };
+            if needs_cell_ids {
+                new_cell["id"] = json!(uuid::Uuid::new_v4().simple().to_string()[..8].to_string());
+            }
+            cells.insert(*cell, new_cell);
+        },
+        NotebookEdit::DeleteCell { cell } => {
+            if *cell >= cells_n {
+                return Err(format!("there is no cell {}, the notebook has {} cells", cell, cells_n));
+            }
+            cells.remove(*cell);
+        },
+    }
+    notebook_serialize(&nb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "\n", "Some text"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "import math\nx = math.sqrt(2)\nx",
+   "outputs": [{"output_type": "execute_result", "execution_count": 1, "metadata": {}, "data": {"text/plain": ["1.4142135623730951"]}}]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": ["def f():\n", "    return 1 / 0\n", "f()"],
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["calling f\n"]},
+    {"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero", "traceback": ["\u001b[0;31mZeroDivisionError\u001b[0m: division by zero"]},
+    {"output_type": "display_data", "metadata": {}, "data": {"image/png": "iVBOR...", "text/plain": ["<Figure>"]}}
+   ]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn test_notebook_to_view() {
+        let view = notebook_to_view(NOTEBOOK).unwrap();
+        assert_eq!(view, "\
+# %% [cell 0] markdown
+# # Title
+#
+# Some text
+# %% [cell 1] code
+import math
+x = math.sqrt(2)
+x
+# Out:
+# 1.4142135623730951
+# %% [cell 2] code
+def f():
+    return 1 / 0
+f()
+# Out:
+# calling f
+# ZeroDivisionError: division by zero
+# [image/png output]
+# <Figure>
+");
+        assert_eq!(notebook_view_if_notebook(Path::new("a.py"), "x = 1".to_string()), "x = 1");
+        assert_eq!(notebook_view_if_notebook(Path::new("broken.ipynb"), "{".to_string()), "{");
+        let long_output = (0..100).map(|i| format!("line {}\n", i)).collect::<String>();
+        assert!(trim_output(&long_output).ends_with("line 29\n... 70 more lines\n"));
+    }
+
+    #[test]
+    fn test_notebook_edits() {
+        let replaced = notebook_apply_edit(NOTEBOOK, &NotebookEdit::ReplaceCell { cell: 1, source: "y = 2\ny".to_string() }).unwrap();
+        let nb = notebook_parse(&replaced).unwrap();
+        assert_eq!(nb["cells"][1]["source"], json!(["y = 2\n", "y"]));
+        assert_eq!(nb["cells"][1]["outputs"], json!([]));
+        assert_eq!(nb["cells"][1]["execution_count"], Value::Null);
+        assert!(replaced.starts_with("{\n \"cells\": [\n  {\n   \"cell_type\": \"markdown\""), "{}", replaced);
+        assert!(replaced.ends_with("}\n"));
+
+        let inserted = notebook_apply_edit(&replaced, &NotebookEdit::InsertCell { cell: 3, cell_type: "markdown".to_string(), source: "The end".to_string() }).unwrap();
+        let nb = notebook_parse(&inserted).unwrap();
+        assert_eq!(nb["cells"].as_array().unwrap().len(), 4);
+        assert_eq!(nb["cells"][3]["cell_type"], json!("markdown"));
+        assert_eq!(nb["cells"][3]["id"].as_str().unwrap().len(), 8);
+        assert!(notebook_to_view(&inserted).unwrap().ends_with("# %% [cell 3] markdown\n# The end\n"));
+
+        let deleted = notebook_apply_edit(&inserted, &NotebookEdit::DeleteCell { cell: 0 }).unwrap();
+        assert!(notebook_to_view(&deleted).unwrap().starts_with("# %% [cell 0] code\ny = 2\n"));
+
+        assert!(notebook_apply_edit(NOTEBOOK, &NotebookEdit::DeleteCell { cell: 3 }).is_err());
+        assert!(notebook_apply_edit(NOTEBOOK, &NotebookEdit::InsertCell { cell: 4, cell_type: "code".to_string(), source: "".to_string() }).is_err());
+        assert!(notebook_apply_edit(NOTEBOOK, &NotebookEdit::InsertCell { cell: 0, cell_type: "sql".to_string(), source: "".to_string() }).is_err());
+        assert!(notebook_apply_edit("{\"cells\": 1}", &NotebookEdit::DeleteCell { cell: 0 }).is_err());
+    }
+}
@@ -2,3 +2,4 @@ pub mod pp_utils;
 pub mod pp_context_files;
 pub mod pp_plain_text;
 pub mod pp_command_output;
+pub mod pp_redact;
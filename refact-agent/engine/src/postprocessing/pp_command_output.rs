@@ -2,6 +2,8 @@ use serde::Serialize;
 use serde::Deserialize;
 use regex::Regex;
 
+use crate::postprocessing::pp_redact::{log_redactions, RedactionCounts, Redactor};
+
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct CmdlineOutputFilter {
@@ -58,7 +60,11 @@ fn default_remove_from_output() -> String {
     "".to_string()
 }
 
-pub fn output_mini_postprocessing(filter: &CmdlineOutputFilter, output: &str) -> String {
+/// The redactor comes from load_privacy_if_needed(), so secrets.yaml values and user patterns apply here too
+pub fn output_mini_postprocessing(filter: &CmdlineOutputFilter, output: &str, redactor: &Redactor) -> String {
+    let mut redaction_counts = RedactionCounts::new();
+    let output = redactor.redact(output, &mut redaction_counts);
+    log_redactions(&redaction_counts, "command output");
     let lines: Vec<&str> = output.lines().collect();
     let mut ratings: Vec<f64> = vec![0.0; lines.len()];
     let mut approve: Vec<bool> = vec![false; lines.len()];
@@ -136,9 +142,11 @@ pub fn output_mini_postprocessing(filter: &CmdlineOutputFilter, output: &str) ->
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::postprocessing::pp_redact::RedactionSettings;
 
     #[test]
     fn test_cmdline_output_filter() {
+        let redactor = Redactor::default();
         let output_to_filter = r#"line1
 line2
 line3
@@ -154,7 +162,7 @@ line6
             grep: "".to_string(),
             grep_context_lines: 1,
             remove_from_output: "".to_string(),
-        }, output_to_filter);
+        }, output_to_filter, &redactor);
         assert_eq!(result, "line1\nline2\nline3\n...3 lines skipped...\n");
 
         let result = output_mini_postprocessing(&CmdlineOutputFilter {
@@ -164,7 +172,7 @@ line6
             grep: "".to_string(),
             grep_context_lines: 1,
             remove_from_output: "".to_string(),
-        }, output_to_filter);
+        }, output_to_filter, &redactor);
         assert_eq!(result, "...3 lines skipped...\nline4\nline5\nline6\n");
 
         let result = output_mini_postprocessing(&CmdlineOutputFilter {
@@ -174,7 +182,7 @@ line6
             grep: "line4".to_string(),
             grep_context_lines: 1,
             remove_from_output: "".to_string(),
-        }, output_to_filter);
+        }, output_to_filter, &redactor);
         assert_eq!(result, "...2 lines skipped...\nline3\nline4\nline5\n...1 lines skipped...\n");
 
         let result = output_mini_postprocessing(&CmdlineOutputFilter {
@@ -182,8 +190,16 @@ line6
             limit_chars: 8000,
             valuable_top_or_bottom: "bottom".to_string(),
             ..Default::default()
-        }, output_to_filter);
+        }, output_to_filter, &redactor);
         assert_eq!(result, "line1\nline2\nline3\nline4\nline5\nline6\n");
     }
-}
 
+    #[test]
+    fn test_cmdline_output_redaction() {
+        let settings = RedactionSettings { enabled: true, patterns: vec![r"(?i)db_password=(\S+)".to_string()] };
+        let redactor = Redactor::new(&settings, vec![("API_TOKEN".to_string(), "s3cr3t-t0ken".to_string())]);
+        let result = output_mini_postprocessing(&CmdlineOutputFilter::default(), "DB_PASSWORD=hunter2\ncurl -H 'X-Token: s3cr3t-t0ken'\n", &redactor);
+        assert!(result.starts_with("DB_PASSWORD=[REDACTED_CUSTOM_"));
+        assert!(result.contains("X-Token: [REDACTED_SECRET_$API_TOKEN]"));
+    }
+}
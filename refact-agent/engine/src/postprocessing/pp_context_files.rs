@@ -10,6 +10,8 @@ use crate::call_validation::{ContextFile, PostprocessSettings};
 use crate::ast::ast_structs::AstDefinition;
 use crate::global_context::GlobalContext;
 use crate::nicer_logs::{first_n_chars, last_n_chars};
+use crate::privacy::load_privacy_if_needed;
+use crate::postprocessing::pp_redact::{log_redactions, RedactionCounts};
 use crate::postprocessing::pp_utils::{color_with_gradient_type, colorize_comments_up, colorize_if_more_useful, colorize_minus_one, colorize_parentof, downgrade_lines_if_subsymbol, pp_ast_markup_files};
 use crate::tokens::count_text_tokens_with_fallback;
 
@@ -363,11 +365,19 @@ pub async fn postprocess_context_files(
         settings,
     ).await;
 
-    pp_limit_and_merge(
+    let mut context_files = pp_limit_and_merge(
         &mut lines_in_files,
         tokenizer,
         tokens_limit,
         single_file_mode,
         settings
-    ).await
+    ).await;
+
+    let redactor = load_privacy_if_needed(gcx.clone()).await.redactor.clone();
+    let mut redaction_counts = RedactionCounts::new();
+    for context_file in context_files.iter_mut() {
+        context_file.file_content = redactor.redact(&context_file.file_content, &mut redaction_counts);
+    }
+    log_redactions(&redaction_counts, "context files");
+    context_files
 }
@@ -1,7 +1,11 @@
 use std::sync::Arc;
 use tokenizers::Tokenizer;
+use tokio::sync::RwLock as ARwLock;
 
 use crate::call_validation::{ChatContent, ChatMessage};
+use crate::global_context::GlobalContext;
+use crate::postprocessing::pp_redact::{log_redactions, RedactionCounts};
+use crate::privacy::load_privacy_if_needed;
 use crate::scratchpads::multimodality::MultimodalElement;
 use crate::tokens::count_text_tokens_with_fallback;
 
@@ -29,6 +33,7 @@ fn limit_text_content(
 }
 
 pub async fn postprocess_plain_text(
+    gcx: Arc<ARwLock<GlobalContext>>,
     plain_text_messages: Vec<ChatMessage>,
     tokenizer: Option<Arc<Tokenizer>>,
     tokens_limit: usize,
@@ -38,6 +43,10 @@ pub async fn postprocess_plain_text(
         return (vec![], tokens_limit);
     }
     let mut messages_sorted = plain_text_messages;
+    // before the limit, so a private key is never cut in half
+    let mut redaction_counts = RedactionCounts::new();
+    load_privacy_if_needed(gcx).await.redactor.redact_messages(&mut messages_sorted, &mut redaction_counts);
+    log_redactions(&redaction_counts, "plain text");
     let messages_len = messages_sorted.len();
     messages_sorted.sort_by(|a, b| a.content.size_estimate(tokenizer.clone(), style).cmp(&b.content.size_estimate(tokenizer.clone(), style)));
 
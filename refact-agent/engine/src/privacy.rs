@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::path::Path;
 use serde::Deserialize;
@@ -10,6 +11,7 @@ use std::time::SystemTime;
 use crate::files_correction::any_glob_matches_path;
 use crate::files_correction::canonical_path;
 use crate::global_context::GlobalContext;
+use crate::postprocessing::pp_redact::{RedactionSettings, Redactor};
 
 
 #[derive(Debug, PartialEq, PartialOrd)]
@@ -23,7 +25,11 @@ pub enum FilePrivacyLevel {
 pub struct PrivacySettings {
     pub privacy_rules: FilePrivacySettings,
     #[serde(default)]
+    pub redaction: RedactionSettings,
+    #[serde(default)]
     pub loaded_ts: u64,
+    #[serde(skip)]
+    pub redactor: Arc<Redactor>,
 }
 
 #[allow(non_snake_case)]
@@ -40,7 +46,9 @@ impl Default for PrivacySettings {
                 blocked: vec!["*".to_string()],
                 only_send_to_servers_I_control: vec![],
             },
+            redaction: RedactionSettings::default(),
             loaded_ts: 0,
+            redactor: Arc::new(Redactor::default()),
         }
     }
 }
@@ -68,16 +76,35 @@ async fn read_privacy_yaml(path: &Path) -> PrivacySettings
     }
 }
 
+async fn read_secret_values(path: &Path) -> Vec<(String, String)>
+{
+    let content = match fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(_) => return vec![],  // no secrets.yaml is fine
+    };
+    match serde_yaml::from_str::<HashMap<String, serde_yaml::Value>>(&content) {
+        Ok(secrets) => secrets.into_iter().filter_map(|(name, value)| match value {
+            serde_yaml::Value::String(s) => Some((name, s)),
+            serde_yaml::Value::Number(n) => Some((name, n.to_string())),
+            _ => None,
+        }).collect(),
+        Err(e) => {
+            error!("parsing {} failed, its values won't be redacted\n{}", path.display(), e);
+            vec![]
+        }
+    }
+}
+
 pub async fn load_privacy_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> Arc<PrivacySettings>
 {
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-    let (config_dir, privacy_yaml) = {
+    let (config_dir, privacy_yaml, secrets_yaml) = {
         let gcx_locked = gcx.read().await;
         let should_reload = gcx_locked.privacy_settings.loaded_ts + PRIVACY_TOO_OLD.as_secs() <= current_time;
         if !should_reload {
             return gcx_locked.privacy_settings.clone();
         }
-        (gcx_locked.config_dir.clone(), gcx_locked.cmdline.privacy_yaml.clone())
+        (gcx_locked.config_dir.clone(), gcx_locked.cmdline.privacy_yaml.clone(), gcx_locked.cmdline.secrets_yaml.clone())
     };
 
     let path = if privacy_yaml.is_empty() {
@@ -89,6 +116,15 @@ pub async fn load_privacy_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> Arc<Pri
     let mut new_privacy_settings = read_privacy_yaml(&path).await;
     new_privacy_settings.loaded_ts = current_time;
 
+    // secrets.yaml values are redacted too, rebuilt together with privacy.yaml so an edited secret is picked up
+    let secrets_path = if secrets_yaml.is_empty() {
+        config_dir.join("secrets.yaml")
+    } else {
+        canonical_path(secrets_yaml)
+    };
+    let secrets = read_secret_values(&secrets_path).await;
+    new_privacy_settings.redactor = Arc::new(Redactor::new(&new_privacy_settings.redaction, secrets));
+
     {
         let mut gcx_locked = gcx.write().await;
         gcx_locked.privacy_settings = Arc::new(new_privacy_settings);
@@ -131,6 +167,7 @@ mod tests {
                 blocked: vec!["*.pem".to_string(), "*/secret_dir/*".to_string(), "secret_passwords.txt".to_string()],
             },
             loaded_ts: 0,
+            ..Default::default()
         });
 
         let current_dir = std::env::current_dir().unwrap();
@@ -171,6 +208,7 @@ mod tests {
                 blocked: vec!["*/make.png".to_string(), "*.txt".to_string()],
             },
             loaded_ts: 0,
+            ..Default::default()
         });
 
         let current_dir = std::env::current_dir().unwrap();
@@ -203,12 +241,15 @@ mod tests {
             }
         }
     }
-}
-
-
-
-
-
-
-
 
+    #[test]
+    fn test_default_privacy_redaction() {
+        let privacy_settings: PrivacySettings = serde_yaml::from_str(include_str!("yaml_configs/default_privacy.yaml")).unwrap();
+        let redactor = Redactor::new(&privacy_settings.redaction, vec![]);
+        let mut counts = crate::postprocessing::pp_redact::RedactionCounts::new();
+        let redacted = redactor.redact("DB_PASSWORD=\"correcthorse\"\nDEBUG=1", &mut counts);
+        assert!(redacted.starts_with("DB_PASSWORD=\"[REDACTED_CUSTOM_"));
+        assert!(redacted.ends_with("\"\nDEBUG=1"));
+        assert_eq!(counts.get("CUSTOM"), Some(&1));
+    }
+}
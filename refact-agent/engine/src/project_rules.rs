@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::files_correction::project_glob_matches;
+use crate::privacy::{check_file_privacy, FilePrivacyLevel, PrivacySettings};
+
+// Per-repo instructions for the model. Rules are read from the workspace root and from every directory between it
+// and the active file: AGENTS.md, then .refact/rules/*.md. Files other agents use are read only where there is
+// no AGENTS.md, so a repo that has both (often the same text) gets it once. Files are read on every system prompt,
+// the same way customization.yaml is, so edits apply to the next chat without a restart.
+
+const PROJECT_RULES_MAX_CHARS: usize = 20_000;
+const RULES_FILE: &str = "AGENTS.md";
+const RULES_DIR: &str = ".refact/rules";
+const OTHER_AGENTS_RULES_FILES: &[&str] = &[
+    "CLAUDE.md",
+    ".cursorrules",
+    ".windsurfrules",
+    ".github/copilot-instructions.md",
+];
+
+#[derive(Deserialize, Default)]
+struct RulesFrontmatter {
+    #[serde(default)]
+    globs: Option<serde_yaml::Value>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProjectRule {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// `---` yaml `---` at the top of a rules file, the rest is the text
+fn split_frontmatter(content: &str) -> (RulesFrontmatter, String) {
+    let rest = match content.strip_prefix("---\n").or(content.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (RulesFrontmatter::default(), content.to_string()),
+    };
+    let end = match rest.find("\n---") {
+        Some(end) => end,
+        None => return (RulesFrontmatter::default(), content.to_string()),
+    };
+    let frontmatter = serde_yaml::from_str::<RulesFrontmatter>(&rest[..end]).unwrap_or_else(|e| {
+        warn!("cannot parse the rules file frontmatter: {}", e);
+        RulesFrontmatter::default()
+    });
+    let text = rest[end + 4..].trim_start_matches('-').trim_start().to_string();
+    (frontmatter, text)
+}
+
+fn frontmatter_globs(frontmatter: &RulesFrontmatter) -> Vec<String> {
+    match &frontmatter.globs {
+        Some(serde_yaml::Value::String(s)) => s.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect(),
+        Some(serde_yaml::Value::Sequence(seq)) => seq.iter().filter_map(|x| x.as_str()).map(|x| x.trim().to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Globs are relative to the directory the rules belong to
+fn globs_match(globs: &Vec<String>, base_dir: &Path, active_file: &Option<PathBuf>) -> bool {
+    if globs.is_empty() {
+        return true;
+    }
+    let relative = match active_file.as_ref().and_then(|f| f.strip_prefix(base_dir).ok()) {
+        Some(relative) => relative,
+        None => return false,
+    };
+    globs.iter().any(|g| project_glob_matches(g, relative))
+}
+
+fn rules_files_in_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let agents_md = dir.join(RULES_FILE);
+    if agents_md.is_file() {
+        files.push(agents_md);
+    } else {
+        files.extend(OTHER_AGENTS_RULES_FILES.iter().map(|f| dir.join(f)).filter(|f| f.is_file()));
+    }
+    if let Ok(entries) = std::fs::read_dir(dir.join(RULES_DIR)) {
+        let mut rules_dir_files: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().map(|e| e == "md" || e == "mdc").unwrap_or(false))
+            .collect();
+        rules_dir_files.sort();
+        files.extend(rules_dir_files);
+    }
+    files
+}
+
+/// The workspace root that has the active file and every directory down to it, or all workspace roots if
+/// there is no active file inside the workspace
+fn rules_dirs(workspace_dirs: &[PathBuf], active_file: &Option<PathBuf>) -> Vec<PathBuf> {
+    if let Some(active_file) = active_file {
+        if let Some(root) = workspace_dirs.iter().filter(|d| active_file.starts_with(d)).max_by_key(|d| d.components().count()) {
+            let mut dirs: Vec<PathBuf> = active_file.ancestors().skip(1)
+                .take_while(|d| d.starts_with(root))
+                .map(|d| d.to_path_buf())
+                .collect();
+            dirs.reverse();
+            return dirs;
+        }
+    }
+    workspace_dirs.to_vec()
+}
+
+/// Blocking, reads the files: call it from spawn_blocking
+pub fn load_project_rules(workspace_dirs: &[PathBuf], active_file: &Option<PathBuf>, privacy_settings: Arc<PrivacySettings>) -> Vec<ProjectRule> {
+    let mut rules = vec![];
+    let mut seen_paths = HashSet::new();
+    let mut seen_texts = HashSet::new();
+    let mut total_chars = 0;
+    for dir in rules_dirs(workspace_dirs, active_file) {
+        for path in rules_files_in_dir(&dir) {
+            let canonical = path.canonicalize().unwrap_or(path.clone());
+            if !seen_paths.insert(canonical) {
+                continue;
+            }
+            if let Err(e) = check_file_privacy(privacy_settings.clone(), &path, &FilePrivacyLevel::AllowToSendAnywhere) {
+                info!("skipping rules file {:?}: {}", path, e);
+                continue;
+            }
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("cannot read rules file {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            let (frontmatter, text) = split_frontmatter(&content);
+            let text = text.trim().to_string();
+            if text.is_empty() || !globs_match(&frontmatter_globs(&frontmatter), &dir, active_file) {
+                continue;
+            }
+            if !seen_texts.insert(text.clone()) {
+                continue;
+            }
+            let text_chars = text.chars().count();
+            if total_chars + text_chars > PROJECT_RULES_MAX_CHARS {
+                warn!("project rules are over {} chars, skipping {:?} and the rest", PROJECT_RULES_MAX_CHARS, path);
+                return rules;
+            }
+            total_chars += text_chars;
+            rules.push(ProjectRule { path, text });
+        }
+    }
+    if !rules.is_empty() {
+        info!("loaded {} project rules files, {} chars", rules.len(), total_chars);
+    }
+    rules
+}
+
+pub fn project_rules_to_prompt(rules: &Vec<ProjectRule>) -> String {
+    if rules.is_empty() {
+        return String::new();
+    }
+    let mut prompt = "The project has rules, follow them. Rules from deeper directories are more specific and take priority.\n".to_string();
+    for rule in rules {
+        prompt.push_str(&format!("\nRules from {}:\n{}\n", rule.path.display(), rule.text));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::FilePrivacySettings;
+
+    #[test]
+    fn test_load_project_rules() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path().to_path_buf();
+        std::fs::create_dir_all(root.join(".refact/rules")).unwrap();
+        std::fs::create_dir_all(root.join("frontend/src")).unwrap();
+        std::fs::write(root.join("AGENTS.md"), "Run cargo fmt.").unwrap();
+        std::fs::write(root.join("CLAUDE.md"), "Ignored, there is AGENTS.md").unwrap();
+        std::fs::write(root.join(".refact/rules/python.md"), "---\nglobs: \"*.py\"\n---\nUse type hints.").unwrap();
+        std::fs::write(root.join(".refact/rules/tsx.md"), "---\nglobs: [\"frontend/**/*.tsx\"]\n---\nUse hooks.").unwrap();
+        std::fs::write(root.join("frontend/.cursorrules"), "No class components.").unwrap();
+        std::fs::create_dir_all(root.join("secret")).unwrap();
+        std::fs::write(root.join("secret/AGENTS.md"), "The password is hunter2.").unwrap();
+        let privacy_settings = Arc::new(PrivacySettings {
+            privacy_rules: FilePrivacySettings { only_send_to_servers_I_control: vec![], blocked: vec!["*/secret/*".to_string()] },
+            ..Default::default()
+        });
+
+        let texts = |active_file: Option<PathBuf>| -> Vec<String> {
+            load_project_rules(std::slice::from_ref(&root), &active_file, privacy_settings.clone()).into_iter().map(|r| r.text).collect()
+        };
+        assert_eq!(texts(None), vec!["Run cargo fmt."]);
+        assert_eq!(texts(Some(root.join("tools/x.py"))), vec!["Run cargo fmt.", "Use type hints."]);
+        assert_eq!(
+            texts(Some(root.join("frontend/src/App.tsx"))),
+            vec!["Run cargo fmt.", "Use hooks.", "No class components."],
+        );
+        assert_eq!(texts(Some(root.join("secret/notes.txt"))), vec!["Run cargo fmt."]);
+
+        // the limit is in chars, not bytes
+        std::fs::write(root.join("AGENTS.md"), "я".repeat(PROJECT_RULES_MAX_CHARS)).unwrap();
+        assert_eq!(texts(None).len(), 1);
+    }
+}
@@ -11,11 +11,12 @@ use tracing::info;
 use uuid;
 
 use crate::call_validation::{ChatMeta, SamplingParameters};
-use crate::caps::BaseModelRecord;
+use crate::caps::{BaseModelRecord, UsageTokens};
 use crate::custom_error::ScratchError;
 use crate::nicer_logs;
 use crate::scratchpad_abstract::{FinishReason, ScratchpadAbstract};
 use crate::telemetry::telemetry_structs;
+use crate::telemetry::usage_ledger::{usage_ledger_record, usage_ledger_record_from_response, usage_tokens_from_response};
 use crate::at_commands::at_commands::AtCommandsContext;
 
 
@@ -80,6 +81,7 @@ pub async fn scratchpad_interaction_not_stream_json(
     ));
     info!("forward to endpoint {:.2}ms, url was {}", t2.elapsed().unwrap().as_millis() as f64, save_url);
     crate::global_context::look_for_piggyback_fields(gcx.clone(), &model_says).await;
+    usage_ledger_record_from_response(ccx.clone(), model_rec, &scope, &model_says).await;
 
     let scratchpad_result: Result<serde_json::Value, String>;
     if only_deterministic_messages {
@@ -356,6 +358,7 @@ pub async fn scratchpad_interaction_stream(
             };
             let mut was_correct_output_even_if_error = false;
             let mut last_finish_reason = FinishReason::None;
+            let mut stream_usage: Option<UsageTokens> = None;
             // let mut test_countdown = 250;
             while let Some(event) = event_source.next().await {
                 match event {
@@ -368,6 +371,9 @@ pub async fn scratchpad_interaction_stream(
                         let mut json = serde_json::from_str::<serde_json::Value>(&message.data).unwrap();
                         generate_id_and_index_for_tool_calls_if_missing(&mut json);
                         crate::global_context::look_for_piggyback_fields(gcx.clone(), &json).await;
+                        if let Some(tokens) = usage_tokens_from_response(&json) {
+                            stream_usage.get_or_insert_with(UsageTokens::default).merge_stream_chunk(&tokens);
+                        }
                         match _push_streaming_json_into_scratchpad(
                             my_scratchpad,
                             &json,
@@ -429,6 +435,9 @@ pub async fn scratchpad_interaction_stream(
                     },
                 }
             }
+            if let Some(tokens) = stream_usage {
+                usage_ledger_record(my_ccx.clone(), &model_rec, &scope, tokens).await;
+            }
 
             let mut value = my_scratchpad.streaming_finished(last_finish_reason)?;
             value["created"] = json!(t1.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs_f64());
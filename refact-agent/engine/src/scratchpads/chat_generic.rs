@@ -6,6 +6,7 @@ use tokenizers::Tokenizer;
 use tokio::sync::Mutex as AMutex;
 use tracing::{info, error};
 
+use crate::agentic::budgets::budget_apply_to_chat;
 use crate::at_commands::execute_at::run_at_commands_locally;
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::call_validation::{ChatMessage, ChatPost, ContextFile, SamplingParameters};
@@ -14,7 +15,7 @@ use crate::scratchpads::chat_utils_deltadelta::DeltaDeltaChatStreamer;
 use crate::scratchpads::chat_utils_limit_history::fix_and_limit_messages_history;
 use crate::scratchpads::chat_utils_prompts::prepend_the_right_system_prompt_and_maybe_more_initial_messages;
 use crate::scratchpads::scratchpad_utils::HasRagResults;
-use crate::tools::tools_list::get_available_tools_by_chat_mode;
+use crate::tools::tools_list::get_available_tools_by_chat_meta;
 
 
 const DEBUG: bool = true;
@@ -110,7 +111,7 @@ impl ScratchpadAbstract for GenericChatScratchpad {
                 self.messages.clone(), 
                 &self.post.meta, 
                 &mut self.has_rag_results,
-                get_available_tools_by_chat_mode(gcx.clone(), self.post.meta.chat_mode)
+                get_available_tools_by_chat_meta(gcx.clone(), &self.post.meta)
                     .await
                     .into_iter()
                     .map(|t| t.tool_description().name)
@@ -119,11 +120,12 @@ impl ScratchpadAbstract for GenericChatScratchpad {
         } else {
             self.messages.clone()
         };
-        let (messages, _any_context_produced) = if self.allow_at && !should_execute_remotely {
+        let (mut messages, _any_context_produced) = if self.allow_at && !should_execute_remotely {
             run_at_commands_locally(ccx.clone(), self.t.tokenizer.clone(), sampling_parameters_to_patch.max_new_tokens, messages, &mut self.has_rag_results).await
         } else {
             (self.messages.clone(), false)
         };
+        budget_apply_to_chat(gcx.clone(), &self.post.meta.chat_id, &self.post.model, &mut messages, &mut self.has_rag_results).await;
         let (limited_msgs, _compression_strength) = fix_and_limit_messages_history(&self.t, &messages, sampling_parameters_to_patch, n_ctx, None, self.post.model.as_str())?;
         // if self.supports_tools {
         // };
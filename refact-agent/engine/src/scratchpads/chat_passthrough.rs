@@ -5,6 +5,7 @@ use tokio::sync::Mutex as AMutex;
 use async_trait::async_trait;
 use tracing::info;
 
+use crate::agentic::budgets::{budget_apply_to_chat, BudgetStatus};
 use crate::at_commands::execute_at::{run_at_commands_locally, run_at_commands_remotely};
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::call_validation::{ChatMessage, ChatPost, ReasoningEffort, SamplingParameters};
@@ -18,7 +19,7 @@ use crate::scratchpads::scratchpad_utils::HasRagResults;
 use crate::scratchpads::chat_utils_prompts::prepend_the_right_system_prompt_and_maybe_more_initial_messages;
 use crate::scratchpads::passthrough_convert_messages::convert_messages_to_openai_format;
 use crate::tools::tools_description::ToolDesc;
-use crate::tools::tools_list::get_available_tools;
+use crate::tools::tools_list::get_tools_to_execute;
 use crate::tools::tools_execute::{run_tools_locally, run_tools_remotely};
 
 
@@ -66,6 +67,7 @@ pub struct ChatPassthrough {
     pub allow_at: bool,
     pub supports_tools: bool,
     pub supports_clicks: bool,
+    pub budget_status: Option<BudgetStatus>,
 }
 
 impl ChatPassthrough {
@@ -90,6 +92,7 @@ impl ChatPassthrough {
             allow_at,
             supports_tools,
             supports_clicks,
+            budget_status: None,
         }
     }
 }
@@ -132,19 +135,22 @@ impl ScratchpadAbstract for ChatPassthrough {
         } else {
             (messages, false)
         };
-        if self.supports_tools {
+        let budget_status = budget_apply_to_chat(gcx.clone(), &self.post.meta.chat_id, &self.post.model, &mut messages, &mut self.has_rag_results).await;
+        let over_budget = budget_status.as_ref().map(|x| x.is_exceeded()).unwrap_or(false);
+        self.budget_status = budget_status;
+
+        if self.supports_tools && !over_budget {
             (messages, _) = if should_execute_remotely {
-                run_tools_remotely(ccx.clone(), &self.post.model, sampling_parameters_to_patch.max_new_tokens, &messages, &mut self.has_rag_results, &style).await?
+                run_tools_remotely(ccx.clone(), &self.post.model, &self.post.meta.custom_mode, sampling_parameters_to_patch.max_new_tokens, &messages, &mut self.has_rag_results, &style).await?
             } else {
-                let mut tools = get_available_tools(gcx.clone()).await.into_iter()
-                    .map(|x| (x.tool_description().name, x)).collect();
+                let mut tools = get_tools_to_execute(gcx.clone(), &self.post.meta.custom_mode).await;
                 run_tools_locally(ccx.clone(), &mut tools, self.t.tokenizer.clone(), sampling_parameters_to_patch.max_new_tokens, &messages, &mut self.has_rag_results, &style).await?
             }
         };
 
         let mut big_json = serde_json::json!({});
 
-        if self.supports_tools {
+        if self.supports_tools && !over_budget {
             let tools: Vec<ToolDesc> = if should_execute_remotely {
                 let port = docker_container_get_host_lsp_port_to_connect(gcx.clone(), &self.post.meta.chat_id).await?;
                 tracing::info!("Calling tools on port: {}", port);
@@ -263,10 +269,14 @@ impl ScratchpadAbstract for ChatPassthrough {
 
     fn streaming_finished(&mut self, finish_reason: FinishReason) -> Result<Value, String> {
         let json_choices = self.delta_sender.feed_delta("assistant", &json!({}), &finish_reason, None);
-        Ok(json!({
+        let mut value = json!({
             "choices": json_choices,
             "object": "chat.completion.chunk",
-        }))
+        });
+        if let Some(budget_status) = &self.budget_status {
+            value["budget_status"] = json!(budget_status);
+        }
+        Ok(value)
     }
 }
 
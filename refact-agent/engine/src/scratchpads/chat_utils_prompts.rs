@@ -170,6 +170,19 @@ pub async fn system_prompt_add_extra_instructions(
         }
     }
 
+    if system_prompt.contains("%PROJECT_RULES%") {
+        let (workspace_dirs, active_file_path) = workspace_files_info(&gcx).await;
+        let workspace_dirs = workspace_dirs.into_iter().map(PathBuf::from).collect::<Vec<_>>();
+        let privacy_settings = crate::privacy::load_privacy_if_needed(gcx.clone()).await;
+        let rules = tokio::task::spawn_blocking(move || {
+            crate::project_rules::load_project_rules(&workspace_dirs, &active_file_path, privacy_settings)
+        }).await.unwrap_or_else(|e| {
+            tracing::error!("loading project rules failed: {}", e);
+            vec![]
+        });
+        system_prompt = system_prompt.replace("%PROJECT_RULES%", &crate::project_rules::project_rules_to_prompt(&rules));
+    }
+
     if system_prompt.contains("%EXPLORE_FILE_EDIT_INSTRUCTIONS%") {
         let replacement = if tool_names.contains("create_textdoc") || tool_names.contains("update_textdoc") {
             "- Then use `*_textdoc()` tools to make changes.\n"
@@ -253,9 +266,19 @@ pub async fn prepend_the_right_system_prompt_and_maybe_more_initial_messages(
 
     match chat_meta.chat_mode {
         ChatMode::EXPLORE | ChatMode::AGENT | ChatMode::NO_TOOLS => {
+            let mut system_prompt = String::new();
+            if !chat_meta.custom_mode.is_empty() {
+                match crate::yaml_configs::customization_loader::load_chat_mode(gcx.clone(), &chat_meta.custom_mode).await {
+                    Ok(mode) => system_prompt = mode.system_prompt,
+                    Err(e) => tracing::error!("{}", e),
+                }
+            }
+            if system_prompt.is_empty() {
+                system_prompt = get_default_system_prompt(gcx.clone(), chat_meta.chat_mode).await;
+            }
             let system_message_content = system_prompt_add_extra_instructions(
                 gcx.clone(),
-                get_default_system_prompt(gcx.clone(), chat_meta.chat_mode.clone()).await,
+                system_prompt,
                 tool_names,
             ).await;
             let msg = ChatMessage {
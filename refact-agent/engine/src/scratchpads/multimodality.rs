@@ -2,7 +2,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 use std::sync::Arc;
 use serde_json::{json, Value};
 use tokenizers::Tokenizer;
-use crate::call_validation::{ChatContent, ChatMessage, ChatToolCall};
+use crate::call_validation::{ChatContent, ChatMessage, ChatToolCall, ChatUsage};
 use crate::scratchpads::scratchpad_utils::{calculate_image_tokens_openai, image_reader_from_b64string, parse_image_b64_from_image_url_openai};
 use crate::tokens::count_text_tokens;
 
@@ -313,6 +313,9 @@ impl<'de> Deserialize<'de> for ChatMessage {
             .map(|v| v.iter().map(|v| serde_json::from_value(v.clone()).map_err(serde::de::Error::custom)).collect::<Result<Vec<_>, _>>())
             .transpose()?;
 
+        let usage: Option<ChatUsage> = value.get("usage")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
         Ok(ChatMessage {
             role,
             content,
@@ -320,6 +323,7 @@ impl<'de> Deserialize<'de> for ChatMessage {
             tool_calls,
             tool_call_id: tool_call_id.unwrap_or_default(),
             thinking_blocks,
+            usage,
             ..Default::default()
         })
     }
@@ -116,7 +116,7 @@ async fn chat_interaction_non_stream(
     let j = crate::restream::scratchpad_interaction_not_stream_json(
         ccx.clone(),
         &mut spad,
-        "chat".to_string(),
+        "subchat".to_string(),
         prompt,
         &model_rec.base,
         &chat_post.parameters,   // careful: includes n
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+use rusqlite::params_from_iter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+use tokio_rusqlite::Connection;
+use tracing::{info, warn};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::caps::{resolve_chat_model, resolve_chat_model_pricing, BaseModelRecord, UsageTokens};
+use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
+
+// Local accounting of every model call that reports usage, stored in cache_dir/usage.sqlite.
+// Unlike telemetry, nothing here is ever sent anywhere.
+
+pub const USAGE_GROUP_BY: [&str; 7] = ["day", "provider", "model", "chat_mode", "tool", "scope", "chat_id"];
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UsageRecord {
+    pub ts: f64,
+    pub chat_id: String,
+    pub chat_mode: String,
+    pub provider: String,
+    pub model: String,
+    pub scope: String,  // "chat-stream", "chat", "subchat", "completion", ...
+    pub tool: String,   // the tool that ran the subchat, empty for the chat itself
+    #[serde(flatten)]
+    pub tokens: UsageTokens,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct UsageReportRow {
+    pub group: serde_json::Map<String, Value>,
+    pub requests: usize,
+    #[serde(flatten)]
+    pub tokens: UsageTokens,
+    pub cost_usd: f64,
+}
+
+#[derive(Clone)]
+pub struct UsageLedger {
+    conn: Connection,
+}
+
+/// Refact cloud reports metering fields next to choices instead of `usage`
+pub fn usage_tokens_from_response(response: &Value) -> Option<UsageTokens> {
+    if let Some(tokens) = response.get("usage").and_then(usage_tokens_from_json) {
+        return Some(tokens);
+    }
+    let prompt_tokens = response.get("metering_prompt_tokens_n").and_then(|x| x.as_u64())?;
+    let completion_tokens = response.get("metering_generated_tokens_n").and_then(|x| x.as_u64()).unwrap_or(0);
+    Some(UsageTokens { prompt_tokens: prompt_tokens as usize, completion_tokens: completion_tokens as usize, ..Default::default() })
+}
+
+/// Understands OpenAI style `prompt_tokens_details.cached_tokens` and Anthropic style `cache_read_input_tokens`.
+/// OpenAI counts cached tokens in prompt_tokens, Anthropic doesn't; here prompt_tokens never include them, so each
+/// kind is priced once.
+pub fn usage_tokens_from_json(usage: &Value) -> Option<UsageTokens> {
+    let get = |v: &Value, key: &str| v.get(key).and_then(|x| x.as_u64()).unwrap_or(0) as usize;
+    let obj = usage.as_object()?;
+    if !obj.contains_key("prompt_tokens") && !obj.contains_key("completion_tokens") {
+        return None;
+    }
+    let openai_cached = usage.get("prompt_tokens_details").map(|d| get(d, "cached_tokens")).unwrap_or(0);
+    Some(UsageTokens {
+        prompt_tokens: get(usage, "prompt_tokens").saturating_sub(openai_cached),
+        completion_tokens: get(usage, "completion_tokens"),
+        cache_read_tokens: get(usage, "cache_read_input_tokens") + openai_cached,
+        cache_creation_tokens: get(usage, "cache_creation_input_tokens"),
+    })
+}
+
+impl UsageLedger {
+    pub async fn open(db_path: &std::path::PathBuf) -> Result<UsageLedger, String> {
+        let conn = Connection::open(db_path).await.map_err(|e| format!("cannot open {:?}: {}", db_path, e))?;
+        Self::init(conn).await
+    }
+
+    #[cfg(test)]
+    pub async fn open_in_memory() -> Result<UsageLedger, String> {
+        Self::init(Connection::open_in_memory().await.map_err(|e| e.to_string())?).await
+    }
+
+    async fn init(conn: Connection) -> Result<UsageLedger, String> {
+        conn.call(|conn| {
+            let _: String = conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS usage (
+                    ts REAL NOT NULL,
+                    chat_id TEXT NOT NULL,
+                    chat_mode TEXT NOT NULL,
+                    provider TEXT NOT NULL,
+                    model TEXT NOT NULL,
+                    scope TEXT NOT NULL,
+                    tool TEXT NOT NULL,
+                    prompt_tokens INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    cache_read_tokens INTEGER NOT NULL,
+                    cache_creation_tokens INTEGER NOT NULL,
+                    cost_usd REAL NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_usage_ts ON usage (ts);"
+            )?;
+            Ok(())
+        }).await.map_err(|e| e.to_string())?;
+        Ok(UsageLedger { conn })
+    }
+
+    pub async fn record(&self, r: UsageRecord) -> Result<(), String> {
+        self.conn.call(move |conn| {
+            conn.execute(
+                "INSERT INTO usage (ts, chat_id, chat_mode, provider, model, scope, tool, prompt_tokens, completion_tokens, \
+                 cache_read_tokens, cache_creation_tokens, cost_usd) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                rusqlite::params![
+                    r.ts, r.chat_id, r.chat_mode, r.provider, r.model, r.scope, r.tool,
+                    r.tokens.prompt_tokens as i64, r.tokens.completion_tokens as i64,
+                    r.tokens.cache_read_tokens as i64, r.tokens.cache_creation_tokens as i64, r.cost_usd,
+                ],
+            )?;
+            Ok(())
+        }).await.map_err(|e| e.to_string())
+    }
+
+    /// Sums usage in [from_ts, to_ts) grouped by any of USAGE_GROUP_BY, most expensive first
+    pub async fn report(&self, from_ts: f64, to_ts: f64, group_by: &Vec<String>) -> Result<Vec<UsageReportRow>, String> {
+        for g in group_by {
+            if !USAGE_GROUP_BY.contains(&g.as_str()) {
+                return Err(format!("cannot group by {:?}, possible values: {}", g, USAGE_GROUP_BY.join(", ")));
+            }
+        }
+        let group_by = group_by.clone();
+        let columns: Vec<String> = group_by.iter().map(|g| match g.as_str() {
+            "day" => "date(ts, 'unixepoch', 'localtime')".to_string(),
+            other => other.to_string(),
+        }).collect();
+        let select_groups = columns.iter().map(|c| format!("{}, ", c)).collect::<String>();
+        let group_clause = if columns.is_empty() { String::new() } else { format!("GROUP BY {}", columns.join(", ")) };
+        let sql = format!(
+            "SELECT {select_groups}COUNT(*), SUM(prompt_tokens), SUM(completion_tokens), SUM(cache_read_tokens), \
+             SUM(cache_creation_tokens), SUM(cost_usd) FROM usage WHERE ts >= ?1 AND ts < ?2 {group_clause} \
+             ORDER BY SUM(cost_usd) DESC, SUM(prompt_tokens) + SUM(completion_tokens) DESC"
+        );
+        self.conn.call(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let n = group_by.len();
+            let rows = stmt.query_map(params_from_iter([from_ts, to_ts]), |row| {
+                let mut group = serde_json::Map::new();
+                for (i, g) in group_by.iter().enumerate() {
+                    group.insert(g.clone(), Value::String(row.get::<_, Option<String>>(i)?.unwrap_or_default()));
+                }
+                Ok(UsageReportRow {
+                    group,
+                    requests: row.get::<_, i64>(n)? as usize,
+                    tokens: UsageTokens {
+                        prompt_tokens: row.get::<_, Option<i64>>(n + 1)?.unwrap_or(0) as usize,
+                        completion_tokens: row.get::<_, Option<i64>>(n + 2)?.unwrap_or(0) as usize,
+                        cache_read_tokens: row.get::<_, Option<i64>>(n + 3)?.unwrap_or(0) as usize,
+                        cache_creation_tokens: row.get::<_, Option<i64>>(n + 4)?.unwrap_or(0) as usize,
+                    },
+                    cost_usd: row.get::<_, Option<f64>>(n + 5)?.unwrap_or(0.0),
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        }).await.map_err(|e| e.to_string())
+    }
+}
+
+pub async fn usage_ledger(gcx: Arc<ARwLock<GlobalContext>>) -> Result<UsageLedger, String> {
+    let (ledger_arc, cache_dir) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.usage_ledger.clone(), gcx_locked.cache_dir.clone())
+    };
+    let mut ledger_locked = ledger_arc.lock().await;
+    if let Some(ledger) = ledger_locked.as_ref() {
+        return Ok(ledger.clone());
+    }
+    let db_path = cache_dir.join("usage.sqlite");
+    let ledger = UsageLedger::open(&db_path).await?;
+    info!("usage ledger opened at {:?}", db_path);
+    *ledger_locked = Some(ledger.clone());
+    Ok(ledger)
+}
+
+/// Called for non-streaming model responses, records them if they report usage
+pub async fn usage_ledger_record_from_response(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    model_rec: &BaseModelRecord,
+    scope: &str,
+    response: &Value,
+) {
+    if let Some(tokens) = usage_tokens_from_response(response) {
+        usage_ledger_record(ccx, model_rec, scope, tokens).await;
+    }
+}
+
+/// Errors are only logged
+pub async fn usage_ledger_record(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    model_rec: &BaseModelRecord,
+    scope: &str,
+    tokens: UsageTokens,
+) {
+    let (gcx, chat_id, chat_mode, tool) = {
+        let ccx_locked = ccx.lock().await;
+        (
+            ccx_locked.global_context.clone(),
+            ccx_locked.chat_id.clone(),
+            ccx_locked.chat_mode.map(|m| format!("{:?}", m)).unwrap_or_default(),
+            ccx_locked.current_tool.clone(),
+        )
+    };
+    let pricing = match try_load_caps_quickly_if_not_present(gcx.clone(), 0).await {
+        Ok(caps) => resolve_chat_model(caps.clone(), &model_rec.id).ok()
+            .and_then(|chat_rec| resolve_chat_model_pricing(&caps, &chat_rec)),
+        Err(_) => None,
+    };
+    let cost_usd = pricing.map(|p| p.cost_usd(&tokens)).unwrap_or(0.0);
+    let record = UsageRecord {
+        ts: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+        chat_id,
+        chat_mode,
+        provider: model_rec.id.split('/').next().unwrap_or_default().to_string(),
+        model: model_rec.name.clone(),
+        scope: scope.to_string(),
+        tool,
+        tokens,
+        cost_usd,
+    };
+    let result = match usage_ledger(gcx).await {
+        Ok(ledger) => ledger.record(record).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        warn!("usage ledger: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::caps::ModelPricing;
+
+    #[test]
+    fn test_usage_tokens_from_json() {
+        let openai = json!({"prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120, "prompt_tokens_details": {"cached_tokens": 64}});
+        assert_eq!(usage_tokens_from_json(&openai).unwrap(), UsageTokens { prompt_tokens: 36, completion_tokens: 20, cache_read_tokens: 64, cache_creation_tokens: 0 });
+        let anthropic = json!({"prompt_tokens": 10, "completion_tokens": 5, "cache_read_input_tokens": 1000, "cache_creation_input_tokens": 200});
+        assert_eq!(usage_tokens_from_json(&anthropic).unwrap(), UsageTokens { prompt_tokens: 10, completion_tokens: 5, cache_read_tokens: 1000, cache_creation_tokens: 200 });
+        assert!(usage_tokens_from_json(&json!(null)).is_none());
+        assert!(usage_tokens_from_json(&json!({"something": 1})).is_none());
+        let refact = json!({"choices": [], "metering_prompt_tokens_n": 300, "metering_generated_tokens_n": 30});
+        assert_eq!(usage_tokens_from_response(&refact).unwrap().prompt_tokens, 300);
+        assert_eq!(usage_tokens_from_response(&json!({"usage": openai})).unwrap().cache_read_tokens, 64);
+        assert!(usage_tokens_from_response(&json!({"choices": []})).is_none());
+
+        let pricing = ModelPricing { prompt: 3.0, generated: 15.0, cache_read: Some(0.3), cache_creation: Some(3.75) };
+        let cost = pricing.cost_usd(&usage_tokens_from_json(&anthropic).unwrap());
+        assert!((cost - (10.0 * 3.0 + 5.0 * 15.0 + 1000.0 * 0.3 + 200.0 * 3.75) / 1e6).abs() < 1e-12);
+        let no_cache_prices = ModelPricing { prompt: 2.0, generated: 8.0, ..Default::default() };
+        let cost = no_cache_prices.cost_usd(&usage_tokens_from_json(&openai).unwrap());
+        assert!((cost - (100.0 * 2.0 + 20.0 * 8.0) / 1e6).abs() < 1e-12);
+
+        let mut streamed = UsageTokens::default();
+        for chunk in [json!({"prompt_tokens": 10, "completion_tokens": 1, "cache_read_input_tokens": 1000}), json!({"prompt_tokens": 0, "completion_tokens": 5}), json!({"prompt_tokens": 10, "completion_tokens": 5})] {
+            streamed.merge_stream_chunk(&usage_tokens_from_json(&chunk).unwrap());
+        }
+        assert_eq!(streamed, UsageTokens { prompt_tokens: 10, completion_tokens: 5, cache_read_tokens: 1000, cache_creation_tokens: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_report_grouping() {
+        let ledger = UsageLedger::open_in_memory().await.unwrap();
+        let rec = |ts: f64, model: &str, tool: &str, prompt_tokens: usize, cost_usd: f64| UsageRecord {
+            ts, model: model.to_string(), provider: "openai".to_string(), tool: tool.to_string(), scope: "chat".to_string(),
+            tokens: UsageTokens { prompt_tokens, completion_tokens: 10, ..Default::default() }, cost_usd, ..Default::default()
+        };
+        ledger.record(rec(100.0, "gpt-4o", "", 1000, 0.5)).await.unwrap();
+        ledger.record(rec(200.0, "gpt-4o", "locate", 2000, 1.0)).await.unwrap();
+        ledger.record(rec(300.0, "gpt-4o-mini", "", 3000, 0.1)).await.unwrap();
+        ledger.record(rec(900.0, "gpt-4o-mini", "", 3000, 0.1)).await.unwrap();
+
+        let by_model = ledger.report(0.0, 500.0, &vec!["model".to_string()]).await.unwrap();
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].group["model"], json!("gpt-4o"));
+        assert_eq!(by_model[0].requests, 2);
+        assert_eq!(by_model[0].tokens.prompt_tokens, 3000);
+        assert!((by_model[0].cost_usd - 1.5).abs() < 1e-9);
+
+        let total = ledger.report(0.0, 1000.0, &vec![]).await.unwrap();
+        assert_eq!(total.len(), 1);
+        assert_eq!(total[0].requests, 4);
+
+        let by_tool = ledger.report(0.0, 1000.0, &vec!["model".to_string(), "tool".to_string()]).await.unwrap();
+        assert_eq!(by_tool.len(), 3);
+        assert!(ledger.report(0.0, 1000.0, &vec!["ts; DROP TABLE usage".to_string()]).await.is_err());
+    }
+}
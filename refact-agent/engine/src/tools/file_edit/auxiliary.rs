@@ -1,7 +1,9 @@
 use crate::ast::ast_indexer_thread::{ast_indexer_block_until_finished, ast_indexer_enqueue_files};
 use crate::call_validation::DiffChunk;
-use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::documents_to_text::is_document;
+use crate::files_in_workspace::{get_file_text_from_memory_or_disk, get_raw_file_text_from_memory_or_disk};
 use crate::global_context::GlobalContext;
+use crate::notebooks::{is_notebook, notebook_parse};
 use regex::{Match, Regex};
 use std::fs;
 use std::path::PathBuf;
@@ -141,7 +143,15 @@ pub async fn sync_documents_ast(
     Ok(())
 }
 
+fn refuse_to_edit_document(path: &PathBuf) -> Result<(), String> {
+    if is_document(path) {
+        return Err(format!("{:?} is a document, it can't be edited", path));
+    }
+    Ok(())
+}
+
 pub async fn write_file(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, file_text: &String, dry: bool) -> Result<(String, String), String> {
+    refuse_to_edit_document(path)?;
     let parent = path.parent().ok_or(format!(
         "Failed to Add: {:?}. Path is invalid.\nReason: path must have had a parent directory",
         path
@@ -157,8 +167,11 @@ pub async fn write_file(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, file_t
         }
     }
     
+    if is_notebook(path) {
+        notebook_parse(file_text).map_err(|e| format!("Failed to write {:?}, {}. Use notebook_edit to change cells.", path, e))?;
+    }
     let before_text = if path.exists() {
-        get_file_text_from_memory_or_disk(gcx.clone(), path).await?
+        get_raw_file_text_from_memory_or_disk(gcx.clone(), path).await?
     } else {
         "".to_string()
     };
@@ -182,6 +195,10 @@ pub async fn str_replace(
     replace_multiple: bool,
     dry: bool,
 ) -> Result<(String, String), String> {
+    refuse_to_edit_document(path)?;
+    if is_notebook(path) {
+        return Err(format!("{:?} is a Jupyter notebook, use notebook_edit to change its cells", path));
+    }
     let file_content = get_file_text_from_memory_or_disk(gcx.clone(), path).await?;
 
     let has_crlf = file_content.contains("\r\n");
@@ -224,6 +241,10 @@ pub async fn str_replace_regex(
     multiple: bool,
     dry: bool
 ) -> Result<(String, String), String> {
+    refuse_to_edit_document(path)?;
+    if is_notebook(path) {
+        return Err(format!("{:?} is a Jupyter notebook, use notebook_edit to change its cells", path));
+    }
     let file_content = get_file_text_from_memory_or_disk(gcx.clone(), path).await?;
     let has_crlf = file_content.contains("\r\n");
 
@@ -253,4 +274,30 @@ pub async fn str_replace_regex(
     let new_file_content = restore_line_endings(&new_content, has_crlf);
     write_file(gcx.clone(), path, &new_file_content, dry).await?;
     Ok((file_content, new_file_content))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_context::create_test_context;
+
+    #[tokio::test]
+    async fn test_documents_are_not_edited() {
+        let t = create_test_context(&[], vec![]).await;
+        let pdf = t.dir.path().join("report.PDF");
+        std::fs::write(&pdf, "%PDF-1.4 report").unwrap();
+        let text = "report".to_string();
+
+        let errors = vec![
+            write_file(t.gcx.clone(), &pdf, &text, false).await.unwrap_err(),
+            write_file(t.gcx.clone(), &t.dir.path().join("new.docx"), &text, false).await.unwrap_err(),
+            str_replace(t.gcx.clone(), &pdf, &text, &"summary".to_string(), false, false).await.unwrap_err(),
+            str_replace_regex(t.gcx.clone(), &pdf, &Regex::new("report").unwrap(), &"summary".to_string(), false, false).await.unwrap_err(),
+        ];
+        for error in errors {
+            assert!(error.ends_with("is a document, it can't be edited"), "{}", error);
+        }
+        assert_eq!(std::fs::read_to_string(&pdf).unwrap(), "%PDF-1.4 report");
+        assert!(!t.dir.path().join("new.docx").exists());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod auxiliary;
 pub mod tool_create_textdoc;
+pub mod tool_notebook_edit;
 pub mod tool_update_textdoc;
 pub mod tool_update_textdoc_regex;
@@ -6,6 +6,7 @@ use crate::tools::file_edit::auxiliary::{
     await_ast_indexing, convert_edit_to_diffchunks, sync_documents_ast, write_file,
 };
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::tools::write_permissions::check_write_permissions;
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -144,17 +145,18 @@ impl Tool for ToolCreateTextDoc {
         let gcx = ccx.lock().await.global_context.clone();
         let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
 
-        async fn can_execute_tool_edit(gcx: Arc<ARwLock<GlobalContext>>, args: &HashMap<String, Value>, privacy_settings: Arc<PrivacySettings>) -> Result<(), String> {
-            let _ = parse_args(gcx.clone(), args, privacy_settings).await?;
-            Ok(())
+        async fn can_execute_tool_edit(gcx: Arc<ARwLock<GlobalContext>>, args: &HashMap<String, Value>, privacy_settings: Arc<PrivacySettings>) -> Result<PathBuf, String> {
+            let args = parse_args(gcx.clone(), args, privacy_settings).await?;
+            Ok(args.path)
         }
 
         let msgs_len = ccx.lock().await.messages.len();
+        let path = can_execute_tool_edit(gcx.clone(), args, privacy_settings).await;
 
         // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
         if msgs_len != 0 {
             // if we cannot execute apply_edit, there's no need for confirmation
-            if let Err(_) = can_execute_tool_edit(gcx.clone(), args, privacy_settings).await {
+            if path.is_err() {
                 return Ok(MatchConfirmDeny {
                     result: MatchConfirmDenyResult::PASS,
                     command: "create_textdoc".to_string(),
@@ -162,6 +164,11 @@ impl Tool for ToolCreateTextDoc {
                 });
             }
         }
+        if let Ok(path) = path {
+            if let Some(res) = check_write_permissions(ccx.clone(), "create_textdoc", &[path]).await {
+                return Ok(res);
+            }
+        }
         Ok(MatchConfirmDeny {
             result: MatchConfirmDenyResult::CONFIRMATION,
             command: "create_textdoc".to_string(),
@@ -0,0 +1,227 @@
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, DiffChunk};
+use crate::integrations::integr_abstract::IntegrationConfirmation;
+use crate::notebooks::{is_notebook, notebook_apply_edit, NotebookEdit};
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel, PrivacySettings};
+use crate::tools::file_edit::auxiliary::{await_ast_indexing, convert_edit_to_diffchunks, sync_documents_ast, write_file};
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::tools::write_permissions::check_write_permissions;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex as AMutex;
+use crate::files_correction::{canonicalize_normalized_path, get_project_dirs, preprocess_path_for_normalization};
+use tokio::sync::RwLock as ARwLock;
+use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_or_a_good_error};
+use crate::global_context::GlobalContext;
+
+struct ToolNotebookEditArgs {
+    path: PathBuf,
+    edit: NotebookEdit,
+}
+
+pub struct ToolNotebookEdit {
+    pub config_path: String,
+}
+
+async fn parse_args(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    privacy_settings: Arc<PrivacySettings>
+) -> Result<ToolNotebookEditArgs, String> {
+    let path = match args.get("path") {
+        Some(Value::String(s)) => {
+            let raw_path = preprocess_path_for_normalization(s.trim().to_string());
+            let candidates_file = file_repair_candidates(gcx.clone(), &raw_path, 3, false).await;
+            let path = match return_one_candidate_or_a_good_error(gcx.clone(), &raw_path, &candidates_file, &get_project_dirs(gcx.clone()).await, false).await {
+                Ok(f) => canonicalize_normalized_path(PathBuf::from(f)),
+                Err(e) => return Err(e),
+            };
+            if check_file_privacy(privacy_settings, &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+                return Err(format!(
+                    "Error: Cannot update the file '{:?}' due to privacy settings.",
+                    s.trim()
+                ));
+            }
+            if !is_notebook(&path) {
+                return Err(format!("Error: '{:?}' is not a Jupyter notebook, use update_textdoc for other files.", path));
+            }
+            path
+        }
+        Some(v) => return Err(format!("Error: The 'path' argument must be a string, but received: {:?}", v)),
+        None => return Err("Error: The 'path' argument is required but was not provided.".to_string()),
+    };
+    let cell = match args.get("cell") {
+        Some(Value::Number(n)) => n.as_u64().map(|n| n as usize).ok_or(format!("Error: The 'cell' argument must be a cell index, but received: {}", n))?,
+        Some(Value::String(s)) => s.trim().parse::<usize>().map_err(|_| format!("Error: The 'cell' argument must be a cell index, but received: {:?}", s))?,
+        Some(v) => return Err(format!("Error: The 'cell' argument must be a cell index, but received: {:?}", v)),
+        None => return Err("Error: The 'cell' argument is required, cell indices are shown in `# %% [cell N]` lines by cat().".to_string()),
+    };
+    let source = match args.get("source") {
+        Some(Value::String(s)) => Some(s.to_string()),
+        Some(Value::Null) | None => None,
+        Some(v) => return Err(format!("Error: The 'source' argument must be a string, but received: {:?}", v)),
+    };
+    let cell_type = match args.get("cell_type") {
+        Some(Value::String(s)) if !s.trim().is_empty() => s.trim().to_string(),
+        _ => "code".to_string(),
+    };
+    let edit = match args.get("operation").and_then(|x| x.as_str()).map(|x| x.trim()) {
+        Some("replace_cell") => NotebookEdit::ReplaceCell {
+            cell,
+            source: source.ok_or("Error: The 'source' argument is required for replace_cell.".to_string())?,
+        },
+        Some("insert_cell") => NotebookEdit::InsertCell {
+            cell,
+            cell_type,
+            source: source.ok_or("Error: The 'source' argument is required for insert_cell.".to_string())?,
+        },
+        Some("delete_cell") => NotebookEdit::DeleteCell { cell },
+        Some(op) => return Err(format!("Error: Unknown operation {:?}, should be one of: replace_cell, insert_cell, delete_cell", op)),
+        None => return Err("Error: The 'operation' argument is required: replace_cell, insert_cell or delete_cell.".to_string()),
+    };
+
+    Ok(ToolNotebookEditArgs {
+        path,
+        edit,
+    })
+}
+
+pub async fn tool_notebook_edit_exec(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    dry: bool
+) -> Result<(String, String, Vec<DiffChunk>), String> {
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+    let args = parse_args(gcx.clone(), args, privacy_settings).await?;
+    await_ast_indexing(gcx.clone()).await?;
+    let notebook_json = tokio::fs::read_to_string(&args.path).await
+        .map_err(|e| format!("Error: Cannot read {:?}: {}", args.path, e))?;
+    let new_notebook_json = notebook_apply_edit(&notebook_json, &args.edit).map_err(|e| format!("Error: {}", e))?;
+    let (before_text, after_text) = write_file(gcx.clone(), &args.path, &new_notebook_json, dry).await?;
+    sync_documents_ast(gcx.clone(), &args.path).await?;
+    let diff_chunks = convert_edit_to_diffchunks(args.path.clone(), &before_text, &after_text)?;
+    Ok((before_text, after_text, diff_chunks))
+}
+
+#[async_trait]
+impl Tool for ToolNotebookEdit {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let (_, _, diff_chunks) = tool_notebook_edit_exec(gcx.clone(), args, false).await?;
+        let results = vec![ChatMessage {
+            role: "diff".to_string(),
+            content: ChatContent::SimpleText(json!(diff_chunks).to_string()),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            usage: None,
+            ..Default::default()
+        }]
+        .into_iter()
+        .map(|x| ContextEnum::ChatMessage(x))
+        .collect::<Vec<_>>();
+        Ok((false, results))
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<MatchConfirmDeny, String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+
+        let msgs_len = ccx.lock().await.messages.len();
+        let parsed = parse_args(gcx.clone(), args, privacy_settings).await;
+
+        // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
+        if msgs_len != 0 {
+            // if we cannot execute the edit, there's no need for confirmation
+            if parsed.is_err() {
+                return Ok(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::PASS,
+                    command: "notebook_edit".to_string(),
+                    rule: "".to_string(),
+                });
+            }
+        }
+        if let Ok(parsed) = parsed {
+            if let Some(res) = check_write_permissions(ccx.clone(), "notebook_edit", &[parsed.path]).await {
+                return Ok(res);
+            }
+        }
+        Ok(MatchConfirmDeny {
+            result: MatchConfirmDenyResult::CONFIRMATION,
+            command: "notebook_edit".to_string(),
+            rule: "default".to_string(),
+        })
+    }
+
+    async fn command_to_match_against_confirm_deny(
+        &self,
+        _ccx: Arc<AMutex<AtCommandsContext>>,
+        _args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        Ok("notebook_edit".to_string())
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(IntegrationConfirmation {
+            ask_user: vec!["notebook_edit*".to_string()],
+            deny: vec![],
+        })
+    }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: "notebook_edit".to_string(),
+            display_name: "Edit Notebook".to_string(),
+            source: ToolSource {
+                source_type: ToolSourceType::Builtin,
+                config_path: self.config_path.clone(),
+            },
+            agentic: false,
+            experimental: false,
+            description: "Changes cells of a Jupyter notebook (.ipynb), use it instead of update_textdoc for notebooks. cat() shows notebooks as python with `# %% [cell N] type` lines, N is the cell index to use here.".to_string(),
+            parameters: vec![
+                ToolParam {
+                    name: "path".to_string(),
+                    description: "Absolute path to the notebook.".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "operation".to_string(),
+                    description: "One of: replace_cell, insert_cell (the new cell gets index `cell`, use the number of cells to append), delete_cell.".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "cell".to_string(),
+                    description: "Cell index, starting from 0.".to_string(),
+                    param_type: "integer".to_string(),
+                },
+                ToolParam {
+                    name: "source".to_string(),
+                    description: "The full new source of the cell, without the `# %% [cell N]` line and without outputs. Markdown without the `# ` comment prefix.".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "cell_type".to_string(),
+                    description: "For insert_cell: code (default), markdown or raw.".to_string(),
+                    param_type: "string".to_string(),
+                },
+            ],
+            parameters_required: vec!["path".to_string(), "operation".to_string(), "cell".to_string()],
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::integrations::integr_abstract::IntegrationConfirmation;
 use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel, PrivacySettings};
 use crate::tools::file_edit::auxiliary::{await_ast_indexing, convert_edit_to_diffchunks, str_replace_regex, sync_documents_ast};
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::tools::write_permissions::check_write_permissions;
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -145,17 +146,18 @@ impl Tool for ToolUpdateTextDocRegex {
         let gcx = ccx.lock().await.global_context.clone();
         let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
 
-        async fn can_execute_tool_edit(gcx: Arc<ARwLock<GlobalContext>>, args: &HashMap<String, Value>, privacy_settings: Arc<PrivacySettings>) -> Result<(), String> {
-            let _ = parse_args(gcx.clone(), args, privacy_settings).await?;
-            Ok(())
+        async fn can_execute_tool_edit(gcx: Arc<ARwLock<GlobalContext>>, args: &HashMap<String, Value>, privacy_settings: Arc<PrivacySettings>) -> Result<PathBuf, String> {
+            let args = parse_args(gcx.clone(), args, privacy_settings).await?;
+            Ok(args.path)
         }
 
         let msgs_len = ccx.lock().await.messages.len();
+        let path = can_execute_tool_edit(gcx.clone(), args, privacy_settings).await;
 
         // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
         if msgs_len != 0 {
             // if we cannot execute apply_edit, there's no need for confirmation
-            if let Err(_) = can_execute_tool_edit(gcx.clone(), args, privacy_settings).await {
+            if path.is_err() {
                 return Ok(MatchConfirmDeny {
                     result: MatchConfirmDenyResult::PASS,
                     command: "update_textdoc_regex".to_string(),
@@ -163,6 +165,11 @@ impl Tool for ToolUpdateTextDocRegex {
                 });
             }
         }
+        if let Ok(path) = path {
+            if let Some(res) = check_write_permissions(ccx.clone(), "update_textdoc_regex", &[path]).await {
+                return Ok(res);
+            }
+        }
         Ok(MatchConfirmDeny {
             result: MatchConfirmDenyResult::CONFIRMATION,
             command: "update_textdoc_regex".to_string(),
@@ -2,6 +2,7 @@ pub mod tools_description;
 pub mod tools_list;
 pub mod tools_execute;
 pub mod scope_utils;
+pub mod write_permissions;
 
 mod tool_ast_definition;
 mod tool_ast_reference;
@@ -17,4 +18,6 @@ mod tool_knowledge;
 mod tool_locate_search;
 mod tool_create_knowledge;
 mod tool_create_memory_bank;
+mod tool_git_history;
+mod tool_subagent;
 pub mod file_edit;
@@ -11,6 +11,7 @@ use crate::custom_error::trace_and_default;
 use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
 
+#[derive(Clone)]
 pub struct ToolAstDefinition {
     pub config_path: String,
 }
@@ -19,6 +20,8 @@ pub struct ToolAstDefinition {
 impl Tool for ToolAstDefinition {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
     async fn tool_execute(
         &mut self,
         ccx: Arc<AMutex<AtCommandsContext>>,
@@ -11,6 +11,7 @@ use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, Too
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
 use crate::tools::tool_ast_definition::there_are_definitions_with_similar_names_though;
 
+#[derive(Clone)]
 pub struct ToolAstReference {
     pub config_path: String,
 }
@@ -19,6 +20,8 @@ pub struct ToolAstReference {
 impl Tool for ToolAstReference {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
     async fn tool_execute(
         &mut self,
         ccx: Arc<AMutex<AtCommandsContext>>,
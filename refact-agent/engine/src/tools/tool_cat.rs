@@ -13,12 +13,14 @@ use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, Too
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
 use crate::files_correction::{canonical_path, correct_to_nearest_dir_path, get_project_dirs, preprocess_path_for_normalization};
 use crate::files_in_workspace::{get_file_text_from_memory_or_disk, ls_files};
+use crate::documents_to_text::document_page_range_to_lines;
 use crate::scratchpads::multimodality::MultimodalElement;
 
 use std::io::Cursor;
 use image::imageops::FilterType;
 use image::{ImageFormat, ImageReader};
 
+#[derive(Clone)]
 pub struct ToolCat {
     pub config_path: String,
 }
@@ -26,7 +28,7 @@ pub struct ToolCat {
 
 const CAT_MAX_IMAGES_CNT: usize = 1;
 
-fn parse_cat_args(args: &HashMap<String, Value>) -> Result<(Vec<String>, HashMap<String, Option<(usize, usize)>>, Vec<String>), String> {
+fn parse_cat_args(args: &HashMap<String, Value>) -> Result<(Vec<String>, HashMap<String, Option<(usize, usize)>>, HashMap<String, (usize, usize)>, Vec<String>), String> {
     fn try_parse_line_range(s: &str) -> Result<Option<(usize, usize)>, String> {
         let s = s.trim();
         
@@ -63,8 +65,19 @@ fn parse_cat_args(args: &HashMap<String, Value>) -> Result<(Vec<String>, HashMap
     
     let mut paths = Vec::new();
     let mut path_line_ranges = HashMap::new();
+    let mut path_page_ranges = HashMap::new();
     
     for path_str in raw_paths {
+        // pdf, docx: spec.pdf:p3-5 means pages
+        if let Some(colon_pos) = path_str.rfind(":p") {
+            if let Some((start, end)) = try_parse_line_range(&path_str[colon_pos+2..])? {
+                let file_path = path_str[..colon_pos].trim().to_string();
+                path_page_ranges.insert(file_path.clone(), (start, end));
+                path_line_ranges.insert(file_path.clone(), None);
+                paths.push(file_path);
+                continue;
+            }
+        }
         let (file_path, range) = if let Some(colon_pos) = path_str.rfind(':') {
             match try_parse_line_range(&path_str[colon_pos+1..])? {
                 Some((start, end)) => {
@@ -94,12 +107,14 @@ fn parse_cat_args(args: &HashMap<String, Value>) -> Result<(Vec<String>, HashMap
         None => vec![],
     };
     
-    Ok((paths, path_line_ranges, symbols))
+    Ok((paths, path_line_ranges, path_page_ranges, symbols))
 }
 
 #[async_trait]
 impl Tool for ToolCat {
     fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
     
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
@@ -111,11 +126,11 @@ impl Tool for ToolCat {
                 config_path: self.config_path.clone(),
             },
             experimental: false,
-            description: "Like cat in console, but better: it can read multiple files and images. Prefer to open full files.".to_string(),
+            description: "Like cat in console, but better: it can read multiple files and images. Prefer to open full files. Jupyter notebooks are shown as python with `# %% [cell N]` lines. PDF, docx and odt are shown as text with `=== page N ===` lines.".to_string(),
             parameters: vec![
                 ToolParam {
                     name: "paths".to_string(),
-                    description: "Comma separated file names or directories: dir1/file1.ext,dir3/dir4.".to_string(),
+                    description: "Comma separated file names or directories: dir1/file1.ext,dir3/dir4. Add :10-20 for lines, or :p3-5 for pages of a pdf.".to_string(),
                     param_type: "string".to_string(),
                 },
             ],
@@ -130,9 +145,9 @@ impl Tool for ToolCat {
         args: &HashMap<String, Value>
     ) -> Result<(bool, Vec<ContextEnum>), String> {
         let mut corrections = false;
-        let (paths, path_line_ranges, symbols) = parse_cat_args(args)?;
+        let (paths, path_line_ranges, path_page_ranges, symbols) = parse_cat_args(args)?;
         let (filenames_present, symbols_not_found, not_found_messages, context_enums, multimodal) = 
-            paths_and_symbols_to_cat_with_path_ranges(ccx.clone(), paths, path_line_ranges, symbols).await;
+            paths_and_symbols_to_cat_with_path_ranges(ccx.clone(), paths, path_line_ranges, path_page_ranges, symbols).await;
 
         let mut content = "".to_string();
         if !filenames_present.is_empty() {
@@ -240,6 +255,7 @@ pub async fn paths_and_symbols_to_cat_with_path_ranges(
     ccx: Arc<AMutex<AtCommandsContext>>,
     paths: Vec<String>,
     path_line_ranges: HashMap<String, Option<(usize, usize)>>,
+    path_page_ranges: HashMap<String, (usize, usize)>,
     arg_symbols: Vec<String>,
 ) -> (Vec<String>, Vec<String>, Vec<String>, Vec<ContextEnum>, Vec<MultimodalElement>)
 {
@@ -361,6 +377,7 @@ pub async fn paths_and_symbols_to_cat_with_path_ranges(
     for p in unique_paths.iter().filter(|x|!filenames_got_symbols_for.contains(x)) {
         let original_path = corrected_path_to_original.get(p).unwrap_or(p);
         let line_range = path_line_ranges.get(original_path).cloned().flatten();
+        let page_range = path_page_ranges.get(original_path).cloned();
         
         // don't have symbols for these, so we need to mention them as files, without a symbol, analog of @file
         let f_type = get_file_type(&PathBuf::from(p));
@@ -384,6 +401,16 @@ pub async fn paths_and_symbols_to_cat_with_path_ranges(
             match get_file_text_from_memory_or_disk(gcx.clone(), &PathBuf::from(p)).await {
                 Ok(text) => {
                     let total_lines = text.lines().count();
+                    let line_range = match page_range {
+                        Some((page1, page2)) => match document_page_range_to_lines(&text, page1, page2) {
+                            Some(lines) => Some(lines),
+                            None => {
+                                not_found_messages.push(format!("{}: there are no pages {}-{}, page ranges work for pdf, docx and odt", p, page1, page2));
+                                None
+                            }
+                        },
+                        None => line_range,
+                    };
                     let (start_line, end_line) = match line_range {
                         Some((start, end)) => {
                             let start = start.max(1);
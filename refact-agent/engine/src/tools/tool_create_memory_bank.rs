@@ -385,6 +385,9 @@ impl Tool for ToolCreateMemoryBank {
             ).await;
             ctx.subchat_tx = ccx_lock.subchat_tx.clone();
             ctx.subchat_rx = ccx_lock.subchat_rx.clone();
+            ctx.chat_mode = ccx_lock.chat_mode;
+            ctx.write_globs = ccx_lock.write_globs.clone();
+            ctx.current_tool = ccx_lock.current_tool.clone();
             Arc::new(AMutex::new(ctx))
         };
 
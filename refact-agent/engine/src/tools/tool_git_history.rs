@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde_json::Value;
+use async_trait::async_trait;
+use tokio::sync::Mutex as AMutex;
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::at_commands::at_diff::git_repos_in_workspace;
+use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_or_a_good_error};
+use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
+use crate::files_correction::{canonical_path, get_project_dirs, preprocess_path_for_normalization};
+use crate::git::history::{git_blame_as_string, git_log_as_string, git_show_as_string};
+use crate::postprocessing::pp_command_output::{output_mini_postprocessing, CmdlineOutputFilter};
+use crate::privacy::load_privacy_if_needed;
+use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+
+
+#[derive(Clone)]
+pub struct ToolGitHistory {
+    pub config_path: String,
+}
+
+const LOG_DEFAULT_COUNT: usize = 30;
+const SHOW_MAX_CHARS: usize = 100_000;
+
+fn parse_line_range(s: &str) -> Result<(usize, usize), String> {
+    let parse = |x: &str| x.trim().parse::<usize>().map_err(|_| format!("argument `lines` should look like 10-20, got {:?}", s));
+    match s.split_once('-') {
+        Some((line1, line2)) => {
+            let (line1, line2) = (parse(line1)?, parse(line2)?);
+            if line1 > line2 {
+                return Err(format!("Start line ({}) cannot be greater than end line ({})", line1, line2));
+            }
+            Ok((line1, line2))
+        },
+        None => parse(s).map(|n| (n, n)),
+    }
+}
+
+fn optional_string_arg(args: &HashMap<String, Value>, name: &str) -> Result<String, String> {
+    match args.get(name) {
+        Some(Value::String(s)) => Ok(s.trim().to_string()),
+        Some(Value::Null) | None => Ok(String::new()),
+        Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+    }
+}
+
+#[async_trait]
+impl Tool for ToolGitHistory {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: "git_history".to_string(),
+            display_name: "Git History".to_string(),
+            source: ToolSource {
+                source_type: ToolSourceType::Builtin,
+                config_path: self.config_path.clone(),
+            },
+            agentic: false,
+            experimental: false,
+            description: "Read-only git history: `log` lists commits (touching a file if path is given), `blame` shows who changed each line of a file, \
+                `show` prints a commit message and its diff. Use it to find out why and when code was changed.".to_string(),
+            parameters: vec![
+                ToolParam {
+                    name: "command".to_string(),
+                    description: "One of: log, blame, show".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "path".to_string(),
+                    description: "A file path, required for blame. For log and show it limits the output to this file.".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "rev".to_string(),
+                    description: "A commit, branch or tag, for example HEAD~3 or main. Defaults to HEAD.".to_string(),
+                    param_type: "string".to_string(),
+                },
+                ToolParam {
+                    name: "lines".to_string(),
+                    description: "Line range for blame, for example 10-20.".to_string(),
+                    param_type: "string".to_string(),
+                },
+            ],
+            parameters_required: vec!["command".to_string()],
+        }
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let command = optional_string_arg(args, "command")?;
+        let path_arg = optional_string_arg(args, "path")?;
+        let rev = optional_string_arg(args, "rev")?;
+        let lines = optional_string_arg(args, "lines")?;
+        let line_range = if lines.is_empty() { None } else { Some(parse_line_range(&lines)?) };
+
+        let gcx = ccx.lock().await.global_context.clone();
+        let file_path = if path_arg.is_empty() {
+            None
+        } else {
+            let path = if PathBuf::from(&path_arg).is_absolute() {
+                canonical_path(&path_arg).to_string_lossy().to_string()
+            } else {
+                preprocess_path_for_normalization(path_arg.clone())
+            };
+            let candidates = file_repair_candidates(gcx.clone(), &path, 10, false).await;
+            let project_dirs = get_project_dirs(gcx.clone()).await;
+            Some(PathBuf::from(return_one_candidate_or_a_good_error(gcx.clone(), &path, &candidates, &project_dirs, false).await?))
+        };
+
+        let repository = match &file_path {
+            Some(file_path) => git2::Repository::discover(file_path)
+                .map_err(|e| format!("{} is not in a git repository: {}", file_path.display(), e))?,
+            None => {
+                let mut repos = git_repos_in_workspace(gcx.clone()).await;
+                match repos.len() {
+                    0 => return Err("no git repositories in the workspace".to_string()),
+                    1 => repos.remove(0).1,
+                    _ => return Err(format!("there are several repositories in the workspace, pass `path` to pick one: {}",
+                        repos.iter().map(|(root, _)| root.display().to_string()).collect::<Vec<_>>().join(", "))),
+                }
+            }
+        };
+        let relative_path = match &file_path {
+            Some(file_path) => {
+                let workdir = repository.workdir().map(|w| canonical_path(w.to_string_lossy().to_string()))
+                    .ok_or("Repository has no working directory".to_string())?;
+                Some(file_path.strip_prefix(&workdir).map_err(|_| format!("{} is outside of {}", file_path.display(), workdir.display()))?.to_path_buf())
+            },
+            None => None,
+        };
+
+        let output = match command.as_str() {
+            "log" => git_log_as_string(&repository, &rev, relative_path.as_deref(), LOG_DEFAULT_COUNT)?,
+            "blame" => {
+                let relative_path = relative_path.ok_or("blame needs a `path`".to_string())?;
+                git_blame_as_string(&repository, &relative_path, line_range)?
+            },
+            "show" => git_show_as_string(&repository, &rev, relative_path.as_deref(), SHOW_MAX_CHARS)?,
+            _ => return Err(format!("unknown command {:?}, should be one of: log, blame, show", command)),
+        };
+        let filter = CmdlineOutputFilter {
+            grep: "".to_string(),
+            ..Default::default()
+        };
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+        let mut content = output_mini_postprocessing(&filter, &output, &privacy_settings.redactor);
+        if content.trim().is_empty() {
+            content = format!("git {}: nothing found", command);
+        }
+
+        Ok((false, vec![
+            ContextEnum::ChatMessage(ChatMessage {
+                role: "tool".to_string(),
+                content: ChatContent::SimpleText(content),
+                tool_calls: None,
+                tool_call_id: tool_call_id.clone(),
+                ..Default::default()
+            })
+        ]))
+    }
+}
@@ -11,6 +11,7 @@ use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 use crate::memories::memories_search;
 
 
+#[derive(Clone)]
 pub struct ToolGetKnowledge {
     pub config_path: String,
 }
@@ -20,6 +21,8 @@ pub struct ToolGetKnowledge {
 impl Tool for ToolGetKnowledge {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
             name: "knowledge".to_string(),
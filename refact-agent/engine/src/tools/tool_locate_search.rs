@@ -288,6 +288,9 @@ impl Tool for ToolLocateSearch {
             ).await;
             t.subchat_tx = ccx_lock.subchat_tx.clone();
             t.subchat_rx = ccx_lock.subchat_rx.clone();
+            t.chat_mode = ccx_lock.chat_mode;
+            t.write_globs = ccx_lock.write_globs.clone();
+            t.current_tool = ccx_lock.current_tool.clone();
             Arc::new(AMutex::new(t))
         };
 
@@ -4,15 +4,17 @@ use serde_json::Value;
 use tokio::fs;
 use std::io;
 use async_trait::async_trait;
-use tokio::sync::Mutex as AMutex;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use serde_json::json;
 
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::at_commands::at_file::return_one_candidate_or_a_good_error;
+use crate::at_commands::at_file::{resolve_existing_file_or_dir, return_one_candidate_or_a_good_error};
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, DiffChunk};
-use crate::files_correction::{canonical_path, correct_to_nearest_dir_path, correct_to_nearest_filename, get_project_dirs, preprocess_path_for_normalization};
-use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::files_correction::{canonical_path, correct_to_nearest_dir_path, get_project_dirs, preprocess_path_for_normalization};
+use crate::files_in_workspace::get_raw_file_text_from_memory_or_disk;
+use crate::global_context::GlobalContext;
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::tools::write_permissions::{check_write_permissions, resolve_path_for_write_check};
 use crate::integrations::integr_abstract::IntegrationConfirmation;
 use crate::privacy::{FilePrivacyLevel, load_privacy_if_needed, check_file_privacy};
 
@@ -25,6 +27,48 @@ impl ToolMv {
         path.trim_end_matches(&['/', '\\'][..]).to_string()
     }
 
+    fn path_arg(args: &HashMap<String, Value>, name: &str) -> Option<String> {
+        match args.get(name) {
+            Some(Value::String(s)) if !s.trim().is_empty() => Some(preprocess_path_for_normalization(Self::preformat_path(&s.trim().to_string()))),
+            _ => None,
+        }
+    }
+
+    // The destination doesn't exist yet, so only its parent directory is corrected.
+    async fn resolve_destination(
+        gcx: Arc<ARwLock<GlobalContext>>,
+        dst_str: &String,
+        top_n: usize,
+    ) -> Result<String, String> {
+        let dst_parent = if let Some(p) = std::path::Path::new(dst_str).parent() {
+            if cfg!(target_os = "windows") {
+                p.to_string_lossy().replace("/", "\\")
+            } else {
+                p.to_string_lossy().to_string()
+            }
+        } else { dst_str.clone() };
+
+        let project_dirs = get_project_dirs(gcx.clone()).await;
+        let dst_dir_candidates = correct_to_nearest_dir_path(gcx.clone(), &dst_parent, false, top_n).await;
+        let dst_parent_path = if !dst_dir_candidates.is_empty() {
+            return_one_candidate_or_a_good_error(
+                gcx.clone(),
+                &dst_parent,
+                &dst_dir_candidates,
+                &project_dirs,
+                true
+            ).await?
+        } else {
+            return Err(format!("Destination parent directory '{}' not found", dst_parent));
+        };
+
+        let dst_name = std::path::Path::new(dst_str)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(dst_str.clone());
+        Ok(format!("{}/{}", dst_parent_path.trim_end_matches('/'), dst_name))
+    }
+
     // Parse the overwrite flag.
     fn parse_overwrite(args: &HashMap<String, Value>) -> Result<bool, String> {
         match args.get("overwrite") {
@@ -49,69 +93,19 @@ impl Tool for ToolMv {
         tool_call_id: &String,
         args: &HashMap<String, Value>
     ) -> Result<(bool, Vec<ContextEnum>), String> {
-        let src_str = match args.get("source") {
-            Some(Value::String(s)) if !s.trim().is_empty() => Self::preformat_path(&s.trim().to_string()),
-            _ => return Err("Missing required argument `source`".to_string()),
-        };
-        let dst_str = match args.get("destination") {
-            Some(Value::String(s)) if !s.trim().is_empty() => Self::preformat_path(&s.trim().to_string()),
-            _ => return Err("Missing required argument `destination`".to_string()),
-        };
-        let src_str = preprocess_path_for_normalization(src_str);
-        let dst_str = preprocess_path_for_normalization(dst_str);
+        let src_str = Self::path_arg(args, "source").ok_or("Missing required argument `source`".to_string())?;
+        let dst_str = Self::path_arg(args, "destination").ok_or("Missing required argument `destination`".to_string())?;
         let overwrite = Self::parse_overwrite(args)?;
 
         let gcx = ccx.lock().await.global_context.clone();
+        let top_n = ccx.lock().await.top_n;
         let project_dirs = get_project_dirs(gcx.clone()).await;
 
-        let src_file_candidates = correct_to_nearest_filename(gcx.clone(), &src_str, false, ccx.lock().await.top_n).await;
-        let src_dir_candidates = correct_to_nearest_dir_path(gcx.clone(), &src_str, false, ccx.lock().await.top_n).await;
-        let (src_corrected_path, src_is_dir) = if !src_file_candidates.is_empty() {
-            (return_one_candidate_or_a_good_error(
-                gcx.clone(),
-                &src_str,
-                &src_file_candidates,
-                &project_dirs,
-                false
-            ).await?, false)
-        } else if !src_dir_candidates.is_empty() {
-            (return_one_candidate_or_a_good_error(
-                gcx.clone(),
-                &src_str,
-                &src_dir_candidates,
-                &project_dirs,
-                true
-            ).await?, true)
-        } else {
-            return Err(format!("Source path '{}' not found", src_str));
-        };
-
-        let dst_parent = if let Some(p) = std::path::Path::new(&dst_str).parent() {
-            if cfg!(target_os = "windows") {
-                p.to_string_lossy().replace("/", "\\")
-            } else {
-                p.to_string_lossy().to_string()
-            }
-        } else { dst_str.clone() };
-
-        let dst_dir_candidates = correct_to_nearest_dir_path(gcx.clone(), &dst_parent, false, ccx.lock().await.top_n).await;
-        let dst_parent_path = if !dst_dir_candidates.is_empty() {
-            return_one_candidate_or_a_good_error(
-                gcx.clone(),
-                &dst_parent,
-                &dst_dir_candidates,
-                &project_dirs,
-                true
-            ).await?
-        } else {
-            return Err(format!("Destination parent directory '{}' not found", dst_parent));
+        let (src_corrected_path, src_is_dir) = match resolve_existing_file_or_dir(gcx.clone(), &src_str, top_n).await? {
+            Some(resolved) => resolved,
+            None => return Err(format!("Source path '{}' not found", src_str)),
         };
-
-        let dst_name = std::path::Path::new(&dst_str)
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or(dst_str.clone());
-        let dst_corrected_path = format!("{}/{}", dst_parent_path.trim_end_matches('/'), dst_name);
+        let dst_corrected_path = Self::resolve_destination(gcx.clone(), &dst_str, top_n).await?;
 
         let src_true_path = canonical_path(&src_corrected_path);
         let dst_true_path = canonical_path(&dst_corrected_path);
@@ -146,7 +140,7 @@ impl Tool for ToolMv {
 
         let mut src_file_content = String::new();
         if !src_is_dir {
-            src_file_content = get_file_text_from_memory_or_disk(gcx.clone(), &src_true_path).await?;
+            src_file_content = get_raw_file_text_from_memory_or_disk(gcx.clone(), &src_true_path).await?;
         }
         let mut dst_file_content = String::new();
         if let Ok(dst_metadata) = fs::metadata(&dst_true_path).await {
@@ -318,6 +312,27 @@ impl Tool for ToolMv {
         ccx: Arc<AMutex<AtCommandsContext>>,
         args: &HashMap<String, Value>,
     ) -> Result<MatchConfirmDeny, String> {
+        let (gcx, top_n) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.top_n)
+        };
+        // check the paths tool_execute() will actually touch, fuzzy resolution included
+        let mut paths = vec![];
+        if let Some(src_str) = Self::path_arg(args, "source") {
+            paths.push(match resolve_existing_file_or_dir(gcx.clone(), &src_str, top_n).await {
+                Ok(Some((src_corrected_path, _))) => canonical_path(&src_corrected_path),
+                _ => resolve_path_for_write_check(gcx.clone(), &src_str).await,
+            });
+        }
+        if let Some(dst_str) = Self::path_arg(args, "destination") {
+            paths.push(match Self::resolve_destination(gcx.clone(), &dst_str, top_n).await {
+                Ok(dst_corrected_path) => canonical_path(&dst_corrected_path),
+                Err(_) => resolve_path_for_write_check(gcx.clone(), &dst_str).await,
+            });
+        }
+        if let Some(res) = check_write_permissions(ccx.clone(), "mv", &paths).await {
+            return Ok(res);
+        }
         let command_to_match = self.command_to_match_against_confirm_deny(ccx.clone(), &args).await.map_err(|e| {
             format!("Error getting tool command to match: {}", e)
         })?;
@@ -20,6 +20,7 @@ use crate::global_context::GlobalContext;
 use crate::tools::scope_utils::{resolve_scope, validate_scope_files};
 use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
 
+#[derive(Clone)]
 pub struct ToolRegexSearch {
     pub config_path: String,
 }
@@ -166,6 +167,8 @@ async fn smart_compress_results(
 #[async_trait]
 impl Tool for ToolRegexSearch {
     fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
     
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
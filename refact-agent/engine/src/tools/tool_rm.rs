@@ -7,12 +7,13 @@ use tokio::sync::Mutex as AMutex;
 use serde_json::json;
 
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::at_commands::at_file::return_one_candidate_or_a_good_error;
+use crate::at_commands::at_file::resolve_existing_file_or_dir;
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, DiffChunk};
-use crate::files_correction::{canonical_path, correct_to_nearest_dir_path, correct_to_nearest_filename, get_project_dirs, preprocess_path_for_normalization};
-use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::files_correction::{canonical_path, get_project_dirs, preprocess_path_for_normalization};
+use crate::files_in_workspace::get_raw_file_text_from_memory_or_disk;
 use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel};
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::tools::write_permissions::{check_write_permissions, resolve_path_for_write_check};
 use crate::integrations::integr_abstract::IntegrationConfirmation;
 
 pub struct ToolRm {
@@ -24,6 +25,13 @@ impl ToolRm {
         path.trim_end_matches(&['/', '\\'][..]).to_string()
     }
 
+    fn path_arg(args: &HashMap<String, Value>) -> Option<String> {
+        match args.get("path") {
+            Some(Value::String(s)) if !s.trim().is_empty() => Some(preprocess_path_for_normalization(Self::preformat_path(&s.trim().to_string()))),
+            _ => None,
+        }
+    }
+
     fn parse_recursive(args: &HashMap<String, Value>) -> Result<(bool, Option<u32>, bool), String> {
         let recursive = match args.get("recursive") {
             Some(Value::Bool(b)) => *b,
@@ -79,6 +87,20 @@ impl Tool for ToolRm {
         ccx: Arc<AMutex<AtCommandsContext>>,
         args: &HashMap<String, Value>,
     ) -> Result<MatchConfirmDeny, String> {
+        let (gcx, top_n) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.top_n)
+        };
+        if let Some(path_str) = Self::path_arg(args) {
+            // fuzzy resolution may turn a bare name into a path under a denied directory, check that one
+            let path = match resolve_existing_file_or_dir(gcx.clone(), &path_str, top_n).await {
+                Ok(Some((corrected_path, _))) => canonical_path(&corrected_path),
+                _ => resolve_path_for_write_check(gcx.clone(), &path_str).await,
+            };
+            if let Some(res) = check_write_permissions(ccx.clone(), "rm", &[path]).await {
+                return Ok(res);
+            }
+        }
         let command_to_match = self.command_to_match_against_confirm_deny(ccx.clone(), &args).await.map_err(|e| {
             format!("Error getting tool command to match: {}", e)
         })?;
@@ -96,11 +118,7 @@ impl Tool for ToolRm {
         args: &HashMap<String, Value>
     ) -> Result<(bool, Vec<ContextEnum>), String> {
         // Get "path" argument.
-        let path_str = match args.get("path") {
-            Some(Value::String(s)) if !s.trim().is_empty() => Self::preformat_path(&s.trim().to_string()),
-            _ => return Err("Missing required argument `path`".to_string()),
-        };
-        let path_str = preprocess_path_for_normalization(path_str);
+        let path_str = Self::path_arg(args).ok_or("Missing required argument `path`".to_string())?;
 
         // Reject if wildcards are present, '?' is allowed if preceeded by '\' or '/' only, like \\?\C:\Some\Path
         if path_str.contains('*') || path_str.contains('[') ||
@@ -115,26 +133,10 @@ impl Tool for ToolRm {
         let project_dirs = get_project_dirs(gcx.clone()).await;
 
         // Use file correction to get a candidate path.
-        let file_candidates = correct_to_nearest_filename(gcx.clone(), &path_str, false, ccx.lock().await.top_n).await;
-        let dir_candidates = correct_to_nearest_dir_path(gcx.clone(), &path_str, false, ccx.lock().await.top_n).await;
-        let corrected_path = if !file_candidates.is_empty() {
-            return_one_candidate_or_a_good_error(
-                gcx.clone(),
-                &path_str,
-                &file_candidates,
-                &project_dirs,
-                false
-            ).await?
-        } else if !dir_candidates.is_empty() {
-            return_one_candidate_or_a_good_error(
-                gcx.clone(),
-                &path_str,
-                &dir_candidates,
-                &project_dirs,
-                true
-            ).await?
-        } else {
-            return Err(format!("Path '{}' not found", path_str));
+        let top_n = ccx.lock().await.top_n;
+        let corrected_path = match resolve_existing_file_or_dir(gcx.clone(), &path_str, top_n).await? {
+            Some((path, _)) => path,
+            None => return Err(format!("Path '{}' not found", path_str)),
         };
 
         let true_path = canonical_path(&corrected_path);
@@ -173,7 +175,7 @@ impl Tool for ToolRm {
         let mut file_size = None;
         let is_dir = true_path.is_dir();
         if !is_dir {
-            file_content = match get_file_text_from_memory_or_disk(gcx.clone(), &true_path).await {
+            file_content = match get_raw_file_text_from_memory_or_disk(gcx.clone(), &true_path).await {
                 Ok(content) => content,
                 Err(e) => {
                     tracing::warn!("Failed to get file content: {}", e);
@@ -298,3 +300,37 @@ impl Tool for ToolRm {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files_correction::canonicalize_normalized_path;
+    use crate::global_context::create_test_context;
+
+    #[tokio::test]
+    async fn test_rm_notebook_can_be_undone() {
+        let workspace = tempfile::tempdir().unwrap();
+        let project = canonicalize_normalized_path(workspace.path().to_path_buf());
+        let notebook = project.join("analysis.ipynb");
+        let notebook_json = r##"{"cells": [{"cell_type": "code", "metadata": {}, "source": ["print(1)\n"], "outputs": [], "execution_count": null}], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}
+"##;
+        std::fs::write(&notebook, notebook_json).unwrap();
+
+        let t = create_test_context(&[], vec![]).await;
+        t.set_workspace(vec![project.clone()], vec![notebook.clone()]).await;
+
+        let args = HashMap::from([("path".to_string(), json!(notebook.to_string_lossy()))]);
+        let (_, messages) = ToolRm { config_path: "".to_string() }.tool_execute(t.ccx.clone(), &"call_1".to_string(), &args).await.unwrap();
+        assert!(!notebook.exists());
+        let diff_message = messages.iter().find_map(|m| match m {
+            ContextEnum::ChatMessage(m) if m.role == "diff" => Some(m.content.content_text_only()),
+            _ => None,
+        }).unwrap();
+        let chunks: Vec<DiffChunk> = serde_json::from_str(&diff_message).unwrap();
+
+        // undo writes lines_remove back, that has to be the notebook JSON, not the python view
+        std::fs::write(&notebook, &chunks[0].lines_remove).unwrap();
+        assert_eq!(std::fs::read_to_string(&notebook).unwrap(), notebook_json);
+        assert!(crate::notebooks::notebook_parse(&chunks[0].lines_remove).is_ok());
+    }
+}
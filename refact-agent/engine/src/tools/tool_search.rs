@@ -14,6 +14,7 @@ use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, Too
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
 
 
+#[derive(Clone)]
 pub struct ToolSearch {
     pub config_path: String,
 }
@@ -36,6 +37,8 @@ async fn execute_att_search(
 impl Tool for ToolSearch {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
             name: "search_semantic".to_string(),
@@ -120,7 +123,10 @@ impl Tool for ToolSearch {
                     all_content.push_str(&format!("{}:\n", rec.file_name.clone()));
                     let file_recs = file_results_to_reqs.get(&rec.file_name).unwrap();
                     for file_req in file_recs.iter().sorted_by(|rec1, rec2| rec2.usefulness.total_cmp(&rec1.usefulness)) {
-                        all_content.push_str(&format!("    lines {}-{} score {:.1}%\n", file_req.line1, file_req.line2, file_req.usefulness));
+                        match file_req.symbols.first() {
+                            Some(symbol) => all_content.push_str(&format!("    {} lines {}-{} score {:.1}%\n", symbol, file_req.line1, file_req.line2, file_req.usefulness)),
+                            None => all_content.push_str(&format!("    lines {}-{} score {:.1}%\n", file_req.line1, file_req.line2, file_req.usefulness)),
+                        }
                     }
                     used_files.insert(rec.file_name.clone());
                 }
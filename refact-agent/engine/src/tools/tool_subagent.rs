@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde_json::Value;
+use tokio::sync::Mutex as AMutex;
+use async_trait::async_trait;
+
+use crate::subchat::subchat;
+use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, ToolSourceType};
+use crate::call_validation::{ChatMessage, ChatContent, ChatUsage, ContextEnum};
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::yaml_configs::customization_loader::SubagentConfig;
+
+pub struct ToolSubagent {
+    pub config_path: String,
+    pub name: String,
+    pub subagent: SubagentConfig,
+}
+
+const SUBAGENT_WRAP_UP: &str = "Stop here and don't call any tools anymore. Report the result of your work: what you did, what you found, \
+what is left undone. The caller sees only this report, not the conversation above.";
+
+fn wrap_up_prompt(output_schema: &Option<Value>) -> String {
+    match output_schema {
+        Some(schema) => format!(
+            "Stop here and don't call any tools anymore. Report the result as a single JSON object that follows this schema:\n```json\n{}\n```",
+            serde_json::to_string_pretty(schema).unwrap_or_default(),
+        ),
+        None => SUBAGENT_WRAP_UP.to_string(),
+    }
+}
+
+/// Only the top level: the type and required fields, that's enough to catch a model that ignored the schema
+fn check_output(output: &Value, schema: &Value) -> Result<(), String> {
+    if schema.get("type").and_then(|t| t.as_str()) == Some("object") && !output.is_object() {
+        return Err("the answer is not a JSON object".to_string());
+    }
+    let missing: Vec<&str> = schema.get("required").and_then(|r| r.as_array()).into_iter().flatten()
+        .filter_map(|key| key.as_str())
+        .filter(|key| output.get(key).is_none())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("the answer has no required fields {:?}", missing));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Tool for ToolSubagent {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: self.name.clone(),
+            display_name: self.name.clone(),
+            source: ToolSource {
+                source_type: ToolSourceType::Builtin,
+                config_path: self.config_path.clone(),
+            },
+            agentic: true,
+            experimental: false,
+            description: self.subagent.description.clone(),
+            parameters: vec![
+                ToolParam {
+                    name: "task".to_string(),
+                    param_type: "string".to_string(),
+                    description: "What to do, with everything the subagent needs to know: it doesn't see this conversation.".to_string(),
+                },
+            ],
+            parameters_required: vec!["task".to_string()],
+        }
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let task = match args.get("task") {
+            Some(Value::String(s)) if !s.trim().is_empty() => s.clone(),
+            Some(v) => return Err(format!("argument `task` is not a string: {:?}", v)),
+            None => return Err("Missing argument `task`".to_string())
+        };
+
+        let calling_subagent = ccx.lock().await.subagent.clone();
+        if !calling_subagent.is_empty() {
+            return Err(format!("{} can't be called from the subagent {}, subagents don't call other subagents", self.name, calling_subagent));
+        }
+
+        let params = match ccx.lock().await.subchat_tool_parameters.get(&self.name).cloned() {
+            Some(params) => params,  // the request can override the model like for any other subchat tool
+            None => self.subagent.subchat_parameters(),
+        };
+        let params = crate::tools::tools_execute::resolve_subchat_model(ccx.clone(), params).await?;
+
+        let ccx_subchat = {
+            let ccx_lock = ccx.lock().await;
+            let mut t = AtCommandsContext::new(
+                ccx_lock.global_context.clone(),
+                params.subchat_n_ctx,
+                8,
+                false,
+                vec![],
+                ccx_lock.chat_id.clone(),
+                ccx_lock.should_execute_remotely,
+                params.subchat_model.clone(),
+            ).await;
+            t.subchat_tx = ccx_lock.subchat_tx.clone();
+            t.subchat_rx = ccx_lock.subchat_rx.clone();
+            t.chat_mode = ccx_lock.chat_mode;
+            t.write_globs = ccx_lock.write_globs.clone();
+            t.current_tool = ccx_lock.current_tool.clone();
+            t.subagent = self.name.clone();
+            t.subagent_skips_confirmation = self.subagent.run_tools_needing_confirmation;
+            Arc::new(AMutex::new(t))
+        };
+
+        // it would be refused anyway
+        let tools: Vec<String> = self.subagent.tools.iter().filter(|t| **t != self.name).cloned().collect();
+        let log_prefix = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let messages = vec![
+            ChatMessage::new("system".to_string(), self.subagent.prompt.clone()),
+            ChatMessage::new("user".to_string(), task),
+        ];
+        let result = subchat(
+            ccx_subchat,
+            params.subchat_model.as_str(),
+            messages,
+            tools,
+            self.subagent.max_steps,
+            params.subchat_n_ctx,
+            &wrap_up_prompt(&self.subagent.output_schema),
+            1,
+            params.subchat_temperature,
+            params.subchat_reasoning_effort.clone(),
+            Some(tool_call_id.clone()),
+            Some(format!("{log_prefix}-{}", self.name)),
+            Some(false),
+        ).await?[0].clone();
+
+        let last_message = result.last().ok_or(format!("{} returned nothing", self.name))?;
+        let mut usage = ChatUsage { ..Default::default() };
+        crate::tools::tools_execute::update_usage_from_message(&mut usage, last_message);
+        let answer = last_message.content.content_text_only();
+        let tool_message = match &self.subagent.output_schema {
+            Some(schema) => {
+                let output = crate::json_utils::extract_json_object::<Value>(&answer).map_err(|e| {
+                    tracing::warn!("\n{}\nUnable to parse JSON: {:?}", answer, e);
+                    format!("{} didn't answer with JSON: {}", self.name, e)
+                })?;
+                check_output(&output, schema).map_err(|e| format!("{}: {}", self.name, e))?;
+                serde_json::to_string_pretty(&output).unwrap()
+            }
+            None => answer,
+        };
+        tracing::info!("{} done after {} messages", self.name, result.len());
+
+        Ok((false, vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(tool_message),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            usage: Some(usage),
+            ..Default::default()
+        })]))
+    }
+
+    fn tool_depends_on(&self) -> Vec<String> {
+        vec![]
+    }
+}
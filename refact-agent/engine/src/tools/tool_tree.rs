@@ -14,6 +14,7 @@ use crate::files_correction::{correct_to_nearest_dir_path, correct_to_nearest_fi
 use crate::files_in_workspace::ls_files;
 
 
+#[derive(Clone)]
 pub struct ToolTree {
     pub config_path: String,
 }
@@ -26,6 +27,8 @@ fn preformat_path(path: &String) -> String {
 impl Tool for ToolTree {
     fn as_any(&self) -> &dyn std::any::Any { self }
 
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
+
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
             name: "tree".to_string(),
@@ -10,6 +10,7 @@ use crate::tools::tools_description::{Tool, ToolDesc, ToolParam, ToolSource, Too
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 
 
+#[derive(Clone)]
 pub struct ToolWeb {
     pub config_path: String,
 }
@@ -17,6 +18,8 @@ pub struct ToolWeb {
 #[async_trait]
 impl Tool for ToolWeb {
     fn as_any(&self) -> &dyn std::any::Any { self }
+
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { Some(Box::new(self.clone())) }
     
     fn tool_description(&self) -> ToolDesc {
         ToolDesc {
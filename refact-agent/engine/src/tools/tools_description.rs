@@ -187,6 +187,10 @@ pub trait Tool: Send + Sync {
 
     fn tool_depends_on(&self) -> Vec<String> { vec![] }   // "ast", "vecdb"
 
+    /// Tools without side effects return a fresh copy of themselves: calls to them in one assistant message
+    /// run concurrently, each on its own copy. Tools that change anything return None and run one at a time.
+    fn read_only_copy(&self) -> Option<Box<dyn Tool + Send>> { None }
+
     fn usage(&mut self) -> &mut Option<ChatUsage> {
         static mut DEFAULT_USAGE: Option<ChatUsage> = None;
         #[allow(static_mut_refs)]
@@ -1,11 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use indexmap::IndexMap;
 
 use tokio::sync::RwLock as ARwLock;
 
-use crate::call_validation::ChatMode;
+use crate::call_validation::{ChatMeta, ChatMode};
 use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
 use crate::integrations::running_integrations::load_integrations;
+use crate::yaml_configs::customization_loader::load_chat_mode;
 
 use super::tools_description::{Tool, ToolGroup, ToolGroupCategory};
 
@@ -88,6 +90,7 @@ async fn get_builtin_tools(
         Box::new(crate::tools::tool_cat::ToolCat{config_path: config_path.clone()}),
         Box::new(crate::tools::tool_regex_search::ToolRegexSearch{config_path: config_path.clone()}),
         Box::new(crate::tools::tool_search::ToolSearch{config_path: config_path.clone()}),
+        Box::new(crate::tools::tool_git_history::ToolGitHistory{config_path: config_path.clone()}),
         // Box::new(crate::tools::tool_locate_search::ToolLocateSearch{config_path: config_path.clone()}),
     ];
 
@@ -95,6 +98,7 @@ async fn get_builtin_tools(
         Box::new(crate::tools::file_edit::tool_create_textdoc::ToolCreateTextDoc{config_path: config_path.clone()}),
         Box::new(crate::tools::file_edit::tool_update_textdoc::ToolUpdateTextDoc{config_path: config_path.clone()}),
         Box::new(crate::tools::file_edit::tool_update_textdoc_regex::ToolUpdateTextDocRegex{config_path: config_path.clone()}),
+        Box::new(crate::tools::file_edit::tool_notebook_edit::ToolNotebookEdit{config_path: config_path.clone()}),
         Box::new(crate::tools::tool_rm::ToolRm{config_path: config_path.clone()}),
         Box::new(crate::tools::tool_mv::ToolMv{config_path: config_path.clone()}),
     ];
@@ -146,6 +150,34 @@ async fn get_builtin_tools(
         },
     ];
 
+    let subagents = crate::yaml_configs::customization_loader::load_subagents(gcx.clone()).await;
+    let builtin_names: HashSet<String> = tool_groups.iter()
+        .flat_map(|g| g.tools.iter().map(|t| t.tool_description().name))
+        .collect();
+    let subagent_tools: Vec<Box<dyn Tool + Send>> = subagents.into_iter()
+        .filter(|(name, _)| {
+            if builtin_names.contains(name) {
+                tracing::error!("subagent {:?} is skipped, there is a built-in tool with the same name", name);
+                return false;
+            }
+            true
+        })
+        .map(|(name, subagent)| {
+            Box::new(crate::tools::tool_subagent::ToolSubagent {
+                config_path: config_path.clone(),
+                name,
+                subagent,
+            }) as Box<dyn Tool + Send>
+        }).collect();
+    if !subagent_tools.is_empty() {
+        tool_groups.push(ToolGroup {
+            name: "Subagents".to_string(),
+            description: "Subagents from customization.yaml".to_string(),
+            category: ToolGroupCategory::Builtin,
+            tools: subagent_tools,
+        });
+    }
+
     for tool_group in tool_groups.iter_mut() {
         tool_group.retain_available_tools(gcx.clone()).await;
     }
@@ -253,3 +285,67 @@ pub async fn get_available_tools_by_chat_mode(
     }
 }
 
+/// "codebase_search" and "Codebase Search" both name the same group in chat mode configs
+fn tool_group_name_matches(group_name: &str, wanted: &str) -> bool {
+    let normalize = |s: &str| s.trim().to_lowercase().replace([' ', '-'], "_");
+    normalize(group_name) == normalize(wanted)
+}
+
+pub async fn get_available_tools_by_chat_meta(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    chat_meta: &ChatMeta,
+) -> Vec<Box<dyn Tool + Send>> {
+    if chat_meta.custom_mode.is_empty() {
+        return get_available_tools_by_chat_mode(gcx, chat_meta.chat_mode).await;
+    }
+    get_available_tools_by_custom_mode(gcx, &chat_meta.custom_mode).await
+}
+
+/// Tools the chat is allowed to run, by name. A custom mode limits them to the ones it offers to the model,
+/// without it every tool can run, as before chat modes.
+pub async fn get_tools_to_execute(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    custom_mode: &str,
+) -> IndexMap<String, Box<dyn Tool + Send>> {
+    let tools = if custom_mode.is_empty() {
+        get_available_tools(gcx).await
+    } else {
+        get_available_tools_by_custom_mode(gcx, custom_mode).await
+    };
+    tools.into_iter().map(|tool| (tool.tool_description().name, tool)).collect()
+}
+
+async fn get_available_tools_by_custom_mode(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    custom_mode: &str,
+) -> Vec<Box<dyn Tool + Send>> {
+    let mode = match load_chat_mode(gcx.clone(), custom_mode).await {
+        Ok(mode) => mode,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return vec![];
+        }
+    };
+    if mode.tools.is_empty() {
+        return get_available_tools_by_chat_mode(gcx, mode.base_mode).await;
+    }
+
+    let mut allowed = HashSet::new();
+    let mut used = HashSet::new();
+    for group in get_available_tool_groups(gcx.clone()).await {
+        for tool in group.tools.iter() {
+            let tool_name = tool.tool_description().name;
+            for wanted in mode.tools.iter().filter(|x| **x == tool_name || tool_group_name_matches(&group.name, x)) {
+                allowed.insert(tool_name.clone());
+                used.insert(wanted.clone());
+            }
+        }
+    }
+    for wanted in mode.tools.iter().filter(|x| !used.contains(*x)) {
+        tracing::warn!("chat mode {:?}: there is no tool or tool group {:?}", custom_mode, wanted);
+    }
+
+    get_available_tools_by_chat_mode(gcx, mode.base_mode).await.into_iter()
+        .filter(|tool| allowed.contains(&tool.tool_description().name))
+        .collect()
+}
@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::Deserialize;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tracing::warn;
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::files_correction::{canonicalize_normalized_path, get_project_dirs, preprocess_path_for_normalization, project_glob_matches};
+use crate::global_context::GlobalContext;
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult};
+
+// Where the agent may write, per project, in <project>/.refact/write_permissions.yaml:
+//
+//   deny: ["migrations/**", "*.lock", ".github/**"]
+//   ask_user: ["Cargo.toml"]
+//   allow: ["docs/**", "*.md"]      # if not empty, everything else is denied
+//
+// Globs are relative to the project root. File edits, rm and mv check every path they write to, the first rule that
+// matches turns into DENY or CONFIRMATION in match_against_confirm_deny. The file is read on every check.
+//
+// A custom chat mode can narrow it down further with write_globs, those work like allow in every project, and paths
+// outside of the projects are denied.
+
+pub const WRITE_PERMISSIONS_FILE: &str = ".refact/write_permissions.yaml";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct WritePermissions {
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub ask_user: Vec<String>,
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl WritePermissions {
+    /// Returns the verdict and the rule that decided it, None if no rule says anything about the path
+    pub fn check(&self, relative: &Path) -> Option<(MatchConfirmDenyResult, String)> {
+        if let Some(glob) = self.deny.iter().find(|g| project_glob_matches(g, relative)) {
+            return Some((MatchConfirmDenyResult::DENY, format!("deny {}", glob)));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|g| project_glob_matches(g, relative)) {
+            return Some((MatchConfirmDenyResult::DENY, format!("not in allow {:?}", self.allow)));
+        }
+        if let Some(glob) = self.ask_user.iter().find(|g| project_glob_matches(g, relative)) {
+            return Some((MatchConfirmDenyResult::CONFIRMATION, format!("ask_user {}", glob)));
+        }
+        None
+    }
+}
+
+fn load_write_permissions(project_dir: &Path) -> Option<WritePermissions> {
+    let path = project_dir.join(WRITE_PERMISSIONS_FILE);
+    let content = std::fs::read_to_string(&path).ok()?;
+    match serde_yaml::from_str::<WritePermissions>(&content) {
+        Ok(permissions) => Some(permissions),
+        Err(e) => {
+            // a broken file should not quietly allow everything
+            warn!("cannot parse {:?}, denying all writes in that project: {}", path, e);
+            Some(WritePermissions { deny: vec!["**".to_string()], ..Default::default() })
+        }
+    }
+}
+
+/// For tools that take a raw path argument: relative paths are relative to a project dir
+pub async fn resolve_path_for_write_check(gcx: Arc<ARwLock<GlobalContext>>, raw_path: &str) -> PathBuf {
+    let path = PathBuf::from(preprocess_path_for_normalization(raw_path.trim().to_string()));
+    if path.is_absolute() {
+        return canonicalize_normalized_path(path);
+    }
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    let candidate = project_dirs.iter().map(|d| d.join(&path)).find(|p| p.exists())
+        .or(project_dirs.first().map(|d| d.join(&path)))
+        .unwrap_or(path);
+    canonicalize_normalized_path(candidate)
+}
+
+/// The strictest verdict over all the paths a tool call writes to: DENY beats CONFIRMATION
+pub async fn check_write_permissions(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    command: &str,
+    paths: &[PathBuf],
+) -> Option<MatchConfirmDeny> {
+    let (gcx, write_globs) = {
+        let ccx_locked = ccx.lock().await;
+        (ccx_locked.global_context.clone(), ccx_locked.write_globs.clone())
+    };
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    let mut verdict: Option<MatchConfirmDeny> = None;
+    for path in paths {
+        let project_dir = project_dirs.iter().filter(|d| path.starts_with(d)).max_by_key(|d| d.components().count());
+        if !write_globs.is_empty() {
+            let relative = project_dir.and_then(|d| path.strip_prefix(d).ok());
+            if !relative.map(|r| write_globs.iter().any(|g| project_glob_matches(g, r))).unwrap_or(false) {
+                return Some(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::DENY,
+                    command: format!("{} {}", command, path.display()),
+                    rule: format!("not in write_globs {:?} of the chat mode", write_globs),
+                });
+            }
+        }
+        let project_dir = match project_dir {
+            Some(project_dir) => project_dir,
+            None => continue,
+        };
+        let permissions = match load_write_permissions(project_dir) {
+            Some(permissions) => permissions,
+            None => continue,
+        };
+        let relative = path.strip_prefix(project_dir).unwrap_or(path);
+        if let Some((result, rule)) = permissions.check(relative) {
+            let is_deny = matches!(result, MatchConfirmDenyResult::DENY);
+            verdict = Some(MatchConfirmDeny {
+                result,
+                command: format!("{} {}", command, path.display()),
+                rule: format!("{} in {}", rule, project_dir.join(WRITE_PERMISSIONS_FILE).display()),
+            });
+            if is_deny {
+                break;
+            }
+        }
+    }
+    verdict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::global_context::create_test_context;
+    use crate::tools::tool_mv::ToolMv;
+    use crate::tools::tool_rm::ToolRm;
+    use crate::tools::tools_description::Tool;
+
+    #[test]
+    fn test_write_permissions() {
+        let permissions: WritePermissions = serde_yaml::from_str(r#"
+deny: ["migrations/**", "*.lock", ".github/**"]
+ask_user: ["Cargo.toml"]
+"#).unwrap();
+        let verdict = |p: &str| permissions.check(Path::new(p)).map(|(result, _)| format!("{:?}", result));
+        assert_eq!(verdict("migrations/0001_init.sql"), Some("DENY".to_string()));
+        assert_eq!(verdict("migrations"), Some("DENY".to_string()));
+        assert_eq!(verdict("frontend/package.lock"), Some("DENY".to_string()));
+        assert_eq!(verdict(".github/workflows/ci.yml"), Some("DENY".to_string()));
+        assert_eq!(verdict("engine/Cargo.toml"), Some("CONFIRMATION".to_string()));
+        assert_eq!(verdict("src/main.rs"), None);
+
+        let docs_only: WritePermissions = serde_yaml::from_str("allow: [\"*.md\"]\ndeny: [\"CHANGELOG.md\"]").unwrap();
+        assert!(docs_only.check(Path::new("docs/intro.md")).is_none());
+        assert!(docs_only.check(Path::new("src/lib.rs")).is_some());
+        assert!(docs_only.check(Path::new("CHANGELOG.md")).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_bare_file_name_is_checked_where_it_resolves() {
+        let workspace = tempfile::tempdir().unwrap();
+        let project = canonicalize_normalized_path(workspace.path().to_path_buf());
+        let migration = project.join("migrations").join("0001_init.sql");
+        std::fs::create_dir_all(project.join(".refact")).unwrap();
+        std::fs::create_dir_all(migration.parent().unwrap()).unwrap();
+        std::fs::write(&migration, "CREATE TABLE t (id INT);\n").unwrap();
+        std::fs::write(project.join(WRITE_PERMISSIONS_FILE), "deny: [\"migrations/**\"]\n").unwrap();
+
+        let t = create_test_context(&[], vec![]).await;
+        t.set_workspace(vec![project.clone()], vec![migration.clone()]).await;
+
+        // there is no 0001_init.sql in the project root, fuzzy resolution finds the one under migrations/
+        let rm_args = HashMap::from([("path".to_string(), serde_json::json!("0001_init.sql"))]);
+        let rm = ToolRm { config_path: "".to_string() };
+        let verdict = rm.match_against_confirm_deny(t.ccx.clone(), &rm_args).await.unwrap();
+        assert!(matches!(verdict.result, MatchConfirmDenyResult::DENY), "{:?}", verdict.rule);
+
+        let mv_args = HashMap::from([
+            ("source".to_string(), serde_json::json!("0001_init.sql")),
+            ("destination".to_string(), serde_json::json!("init.sql")),
+        ]);
+        let mv = ToolMv { config_path: "".to_string() };
+        let verdict = mv.match_against_confirm_deny(t.ccx.clone(), &mv_args).await.unwrap();
+        assert!(matches!(verdict.result, MatchConfirmDenyResult::DENY), "{:?}", verdict.rule);
+    }
+
+    #[tokio::test]
+    async fn test_chat_mode_write_globs() {
+        let workspace = tempfile::tempdir().unwrap();
+        let project = canonicalize_normalized_path(workspace.path().to_path_buf());
+        let t = create_test_context(&[], vec![]).await;
+        t.set_workspace(vec![project.clone()], vec![]).await;
+        t.ccx.lock().await.write_globs = vec!["*.md".to_string()];
+
+        let verdict = |p: PathBuf| {
+            let ccx = t.ccx.clone();
+            async move { check_write_permissions(ccx, "rm", &[p]).await.map(|v| format!("{:?}", v.result)) }
+        };
+        assert_eq!(verdict(project.join("docs").join("intro.md")).await, None);
+        assert_eq!(verdict(project.join("src").join("lib.rs")).await, Some("DENY".to_string()));
+        assert_eq!(verdict(t.dir.path().join("notes.md")).await, Some("DENY".to_string()));
+    }
+}
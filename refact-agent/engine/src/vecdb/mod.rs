@@ -6,4 +6,5 @@ pub mod vdb_sqlite;
 pub mod vdb_thread;
 pub mod vdb_emb_aux;
 pub mod vdb_error;
-pub mod vdb_init;
\ No newline at end of file
+pub mod vdb_init;
+pub mod vdb_rerank;
\ No newline at end of file
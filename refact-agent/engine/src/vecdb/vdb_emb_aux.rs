@@ -21,6 +21,41 @@ pub fn create_emb_table_name(workspace_folders: &Vec<String>) -> String {
     format!("emb_{}_{}", hash, now.format("%Y%m%d_%H%M%S"))
 }
 
+fn emb_table_prefix(emb_table_name: &str) -> &str {
+    // emb_{hash}_{date}_{time}
+    let mut parts = emb_table_name.rsplitn(3, '_');
+    let (_time, _date) = (parts.next(), parts.next());
+    parts.next().unwrap_or(emb_table_name)
+}
+
+/// The latest table made for the same workspace and index by this version of vecdb (older versions
+/// didn't register their tables in vecdb_tables, and their schema may differ)
+pub async fn find_reusable_emb_table(conn: &Connection, emb_table_name: &String) -> Result<Option<String>, String> {
+    let prefix = format!("{}_", emb_table_prefix(emb_table_name));
+    conn.call(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT t.name FROM vecdb_tables t JOIN sqlite_master m ON m.name = t.name \
+            WHERE substr(t.name, 1, length(?1)) = ?1 ORDER BY t.name DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query(rusqlite::params![prefix])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }).await.map_err(|e| e.to_string())
+}
+
+pub async fn mark_emb_table_used(conn: &Connection, emb_table_name: &String) -> Result<(), String> {
+    let emb_table_name = emb_table_name.clone();
+    conn.call(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO vecdb_tables (name, last_used_ts) VALUES (?1, ?2)",
+            rusqlite::params![emb_table_name, Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }).await.map_err(|e| e.to_string())
+}
+
 fn parse_table_timestamp(table_name: &str) -> Option<DateTime<Utc>> {
     let parts: Vec<&str> = table_name.split('_').collect();
     if parts.len() >= 3 {
@@ -42,6 +77,15 @@ fn parse_table_timestamp(table_name: &str) -> Option<DateTime<Utc>> {
     None
 }
 
+fn drop_emb_table(conn: &rusqlite::Connection, name: &String) -> rusqlite::Result<()> {
+    conn.execute(&format!("DROP TABLE {}", name), [])?;
+    for metadata_table in ["vecdb_files", "vecdb_queue"] {
+        conn.execute(&format!("DELETE FROM {} WHERE emb_table = ?1", metadata_table), rusqlite::params![name])?;
+    }
+    conn.execute("DELETE FROM vecdb_tables WHERE name = ?1", rusqlite::params![name])?;
+    Ok(())
+}
+
 pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: usize) -> Result<(), String> {
     async fn get_all_emb_tables(
         conn: &Connection,
@@ -54,13 +98,17 @@ pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: u
                 let name: String = row.get(0)?;
                 Ok(name)
             })?;
+            let mut last_used_stmt = conn.prepare("SELECT last_used_ts FROM vecdb_tables WHERE name = ?1")?;
             let mut table_infos = Vec::new();
             for table_result in tables {
                 let table_name = table_result?;
                 if let Some(creation_time) = parse_table_timestamp(&table_name) {
+                    // reused tables are as old as their last use
+                    let last_used = last_used_stmt.query_row(rusqlite::params![table_name], |row| row.get::<_, i64>(0)).ok()
+                        .and_then(|ts| DateTime::from_timestamp(ts, 0));
                     table_infos.push(TableInfo {
                         name: table_name,
-                        creation_time,
+                        creation_time: last_used.map(|t| t.max(creation_time)).unwrap_or(creation_time),
                     });
                 }
             }
@@ -80,7 +128,7 @@ pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: u
                     "dropping emb table (1): {} (created at {})",
                     table.name, table.creation_time
                 );
-                conn.execute(&format!("DROP TABLE {}", table.name), [])?;
+                drop_emb_table(conn, &table.name)?;
             }
             for table in tables.iter().skip(tables.len().saturating_sub(max_count)) {
                 if table.creation_time < cutoff {
@@ -88,7 +136,7 @@ pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: u
                         "dropping emb table (2): {} (created at {})",
                         table.name, table.creation_time
                     );
-                    conn.execute(&format!("DROP TABLE {}", table.name), [])?;
+                    drop_emb_table(conn, &table.name)?;
                 }
             }
             Ok(())
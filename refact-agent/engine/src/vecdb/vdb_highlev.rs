@@ -1,29 +1,39 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use tokio::task::JoinHandle;
 use async_trait::async_trait;
-use tracing::{error, info};
+use futures::future::join_all;
+use indexmap::IndexMap;
+use tracing::{error, info, warn};
 
 use crate::background_tasks::BackgroundTasksHolder;
 use crate::fetch_embedding;
 use crate::global_context::{CommandLine, GlobalContext};
 use crate::vecdb::vdb_sqlite::VecDBSqlite;
-use crate::vecdb::vdb_structs::{SearchResult, VecDbStatus, VecdbConstants, VecdbSearch};
-use crate::vecdb::vdb_thread::{vecdb_start_background_tasks, vectorizer_enqueue_files, FileVectorizerService};
+use crate::vecdb::vdb_structs::{SearchResult, VecDbStatus, VecdbConstants, VecdbIndexRoute, VecdbRecord, VecdbSearch};
+use crate::vecdb::vdb_thread::{filter_docs_to_enqueue, vecdb_index_for_doc, vecdb_start_background_tasks, vectorizer_enqueue_files, FileVectorizerService};
 
 
-pub struct VecDb {
-    vecdb_emb_client: Arc<AMutex<reqwest::Client>>,
+#[derive(Clone)]
+pub struct VecDbIndex {
     vecdb_handler: Arc<AMutex<VecDBSqlite>>,
     pub vectorizer_service: Arc<AMutex<FileVectorizerService>>,
-    // cmdline: CommandLine,  // TODO: take from command line what's needed, don't store a copy
     constants: VecdbConstants,
 }
 
+pub struct VecDb {
+    vecdb_emb_client: Arc<AMutex<reqwest::Client>>,
+    // cmdline: CommandLine,  // TODO: take from command line what's needed, don't store a copy
+    indexes: Vec<VecDbIndex>,  // the first one is the default index made with caps.embedding_model
+}
+
+const DEFAULT_INDEX_NAME: &str = "default";
+
 async fn do_i_need_to_reload_vecdb(
     gcx: Arc<ARwLock<GlobalContext>>,
-) -> (bool, Option<VecdbConstants>) {
+) -> (bool, Option<Vec<VecdbConstants>>) {
     let caps = match crate::global_context::try_load_caps_quickly_if_not_present(gcx.clone(), 0).await {
         Ok(caps) => caps,
         Err(e) => {
@@ -34,45 +44,67 @@ async fn do_i_need_to_reload_vecdb(
     };
 
     let vecdb_max_files = gcx.read().await.cmdline.vecdb_max_files;
-    let mut consts = {
-        VecdbConstants {
-            embedding_model: caps.embedding_model.clone(),
+    let mut indexes_consts = vec![VecdbConstants {
+        embedding_model: caps.embedding_model.clone(),
+        tokenizer: None,
+        splitter_window_size: caps.embedding_model.base.n_ctx / 2,
+        vecdb_max_files: vecdb_max_files,
+        route: VecdbIndexRoute { name: DEFAULT_INDEX_NAME.to_string(), ..Default::default() },
+    }];
+    for (index_name, index_rec) in caps.embedding_indexes.iter() {
+        if index_name == DEFAULT_INDEX_NAME {
+            error!("vecdb: embedding index can't be called {:?}, skipping it", DEFAULT_INDEX_NAME);
+            continue;
+        }
+        indexes_consts.push(VecdbConstants {
+            embedding_model: index_rec.model.clone(),
             tokenizer: None,
-            splitter_window_size: caps.embedding_model.base.n_ctx / 2,
+            splitter_window_size: index_rec.model.base.n_ctx / 2,
             vecdb_max_files: vecdb_max_files,
-        }
-    };
+            route: VecdbIndexRoute {
+                name: index_name.clone(),
+                file_extensions: index_rec.file_extensions.clone(),
+                additional_indexing_dirs: index_rec.additional_indexing_dirs,
+            },
+        });
+    }
 
     let vec_db = gcx.write().await.vec_db.clone();
     match *vec_db.lock().await {
         None => {}
         Some(ref db) => {
             if
-                db.constants.embedding_model == consts.embedding_model &&
-                db.constants.splitter_window_size == consts.splitter_window_size
+                db.indexes.len() == indexes_consts.len() &&
+                db.indexes.iter().zip(indexes_consts.iter()).all(|(index, consts)| index.constants.same_index_as(consts))
             {
                 return (false, None);
             }
         }
     }
 
-    if consts.embedding_model.base.name.is_empty() || consts.embedding_model.base.endpoint.is_empty() {
+    if caps.embedding_model.base.name.is_empty() || caps.embedding_model.base.endpoint.is_empty() {
         error!("command line says to launch vecdb, but this will not happen: embedding model name or endpoint are empty");
         return (true, None);
     }
 
-    let tokenizer_result = crate::tokens::cached_tokenizer(
-        gcx.clone(), &consts.embedding_model.base,
-    ).await;
-    
-    consts.tokenizer = match tokenizer_result {
-        Ok(tokenizer) => tokenizer,
-        Err(err) => {
-            error!("vecdb launch failed, embedding model tokenizer didn't load: {}", err);
+    for consts in indexes_consts.iter_mut() {
+        if consts.embedding_model.base.name.is_empty() || consts.embedding_model.base.endpoint.is_empty() {
+            error!("vecdb launch failed, index {:?} has no embedding model name or endpoint", consts.route.name);
             return (false, None);
         }
-    };
-    return (true, Some(consts));
+        let tokenizer_result = crate::tokens::cached_tokenizer(
+            gcx.clone(), &consts.embedding_model.base,
+        ).await;
+
+        consts.tokenizer = match tokenizer_result {
+            Ok(tokenizer) => tokenizer,
+            Err(err) => {
+                error!("vecdb launch failed, embedding model tokenizer for index {:?} didn't load: {}", consts.route.name, err);
+                return (false, None);
+            }
+        };
+    }
+    (true, Some(indexes_consts))
 }
 
 pub async fn vecdb_background_reload(
@@ -125,15 +157,28 @@ impl VecDb {
     pub async fn init(
         cache_dir: &PathBuf,
         cmdline: CommandLine,
-        constants: VecdbConstants,
+        indexes_consts: Vec<VecdbConstants>,
     ) -> Result<VecDb, String> {
-        let emb_table_name = crate::vecdb::vdb_emb_aux::create_emb_table_name(&vec![cmdline.workspace_folder]);
-        let handler = VecDBSqlite::init(cache_dir, &constants.embedding_model.base.name, constants.embedding_model.embedding_size, &emb_table_name).await?;
-        let vecdb_handler = Arc::new(AMutex::new(handler));
-        let vectorizer_service = Arc::new(AMutex::new(FileVectorizerService::new(
-            vecdb_handler.clone(),
-            constants.clone(),
-        ).await));
+        let index_routes = indexes_consts.iter().map(|c| c.route.clone()).collect::<Vec<_>>();
+        let mut indexes = vec![];
+        for (index_n, constants) in indexes_consts.into_iter().enumerate() {
+            let emb_table_name = crate::vecdb::vdb_emb_aux::create_emb_table_name(
+                &vec![cmdline.workspace_folder.clone(), constants.route.name.clone(), constants.splitter_window_size.to_string()]
+            );
+            let handler = VecDBSqlite::init(cache_dir, &constants.embedding_model.base.name, constants.embedding_model.embedding_size, &emb_table_name).await?;
+            let vecdb_handler = Arc::new(AMutex::new(handler));
+            let vectorizer_service = Arc::new(AMutex::new(FileVectorizerService::new(
+                vecdb_handler.clone(),
+                constants.clone(),
+                index_routes.clone(),
+                index_n,
+            ).await));
+            indexes.push(VecDbIndex {
+                vecdb_handler,
+                vectorizer_service,
+                constants,
+            });
+        }
         let mut http_client_builder = reqwest::Client::builder();
         if cmdline.insecure {
             http_client_builder = http_client_builder.danger_accept_invalid_certs(true)
@@ -141,9 +186,7 @@ impl VecDb {
         let vecdb_emb_client = Arc::new(AMutex::new(http_client_builder.build().unwrap()));
         Ok(VecDb {
             vecdb_emb_client,
-            vecdb_handler,
-            vectorizer_service,
-            constants: constants.clone(),
+            indexes,
         })
     }
 
@@ -151,30 +194,59 @@ impl VecDb {
         &self,
         gcx: Arc<ARwLock<GlobalContext>>,
     ) -> Vec<JoinHandle<()>> {
-        info!("vecdb: start_background_tasks");
-        vecdb_start_background_tasks(self.vecdb_emb_client.clone(), self.vectorizer_service.clone(), gcx.clone()).await
+        info!("vecdb: start_background_tasks, {} indexes", self.indexes.len());
+        let mut tasks = vec![];
+        for index in self.indexes.iter() {
+            tasks.extend(vecdb_start_background_tasks(self.vecdb_emb_client.clone(), index.vectorizer_service.clone(), gcx.clone()).await);
+        }
+        tasks
     }
 
-    pub async fn vectorizer_enqueue_files(&self, documents: &Vec<String>, process_immediately: bool) {
-        vectorizer_enqueue_files(self.vectorizer_service.clone(), documents, process_immediately).await;
+    pub async fn vectorizer_enqueue_files(&self, gcx: Arc<ARwLock<GlobalContext>>, documents: &Vec<String>, process_immediately: bool) {
+        info!("adding {} files", documents.len());
+        let documents = filter_docs_to_enqueue(documents);
+        let index_routes = self.indexes.iter().map(|i| i.constants.route.clone()).collect::<Vec<_>>();
+        let mut routed: Vec<Vec<String>> = vec![vec![]; self.indexes.len()];
+        for doc in documents {
+            let index_n = vecdb_index_for_doc(gcx.clone(), &index_routes, &PathBuf::from(&doc)).await;
+            routed[index_n].push(doc);
+        }
+        for (index_n, index) in self.indexes.iter().enumerate() {
+            if self.indexes.len() > 1 {
+                // a file routed elsewhere now, after indexes or indexing.yaml changed, leaves its old vectors behind
+                if let Err(err) = remove_files_routed_elsewhere(index, &routed, index_n).await {
+                    warn!("vecdb index {:?}: {}", index.constants.route.name, err);
+                }
+            }
+            vectorizer_enqueue_files(index.vectorizer_service.clone(), &routed[index_n], process_immediately).await;
+        }
     }
 
     pub async fn remove_file(&self, file_path: &PathBuf) -> Result<(), String> {
-        let mut handler_locked = self.vecdb_handler.lock().await;
         let file_path_str = file_path.to_string_lossy().to_string();
-        handler_locked.vecdb_records_remove(vec![file_path_str]).await
+        for index in self.indexes.iter() {
+            index.vecdb_handler.lock().await.vecdb_records_remove(vec![file_path_str.clone()]).await?;
+        }
+        Ok(())
     }
 }
 
+async fn remove_files_routed_elsewhere(index: &VecDbIndex, routed: &Vec<Vec<String>>, index_n: usize) -> Result<(), String> {
+    let tracked: HashSet<String> = index.vecdb_handler.lock().await.files_tracked().await?.into_iter().collect();
+    let moved: Vec<String> = routed.iter().enumerate()
+        .filter(|(n, _)| *n != index_n)
+        .flat_map(|(_, docs)| docs.iter().filter(|doc| tracked.contains(*doc)).cloned())
+        .collect();
+    if moved.is_empty() {
+        return Ok(());
+    }
+    info!("vecdb index {:?}: removing {} files that belong to other indexes now", index.constants.route.name, moved.len());
+    index.vecdb_handler.lock().await.vecdb_records_remove(moved).await
+}
 
-pub async fn get_status(vec_db: Arc<AMutex<Option<VecDb>>>) -> Result<Option<VecDbStatus>, String> {
-    let vectorizer_service = {
-        let vec_db_guard = vec_db.lock().await;
-        let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.vectorizer_service.clone()
-    };
+async fn get_index_status(index: &VecDbIndex) -> Result<VecDbStatus, String> {
     let (vstatus, vecdb_handler) = {
-        let vectorizer_locked = vectorizer_service.lock().await;
+        let vectorizer_locked = index.vectorizer_service.lock().await;
         (
             vectorizer_locked.vstatus.clone(),
             vectorizer_locked.vecdb_handler.clone(),
@@ -192,9 +264,125 @@ pub async fn get_status(vec_db: Arc<AMutex<Option<VecDb>>>) -> Result<Option<Vec
     if vstatus_copy.state == "done" && vstatus_copy.queue_additions {
         vstatus_copy.state = "cooldown".to_string();
     }
-    Ok(Some(vstatus_copy))
+    Ok(vstatus_copy)
+}
+
+fn merge_index_statuses(statuses: IndexMap<String, VecDbStatus>) -> Option<VecDbStatus> {
+    if statuses.len() <= 1 {
+        return statuses.into_values().next();
+    }
+    let mut total = statuses[0].clone();
+    for status in statuses.values().skip(1) {
+        // every index looks at every file, so the queue is as long as the longest one
+        total.files_unprocessed = total.files_unprocessed.max(status.files_unprocessed);
+        total.files_total = total.files_total.max(status.files_total);
+        total.requests_made_since_start += status.requests_made_since_start;
+        total.vectors_made_since_start += status.vectors_made_since_start;
+        total.db_size += status.db_size;
+        total.db_cache_size += status.db_cache_size;
+        total.queue_additions |= status.queue_additions;
+        total.vecdb_max_files_hit |= status.vecdb_max_files_hit;
+        total.vectors_per_second += status.vectors_per_second;
+        total.eta_seconds = total.eta_seconds.max(status.eta_seconds);
+        for (err, count) in status.vecdb_errors.iter() {
+            *total.vecdb_errors.entry(err.clone()).or_insert(0) += count;
+        }
+    }
+    total.state = ["parsing", "cooldown", "starting", "done"].iter()
+        .find(|state| statuses.values().any(|s| s.state == **state))
+        .map(|state| state.to_string())
+        .unwrap_or(total.state);
+    // the slowest index decides when everything is done
+    total.files_per_second = statuses.values().map(|s| s.files_per_second).filter(|x| *x > 0.0).reduce(f32::min).unwrap_or(0.0);
+    total.indexes = statuses;
+    Some(total)
+}
+
+pub async fn get_status(vec_db: Arc<AMutex<Option<VecDb>>>) -> Result<Option<VecDbStatus>, String> {
+    let indexes = vec_db.lock().await.as_ref().ok_or("VecDb is not initialized")?.indexes.clone();
+    let mut statuses = IndexMap::new();
+    for index in indexes.iter() {
+        statuses.insert(index.constants.route.name.clone(), get_index_status(index).await?);
+    }
+    Ok(merge_index_statuses(statuses))
 }
 
+async fn search_one_index(
+    client: Arc<AMutex<reqwest::Client>>,
+    index: &VecDbIndex,
+    query: &String,
+    top_n: usize,
+    vecdb_scope_filter_mb: Option<String>,
+) -> Result<Vec<VecdbRecord>, String> {
+    let t0 = std::time::Instant::now();
+    let embedding_mb = fetch_embedding::get_embedding_with_retries(
+        client,
+        &index.constants.embedding_model,
+        vec![query.clone()],
+        5,
+    ).await;
+    if embedding_mb.is_err() {
+        return Err(embedding_mb.unwrap_err().to_string());
+    }
+    info!("search query {:?}, it took {:.3}s to vectorize the query for index {:?}", query, t0.elapsed().as_secs_f64(), index.constants.route.name);
+
+    let mut handler_locked = index.vecdb_handler.lock().await;
+    let t1 = std::time::Instant::now();
+    let mut results = match handler_locked.vecdb_search(&embedding_mb.unwrap()[0], top_n, vecdb_scope_filter_mb).await {
+        Ok(res) => res,
+        Err(err) => { return Err(err.to_string()) }
+    };
+    info!("search itself {:.3}s", t1.elapsed().as_secs_f64());
+    let mut dist0 = 0.0;
+    let mut filtered_results = Vec::new();
+    let rejection_threshold = index.constants.embedding_model.rejection_threshold;
+    info!("rejection_threshold {:.3}", rejection_threshold);
+    for rec in results.iter_mut() {
+        if dist0 == 0.0 {
+            dist0 = rec.distance.abs();
+        }
+        let last_35_chars = crate::nicer_logs::last_n_chars(&rec.file_path.display().to_string(), 35);
+        rec.usefulness = 100.0 - 75.0 * ((rec.distance.abs() - dist0) / (dist0 + 0.01)).max(0.0).min(1.0);
+        if rec.distance.abs() >= rejection_threshold {
+            info!("distance {:.3} -> dropped {}:{}-{}", rec.distance, last_35_chars, rec.start_line, rec.end_line);
+        } else {
+            info!("distance {:.3} -> useful {:.1}, found {}:{}-{}", rec.distance, rec.usefulness, last_35_chars, rec.start_line, rec.end_line);
+            filtered_results.push(rec.clone());
+        }
+    }
+    Ok(filtered_results)
+}
+
+/// Distances of different embedding models can't be compared, and usefulness is relative to the best result
+/// of its own index, so results are taken by rank: the best of every index, then the second best, and so on.
+/// Usefulness is capped by the previous result to keep that order when results are sorted later; a reranker,
+/// if configured, scores them all again on the same scale (see at_search.rs).
+fn interleave_index_results(per_index: Vec<Vec<VecdbRecord>>, top_n: usize) -> Vec<VecdbRecord> {
+    if per_index.len() == 1 {
+        return per_index.into_iter().next().unwrap().into_iter().take(top_n).collect();
+    }
+    let mut iters: Vec<_> = per_index.into_iter().map(|results| results.into_iter()).collect();
+    let mut results: Vec<VecdbRecord> = vec![];
+    let mut seen = std::collections::HashSet::new();
+    while results.len() < top_n {
+        let mut added_any = false;
+        for it in iters.iter_mut() {
+            let Some(mut rec) = it.next() else { continue };
+            added_any = true;
+            if results.len() >= top_n || !seen.insert((rec.file_path.clone(), rec.start_line, rec.end_line)) {
+                continue;
+            }
+            if let Some(prev) = results.last() {
+                rec.usefulness = rec.usefulness.min(prev.usefulness);
+            }
+            results.push(rec);
+        }
+        if !added_any {
+            break;
+        }
+    }
+    results
+}
 
 #[async_trait]
 impl VecdbSearch for VecDb {
@@ -205,43 +393,23 @@ impl VecdbSearch for VecDb {
         vecdb_scope_filter_mb: Option<String>,
     ) -> Result<SearchResult, String> {
         // TODO: move out of struct, replace self with Arc
-        let t0 = std::time::Instant::now();
-        let embedding_mb = fetch_embedding::get_embedding_with_retries(
-            self.vecdb_emb_client.clone(),
-            &self.constants.embedding_model,
-            vec![query.clone()],
-            5,
-        ).await;
-        if embedding_mb.is_err() {
-            return Err(embedding_mb.unwrap_err().to_string());
-        }
-        info!("search query {:?}, it took {:.3}s to vectorize the query", query, t0.elapsed().as_secs_f64());
-
-        let mut handler_locked = self.vecdb_handler.lock().await;
-        let t1 = std::time::Instant::now();
-        let mut results = match handler_locked.vecdb_search(&embedding_mb.unwrap()[0], top_n, vecdb_scope_filter_mb).await {
-            Ok(res) => res,
-            Err(err) => { return Err(err.to_string()) }
-        };
-        info!("search itself {:.3}s", t1.elapsed().as_secs_f64());
-        let mut dist0 = 0.0;
-        let mut filtered_results = Vec::new();
-        let rejection_threshold = self.constants.embedding_model.rejection_threshold;
-        info!("rejection_threshold {:.3}", rejection_threshold);
-        for rec in results.iter_mut() {
-            if dist0 == 0.0 {
-                dist0 = rec.distance.abs();
-            }
-            let last_35_chars = crate::nicer_logs::last_n_chars(&rec.file_path.display().to_string(), 35);
-            rec.usefulness = 100.0 - 75.0 * ((rec.distance.abs() - dist0) / (dist0 + 0.01)).max(0.0).min(1.0);
-            if rec.distance.abs() >= rejection_threshold {
-                info!("distance {:.3} -> dropped {}:{}-{}", rec.distance, last_35_chars, rec.start_line, rec.end_line);
-            } else {
-                info!("distance {:.3} -> useful {:.1}, found {}:{}-{}", rec.distance, rec.usefulness, last_35_chars, rec.start_line, rec.end_line);
-                filtered_results.push(rec.clone());
+        let searches = self.indexes.iter()
+            .map(|index| search_one_index(self.vecdb_emb_client.clone(), index, &query, top_n, vecdb_scope_filter_mb.clone()));
+        let mut per_index = vec![];
+        let mut errors = vec![];
+        for (index, result) in self.indexes.iter().zip(join_all(searches).await) {
+            match result {
+                Ok(index_results) => per_index.push(index_results),
+                Err(err) => {
+                    warn!("vecdb search in index {:?} failed: {}", index.constants.route.name, err);
+                    errors.push(err);
+                }
             }
         }
-        results = filtered_results;
+        if errors.len() == self.indexes.len() && !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        let results = interleave_index_results(per_index, top_n);
         Ok(
             SearchResult {
                 query_text: query,
@@ -250,3 +418,70 @@ impl VecdbSearch for VecDb {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(file: &str, start_line: u64, usefulness: f32) -> VecdbRecord {
+        VecdbRecord { vector: None, file_path: PathBuf::from(file), start_line, end_line: start_line + 10, distance: 0.0, usefulness, symbol_path: String::new() }
+    }
+
+    #[test]
+    fn test_interleave_index_results() {
+        let code_index = vec![rec("a.rs", 1, 100.0), rec("b.rs", 1, 99.0), rec("c.rs", 1, 98.0)];
+        let docs_index = vec![rec("readme.md", 1, 100.0), rec("a.rs", 1, 40.0), rec("guide.md", 1, 30.0)];
+        let merged = interleave_index_results(vec![code_index.clone(), docs_index], 5);
+        let order: Vec<_> = merged.iter().map(|r| r.file_path.to_string_lossy().to_string()).collect();
+        assert_eq!(order, vec!["a.rs", "readme.md", "b.rs", "c.rs", "guide.md"]);
+        assert!(merged.windows(2).all(|w| w[0].usefulness >= w[1].usefulness));
+        assert_eq!(merged[2].usefulness, 99.0);
+        assert_eq!(interleave_index_results(vec![code_index], 2).len(), 2);
+        assert!(interleave_index_results(vec![vec![], vec![]], 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_routes_files_before_the_limit() {
+        unsafe {
+            rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(sqlite_vec::sqlite3_vec_init as *const ())));
+        }
+        let t = crate::global_context::create_test_context(&[], vec![]).await;
+        let cache_dir = t.dir.path().to_path_buf();
+        let cmdline = t.gcx.read().await.cmdline.clone();
+        let mut embedding_model = crate::caps::EmbeddingModelRecord::default();
+        embedding_model.embedding_size = 4;
+        let code = VecdbConstants {
+            embedding_model,
+            tokenizer: None,
+            splitter_window_size: 512,
+            vecdb_max_files: 2,
+            route: VecdbIndexRoute { name: DEFAULT_INDEX_NAME.to_string(), ..Default::default() },
+        };
+        let docs = VecdbConstants {
+            route: VecdbIndexRoute { name: "docs".to_string(), file_extensions: vec!["md".to_string()], ..Default::default() },
+            ..code.clone()
+        };
+        let vecdb = VecDb::init(&cache_dir, cmdline, vec![code, docs]).await.unwrap();
+
+        // main.rs got into the docs index under an older routing
+        vecdb.indexes[1].vecdb_handler.lock().await.vecdb_records_add(&vec![VecdbRecord {
+            vector: Some(vec![1.0, 0.0, 0.0, 0.0]),
+            ..rec("/project/src/main.rs", 0, 0.0)
+        }]).await.unwrap();
+
+        let files: Vec<String> = ["src/main.rs", "src/lib.rs", "src/util.rs", "README.md", "docs/guide.md", "docs/faq.md"]
+            .iter().map(|f| format!("/project/{}", f)).collect();
+        vecdb.vectorizer_enqueue_files(t.gcx.clone(), &files, false).await;
+
+        let code_queue = vecdb.indexes[0].vecdb_handler.lock().await.queue_load().await.unwrap();
+        let docs_queue = vecdb.indexes[1].vecdb_handler.lock().await.queue_load().await.unwrap();
+        assert_eq!(code_queue.len(), 2);
+        assert_eq!(docs_queue.len(), 2);
+        assert!(code_queue.iter().all(|f| f.ends_with(".rs")));
+        assert!(docs_queue.iter().all(|f| f.ends_with(".md")));
+        for index in vecdb.indexes.iter() {
+            assert!(index.vectorizer_service.lock().await.vstatus.lock().await.vecdb_max_files_hit);
+        }
+        assert!(vecdb.indexes[1].vecdb_handler.lock().await.files_tracked().await.unwrap().is_empty());
+    }
+}
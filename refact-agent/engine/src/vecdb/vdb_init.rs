@@ -49,7 +49,7 @@ impl std::fmt::Display for VecDbInitError {
 pub async fn init_vecdb_fail_safe(
     cache_dir: &PathBuf,
     cmdline: CommandLine,
-    constants: VecdbConstants,
+    indexes_consts: Vec<VecdbConstants>,
     init_config: VecDbInitConfig,
 ) -> Result<VecDb, VecDbInitError> {
     let mut attempt: usize = 0;
@@ -59,7 +59,7 @@ pub async fn init_vecdb_fail_safe(
         attempt += 1;
         info!("VecDb init attempt {}/{}", attempt, init_config.max_attempts);
         
-        match VecDb::init(cache_dir, cmdline.clone(), constants.clone()).await {
+        match VecDb::init(cache_dir, cmdline.clone(), indexes_consts.clone()).await {
             Ok(vecdb) => {
                 info!("Successfully initialized VecDb on attempt {}", attempt);
                 
@@ -112,7 +112,7 @@ async fn vecdb_test_search(vecdb: &VecDb) -> Result<(), String> {
 
 pub async fn initialize_vecdb_with_context(
     gcx: Arc<ARwLock<GlobalContext>>,
-    constants: VecdbConstants,
+    indexes_consts: Vec<VecdbConstants>,
     init_config: Option<VecDbInitConfig>,
 ) -> Result<(), VecDbInitError> {
     
@@ -130,7 +130,7 @@ pub async fn initialize_vecdb_with_context(
     let vec_db = init_vecdb_fail_safe(
         &base_dir_cache,
         cmdline.clone(),
-        constants,
+        indexes_consts,
         config,
     ).await?;
     
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde_json::{json, Value};
+use tokio::sync::RwLock as ARwLock;
+use tracing::{info, warn};
+
+use crate::caps::RerankModelRecord;
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::global_context::GlobalContext;
+use crate::vecdb::vdb_structs::VecdbRecord;
+
+// Vector search is good at recall and bad at ordering: the top_k candidates go through a second, more precise
+// scoring before postprocessing cuts them down. Results keep the 25..100 usefulness scale vdb_highlev uses.
+
+const RERANK_CHUNK_MAX_CHARS: usize = 4000;
+const LEXICAL_WEIGHT: f32 = 0.5;
+
+async fn chunk_texts(gcx: Arc<ARwLock<GlobalContext>>, records: &Vec<VecdbRecord>) -> Vec<String> {
+    let mut file_cache: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut texts = vec![];
+    for rec in records {
+        if !file_cache.contains_key(&rec.file_path) {
+            let lines = match get_file_text_from_memory_or_disk(gcx.clone(), &rec.file_path).await {
+                Ok(text) => text.lines().map(|x| x.to_string()).collect(),
+                Err(e) => {
+                    warn!("rerank: cannot read {:?}: {}", rec.file_path, e);
+                    vec![]
+                }
+            };
+            file_cache.insert(rec.file_path.clone(), lines);
+        }
+        let lines = &file_cache[&rec.file_path];
+        let start = (rec.start_line as usize).min(lines.len());
+        let end = (rec.end_line as usize + 1).min(lines.len()).max(start);
+        let mut text = format!("{}\n", rec.file_path.display());
+        if !rec.symbol_path.is_empty() {
+            text.push_str(&format!("{}\n", rec.symbol_path));
+        }
+        text.push_str(&lines[start..end].join("\n"));
+        if text.len() > RERANK_CHUNK_MAX_CHARS {
+            let mut cut = RERANK_CHUNK_MAX_CHARS;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            text.truncate(cut);
+        }
+        texts.push(text);
+    }
+    texts
+}
+
+/// Cohere, Jina and vLLM answer `{"results": [{"index", "relevance_score"}]}`, TEI answers `[{"index", "score"}]`
+pub fn parse_rerank_response(json: &Value, documents_n: usize) -> Result<Vec<f32>, String> {
+    let items = match json.get("results").or(json.get("data")) {
+        Some(Value::Array(items)) => items,
+        _ => json.as_array().ok_or(format!("unexpected rerank response: {}", json))?,
+    };
+    let mut scores = vec![f32::NEG_INFINITY; documents_n];
+    for item in items {
+        let index = item["index"].as_u64().ok_or(format!("no index in rerank result: {}", item))? as usize;
+        let score = item.get("relevance_score").or(item.get("score")).and_then(|x| x.as_f64())
+            .ok_or(format!("no score in rerank result: {}", item))?;
+        if index >= documents_n {
+            return Err(format!("rerank result index {} is out of range, there are {} documents", index, documents_n));
+        }
+        scores[index] = score as f32;
+    }
+    Ok(scores)
+}
+
+async fn rerank_api_scores(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    model_rec: &RerankModelRecord,
+    query: &str,
+    documents: &Vec<String>,
+) -> Result<Vec<f32>, String> {
+    if model_rec.base.endpoint.is_empty() {
+        return Err("No rerank endpoint configured".to_string());
+    }
+    let client = gcx.read().await.http_client.clone();
+    let mut request = client.post(&model_rec.base.endpoint)
+        .json(&json!({
+            "model": model_rec.base.name,
+            "query": query,
+            "documents": documents,
+            "texts": documents,  // TEI calls them texts
+            "top_n": documents.len(),
+        }));
+    if !model_rec.base.api_key.is_empty() {
+        request = request.bearer_auth(&model_rec.base.api_key);
+    }
+    let response = request.send().await.map_err(|e| format!("Failed to send a rerank request: {:?}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("rerank: bad status: {:?}", response.status()));
+    }
+    let json = response.json::<Value>().await
+        .map_err(|e| format!("rerank: failed to parse the response: {:?}", e))?;
+    parse_rerank_response(&json, documents.len())
+}
+
+fn split_identifiers(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        // camelCase and snake_case parts, plus the whole identifier
+        let mut part = String::new();
+        let mut prev_lower = false;
+        for c in word.chars() {
+            if c.is_uppercase() && prev_lower && part.len() > 1 {
+                tokens.push(part.to_lowercase());
+                part.clear();
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            part.push(c);
+        }
+        if part.len() > 1 {
+            tokens.push(part.to_lowercase());
+        }
+        if word.len() > 1 && word.to_lowercase() != *tokens.last().unwrap_or(&String::new()) {
+            tokens.push(word.to_lowercase());
+        }
+    }
+    tokens
+}
+
+/// BM25 over identifier parts, the documents are the candidates themselves
+pub fn lexical_scores(query: &str, documents: &Vec<String>) -> Vec<f32> {
+    let (k1, b) = (1.2_f32, 0.75_f32);
+    let query_terms: HashSet<String> = split_identifiers(query).into_iter().collect();
+    let docs_terms: Vec<Vec<String>> = documents.iter().map(|d| split_identifiers(d)).collect();
+    let avg_len = docs_terms.iter().map(|t| t.len()).sum::<usize>() as f32 / docs_terms.len().max(1) as f32;
+    let n = docs_terms.len() as f32;
+    docs_terms.iter().map(|terms| {
+        let doc_len = terms.len() as f32;
+        query_terms.iter().map(|q| {
+            let tf = terms.iter().filter(|t| *t == q).count() as f32;
+            if tf == 0.0 {
+                return 0.0;
+            }
+            let df = docs_terms.iter().filter(|d| d.contains(q)).count() as f32;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            idf * tf * (k1 + 1.0) / (tf + k1 * (1.0 - b + b * doc_len / avg_len.max(1.0)))
+        }).sum::<f32>()
+    }).collect()
+}
+
+fn normalize_to_usefulness(scores: &Vec<f32>) -> Vec<f32> {
+    let finite = scores.iter().cloned().filter(|x| x.is_finite());
+    let max = finite.clone().fold(f32::NEG_INFINITY, f32::max);
+    let min = finite.fold(f32::INFINITY, f32::min);
+    scores.iter().map(|s| {
+        if !s.is_finite() {
+            25.0
+        } else if max - min < 1e-6 {
+            100.0
+        } else {
+            25.0 + 75.0 * (s - min) / (max - min)
+        }
+    }).collect()
+}
+
+pub async fn rerank_search_results(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    model_rec: &RerankModelRecord,
+    query: &str,
+    mut records: Vec<VecdbRecord>,
+) -> Vec<VecdbRecord> {
+    if records.len() < 2 {
+        return records;
+    }
+    let t0 = std::time::Instant::now();
+    let documents = chunk_texts(gcx.clone(), &records).await;
+    let api_scores = if model_rec.rerank_style == "lexical" {
+        None
+    } else {
+        match rerank_api_scores(gcx.clone(), model_rec, query, &documents).await {
+            Ok(scores) => Some(scores),
+            Err(e) => {
+                warn!("rerank with {:?} failed, falling back to lexical: {}", model_rec.base.name, e);
+                None
+            }
+        }
+    };
+    let new_usefulness = match api_scores {
+        Some(scores) => normalize_to_usefulness(&scores),
+        None => {
+            let lexical = normalize_to_usefulness(&lexical_scores(query, &documents));
+            records.iter().zip(lexical.iter())
+                .map(|(rec, lex)| rec.usefulness * (1.0 - LEXICAL_WEIGHT) + lex * LEXICAL_WEIGHT)
+                .collect()
+        }
+    };
+    for (rec, usefulness) in records.iter_mut().zip(new_usefulness) {
+        rec.usefulness = usefulness;
+    }
+    records.sort_by(|a, b| b.usefulness.partial_cmp(&a.usefulness).unwrap_or(std::cmp::Ordering::Equal));
+    info!("rerank of {} results took {:.3}s", records.len(), t0.elapsed().as_secs_f64());
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rerank_response() {
+        let cohere = json!({"results": [{"index": 1, "relevance_score": 0.9}, {"index": 0, "relevance_score": 0.1}]});
+        assert_eq!(parse_rerank_response(&cohere, 2).unwrap(), vec![0.1, 0.9]);
+        let tei = json!([{"index": 0, "score": 0.5}, {"index": 2, "score": 0.7}]);
+        assert_eq!(parse_rerank_response(&tei, 3).unwrap(), vec![0.5, f32::NEG_INFINITY, 0.7]);
+        assert!(parse_rerank_response(&tei, 2).is_err());
+        assert!(parse_rerank_response(&json!({"error": "oops"}), 2).is_err());
+    }
+
+    #[test]
+    fn test_lexical_scores() {
+        assert_eq!(split_identifiers("parseHttpRequest my_func"), vec!["parse", "http", "request", "parsehttprequest", "my", "func"]);
+        let documents = vec![
+            "fn render_page(ctx: &Context) { draw(ctx) }".to_string(),
+            "fn parse_http_request(buf: &[u8]) -> HttpRequest { HttpRequest::parse(buf) }".to_string(),
+            "struct Config { port: u16 }".to_string(),
+        ];
+        let scores = lexical_scores("where is the http request parsed", &documents);
+        assert!(scores[1] > scores[0] && scores[1] > scores[2], "{:?}", scores);
+        assert_eq!(normalize_to_usefulness(&vec![1.0, 3.0, f32::NEG_INFINITY]), vec![25.0, 100.0, 25.0]);
+    }
+}
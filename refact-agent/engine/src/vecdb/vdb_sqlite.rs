@@ -106,12 +106,39 @@ async fn migrate_202501(conn: &Connection, embedding_size: i32, emb_table_name:
               embedding float[{embedding_size}] distance_metric=cosine,
               scope TEXT,
               +start_line INTEGER,
-              +end_line INTEGER
+              +end_line INTEGER,
+              +symbol_path TEXT
             );"), [])?;
         Ok(())
     }).await
 }
 
+// Lets a restart continue where the previous run stopped: the emb table is reused, vecdb_files remembers what
+// content each file had when it was vectorized, and vecdb_queue keeps files that were enqueued but not done yet
+async fn migrate_202510(conn: &Connection) -> tokio_rusqlite::Result<()> {
+    conn.call(move |conn| {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS vecdb_tables (
+                name TEXT PRIMARY KEY,
+                last_used_ts INTEGER NOT NULL
+            )", [])?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS vecdb_files (
+                emb_table TEXT NOT NULL,
+                path TEXT NOT NULL,
+                text_hash TEXT NOT NULL,
+                PRIMARY KEY (emb_table, path)
+            )", [])?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS vecdb_queue (
+                emb_table TEXT NOT NULL,
+                path TEXT NOT NULL,
+                PRIMARY KEY (emb_table, path)
+            )", [])?;
+        Ok(())
+    }).await
+}
+
 impl VecDBSqlite {
     pub async fn init(cache_dir: &PathBuf, model_name: &String, embedding_size: i32, emb_table_name: &String) -> Result<VecDBSqlite, String> {
         let db_path = get_db_path(cache_dir, model_name, embedding_size).await?;
@@ -128,11 +155,89 @@ impl VecDBSqlite {
             Ok(())
         }).await.map_err(|e| e.to_string())?;
         migrate_202406(&conn).await.map_err(|e| e.to_string())?;
+        migrate_202510(&conn).await.map_err(|e| e.to_string())?;
+        let emb_table_name = match crate::vecdb::vdb_emb_aux::find_reusable_emb_table(&conn, emb_table_name).await? {
+            Some(existing) => {
+                info!("vecdb continues with {}", existing);
+                existing
+            }
+            None => emb_table_name.clone(),
+        };
         migrate_202501(&conn, embedding_size, emb_table_name.clone()).await.map_err(|e| e.to_string())?;
+        crate::vecdb::vdb_emb_aux::mark_emb_table_used(&conn, &emb_table_name).await?;
         crate::vecdb::vdb_emb_aux::cleanup_old_emb_tables(&conn, 7, 10).await?;
 
         info!("vecdb initialized");
-        Ok(VecDBSqlite { conn, emb_table_name: emb_table_name.clone() })
+        Ok(VecDBSqlite { conn, emb_table_name })
+    }
+
+    pub async fn file_text_hash(&self, path: &String) -> Result<Option<String>, String> {
+        let emb_table_name = self.emb_table_name.clone();
+        let path = path.clone();
+        self.conn.call(move |connection| {
+            let mut stmt = connection.prepare("SELECT text_hash FROM vecdb_files WHERE emb_table = ?1 AND path = ?2")?;
+            let mut rows = stmt.query(rusqlite::params![emb_table_name, path])?;
+            match rows.next()? {
+                Some(row) => Ok(Some(row.get(0)?)),
+                None => Ok(None),
+            }
+        }).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn files_tracked(&self) -> Result<Vec<String>, String> {
+        let emb_table_name = self.emb_table_name.clone();
+        self.conn.call(move |connection| {
+            let mut stmt = connection.prepare("SELECT path FROM vecdb_files WHERE emb_table = ?1")?;
+            let rows = stmt.query_map(rusqlite::params![emb_table_name], |row| row.get(0))?;
+            Ok(rows.collect::<Result<Vec<String>, _>>()?)
+        }).await.map_err(|e| e.to_string())
+    }
+
+    /// Takes the files out of the persistent queue, remembering text hashes for the files that now have vectors
+    pub async fn files_done(&mut self, done: Vec<(String, Option<String>)>) -> Result<(), String> {
+        if done.is_empty() {
+            return Ok(());
+        }
+        let emb_table_name = self.emb_table_name.clone();
+        self.conn.call(move |connection| {
+            let tx = connection.transaction()?;
+            {
+                let mut set_hash = tx.prepare("INSERT OR REPLACE INTO vecdb_files (emb_table, path, text_hash) VALUES (?1, ?2, ?3)")?;
+                let mut dequeue = tx.prepare("DELETE FROM vecdb_queue WHERE emb_table = ?1 AND path = ?2")?;
+                for (path, text_hash_mb) in done.iter() {
+                    if let Some(text_hash) = text_hash_mb {
+                        set_hash.execute(rusqlite::params![emb_table_name, path, text_hash])?;
+                    }
+                    dequeue.execute(rusqlite::params![emb_table_name, path])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        }).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn queue_add(&mut self, paths: Vec<String>) -> Result<(), String> {
+        let emb_table_name = self.emb_table_name.clone();
+        self.conn.call(move |connection| {
+            let tx = connection.transaction()?;
+            {
+                let mut stmt = tx.prepare("INSERT OR IGNORE INTO vecdb_queue (emb_table, path) VALUES (?1, ?2)")?;
+                for path in paths.iter() {
+                    stmt.execute(rusqlite::params![emb_table_name, path])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        }).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn queue_load(&self) -> Result<Vec<String>, String> {
+        let emb_table_name = self.emb_table_name.clone();
+        self.conn.call(move |connection| {
+            let mut stmt = connection.prepare("SELECT path FROM vecdb_queue WHERE emb_table = ?1")?;
+            let rows = stmt.query_map(rusqlite::params![emb_table_name], |row| row.get(0))?;
+            Ok(rows.collect::<Result<Vec<String>, _>>()?)
+        }).await.map_err(|e| e.to_string())
     }
 
     pub async fn fetch_vectors_from_cache(&mut self, splits: &Vec<SplitResult>) -> Result<Vec<Option<Vec<f32>>>, String> {
@@ -250,7 +355,7 @@ impl VecDBSqlite {
                     
                     {
                         let mut stmt = tx.prepare(&format!(
-                            "INSERT INTO {}(embedding, scope, start_line, end_line) VALUES (?, ?, ?, ?)", emb_table_name
+                            "INSERT INTO {}(embedding, scope, start_line, end_line, symbol_path) VALUES (?, ?, ?, ?, ?)", emb_table_name
                         ))?;
                         
                         for item in records_owned.iter() {
@@ -258,7 +363,8 @@ impl VecDBSqlite {
                                 item.vector.clone().expect("No embedding is provided").as_bytes(),
                                 item.file_path.to_string_lossy().to_string(),
                                 item.start_line,
-                                item.end_line
+                                item.end_line,
+                                item.symbol_path
                             ])?;
                         }
                     }
@@ -306,7 +412,8 @@ impl VecDBSqlite {
                             start_line,
                             end_line,
                             embedding,
-                            distance
+                            distance,
+                            symbol_path
                         FROM {}
                         WHERE embedding MATCH ?
                             AND k = ?
@@ -337,6 +444,7 @@ impl VecDBSqlite {
                                 end_line: row.get(2)?,
                                 distance: row.get(4)?,
                                 usefulness: 0.0,
+                                symbol_path: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
                             })
                         },
                     )?;
@@ -388,6 +496,11 @@ impl VecDBSqlite {
                         )?;
 
                         stmt.execute(rusqlite::params_from_iter(scopes_to_remove.iter()))?;
+                        // no vectors, no text hash: the file will be vectorized again when it comes back
+                        let mut stmt = tx.prepare(
+                            &format!("DELETE FROM vecdb_files WHERE emb_table = ? AND path IN ({})", placeholders)
+                        )?;
+                        stmt.execute(rusqlite::params_from_iter(std::iter::once(&emb_table_name).chain(scopes_to_remove.iter())))?;
                     }
                     
                     // Commit the transaction
@@ -401,3 +514,51 @@ impl VecDBSqlite {
         ).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_vecdb_continues_after_restart() {
+        unsafe {
+            rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(sqlite_vec::sqlite3_vec_init as *const ())));
+        }
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tmp_dir.path().to_path_buf();
+        let model_name = "test-model".to_string();
+        let table1 = "emb_abc_20250101_000000".to_string();
+        let file = "/project/src/main.rs".to_string();
+        {
+            let mut db = VecDBSqlite::init(&cache_dir, &model_name, 4, &table1).await.unwrap();
+            db.queue_add(vec![file.clone(), "/project/README.md".to_string()]).await.unwrap();
+            db.vecdb_records_add(&vec![VecdbRecord {
+                vector: Some(vec![1.0, 0.0, 0.0, 0.0]),
+                file_path: PathBuf::from(&file),
+                start_line: 0,
+                end_line: 10,
+                distance: -1.0,
+                usefulness: 0.0,
+                symbol_path: "main".to_string(),
+            }]).await.unwrap();
+            db.files_done(vec![(file.clone(), Some("hash1".to_string()))]).await.unwrap();
+        }
+
+        // the next run gets a new table name, but continues with the old table
+        let mut db = VecDBSqlite::init(&cache_dir, &model_name, 4, &"emb_abc_20250102_000000".to_string()).await.unwrap();
+        assert_eq!(db.emb_table_name, table1);
+        assert_eq!(db.size().await.unwrap(), 1);
+        assert_eq!(db.file_text_hash(&file).await.unwrap(), Some("hash1".to_string()));
+        assert_eq!(db.queue_load().await.unwrap(), vec!["/project/README.md".to_string()]);
+        assert_eq!(db.files_tracked().await.unwrap(), vec![file.clone()]);
+
+        db.vecdb_records_remove(vec![file.clone()]).await.unwrap();
+        assert_eq!(db.size().await.unwrap(), 0);
+        assert_eq!(db.file_text_hash(&file).await.unwrap(), None);
+
+        // another workspace or index doesn't see any of that
+        let db = VecDBSqlite::init(&cache_dir, &model_name, 4, &"emb_def_20250102_000000".to_string()).await.unwrap();
+        assert_eq!(db.emb_table_name, "emb_def_20250102_000000");
+        assert!(db.queue_load().await.unwrap().is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use indexmap::IndexMap;
@@ -26,6 +26,40 @@ pub struct VecdbConstants {
     pub tokenizer: Option<Arc<Tokenizer>>,
     pub splitter_window_size: usize,
     pub vecdb_max_files: usize,
+    pub route: VecdbIndexRoute,
+}
+
+impl VecdbConstants {
+    pub fn same_index_as(&self, other: &VecdbConstants) -> bool {
+        self.embedding_model == other.embedding_model &&
+            self.splitter_window_size == other.splitter_window_size &&
+            self.route == other.route
+    }
+}
+
+/// Which files an index takes, the default index (the first one) takes everything other indexes don't
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VecdbIndexRoute {
+    pub name: String,
+    pub file_extensions: Vec<String>,
+    pub additional_indexing_dirs: bool,
+}
+
+impl VecdbIndexRoute {
+    fn takes(&self, path: &Path, additional_indexing_dirs: &Vec<String>) -> bool {
+        if self.file_extensions.is_empty() && !self.additional_indexing_dirs {
+            return false;
+        }
+        let extension_ok = self.file_extensions.is_empty() || path.extension()
+            .map(|e| self.file_extensions.iter().any(|x| e.eq_ignore_ascii_case(x.trim_start_matches('.'))))
+            .unwrap_or(false);
+        let dir_ok = !self.additional_indexing_dirs || additional_indexing_dirs.iter().any(|d| path.starts_with(d));
+        extension_ok && dir_ok
+    }
+}
+
+pub fn vecdb_index_for_path(routes: &Vec<VecdbIndexRoute>, path: &Path, additional_indexing_dirs: &Vec<String>) -> usize {
+    routes.iter().skip(1).position(|r| r.takes(path, additional_indexing_dirs)).map(|i| i + 1).unwrap_or(0)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -40,6 +74,14 @@ pub struct VecDbStatus {
     pub queue_additions: bool,
     pub vecdb_max_files_hit: bool,
     pub vecdb_errors: IndexMap<String, usize>,
+    #[serde(default)]
+    pub files_per_second: f32,
+    #[serde(default)]
+    pub vectors_per_second: f32,
+    #[serde(default)]
+    pub eta_seconds: Option<u64>,  // until files_unprocessed are done, at the current speed
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub indexes: IndexMap<String, VecDbStatus>,  // per index, when there is more than one
 }
 
 
@@ -51,6 +93,8 @@ pub struct VecdbRecord {
     pub end_line: u64,
     pub distance: f32,
     pub usefulness: f32,
+    #[serde(default)]
+    pub symbol_path: String,  // the function or class the chunk belongs to, empty for plain text chunks
 }
 
 #[derive(Debug, Clone)]
@@ -75,3 +119,27 @@ pub struct SearchResult {
     pub query_text: String,
     pub results: Vec<VecdbRecord>,
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vecdb_index_for_path() {
+        let routes = vec![
+            VecdbIndexRoute { name: "default".to_string(), ..Default::default() },
+            VecdbIndexRoute { name: "external_docs".to_string(), file_extensions: vec!["md".to_string()], additional_indexing_dirs: true },
+            VecdbIndexRoute { name: "docs".to_string(), file_extensions: vec![".md".to_string(), "RST".to_string()], additional_indexing_dirs: false },
+            VecdbIndexRoute { name: "external".to_string(), file_extensions: vec![], additional_indexing_dirs: true },
+        ];
+        let dirs = vec!["/home/user/lib".to_string()];
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/project/src/main.rs"), &dirs), 0);
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/project/README.md"), &dirs), 2);
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/project/docs/index.rst"), &dirs), 2);
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/home/user/lib/README.md"), &dirs), 1);
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/home/user/lib/src/lib.rs"), &dirs), 3);
+        assert_eq!(vecdb_index_for_path(&routes, Path::new("/home/user/library/src/lib.rs"), &dirs), 0);
+        assert_eq!(vecdb_index_for_path(&routes[..1].to_vec(), Path::new("/project/README.md"), &dirs), 0);
+    }
+}
@@ -4,8 +4,9 @@ use std::collections::{HashMap, VecDeque};
 use std::io::Write;
 use std::ops::Div;
 use std::option::Option;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use tokio::sync::{Mutex as AMutex, Notify as ANotify, RwLock as ARwLock};
 use tokio::task::JoinHandle;
 use tracing::{info, warn};
@@ -15,7 +16,7 @@ use crate::fetch_embedding::get_embedding_with_retries;
 use crate::files_in_workspace::{is_path_to_enqueue_valid, Document};
 use crate::global_context::GlobalContext;
 use crate::vecdb::vdb_sqlite::VecDBSqlite;
-use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecDbStatus, VecdbConstants, VecdbRecord};
+use crate::vecdb::vdb_structs::{vecdb_index_for_path, SimpleTextHashVector, SplitResult, VecDbStatus, VecdbConstants, VecdbIndexRoute, VecdbRecord};
 
 const DEBUG_WRITE_VECDB_FILES: bool = false;
 const COOLDOWN_SECONDS: u64 = 10;
@@ -31,6 +32,8 @@ pub struct FileVectorizerService {
     pub vstatus: Arc<AMutex<VecDbStatus>>,
     pub vstatus_notify: Arc<ANotify>,   // fun stuff https://docs.rs/tokio/latest/tokio/sync/struct.Notify.html
     constants: VecdbConstants,
+    index_routes: Vec<VecdbIndexRoute>,  // every index sees every file, and takes those routed to it
+    index_n: usize,
     vecdb_todo: Arc<AMutex<VecDeque<MessageToVecdbThread>>>,
 }
 
@@ -85,6 +88,7 @@ async fn vectorize_batch_from_q(
                 end_line: data_res.end_line,
                 distance: -1.0,
                 usefulness: 0.0,
+                symbol_path: data_res.symbol_path.clone(),
             }
         );
         send_to_cache.push(
@@ -137,6 +141,7 @@ async fn from_splits_to_vecdb_records_applying_cache(
                     end_line: split.end_line,
                     distance: -1.0,
                     usefulness: 0.0,
+                    symbol_path: split.symbol_path.clone(),
                 });
             }
         } else if let Err(err) = vectors_maybe {
@@ -158,6 +163,8 @@ async fn vectorize_thread(
 
     let (vecdb_todo,
         constants,
+        index_routes,
+        index_n,
         vecdb_handler_arc,
         vstatus,
         vstatus_notify,
@@ -166,12 +173,39 @@ async fn vectorize_thread(
         (
             vservice_locked.vecdb_todo.clone(),
             vservice_locked.constants.clone(),
+            vservice_locked.index_routes.clone(),
+            vservice_locked.index_n,
             vservice_locked.vecdb_handler.clone(),
             vservice_locked.vstatus.clone(),
             vservice_locked.vstatus_notify.clone(),
         )
     };
 
+    // files enqueued by the previous run that it didn't finish, and files that went away while we were not running
+    match vecdb_handler_arc.lock().await.queue_load().await {
+        Ok(paths) if !paths.is_empty() => {
+            info!("continuing with {} files queued by the previous run", paths.len());
+            let mut vecdb_todo_locked = vecdb_todo.lock().await;
+            vecdb_todo_locked.extend(paths.into_iter().map(MessageToVecdbThread::RegularDocument));
+        }
+        Ok(_) => {}
+        Err(err) => warn!("cannot load the vecdb queue: {}", err),
+    }
+    let files_tracked = vecdb_handler_arc.lock().await.files_tracked().await.unwrap_or_else(|err| {
+        warn!("cannot load vectorized files: {}", err);
+        vec![]
+    });
+    let files_gone = files_tracked.into_iter().filter(|p| !PathBuf::from(p).exists()).collect::<Vec<_>>();
+    if !files_gone.is_empty() {
+        info!("removing {} files that don't exist anymore", files_gone.len());
+        if let Err(err) = vecdb_handler_arc.lock().await.vecdb_records_remove(files_gone).await {
+            warn!("VECDB Error removing: {}", err);
+        }
+    }
+
+    let mut files_done: Vec<(String, Option<String>)> = vec![];  // text hash for files that got new vectors
+    let mut busy_since: Option<(Instant, usize)> = None;  // when the queue started to move, vectors made before that
+    let mut files_done_since_busy: usize = 0;
     let mut last_updated: HashMap<String, SystemTime> = HashMap::new();
     loop {
         let mut work_on_one: Option<MessageToVecdbThread> = None;
@@ -216,6 +250,15 @@ async fn vectorize_thread(
                     vstatus_locked.state = "cooldown".to_string();
                     vstatus_changed = true;
                 }
+                if work_on_one.is_some() {
+                    let (t0, vectors0) = *busy_since.get_or_insert((Instant::now(), vstatus_locked.vectors_made_since_start));
+                    let elapsed = t0.elapsed().as_secs_f32();
+                    if elapsed > 1.0 && files_done_since_busy > 0 {
+                        vstatus_locked.files_per_second = files_done_since_busy as f32 / elapsed;
+                        vstatus_locked.vectors_per_second = (vstatus_locked.vectors_made_since_start - vectors0) as f32 / elapsed;
+                        vstatus_locked.eta_seconds = Some((files_unprocessed as f32 / vstatus_locked.files_per_second).ceil() as u64);
+                    }
+                }
             }
         }
         if vstatus_changed {
@@ -247,7 +290,16 @@ async fn vectorize_thread(
         if flush {
             assert!(run_actual_model_on_these.len() == 0);
             // This function assumes it can delete records with the filenames mentioned, therefore assert above
-            _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb).await;
+            let all_added = _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb).await;
+            let mut done = std::mem::take(&mut files_done);
+            files_done_since_busy += done.len();  // counted once the vectors are written, not when a file is picked
+            if !all_added {
+                // without a text hash these files get vectorized again next time
+                done.iter_mut().for_each(|(_, text_hash)| *text_hash = None);
+            }
+            if let Err(err) = vecdb_handler_arc.lock().await.files_done(done).await {
+                warn!("VECDB Error saving processed files: {}", err);
+            }
         }
 
         if (files_unprocessed + 99).div(100) != (reported_unprocessed + 99).div(100) {
@@ -269,8 +321,13 @@ async fn vectorize_thread(
                         let done = vstatus_locked.state == "done";
                         if !done {
                             files_total = 0;
+                            busy_since = None;
+                            files_done_since_busy = 0;
                             vstatus_locked.files_unprocessed = 0;
                             vstatus_locked.files_total = 0;
+                            vstatus_locked.files_per_second = 0.0;
+                            vstatus_locked.vectors_per_second = 0.0;
+                            vstatus_locked.eta_seconds = None;
                             vstatus_locked.state = "done".to_string();
                             info!(
                                 "vectorizer since start {} API calls, {} vectors",
@@ -305,6 +362,11 @@ async fn vectorize_thread(
                 _ => continue
             }
         };
+        // the queue saved by a previous run might have been routed differently
+        if vecdb_index_for_doc(gcx.clone(), &index_routes, &PathBuf::from(&cpath)).await != index_n {
+            files_done.push((cpath, None));
+            continue;
+        }
         let last_30_chars = crate::nicer_logs::last_n_chars(&cpath, 30);
 
         // Not from memory, vecdb works on files from disk, because they change less
@@ -317,14 +379,27 @@ async fn vectorize_thread(
                     info!("VECDB Error removing: {}", err);                    
                 }
             };
+            files_done.push((cpath, None));
             continue;
         }
 
         if let Err(err) = doc.does_text_look_good() {
             info!("embeddings {} doesn't look good: {}", last_30_chars, err);
+            files_done.push((cpath, None));
             continue;
         }
 
+        let text_hash = crate::ast::chunk_utils::official_text_hashing_function(&doc.text_as_string().unwrap_or_default());
+        match vecdb_handler_arc.lock().await.file_text_hash(&cpath).await {
+            Ok(Some(old_hash)) if old_hash == text_hash => {
+                // vectorized by this or a previous run, and didn't change since
+                files_done.push((cpath, None));
+                continue;
+            }
+            Ok(_) => {}
+            Err(err) => warn!("cannot get the text hash of {}: {}", last_30_chars, err),
+        }
+
         let file_splitter = AstBasedFileSplitter::new(constants.splitter_window_size);
         let mut splits = file_splitter.vectorization_split(&doc, None, gcx.clone(), constants.embedding_model.base.n_ctx).await.unwrap_or_else(|err| {
             info!("{}", err);
@@ -363,13 +438,15 @@ async fn vectorize_thread(
             vecdb_handler_arc.clone(),
             1024,
         ).await;
+        files_done.push((cpath, Some(text_hash)));
     }
 }
 
 async fn _send_to_vecdb(
     vecdb_handler_arc: Arc<AMutex<VecDBSqlite>>,
     ready_to_vecdb: &mut Vec<VecdbRecord>,
-) {
+) -> bool {
+    let mut all_added = true;
     while !ready_to_vecdb.is_empty() {
         let unique_file_paths: HashSet<String> = ready_to_vecdb.iter()
             .map(|x| x.file_path.to_str().unwrap_or("No filename").to_string())
@@ -387,16 +464,20 @@ async fn _send_to_vecdb(
                 Ok(_) => {}
                 Err(err) => {
                     info!("VECDB Error adding: {}", err);                                                        
+                    all_added = false;
                 }
             }
         }
     }
+    all_added
 }
 
 impl FileVectorizerService {
     pub async fn new(
         vecdb_handler: Arc<AMutex<VecDBSqlite>>,
         constants: VecdbConstants,
+        index_routes: Vec<VecdbIndexRoute>,
+        index_n: usize,
     ) -> Self {
         let vstatus = Arc::new(AMutex::new(
             VecDbStatus {
@@ -410,6 +491,10 @@ impl FileVectorizerService {
                 queue_additions: true,
                 vecdb_max_files_hit: false,
                 vecdb_errors: IndexMap::new(),
+                files_per_second: 0.0,
+                vectors_per_second: 0.0,
+                eta_seconds: None,
+                indexes: IndexMap::new(),
             }
         ));
         FileVectorizerService {
@@ -417,6 +502,8 @@ impl FileVectorizerService {
             vstatus: vstatus.clone(),
             vstatus_notify: Arc::new(ANotify::new()),
             constants,
+            index_routes,
+            index_n,
             vecdb_todo: Default::default(),
         }
     }
@@ -437,7 +524,7 @@ pub async fn vecdb_start_background_tasks(
     vec![retrieve_thread_handle]
 }
 
-fn _filter_docs_to_enqueue(docs: &Vec<String>) -> Vec<String> {
+pub fn filter_docs_to_enqueue(docs: &Vec<String>) -> Vec<String> {
     let mut rejected_reasons = HashMap::new();
     let mut filtered_docs = vec![];
 
@@ -461,17 +548,35 @@ fn _filter_docs_to_enqueue(docs: &Vec<String>) -> Vec<String> {
     filtered_docs
 }
 
+/// Which index takes the file, additional_indexing_dirs come from indexing.yaml
+pub async fn vecdb_index_for_doc(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    index_routes: &Vec<VecdbIndexRoute>,
+    path: &PathBuf,
+) -> usize {
+    if index_routes.len() <= 1 {
+        return 0;
+    }
+    let additional_indexing_dirs = if index_routes.iter().any(|r| r.additional_indexing_dirs) && path.is_absolute() {
+        let indexing_everywhere = crate::files_blocklist::reload_indexing_everywhere_if_needed(gcx.clone()).await;
+        indexing_everywhere.indexing_for_path(path).additional_indexing_dirs
+    } else {
+        vec![]
+    };
+    vecdb_index_for_path(index_routes, path, &additional_indexing_dirs)
+}
+
+/// Takes only the files routed to this index, vecdb_max_files applies to each index separately
 pub async fn vectorizer_enqueue_files(
     vservice: Arc<AMutex<FileVectorizerService>>,
     documents: &Vec<String>,
     process_immediately: bool,
 ) {
-    info!("adding {} files", documents.len());
-    let documents = _filter_docs_to_enqueue(documents);
-    let (vecdb_todo, vstatus, vstatus_notify, vecdb_max_files) = {
+    let (vecdb_todo, vecdb_handler, vstatus, vstatus_notify, vecdb_max_files) = {
         let service = vservice.lock().await;
         (
             service.vecdb_todo.clone(),
+            service.vecdb_handler.clone(),
             service.vstatus.clone(),
             service.vstatus_notify.clone(),
             service.constants.vecdb_max_files
@@ -483,11 +588,14 @@ pub async fn vectorizer_enqueue_files(
         documents_my_copy.truncate(vecdb_max_files);
         vstatus.lock().await.vecdb_max_files_hit = true;
     }
+    if let Err(err) = vecdb_handler.lock().await.queue_add(documents_my_copy.clone()).await {
+        warn!("cannot save the vecdb queue: {}", err);
+    }
     {
         {
             // two locks in sequence, vecdb_todo.lock -> vstatus.lock
             let mut vecdb_todo_locked = vecdb_todo.lock().await;
-            for doc in documents.iter() {
+            for doc in documents_my_copy.iter() {
                 if process_immediately {
                     vecdb_todo_locked.push_back(MessageToVecdbThread::ImmediatelyRegularDocument(doc.clone()));
                 } else {
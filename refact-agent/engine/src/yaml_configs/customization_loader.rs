@@ -2,10 +2,12 @@ use serde_yaml;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 use indexmap::IndexMap;
 use tokio::sync::RwLock as ARwLock;
 
-use crate::call_validation::{ChatMessage, SubchatParameters};
+use crate::call_validation::{ChatMessage, ChatMode, ChatModelType, ReasoningEffort, SubchatParameters};
+use crate::caps::CodeAssistantCaps;
 use crate::global_context::{GlobalContext, try_load_caps_quickly_if_not_present};
 use crate::custom_error::YamlError;
 
@@ -20,6 +22,45 @@ pub struct CustomizationYaml {
     pub toolbox_commands: IndexMap<String, ToolboxCommand>,
     #[serde(default)]
     pub code_lens: IndexMap<String, CodeLensCommand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budgets: Option<BudgetsConfig>,
+    #[serde(default)]
+    pub chat_modes: IndexMap<String, ChatModeConfig>,
+    #[serde(default)]
+    pub subagents: IndexMap<String, SubagentConfig>,
+}
+
+/// Zero means no limit
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BudgetLimits {
+    #[serde(default)]
+    pub max_tokens: usize,
+    #[serde(default)]
+    pub max_usd: f64,
+    #[serde(default)]
+    pub max_tool_calls: usize,
+    #[serde(default)]
+    pub max_duration_minutes: f64,  // per_chat only
+}
+
+impl BudgetLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_tokens == 0 && self.max_usd <= 0.0 && self.max_tool_calls == 0 && self.max_duration_minutes <= 0.0
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BudgetsConfig {
+    #[serde(default)]
+    pub per_chat: BudgetLimits,
+    #[serde(default)]
+    pub per_day: BudgetLimits,
+    #[serde(default = "default_warn_at")]
+    pub warn_at: f64,  // fraction of a limit when the model is told to wrap up
+}
+
+fn default_warn_at() -> f64 {
+    0.8
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,6 +72,82 @@ pub struct SystemPrompt {
     pub show: String,  // "always" (same as "") "never" "experimental"
 }
 
+/// A user-defined mode on top of a built-in one, the base mode decides everything not set here
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatModeConfig {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_base_mode")]
+    pub base_mode: ChatMode,
+    #[serde(default)]
+    pub system_prompt: String,  // empty means the prompt of the base mode
+    #[serde(default)]
+    pub tools: Vec<String>,  // tool names or tool group names, empty means all tools of the base mode
+    #[serde(default)]
+    pub write_globs: Vec<String>,  // file edits, rm and mv can write only to paths matching these, empty means anywhere
+    #[serde(default)]
+    pub model: String,  // used when the chat doesn't specify a model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoints: Option<bool>,
+}
+
+fn default_base_mode() -> ChatMode {
+    ChatMode::AGENT
+}
+
+/// Every subagent is a tool with the same name, it runs a subchat with its own prompt, model and tools
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubagentConfig {
+    pub description: String,  // for the model that decides when to call it
+    pub prompt: String,
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default = "default_subagent_max_steps")]
+    pub max_steps: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,  // JSON schema of the answer, free text if not set
+    #[serde(default)]
+    pub model: String,  // empty means model_type is used to pick one
+    #[serde(default)]
+    pub model_type: ChatModelType,
+    #[serde(default = "default_subagent_n_ctx")]
+    pub n_ctx: usize,
+    #[serde(default = "default_subagent_max_new_tokens")]
+    pub max_new_tokens: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(default)]
+    pub run_tools_needing_confirmation: bool,  // otherwise the calls that would ask the user are refused
+}
+
+fn default_subagent_max_steps() -> usize {
+    10
+}
+
+fn default_subagent_n_ctx() -> usize {
+    128000
+}
+
+fn default_subagent_max_new_tokens() -> usize {
+    8192
+}
+
+impl SubagentConfig {
+    pub fn subchat_parameters(&self) -> SubchatParameters {
+        SubchatParameters {
+            subchat_model_type: self.model_type,
+            subchat_model: self.model.clone(),
+            subchat_n_ctx: self.n_ctx,
+            subchat_tokens_for_rag: 0,
+            subchat_temperature: self.temperature,
+            subchat_max_new_tokens: self.max_new_tokens,
+            subchat_reasoning_effort: self.reasoning_effort.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ToolboxCommand {
     pub description: String,
@@ -116,6 +233,22 @@ fn _replace_variables_in_system_prompts(config: &mut CustomizationYaml, variable
             countdown -= 1;
         }
     }
+    for subagent in config.subagents.values_mut() {
+        let mut replaced = true;
+        let mut countdown = 10;
+        while replaced && countdown > 0 {
+            replaced = _replace_variables_in_text(&mut subagent.prompt, variables);
+            countdown -= 1;
+        }
+    }
+    for mode in config.chat_modes.values_mut() {
+        let mut replaced = true;
+        let mut countdown = 10;
+        while replaced && countdown > 0 {
+            replaced = _replace_variables_in_text(&mut mode.system_prompt, variables);
+            countdown -= 1;
+        }
+    }
 }
 
 
@@ -180,6 +313,11 @@ pub fn load_and_mix_with_users_config(
     work_config.system_prompts.extend(user_config.system_prompts.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.toolbox_commands.extend(user_config.toolbox_commands.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.code_lens.extend(user_config.code_lens.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.chat_modes.extend(caps_config.chat_modes.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.chat_modes.extend(user_config.chat_modes.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.subagents.extend(caps_config.subagents.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.subagents.extend(user_config.subagents.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.budgets = user_config.budgets.or(caps_config.budgets).or(work_config.budgets);
 
     let filtered_system_prompts = work_config.system_prompts
         .iter()
@@ -238,6 +376,68 @@ pub async fn load_customization(
     )
 }
 
+/// Subagents are tools, and tools are listed for every chat request: they are loaded again only when caps or
+/// customization.yaml change
+#[derive(Default)]
+pub struct SubagentsCache {
+    caps: Option<Arc<CodeAssistantCaps>>,
+    yaml_mtime: Option<SystemTime>,
+    subagents: IndexMap<String, SubagentConfig>,
+}
+
+pub async fn load_subagents(gcx: Arc<ARwLock<GlobalContext>>) -> IndexMap<String, SubagentConfig> {
+    let (config_dir, subagents_cache) = {
+        let gcx_locked = gcx.read().await;
+        (gcx_locked.config_dir.clone(), gcx_locked.subagents_cache.clone())
+    };
+    let yaml_mtime = tokio::fs::metadata(config_dir.join("customization.yaml")).await.and_then(|m| m.modified()).ok();
+    let mut cache = subagents_cache.lock().await;
+    let caps = gcx.read().await.caps.clone();
+    let caps_unchanged = matches!((&caps, &cache.caps), (Some(a), Some(b)) if Arc::ptr_eq(a, b));
+    if caps_unchanged && cache.yaml_mtime == yaml_mtime {
+        return cache.subagents.clone();
+    }
+    let mut error_log = Vec::new();
+    let tconfig = load_customization(gcx.clone(), true, &mut error_log).await;
+    for e in error_log.iter() {
+        tracing::error!("{e}");
+    }
+    cache.caps = gcx.read().await.caps.clone();  // loaded just now if there were none, then it's the key
+    cache.yaml_mtime = yaml_mtime;
+    cache.subagents = tconfig.subagents;
+    cache.subagents.clone()
+}
+
+pub async fn load_chat_mode(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    mode_name: &str,
+) -> Result<ChatModeConfig, String> {
+    let mut error_log = Vec::new();
+    let tconfig = load_customization(gcx.clone(), true, &mut error_log).await;
+    for e in error_log.iter() {
+        tracing::error!("{e}");
+    }
+    let mode = tconfig.chat_modes.get(mode_name).cloned()
+        .ok_or(format!("unknown chat mode {:?}, chat modes in customization.yaml: {:?}", mode_name, tconfig.chat_modes.keys().collect::<Vec<_>>()))?;
+    check_chat_mode(mode_name, &mode)?;
+    Ok(mode)
+}
+
+/// An unknown mode has no tools at all, so there's nothing to restrict then
+pub async fn chat_mode_write_globs(gcx: Arc<ARwLock<GlobalContext>>, mode_name: &str) -> Vec<String> {
+    if mode_name.is_empty() {
+        return vec![];
+    }
+    load_chat_mode(gcx, mode_name).await.map(|mode| mode.write_globs).unwrap_or_default()
+}
+
+pub fn check_chat_mode(mode_name: &str, mode: &ChatModeConfig) -> Result<(), String> {
+    match mode.base_mode {
+        ChatMode::NO_TOOLS | ChatMode::EXPLORE | ChatMode::AGENT => Ok(()),
+        _ => Err(format!("chat mode {:?}: base_mode must be NO_TOOLS, EXPLORE or AGENT", mode_name)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,4 +458,55 @@ mod tests {
         assert_eq!(config.system_prompts.get("configurator").is_some(), true);
         assert_eq!(config.system_prompts.get("project_summary").is_some(), true);
     }
+
+    #[test]
+    fn test_chat_modes() {
+        let user_yaml = r#"
+chat_modes:
+  reviewer:
+    base_mode: EXPLORE
+    system_prompt: "You review code.\n%CD_INSTRUCTIONS%"
+    tools: ["cat", "codebase_search"]
+  docs_writer:
+    model: gpt-4.1
+    checkpoints: false
+    write_globs: ["*.md"]
+"#;
+        let mut error_log = Vec::new();
+        let config = load_and_mix_with_users_config(user_yaml, "", true, true, &mut error_log);
+        assert!(error_log.is_empty());
+        let reviewer = &config.chat_modes["reviewer"];
+        assert_eq!(reviewer.base_mode, ChatMode::EXPLORE);
+        assert!(reviewer.system_prompt.starts_with("You review code.\nYou might receive additional instructions"));
+        let docs_writer = &config.chat_modes["docs_writer"];
+        assert_eq!(docs_writer.base_mode, ChatMode::AGENT);
+        assert_eq!(docs_writer.checkpoints, Some(false));
+        assert!(docs_writer.tools.is_empty());
+        assert_eq!(docs_writer.write_globs, vec!["*.md".to_string()]);
+        assert!(reviewer.write_globs.is_empty());
+    }
+
+    #[test]
+    fn test_subagents() {
+        let user_yaml = r#"
+subagents:
+  dependency_auditor:
+    description: "Checks dependencies for outdated and unused packages"
+    prompt: "You audit dependencies."
+    tools: ["cat", "search_pattern"]
+    output_schema:
+      type: object
+      required: ["outdated", "unused"]
+    model_type: light
+"#;
+        let mut error_log = Vec::new();
+        let config = load_and_mix_with_users_config(user_yaml, "", true, true, &mut error_log);
+        assert!(error_log.is_empty());
+        let auditor = &config.subagents["dependency_auditor"];
+        assert_eq!(auditor.max_steps, 10);
+        assert_eq!(auditor.output_schema.as_ref().unwrap()["required"][1], "unused");
+        let params = auditor.subchat_parameters();
+        assert_eq!(params.subchat_model_type, ChatModelType::Light);
+        assert_eq!(params.subchat_n_ctx, 128000);
+    }
 }